@@ -1,28 +1,125 @@
-use clap::{value_parser, Arg, ArgAction, Command};
-use std::path::PathBuf;
+use bitslideslib::{
+    config::{
+        parse_check, parse_identical, parse_reverify, parse_sanitize, parse_sidecar, parse_size,
+        parse_trash,
+    },
+    parse_time, CheckMode, CollisionPolicy, ConflictPolicy, ExportFormat, OutputFormat,
+};
+use clap::{
+    builder::PossibleValuesParser, builder::TypedValueParser, parser::ValueSource, value_parser,
+    Arg, ArgAction, ArgMatches, Command,
+};
+use log::LevelFilter;
+use std::{ffi::OsString, path::PathBuf};
 
 const APP_NAME: &str = env!("CARGO_PKG_NAME");
 const APP_VERS: &str = env!("CARGO_PKG_VERSION");
 
+/// Default number of retries in case of a failure.
+///
+pub const DEFAULT_RETRIES: u8 = 5;
+
+/// Default number of syncs failing in a row before giving up on a slide.
+///
+pub const DEFAULT_MAX_FAILURES: u32 = 5;
+
+/// Default least seconds between the starts of two syncs of a slide, no limit.
+///
+pub const DEFAULT_MIN_INTERVAL: u64 = 0;
+
+/// Default extension of the .wip files of the safe mode.
+///
+pub const DEFAULT_WIP_SUFFIX: &str = "wip";
+
+/// Default seconds without filesystem events awaited before syncing.
+///
+pub const DEFAULT_DEBOUNCE: u64 = 2;
+
+/// Default most seconds a burst of filesystem events may postpone a sync.
+///
+pub const DEFAULT_DEBOUNCE_MAX: u64 = 60;
+
+/// Default seconds between two scans of the slides polled.
+///
+pub const DEFAULT_POLL_INTERVAL: u64 = 30;
+
+/// Default seconds between two heartbeats of a sync lasting longer.
+///
+pub const DEFAULT_HEARTBEAT: u64 = 60;
+
+/// Returns the default number of concurrent jobs, the number of CPUs.
+///
+pub fn default_jobs() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get().min(u16::MAX as usize))
+        .unwrap_or(1)
+}
+
+/// Environment variable pointing to the configuration file to use instead of the default ones.
+///
+pub const CONFIG_ENV_VAR: &str = "BITSLIDES_CONFIG";
+
+/// Returns the configuration file of the current user, at the platform-native location.
+///
+/// That is `$XDG_CONFIG_HOME/bitslides/config.yml` (or `~/.config`) on Linux, `%APPDATA%` on Windows
+/// and `~/Library/Application Support` on macOS.
+///
+pub fn user_config_file() -> Option<PathBuf> {
+    dirs::config_dir().map(|config_dir| config_dir.join("bitslides").join("config.yml"))
+}
+
+/// Returns the legacy configuration file of the current user, if there is a home folder.
+///
+fn legacy_user_config_file() -> Option<PathBuf> {
+    dirs::home_dir().map(|home_dir| home_dir.join(".bitslides").join("default.conf"))
+}
+
 /// Returns a list of default configuration files.
 ///
+/// The list is sorted in precedence order, lowest first: the system-wide files, then the legacy
+/// user file, and finally the platform-native user file.
+///
 fn default_config_files() -> Vec<PathBuf> {
     let mut paths = vec![
         PathBuf::from("/etc/bitslides/default.conf"),
         PathBuf::from("C:\\bitslides\\default.conf"),
     ];
 
-    if let Some(home_dir) = dirs::home_dir() {
-        paths.push(home_dir.join(".bitslides").join("default.conf"));
+    if let Some(legacy_user_config_file) = legacy_user_config_file() {
+        paths.push(legacy_user_config_file);
+    }
+
+    if let Some(user_config_file) = user_config_file() {
+        paths.push(user_config_file);
     }
 
     paths
 }
 
+/// Returns the configuration files to load, and whether they are the default ones, which may be
+/// missing.
+///
+/// The files given on the command line take precedence over the one in the `env` value of the
+/// [`CONFIG_ENV_VAR`] variable, which replaces the default ones.
+///
+pub fn config_files(matches: &ArgMatches, env: Option<OsString>) -> (Vec<PathBuf>, bool) {
+    let given = matches
+        .get_many::<PathBuf>("config")
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect::<Vec<_>>();
+    match env.filter(|path| !path.is_empty()) {
+        _ if matches.value_source("config") == Some(ValueSource::CommandLine) => (given, false),
+        Some(path) => (vec![PathBuf::from(path)], false),
+        None => (given, true),
+    }
+}
+
 /// Returns a `clap::Command` instance for the CLI.
 ///
 pub fn cli() -> Command {
-    Command::new(APP_NAME)
+    let mut cli = Command::new(APP_NAME)
         .version(APP_VERS)
         .about("Synchronizes contents between locations")
         .arg(
@@ -30,9 +127,10 @@ pub fn cli() -> Command {
                 .short('c')
                 .long("config")
                 .value_name("root_config")
-                .help("Specify a custom config file")
+                .help(format!("Specify a custom config file. Defaults to the one in {CONFIG_ENV_VAR} if set"))
                 .action(ArgAction::Append)
                 .value_parser(value_parser!(PathBuf))
+                .global(true)
                 .default_values(
                     default_config_files()
                         .iter()
@@ -42,46 +140,626 @@ pub fn cli() -> Command {
                 .required(false),
         )
         .arg(
-            Arg::new("verbose")
-                .short('v')
-                .long("verbose")
-                .help("Prints verbose output (more verbose with multiple -v)")
-                .action(ArgAction::Count)
-                .value_parser(value_parser!(u8))
+            Arg::new("profile")
+                .short('p')
+                .long("profile")
+                .value_name("profile")
+                .help("Select a named profile among the ones declared in the config files")
+                .global(true)
                 .required(false),
         )
         .arg(
-            Arg::new("dry-run")
-                .short('n')
-                .long("dry-run")
-                .help("Performs a dry run without making any changes. Requires at least -vv")
+            Arg::new("strict-config")
+                .long("strict-config")
+                .help("Reject unknown fields in the config files, volume and slide config files included")
                 .action(ArgAction::SetTrue)
+                .global(true)
                 .required(false),
         )
+        .arg(output_arg().default_value("text"))
         .arg(
-            Arg::new("non-safe")
-                .long("non-safe")
-                .value_name("safe")
-                .help("The \"safe\" mode creates a temporary file for each file. If the move is successful, the temporary file is deleted. This option disables that behavior.")
-                .action(ArgAction::SetTrue) // Hence is false (or safe) by default
-                .required(false),
+            Arg::new("log-file")
+                .long("log-file")
+                .value_name("path")
+                .help("Also write the log to this file, appending to it. Independent of the verbosity of the terminal")
+                .value_parser(value_parser!(PathBuf))
+                .global(true),
+        )
+        .arg(
+            Arg::new("log-file-level")
+                .long("log-file-level")
+                .value_name("level")
+                .help("Level of the messages written to the log file")
+                .value_parser(
+                    PossibleValuesParser::new(["off", "error", "warn", "info", "debug", "trace"])
+                        .map(|s| s.parse::<LevelFilter>().unwrap()),
+                )
+                .default_value("info")
+                .requires("log-file")
+                .global(true),
         )
         .arg(
-            Arg::new("retries")
-                .long("retries")
-                .value_name("retries")
-                .help("Number of retries in case of a failure (checksum mismatch, etc)")
+            Arg::new("verbose")
+                .short('v')
+                .long("verbose")
+                .help("Prints verbose output (more verbose with multiple -v)")
+                .action(ArgAction::Count)
                 .value_parser(value_parser!(u8))
-                .default_value("5"),
+                .global(true)
+                .required(false),
+        )
+        .subcommand(
+            Command::new("init")
+                .about("Writes a starter configuration file")
+                .arg(
+                    Arg::new("path")
+                        .value_name("path")
+                        .help("Where to write the configuration file. Defaults to the one of the current user")
+                        .value_parser(value_parser!(PathBuf))
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_name("path")
+                        .help("Where to write the configuration file, same as the path")
+                        .value_parser(value_parser!(PathBuf))
+                        .conflicts_with("path"),
+                )
+                .arg(
+                    Arg::new("force")
+                        .short('f')
+                        .long("force")
+                        .help("Overwrite the configuration file if it already exists")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("probe")
+                        .long("probe")
+                        .help("Pre-populate the roots with the usual mount points having volumes mounted")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("keygen")
+                .about("Writes a new key signing the manifests of the deliveries, and prints its public key")
+                .arg(
+                    Arg::new("path")
+                        .value_name("path")
+                        .help("Where to write the key, to be set as the signing_key of the configuration")
+                        .value_parser(value_parser!(PathBuf))
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("force")
+                        .short('f')
+                        .long("force")
+                        .help("Overwrite the key if it already exists")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("run")
+                .about("Syncs the slides and keeps watching them for changes (default)")
+                .args(transfer_args())
+                .arg(
+                    Arg::new("once")
+                        .long("once")
+                        .help("Syncs the slides a single time and exits, same as the once subcommand")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("daemon")
+                        .long("daemon")
+                        .help("Detaches from the terminal and runs in the background (Unix only)")
+                        .long_help(
+                            "Detaches from the terminal and runs in the background (Unix only). \
+                             Logs go to the --log-file, or to the user data folder if not given",
+                        )
+                        .conflicts_with("once")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("pid-file")
+                        .long("pid-file")
+                        .help("File holding the PID of the daemon")
+                        .value_name("PATH")
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .default_value(crate::daemon::default_pid_file().into_os_string())
+                        .requires("daemon"),
+                )
+                .arg(
+                    Arg::new("control")
+                        .long("control")
+                        .help("Socket (named pipe on Windows) where to answer the status, pause, resume, sync-now and shutdown commands. Defaults to the one of those commands with --daemon")
+                        .value_name("PATH")
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .conflicts_with("once"),
+                ),
+        )
+        .subcommand(
+            Command::new("once")
+                .about("Syncs the slides a single time and exits")
+                .args(transfer_args()),
+        )
+        .subcommand(
+            Command::new("plan").about("Prints the sync jobs that would be run, without running them"),
+        )
+        .subcommand(
+            Command::new("list-volumes")
+                .about("Prints every volume found, along with its slides and their routes"),
+        )
+        .subcommand(
+            Command::new("explain-routes")
+                .about("Prints the route decided for every slide, and why the dropped ones are not synced"),
+        )
+        .subcommand(
+            Command::new("verify")
+                .about("Checks the integrity of the files in the slides and of the ones delivered, without moving anything")
+                .arg(
+                    Arg::new("trace")
+                        .help("Trace files recording the deliveries to check, oldest first")
+                        .value_name("TRACE")
+                        .num_args(0..)
+                        .value_parser(clap::value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("rehash")
+                        .long("rehash")
+                        .help("Hash the delivered files again even if unchanged since their checksum was cached")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("status")
+                .about("Prints the state of every syncjob of a running instance")
+                .arg(control_arg()),
+        )
+        .subcommand(
+            Command::new("pause")
+                .about("Stops syncing in a running instance, waiting for the ongoing transfers to end")
+                .arg(control_arg())
+                .arg(target_arg()),
+        )
+        .subcommand(
+            Command::new("resume")
+                .about("Resumes syncing in a running instance")
+                .arg(control_arg())
+                .arg(target_arg()),
+        )
+        .subcommand(
+            Command::new("sync-now")
+                .about("Syncs right away in a running instance, without waiting for changes")
+                .arg(control_arg())
+                .arg(target_arg()),
+        )
+        .subcommand(
+            Command::new("shutdown")
+                .about("Stops a running instance")
+                .arg(control_arg()),
+        )
+        .subcommand(
+            Command::new("tidy")
+                .about("Tidies up the volumes")
+                .arg(dry_run_arg()),
         )
+        .subcommand(
+            Command::new("history")
+                .about("Prints the events recorded in the history database, oldest first")
+                .args(history_args())
+                .arg(
+                    Arg::new("operation")
+                        .long("operation")
+                        .value_name("operation")
+                        .help("Only the events of this operation (MV, MKDIR, FAILED...)"),
+                )
+                .subcommand(
+                    Command::new("export")
+                        .about("Writes the files delivered according to the history database to stdout, one row per file, oldest first")
+                        .args(history_args())
+                        .arg(
+                            Arg::new("format")
+                                .long("format")
+                                .value_name("format")
+                                .help("Format of the files exported. With csv, a header row is followed by the time, syncjob, volumes, paths, size, duration, throughput, checksum, retries and collision of each file")
+                                .value_parser(
+                                    PossibleValuesParser::new(["csv"])
+                                        .map(|s| s.parse::<ExportFormat>().unwrap()),
+                                )
+                                .default_value("csv"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("undo")
+                .about("Moves the files delivered back to where they came from, replaying the traces in reverse")
+                .arg(
+                    Arg::new("trace")
+                        .long("trace")
+                        .value_name("TRACE")
+                        .help("Trace file recording the deliveries to undo, oldest first if repeated")
+                        .required(true)
+                        .action(ArgAction::Append)
+                        .value_parser(clap::value_parser!(PathBuf)),
+                )
+                .arg(since_arg().help("Only the deliveries from then on: a date, a date and time, or a time ago (ex. 2024-12-31, \"2024-12-31 23:59:59\", 12h, 7d)"))
+                .arg(dry_run_arg()),
+        )
+        .subcommand(
+            Command::new("validate")
+                .about("Validates the configuration files, volumes and slides without syncing anything"),
+        );
+    // init takes --output for the file it writes, so every other command takes the format
+    for command in cli
+        .get_subcommands_mut()
+        .filter(|command| command.get_name() != "init")
+    {
+        *command = std::mem::take(command).arg(output_arg().global(true));
+    }
+    cli
+}
+
+/// Returns the argument selecting the format of the information printed to stdout.
+///
+fn output_arg() -> Arg {
+    Arg::new("output")
+        .long("output")
+        .value_name("format")
+        .help("Format of the information printed to stdout. With json, every event and planned job is printed as a JSON object per line")
+        .value_parser(
+            PossibleValuesParser::new(["text", "json"]).map(|s| s.parse::<OutputFormat>().unwrap()),
+        )
+}
+
+/// Returns the output format selected, given after the command or before it.
+///
+pub fn output_format(matches: &ArgMatches) -> OutputFormat {
+    let mut output = matches.get_one::<OutputFormat>("output").copied();
+    let mut matches = matches;
+    while let Some((name, sub_matches)) = matches.subcommand() {
+        if name == "init" {
+            break;
+        }
+        output = sub_matches
+            .get_one::<OutputFormat>("output")
+            .copied()
+            .or(output);
+        matches = sub_matches;
+    }
+    output.unwrap_or_default()
+}
+
+/// Returns the argument locating the control interface of a running instance.
+///
+fn control_arg() -> Arg {
+    Arg::new("control")
+        .long("control")
+        .help("Socket (named pipe on Windows) of the running instance")
+        .value_name("PATH")
+        .value_parser(clap::value_parser!(PathBuf))
+        .default_value(crate::daemon::default_control_socket().into_os_string())
+}
+
+/// Returns the argument selecting the syncjobs of a control command.
+///
+fn target_arg() -> Arg {
+    Arg::new("target")
+        .value_name("TARGET")
+        .help("Syncjob, as printed by status, or volume whose syncjobs are affected. All of them if not given")
+}
+
+/// Returns the argument limiting a command to what happened from a point in time on.
+///
+fn since_arg() -> Arg {
+    Arg::new("since")
+        .long("since")
+        .value_name("time")
+        .value_parser(parse_time)
+}
+
+/// Returns the arguments filtering the events of the history database, and selecting it.
+///
+fn history_args() -> Vec<Arg> {
+    vec![
+        since_arg().help("Only the events from then on: a date, a date and time, or a time ago (ex. 2024-12-31, \"2024-12-31 23:59:59\", 12h, 7d)"),
+        Arg::new("until")
+            .long("until")
+            .value_name("time")
+            .help("Only the events up to then, given like --since")
+            .value_parser(parse_time),
+        Arg::new("volume")
+            .long("volume")
+            .value_name("name")
+            .help("Only the events of the syncjobs from, through or to this volume"),
+        Arg::new("file")
+            .long("file")
+            .value_name("path")
+            .help("Only the events mentioning this file, or any path containing it"),
+        Arg::new("db")
+            .long("db")
+            .value_name("PATH")
+            .help("History database to query, instead of the one of the configuration")
+            .value_parser(clap::value_parser!(PathBuf)),
+    ]
+}
+
+/// Returns the dry-run argument.
+///
+fn dry_run_arg() -> Arg {
+    Arg::new("dry-run")
+        .short('n')
+        .long("dry-run")
+        .help("Performs a dry run without making any changes. Requires at least -vv")
+        .action(ArgAction::SetTrue)
+        .required(false)
+}
+
+/// Returns the arguments of the subcommands transferring files.
+///
+fn transfer_args() -> Vec<Arg> {
+    vec![
+        dry_run_arg(),
+        Arg::new("non-safe")
+            .long("non-safe")
+            .value_name("safe")
+            .help("The \"safe\" mode creates a temporary file for each file. If the move is successful, the temporary file is deleted. This option disables that behavior.")
+            .action(ArgAction::SetTrue) // Hence is false (or safe) by default
+            .required(false),
+        Arg::new("wip-suffix")
+            .long("wip-suffix")
+            .value_name("suffix")
+            .help(format!("Extension of the temporary files of the \"safe\" mode [default: {DEFAULT_WIP_SUFFIX}]. Overrides the config files")),
+        Arg::new("staging")
+            .long("staging")
+            .help("Creates the temporary files of the \"safe\" mode in a .bitslides-tmp folder of the destination slide, instead of next to their destination")
+            .action(ArgAction::SetTrue),
+        Arg::new("retries")
+            .long("retries")
+            .value_name("retries")
+            .help("Number of retries in case of a transient failure (timeout, I/O error, checksum mismatch...), waiting longer each time. Permission errors and full destinations are not retried")
+            .value_parser(value_parser!(u8))
+            .default_value(DEFAULT_RETRIES.to_string()),
+        Arg::new("max-failures")
+            .long("max-failures")
+            .value_name("syncs")
+            .help(format!("Number of syncs of a slide failing in a row before giving up on it, or 0 to never give up. The syncs are attempted again after waiting longer each time [default: {DEFAULT_MAX_FAILURES}]. Overrides the config files"))
+            .value_parser(value_parser!(u32)),
+        Arg::new("min-interval")
+            .long("min-interval")
+            .value_name("seconds")
+            .help(format!("Least seconds between the starts of two syncs of a slide, so a slide written all the time is not synced over and over, or 0 for no limit. The changes made meanwhile are synced once it elapses [default: {DEFAULT_MIN_INTERVAL}]. Overrides the config files"))
+            .value_parser(value_parser!(u64)),
+        Arg::new("collision")
+            .long("collision")
+            .value_name("policy")
+            .help("What to do when a file already exists at the destination: overwrite, skip, fail, rename[:suffix or template] or keep-newer[:remove]. Overrides the config files")
+            .value_parser(|s: &str| s.parse::<CollisionPolicy>()),
+        Arg::new("conflict")
+            .long("conflict")
+            .value_name("policy")
+            .help("What to do with a file sent both ways between two volumes, found under the same path in the slides they have for each other: report, newer or rename. Overrides the config files")
+            .value_parser(|s: &str| s.parse::<ConflictPolicy>()),
+        Arg::new("skip-identical")
+            .long("skip-identical")
+            .value_name("comparison")
+            .help("Removes the files already at their destination instead of applying the collision policy, found by size and modification time (quick) or by contents, or none. Overrides the config files")
+            .value_parser(parse_identical),
+        Arg::new("check")
+            .long("check")
+            .value_name("algorithm")
+            .help("Checksum algorithm used to verify each moved file (crc32, crc64, md5, sha1, sha256, blake, blake3, xxh64, xxh128...), or none to disable the verification. Overrides the config files")
+            .value_parser(parse_check),
+        Arg::new("recheck")
+            .long("recheck")
+            .help("Reads each copy back to compare its checksum to the one computed while copying, retrying on mismatch")
+            .action(ArgAction::SetTrue),
+        Arg::new("check-mode")
+            .long("check-mode")
+            .value_name("mode")
+            .help("How much of each copy --recheck reads back: all of it (full), or for the files over a size only both ends and some random blocks (sampled[:threshold[:edge[:blocks]]], ex. sampled:1G:16M:64). Overrides the config files")
+            .value_parser(|s: &str| s.parse::<CheckMode>()),
+        Arg::new("jobs")
+            .short('j')
+            .long("jobs")
+            .value_name("N")
+            .help("Number of files moved concurrently within a slide, and of slides synced in parallel unless --parallel is given. Defaults to the number of CPUs")
+            .value_parser(value_parser!(u16).range(1..))
+            .default_value(default_jobs().to_string()),
+        Arg::new("parallel")
+            .long("parallel")
+            .value_name("N")
+            .help("Number of syncjobs doing filesystem work at once, the others waiting for their turn. Defaults to --jobs. Overrides the config files")
+            .value_parser(value_parser!(u16).range(1..)),
+        Arg::new("exclude")
+            .long("exclude")
+            .value_name("pattern")
+            .help("Gitignore-style pattern of the slide contents to leave untouched, added to the configured ones. Can be repeated")
+            .action(ArgAction::Append),
+        Arg::new("include")
+            .long("include")
+            .value_name("pattern")
+            .help("Gitignore-style pattern of the slide contents to sync even if excluded. Can be repeated")
+            .action(ArgAction::Append),
+        Arg::new("min-size")
+            .long("min-size")
+            .value_name("size")
+            .help("Leaves untouched the files smaller than this size (ex. 10K). Overrides the config files")
+            .value_parser(parse_size),
+        Arg::new("max-size")
+            .long("max-size")
+            .value_name("size")
+            .help("Leaves untouched the files larger than this size (ex. 50G). Overrides the config files")
+            .value_parser(parse_size),
+        Arg::new("progress")
+            .long("progress")
+            .help("Shows the bytes moved per file and per syncjob")
+            .action(ArgAction::SetTrue),
+        Arg::new("delta")
+            .long("delta")
+            .help("When overwriting a file, copies only the blocks that changed, rsync-style")
+            .action(ArgAction::SetTrue),
+        Arg::new("preserve-times")
+            .long("preserve-times")
+            .help("Keeps the modification and access times of the files moved")
+            .action(ArgAction::SetTrue),
+        Arg::new("preserve-permissions")
+            .long("preserve-permissions")
+            .help("Keeps the permissions of the files moved, and their owner when running as root")
+            .action(ArgAction::SetTrue),
+        Arg::new("preserve-xattrs")
+            .long("preserve-xattrs")
+            .help("Keeps the extended attributes of the files moved (alternate data streams on Windows)")
+            .action(ArgAction::SetTrue),
+        Arg::new("rate-limit")
+            .long("rate-limit")
+            .value_name("size")
+            .help("Limits the bytes copied per second by all the syncjobs together (ex. 10M), 0 for no limit. Overrides the config files")
+            .value_parser(parse_size),
+        Arg::new("sanitize")
+            .long("sanitize")
+            .value_name("scheme")
+            .help("Rewrites the file names FAT, exFAT and NTFS destinations cannot hold, with underscores (underscore) or Unicode lookalikes (unicode), or none. Overrides the config files")
+            .value_parser(parse_sanitize),
+        Arg::new("use-trash")
+            .long("use-trash")
+            .value_name("files")
+            .help("Keeps the overwritten files (overwritten), or also the sources removed after their move (all), in the .bitslides-trash folder of their slide for some days (ex. all:7, 30 by default), or none. Overrides the config files")
+            .value_parser(parse_trash),
+        Arg::new("debounce")
+            .long("debounce")
+            .value_name("seconds")
+            .help(format!("Seconds without filesystem events awaited before syncing a slide, so a folder being copied is synced once, or 0 to sync after every event [default: {DEFAULT_DEBOUNCE}]. Overrides the config files"))
+            .value_parser(value_parser!(u64)),
+        Arg::new("debounce-max")
+            .long("debounce-max")
+            .value_name("seconds")
+            .help(format!("Most seconds a burst of filesystem events may postpone the sync of a slide, so a slide changing all the time is still synced, or 0 to wait for the burst to end [default: {DEFAULT_DEBOUNCE_MAX}]. Overrides the config files"))
+            .value_parser(value_parser!(u64)),
+        Arg::new("poll")
+            .long("poll")
+            .help("Polls every slide for changes instead of watching them, for the filesystems not reporting their events. The slides the filesystem watcher fails to watch are always polled")
+            .action(ArgAction::SetTrue),
+        Arg::new("poll-interval")
+            .long("poll-interval")
+            .value_name("seconds")
+            .help(format!("Seconds between two scans of the slides polled [default: {DEFAULT_POLL_INTERVAL}]. Overrides the config files"))
+            .value_parser(value_parser!(u64)),
+        Arg::new("heartbeat")
+            .long("heartbeat")
+            .value_name("seconds")
+            .help(format!("Seconds between two heartbeats traced along a sync lasting longer, with the files being copied and the bytes done, or 0 for none [default: {DEFAULT_HEARTBEAT}]. Overrides the config files"))
+            .value_parser(value_parser!(u64)),
+        Arg::new("min-age")
+            .long("min-age")
+            .value_name("seconds")
+            .help("Leaves for a later sync the files modified within the last seconds, as they may still be being written, or 0 to move them at once. Overrides the config files")
+            .value_parser(value_parser!(u64)),
+        Arg::new("max-file-size")
+            .long("max-file-size")
+            .value_name("size")
+            .help("Leaves in their slide the files larger than this size (ex. 100G), listing them in its .bitslides-oversize.txt report, or 0 for no cap. Overrides the config files")
+            .value_parser(parse_size),
+        Arg::new("sidecar")
+            .long("sidecar")
+            .value_name("kind")
+            .help("Writes the checksum of each delivered file next to it (file, ex. photo.jpg.sha256) or to a list per folder (folder, ex. SHA256SUMS), or none. Overrides the config files")
+            .value_parser(parse_sidecar),
+        Arg::new("reverify")
+            .long("reverify")
+            .value_name("minutes")
+            .help("Hashes the checksummed files delivered again this many minutes later, reporting the ones corrupted meanwhile, and restoring them from the trash with :restore (ex. 30:restore), or none. Overrides the config files")
+            .value_parser(parse_reverify),
+        Arg::new("delivery-manifest")
+            .long("delivery-manifest")
+            .help("Writes after each sync a .bitslides-manifest-<time>-<source>.json file at the root of the destination slide, listing the files delivered and where they came from")
+            .action(ArgAction::SetTrue),
+    ]
 }
 
 #[cfg(test)]
 mod tests {
-    use super::cli;
+    use super::{cli, config_files, default_config_files, user_config_file};
+    use bitslideslib::OutputFormat;
+    use log::LevelFilter;
+    use std::{ffi::OsString, path::PathBuf};
 
     #[test]
     fn verify_cli() {
         cli().debug_assert();
     }
+
+    #[test]
+    fn verify_log_file() {
+        let matches = cli().get_matches_from(["bitslides", "--log-file", "bitslides.log", "run"]);
+        assert_eq!(
+            matches.get_one::<LevelFilter>("log-file-level"),
+            Some(&LevelFilter::Info)
+        );
+
+        // The level is meaningless without a file
+        assert!(cli()
+            .try_get_matches_from(["bitslides", "--log-file-level", "debug"])
+            .is_err());
+    }
+
+    #[test]
+    fn verify_init_output() {
+        // The path to write to may also be given with -o or --output
+        for args in [
+            vec!["bitslides", "init", "bitslides.yml"],
+            vec!["bitslides", "init", "-o", "bitslides.yml"],
+            vec!["bitslides", "init", "--output", "bitslides.yml"],
+        ] {
+            let matches = cli().get_matches_from(args);
+            let (_, init) = matches.subcommand().unwrap();
+            assert_eq!(
+                init.get_one::<PathBuf>("path")
+                    .or(init.get_one::<PathBuf>("output")),
+                Some(&PathBuf::from("bitslides.yml"))
+            );
+        }
+
+        // The output format is given to the other commands, before or after their name
+        for args in [
+            vec!["bitslides", "--output", "json", "once"],
+            vec!["bitslides", "once", "--output", "json"],
+            vec!["bitslides", "history", "export", "--output", "json"],
+        ] {
+            assert_eq!(
+                super::output_format(&cli().get_matches_from(args)),
+                OutputFormat::Json
+            );
+        }
+        assert_eq!(
+            super::output_format(&cli().get_matches_from(["bitslides", "once"])),
+            OutputFormat::Text
+        );
+    }
+
+    #[test]
+    fn verify_config_lookup() {
+        // The platform-native user file takes precedence over the legacy ones
+        let defaults = default_config_files();
+        assert_eq!(defaults.last(), user_config_file().as_ref());
+        assert!(defaults.contains(&PathBuf::from("/etc/bitslides/default.conf")));
+
+        // Without anything else, the default files are looked up
+        let env = Some(OsString::from("/tmp/bitslides.yml"));
+        let matches = cli().get_matches_from(["bitslides"]);
+        assert_eq!(config_files(&matches, None), (defaults, true));
+
+        // The environment variable replaces the default lookup
+        assert_eq!(
+            config_files(&matches, env.clone()),
+            (vec![PathBuf::from("/tmp/bitslides.yml")], false)
+        );
+
+        // The files given on the command line replace both
+        let matches = cli().get_matches_from(["bitslides", "-c", "mine.yml"]);
+        assert_eq!(
+            config_files(&matches, env),
+            (vec![PathBuf::from("mine.yml")], false)
+        );
+    }
 }
@@ -1,9 +1,27 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
+use bitslideslib::config::{
+    deserialize_size, MqttConfig, StrategyOverrides, Strict, UnknownFields,
+};
 use serde::Deserialize;
-use std::path::Path;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
 pub const DEFAULT_KEYWORD: &str = "Slides";
 
+/// Base location used to solve the relative roots.
+///
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RelativeTo {
+    /// Relative to the folder containing the config file
+    #[default]
+    Config,
+    /// Relative to the current working directory of the process
+    Cwd,
+}
+
 /// Configuration file representation.
 ///
 #[derive(Deserialize)]
@@ -11,14 +29,129 @@ pub struct Config {
     // Slides folder keyword
     pub keyword: Option<String>,
     // Root folders to watch
+    #[serde(default)]
     pub roots: Vec<String>,
+    // Base location used to solve the relative roots
+    #[serde(default)]
+    pub relative_roots: RelativeTo,
     // Optional trace file path format
     pub trace: Option<String>,
+    // Gitignore-style patterns of the slide contents that will never be synced
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    // Files smaller than this are not synced
+    #[serde(default, deserialize_with = "deserialize_size")]
+    pub min_size: Option<u64>,
+    // Files larger than this are not synced
+    #[serde(default, deserialize_with = "deserialize_size")]
+    pub max_size: Option<u64>,
+    // Additional config fragments to pull in (globs allowed, relative to this file)
+    #[serde(default)]
+    pub include: Vec<String>,
+    // Key signing the manifests of the deliveries (relative to this file)
+    pub signing_key: Option<String>,
+    // SQLite database recording the traced events (relative to this file)
+    pub history: Option<String>,
+    // Public keys whose manifests are trusted
+    #[serde(default)]
+    pub trusted_keys: Vec<String>,
+    // Number of syncjobs syncing at once
+    pub parallel: Option<usize>,
+    // Seconds without filesystem events awaited before syncing
+    pub debounce: Option<u64>,
+    // Most seconds a burst of filesystem events may postpone a sync
+    pub debounce_max: Option<u64>,
+    // Poll the slides of these roots for changes rather than watching them
+    #[serde(default)]
+    pub poll: bool,
+    // Seconds between two scans of the slides polled
+    pub poll_interval: Option<u64>,
+    // Seconds between two heartbeats of a sync lasting longer
+    pub heartbeat: Option<u64>,
+    // MQTT broker where the state of the syncjobs is published
+    pub mqtt: Option<MqttConfig>,
+    // Move strategy settings
+    #[serde(flatten)]
+    pub overrides: StrategyOverrides,
+    // Named profiles, selectable from the command line
+    #[serde(default)]
+    pub profiles: HashMap<String, Profile>,
+    // Reject unknown fields in the config files
+    #[serde(default)]
+    pub strict: bool,
+    // Fields not matching any setting
+    #[serde(flatten)]
+    pub unknown: UnknownFields,
+}
+
+/// Named profile representation.
+///
+/// The settings provided by a selected profile take precedence over the ones at the top level of
+/// the config file declaring it.
+///
+#[derive(Deserialize)]
+pub struct Profile {
+    // Slides folder keyword
+    pub keyword: Option<String>,
+    // Root folders to watch. Replace the top level ones if any
+    #[serde(default)]
+    pub roots: Vec<String>,
+    // Base location used to solve the relative roots
+    pub relative_roots: Option<RelativeTo>,
+    // Optional trace file path format
+    pub trace: Option<String>,
+    // Gitignore-style patterns added to the top level ones
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    // Files smaller than this are not synced
+    #[serde(default, deserialize_with = "deserialize_size")]
+    pub min_size: Option<u64>,
+    // Files larger than this are not synced
+    #[serde(default, deserialize_with = "deserialize_size")]
+    pub max_size: Option<u64>,
+    // Key signing the manifests of the deliveries (relative to the config file)
+    pub signing_key: Option<String>,
+    // SQLite database recording the traced events (relative to the config file)
+    pub history: Option<String>,
+    // Public keys added to the top level ones
+    #[serde(default)]
+    pub trusted_keys: Vec<String>,
+    // Number of syncjobs syncing at once
+    pub parallel: Option<usize>,
+    // Seconds without filesystem events awaited before syncing
+    pub debounce: Option<u64>,
+    // Most seconds a burst of filesystem events may postpone a sync
+    pub debounce_max: Option<u64>,
+    // Poll the slides of the roots for changes rather than watching them
+    pub poll: Option<bool>,
+    // Seconds between two scans of the slides polled
+    pub poll_interval: Option<u64>,
+    // Seconds between two heartbeats of a sync lasting longer
+    pub heartbeat: Option<u64>,
+    // MQTT broker where the state of the syncjobs is published
+    pub mqtt: Option<MqttConfig>,
+    // Move strategy settings
+    #[serde(flatten)]
+    pub overrides: StrategyOverrides,
+    // Fields not matching any setting
+    #[serde(flatten)]
+    pub unknown: UnknownFields,
+}
+
+/// A configuration file already read from disk, along with its location.
+///
+pub struct Fragment {
+    /// Path to the file the fragment was read from
+    pub path: PathBuf,
+    /// Parsed contents
+    pub config: Config,
 }
 
 impl Config {
     /// Reads a configuration file.
     ///
+    /// Unknown fields are not an error, but they are kept so strict mode can reject them later.
+    ///
     pub fn new<P>(file_path: P) -> Result<Self>
     where
         P: AsRef<Path>,
@@ -27,4 +160,168 @@ impl Config {
         let config = serde_yaml::from_str(&file_content)?;
         Ok(config)
     }
+
+    /// Applies the named profile on top of the config, if declared.
+    ///
+    /// Returns whether the profile was found.
+    ///
+    pub fn select_profile(&mut self, name: &str) -> bool {
+        let Some(profile) = self.profiles.remove(name) else {
+            return false;
+        };
+
+        if profile.keyword.is_some() {
+            self.keyword = profile.keyword;
+        }
+        if !profile.roots.is_empty() {
+            self.roots = profile.roots;
+        }
+        if let Some(relative_roots) = profile.relative_roots {
+            self.relative_roots = relative_roots;
+        }
+        if profile.trace.is_some() {
+            self.trace = profile.trace;
+        }
+        self.exclude.extend(profile.exclude);
+        if profile.min_size.is_some() {
+            self.min_size = profile.min_size;
+        }
+        if profile.max_size.is_some() {
+            self.max_size = profile.max_size;
+        }
+        if profile.signing_key.is_some() {
+            self.signing_key = profile.signing_key;
+        }
+        if profile.history.is_some() {
+            self.history = profile.history;
+        }
+        self.trusted_keys.extend(profile.trusted_keys);
+        if profile.parallel.is_some() {
+            self.parallel = profile.parallel;
+        }
+        if profile.debounce.is_some() {
+            self.debounce = profile.debounce;
+        }
+        if profile.debounce_max.is_some() {
+            self.debounce_max = profile.debounce_max;
+        }
+        if let Some(poll) = profile.poll {
+            self.poll = poll;
+        }
+        if profile.poll_interval.is_some() {
+            self.poll_interval = profile.poll_interval;
+        }
+        if profile.heartbeat.is_some() {
+            self.heartbeat = profile.heartbeat;
+        }
+        if profile.mqtt.is_some() {
+            self.mqtt = profile.mqtt;
+        }
+        self.overrides.merge(profile.overrides);
+
+        true
+    }
+
+    /// Reads a configuration file and every fragment it includes.
+    ///
+    /// The fragments are returned in precedence order, lowest first: the included files come
+    /// before the file including them, and later includes come after earlier ones. Fragments
+    /// not declaring a `keyword` inherit the one of the file including them.
+    ///
+    pub fn load<P>(file_path: P) -> Result<Vec<Fragment>>
+    where
+        P: AsRef<Path>,
+    {
+        let mut fragments = Vec::new();
+        load_fragment(file_path.as_ref(), None, &mut vec![], &mut fragments)?;
+        Ok(fragments)
+    }
+}
+
+impl Strict for Config {
+    fn unknown_fields(&self) -> Vec<String> {
+        let mqtt_fields = |mqtt: &Option<MqttConfig>| {
+            mqtt.iter()
+                .flat_map(|mqtt| mqtt.unknown.keys().map(|field| format!("mqtt.{field}")))
+                .collect::<Vec<_>>()
+        };
+        let mut fields = self.unknown.keys().cloned().collect::<Vec<_>>();
+        fields.extend(mqtt_fields(&self.mqtt));
+        let mut profiles = self.profiles.iter().collect::<Vec<_>>();
+        profiles.sort_by_key(|(name, _)| *name);
+        for (name, profile) in profiles {
+            fields.extend(
+                profile
+                    .unknown
+                    .keys()
+                    .cloned()
+                    .chain(mqtt_fields(&profile.mqtt))
+                    .map(|field| format!("profiles.{name}.{field}")),
+            );
+        }
+        fields
+    }
+}
+
+/// Recursively load a fragment and its includes.
+///
+/// `stack` holds the canonical paths of the fragments being loaded, and is used to detect
+/// include cycles. A file included more than once (but not in a cycle) is only loaded the first time.
+///
+fn load_fragment(
+    file_path: &Path,
+    parent_keyword: Option<&str>,
+    stack: &mut Vec<PathBuf>,
+    fragments: &mut Vec<Fragment>,
+) -> Result<()> {
+    let canonical = file_path.canonicalize()?;
+
+    if let Some(pos) = stack.iter().position(|p| p == &canonical) {
+        let cycle = stack[pos..]
+            .iter()
+            .chain(std::iter::once(&canonical))
+            .map(|p| format!("{p:?}"))
+            .collect::<Vec<_>>()
+            .join(" -> ");
+        bail!("Include cycle detected: {cycle}");
+    }
+    if fragments.iter().any(|f| f.path == canonical) {
+        log::warn!("{file_path:?}: Already included, skipping");
+        return Ok(());
+    }
+
+    let mut config = Config::new(&canonical)?;
+    if config.keyword.is_none() {
+        config.keyword = parent_keyword.map(|k| k.to_owned());
+    }
+
+    stack.push(canonical.clone());
+    let base = canonical.parent().unwrap_or(Path::new("/")).to_owned();
+    for pattern in &config.include {
+        let pattern = if Path::new(pattern).is_absolute() {
+            pattern.to_owned()
+        } else {
+            base.join(pattern).to_string_lossy().to_string()
+        };
+        let mut matches = glob::glob(&pattern)?
+            .flatten()
+            .filter(|p| p.is_file())
+            .collect::<Vec<_>>();
+        if matches.is_empty() {
+            log::warn!("{file_path:?}: Include {pattern:?} does not match any file");
+        }
+        // Glob results are sorted already, but make the order explicit
+        matches.sort();
+        for include in matches {
+            load_fragment(&include, config.keyword.as_deref(), stack, fragments)?;
+        }
+    }
+    stack.pop();
+
+    fragments.push(Fragment {
+        path: canonical,
+        config,
+    });
+
+    Ok(())
 }
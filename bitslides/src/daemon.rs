@@ -0,0 +1,161 @@
+use anyhow::{bail, Result};
+use std::{
+    fs::{File, TryLockError},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// Returns the default PID file location.
+///
+pub fn default_pid_file() -> PathBuf {
+    user_runtime_dir().join("bitslides.pid")
+}
+
+/// Returns the default socket (named pipe on Windows) of the control interface.
+///
+pub fn default_control_socket() -> PathBuf {
+    if cfg!(windows) {
+        PathBuf::from(r"\\.\pipe\bitslides")
+    } else {
+        user_runtime_dir().join("bitslides.sock")
+    }
+}
+
+/// Returns the folder of the files of a running instance.
+///
+/// Without a runtime folder, they are kept in the local data folder of the user rather than in the
+/// temp folder, where any other user could take their place. The working folder is the last
+/// resort.
+///
+fn user_runtime_dir() -> PathBuf {
+    dirs::runtime_dir()
+        .or_else(|| dirs::data_local_dir().map(|data_dir| data_dir.join("bitslides")))
+        .unwrap_or_default()
+}
+
+/// Returns the default log file of a daemon, if there is a data folder.
+///
+pub fn default_log_file() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|data_dir| data_dir.join("bitslides").join("bitslides.log"))
+}
+
+/// Requests to sync every slide right away: SIGUSR1 on Unix. None elsewhere.
+///
+pub struct Rescan {
+    #[cfg(unix)]
+    signal: tokio::signal::unix::Signal,
+}
+
+impl Rescan {
+    /// Start listening for the requests. Must be called within the runtime.
+    ///
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            #[cfg(unix)]
+            signal: tokio::signal::unix::signal(tokio::signal::unix::SignalKind::user_defined1())?,
+        })
+    }
+
+    /// Wait for the next request.
+    ///
+    pub async fn requested(&mut self) {
+        #[cfg(unix)]
+        if self.signal.recv().await.is_some() {
+            return;
+        }
+        std::future::pending().await
+    }
+}
+
+/// Lock the PID file, returning it locked for as long as it is kept open, and inherited by the
+/// processes forked in the meantime.
+///
+/// The running instance keeps the PID file locked, so a PID file left behind by an instance no
+/// longer running is not in the way. Locking the file before writing it, rather than checking it
+/// beforehand, leaves no room for a second instance starting at the same time.
+///
+pub fn lock_pid_file(pid_file: &Path) -> Result<File> {
+    if let Some(parent) = pid_file.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(pid_file)?;
+    match file.try_lock() {
+        Ok(()) => Ok(file),
+        Err(TryLockError::WouldBlock) => bail!("{pid_file:?}: {}", held_by(pid_file)),
+        Err(TryLockError::Error(e)) => Err(e.into()),
+    }
+}
+
+/// Record the PID of the current process in the PID `file` locked, replacing a stale one.
+///
+pub fn record_pid(file: &mut File, pid_file: &Path) -> Result<()> {
+    if file.metadata()?.len() > 0 {
+        log::warn!("{pid_file:?}: Replacing a stale PID file");
+        file.set_len(0)?;
+    }
+    writeln!(file, "{}", std::process::id())?;
+    Ok(())
+}
+
+/// Describe the instance holding a PID file.
+///
+fn held_by(pid_file: &Path) -> String {
+    match std::fs::read_to_string(pid_file)
+        .ok()
+        .and_then(|content| content.trim().parse::<u32>().ok())
+    {
+        Some(pid) => format!("Already running with PID {pid}"),
+        None => "Already running".to_owned(),
+    }
+}
+
+/// Detach the process from the terminal and write the PID file, returned locked.
+///
+/// The process forks twice, so the daemon is neither a session leader nor a child of the
+/// terminal, and its standard streams are redirected to `/dev/null`. The working directory is kept,
+/// so relative paths given in the command line keep working.
+///
+/// This has to be called before any thread is spawned, that is, before starting the async runtime.
+///
+#[cfg(unix)]
+pub fn daemonize(pid_file: &Path) -> Result<File> {
+    use nix::unistd::{fork, setsid, ForkResult};
+    use std::os::fd::AsRawFd;
+
+    // Locked upfront, so a second instance is reported on the terminal, and the lock goes along
+    let mut file = lock_pid_file(pid_file)?;
+
+    // SAFETY: No other thread is running yet
+    if let ForkResult::Parent { .. } = unsafe { fork() }? {
+        std::process::exit(0);
+    }
+    setsid()?;
+    // SAFETY: Same as above, the new session only has this thread
+    if let ForkResult::Parent { .. } = unsafe { fork() }? {
+        std::process::exit(0);
+    }
+
+    let devnull = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/null")?;
+    for fd in 0..3 {
+        // SAFETY: Both file descriptors are valid
+        if unsafe { nix::libc::dup2(devnull.as_raw_fd(), fd) } < 0 {
+            bail!("Unable to redirect the standard streams");
+        }
+    }
+
+    record_pid(&mut file, pid_file)?;
+    Ok(file)
+}
+
+#[cfg(not(unix))]
+pub fn daemonize(_pid_file: &Path) -> Result<File> {
+    bail!("Daemon mode is not supported on this platform, use a service manager instead");
+}
@@ -0,0 +1,72 @@
+use anyhow::{anyhow, Context, Result};
+use bitslideslib::Summary;
+use std::{fmt, process::ExitCode};
+
+/// Exit status of the process.
+///
+/// Scripts wrapping bitslides can tell the outcomes apart by the exit code. Errors carry their
+/// status as context, any error without it exits with `Failure`.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// Everything went fine, including when there was nothing to do
+    Success = 0,
+    /// Unexpected error
+    Failure = 1,
+    /// Invalid command line or configuration
+    Config = 2,
+    /// Some of the syncjobs failed
+    PartialFailure = 3,
+    /// Every syncjob failed
+    AllFailed = 4,
+    /// A single pass was interrupted by a termination signal
+    Interrupted = 5,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            Self::Success => "Success",
+            Self::Failure => "Failure",
+            Self::Config => "Invalid configuration",
+            Self::PartialFailure => "Some syncjobs failed",
+            Self::AllFailed => "Every syncjob failed",
+            Self::Interrupted => "Interrupted",
+        };
+        write!(f, "{description}")
+    }
+}
+
+impl Status {
+    /// Status of the result of a command.
+    ///
+    pub fn of(result: &Result<()>) -> Self {
+        match result {
+            Ok(()) => Self::Success,
+            Err(e) => e.downcast_ref::<Self>().copied().unwrap_or(Self::Failure),
+        }
+    }
+}
+
+impl From<Status> for ExitCode {
+    fn from(status: Status) -> Self {
+        ExitCode::from(status as u8)
+    }
+}
+
+/// Turns the outcome of the syncjobs into an error if any of them failed.
+///
+pub fn check_summary(summary: Summary) -> Result<()> {
+    let Summary {
+        syncjobs, failed, ..
+    } = summary;
+    if failed == 0 {
+        return Ok(());
+    }
+    let status = if failed == syncjobs {
+        Status::AllFailed
+    } else {
+        Status::PartialFailure
+    };
+    Err(anyhow!("{failed} of {syncjobs} syncjob(s) failed")).context(status)
+}
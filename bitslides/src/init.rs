@@ -0,0 +1,165 @@
+use anyhow::{anyhow, bail, Result};
+use bitslideslib::Volume;
+use std::path::{Path, PathBuf};
+
+use crate::config::DEFAULT_KEYWORD;
+
+/// Starter configuration file. `{roots}` is replaced by the list of roots.
+///
+const TEMPLATE: &str = r#"# keyword: Any synchable location has to contain a folder called as this keyword. The keyword defaults to "Slides"
+keyword: "Slides"
+
+# roots: List of root folders where the software will look for volumes (synchable locations).
+#  Relative paths will be solved from the conf file location
+#  Glob patterns (ex. /media/*) are expanded on every scan
+#  On Windows, every available logical drive will also be checked to be a volume
+{roots}
+
+# trace: Configure the software to write each event to a file.
+#trace: "bitslides.%Y%m%d_%H%M%S.log"
+
+# history: SQLite database recording every traced event too, queried by "bitslides history".
+#history: "bitslides.db"
+
+# exclude: Gitignore-style patterns of the slide contents that will never be synced.
+#exclude:
+# - "*.tmp"
+# - "Thumbs.db"
+
+# min_size, max_size: Files out of this size range are left untouched (ex. "50G").
+#max_size: "50G"
+
+# Move strategy settings
+#collision: "fail"
+#conflict: "report"
+#skip_identical: "quick"
+#check: "blake"
+#recheck: false
+#check_mode: "full"
+#safe: true
+#wip_suffix: "wip"
+#staging: false
+#retries: 5
+#max_failures: 5
+#min_interval: 0
+#jobs: 4
+#delta: false
+#preserve_times: true
+#preserve_permissions: true
+#preserve_xattrs: true
+#rate_limit: "10M"
+#sanitize: "underscore"
+#use_trash: "overwritten:30"
+#min_age: 10
+#max_file_size: "100G"
+#sidecar: "folder"
+#reverify: "30:restore"
+#delivery_manifest: true
+
+# parallel: Number of syncjobs syncing at once, the others waiting for their turn. Defaults to jobs.
+#parallel: 2
+
+# debounce: Seconds without filesystem events awaited before syncing a slide, or 0 to sync at once.
+#debounce: 2
+# debounce_max: Most seconds a burst of filesystem events may postpone a sync, or 0 to wait for
+#  the burst to end.
+#debounce_max: 60
+
+# poll: Poll the slides of these roots for changes rather than watching them, for the network
+#  filesystems and FUSE mounts not reporting their events. The slides the filesystem watcher
+#  fails to watch are always polled.
+#poll: false
+# poll_interval: Seconds between two scans of the slides polled.
+#poll_interval: 30
+
+# heartbeat: Seconds between two heartbeats traced along a sync lasting longer, 0 for none.
+#heartbeat: 60
+
+# signing_key: Key signing the manifests of the deliveries, written by "bitslides keygen".
+#signing_key: "bitslides.key"
+
+# trusted_keys: Public keys of the machines whose manifests are checked by verify.
+#trusted_keys:
+# - "<public key printed by bitslides keygen>"
+
+# mqtt: Broker where the state of the syncjobs is published, under the topic prefix.
+#mqtt:
+#  host: "localhost"
+#  port: 1883
+#  topic: "bitslides"
+
+# strict: Reject unknown settings (typically typos) in the config files.
+#strict: true
+"#;
+
+/// Usual mount points, probed to pre-populate the roots.
+///
+fn mount_points() -> Vec<PathBuf> {
+    let mut candidates = vec![
+        PathBuf::from("/media"),
+        PathBuf::from("/mnt"),
+        PathBuf::from("/Volumes"),
+    ];
+    if let Ok(user) = std::env::var("USER") {
+        candidates.insert(0, PathBuf::from("/run/media").join(&user));
+        candidates.insert(0, PathBuf::from("/media").join(&user));
+    }
+    candidates
+}
+
+/// Keeps the mount points with a volume mounted on them, that is with a drive holding a folder
+/// called as the keyword.
+///
+pub fn volume_roots(mount_points: Vec<PathBuf>, keyword: &str) -> Vec<PathBuf> {
+    mount_points
+        .into_iter()
+        .filter(|mount_point| {
+            mount_point.read_dir().is_ok_and(|entries| {
+                entries.flatten().any(|entry| {
+                    entry.file_type().is_ok_and(|t| t.is_dir())
+                        && Volume::from_path(entry.path(), keyword).is_some()
+                })
+            })
+        })
+        .collect()
+}
+
+/// Renders the starter configuration with the given roots.
+///
+fn render(roots: &[PathBuf]) -> String {
+    let roots = if roots.is_empty() {
+        "roots: []\n# - /media\n# - /mnt".to_owned()
+    } else {
+        std::iter::once("roots:".to_owned())
+            .chain(roots.iter().map(|r| format!(" - \"{}\"", r.display())))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    TEMPLATE.replace("{roots}", &roots)
+}
+
+/// Writes a starter configuration file.
+///
+/// Refuses to overwrite an existing file unless `force` is set. If `probe` is set, the roots are
+/// pre-populated with the usual mount points that have volumes mounted on them.
+///
+pub fn init(path: &Path, force: bool, probe: bool) -> Result<()> {
+    if path.exists() && !force {
+        bail!("{path:?}: Config already exists, use --force to overwrite it");
+    }
+
+    let roots = if probe {
+        volume_roots(mount_points(), DEFAULT_KEYWORD)
+    } else {
+        vec![]
+    };
+
+    let parent = path
+        .parent()
+        .ok_or_else(|| anyhow!("Config path has no parent: {path:?}"))?;
+    std::fs::create_dir_all(parent)?;
+    std::fs::write(path, render(&roots))?;
+
+    println!("Configuration written to {}", path.display());
+    Ok(())
+}
@@ -1,18 +1,37 @@
-use anyhow::{bail, Result};
-use bitslideslib::{enough, slide, Algorithm, CollisionPolicy, GlobalConfig, RootsetConfig};
+use anyhow::{anyhow, bail, Context, Result};
+use bitslideslib::{
+    config::{StrategyOverrides, Strict},
+    control, enough, explain_routes, export_history, generate_key, history, list_volumes, plan,
+    rebuild, slide, status, tidy_up, undo, validate, verify, Algorithm, CheckMode, CollisionPolicy,
+    ConflictPolicy, ControlCommand, ExportFormat, GlobalConfig, HistoryQuery, Identical,
+    MqttConfig, OutputFormat, Reverify, RootsetConfig, Sanitize, Sidecar, Trash, UndoOutcome,
+};
 use chrono::prelude::*;
-use config::DEFAULT_KEYWORD;
-use std::path::PathBuf;
+use clap::{parser::ValueSource, ArgMatches};
+use config::{RelativeTo, DEFAULT_KEYWORD};
+use exit::Status;
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::Duration,
+};
 
-#[cfg(not(test))]
-use anyhow::anyhow;
 #[cfg(not(test))]
 use log::LevelFilter;
 #[cfg(not(test))]
-use simplelog::{ColorChoice, Config, TermLogger, TerminalMode};
+use simplelog::{
+    ColorChoice, CombinedLogger, Config, SharedLogger, TermLogger, TerminalMode, WriteLogger,
+};
 
 mod cli;
 mod config;
+mod daemon;
+mod exit;
+mod init;
+
+/// Longest wait for the moves still running once interrupted, before exiting anyway.
+///
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(10);
 
 /// Generates the trace path from the given format.
 ///
@@ -40,43 +59,290 @@ fn generate_trace_path(trace_fmt: &str) -> Option<PathBuf> {
     Some(trace)
 }
 
-/// Processes all configuration files and returns a list of `RootsetConfig` instances.
+/// Settings gathered from all the configuration files.
+///
+#[derive(Default)]
+struct Settings {
+    /// Rootsets declared by every config file
+    rootsets: Vec<RootsetConfig>,
+    /// Trace path, if any config file declared it
+    trace: Option<PathBuf>,
+    /// History database, if any config file declared it. Later config files take precedence
+    history: Option<PathBuf>,
+    /// Exclusion patterns declared by every config file
+    exclude: Vec<String>,
+    /// Size range of the files to sync, in bytes. Later config files take precedence
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    /// Move strategy settings. Later config files take precedence
+    overrides: StrategyOverrides,
+    /// Key signing the manifests of the deliveries. Later config files take precedence
+    signing_key: Option<PathBuf>,
+    /// Public keys whose manifests are trusted, declared by every config file
+    trusted_keys: Vec<String>,
+    /// Number of syncjobs syncing at once. Later config files take precedence
+    parallel: Option<usize>,
+    /// Seconds without filesystem events awaited before syncing. Later config files take
+    /// precedence
+    debounce: Option<u64>,
+    /// Most seconds a burst of filesystem events may postpone a sync. Later config files take
+    /// precedence
+    debounce_max: Option<u64>,
+    /// Seconds between two scans of the slides polled. Later config files take precedence
+    poll_interval: Option<u64>,
+    /// Seconds between two heartbeats of a sync lasting longer. Later config files take precedence
+    heartbeat: Option<u64>,
+    /// MQTT broker where the state of the syncjobs is published. Later config files take
+    /// precedence
+    mqtt: Option<MqttConfig>,
+    /// Trace path format the trace path was generated from
+    trace_fmt: Option<String>,
+    /// Conflicts found while merging the config files
+    conflicts: Vec<String>,
+    /// If true, unknown fields in any config file are an error
+    strict: bool,
+}
+
+impl Settings {
+    /// Merge a rootset declared in the config file at `origin`.
+    ///
+    /// Rootsets sharing the keyword are merged, and duplicated roots are dropped. A root declared
+    /// with different keywords is reported as a conflict, although it is kept.
+    ///
+    fn add_rootset(&mut self, rootset: RootsetConfig, origin: &Path) {
+        for root in &rootset.roots {
+            for other in &self.rootsets {
+                if other.keyword != rootset.keyword && other.roots.contains(root) {
+                    self.conflicts.push(format!(
+                        "{origin:?}: Root {root:?} declared with keywords \"{}\" and \"{}\"",
+                        other.keyword, rootset.keyword
+                    ));
+                }
+            }
+        }
+
+        match self
+            .rootsets
+            .iter_mut()
+            .find(|r| r.keyword == rootset.keyword)
+        {
+            Some(existing) => {
+                existing.poll |= rootset.poll;
+                for root in rootset.roots {
+                    if existing.roots.contains(&root) {
+                        log::debug!("{origin:?}: Root {root:?} already declared");
+                        continue;
+                    }
+                    existing.roots.push(root);
+                }
+            }
+            None => self.rootsets.push(rootset),
+        }
+    }
+
+    /// Merge the scalar settings declared in the config file at `origin`. Later files take precedence.
+    ///
+    /// Overriding a previous value with a different one is reported as a conflict.
+    ///
+    #[allow(clippy::too_many_arguments)]
+    fn merge_scalars(
+        &mut self,
+        trace_fmt: Option<String>,
+        overrides: StrategyOverrides,
+        size_range: (Option<u64>, Option<u64>),
+        paths: (Option<PathBuf>, Option<PathBuf>),
+        parallel: Option<usize>,
+        debounce: (Option<u64>, Option<u64>),
+        intervals: (Option<u64>, Option<u64>),
+        mqtt: Option<MqttConfig>,
+        origin: &Path,
+    ) {
+        if let Some(trace_fmt) = trace_fmt {
+            if self.trace_fmt.as_ref().is_some_and(|t| t != &trace_fmt) {
+                self.conflicts.push(format!(
+                    "{origin:?}: Setting \"trace\" overrides a previous value"
+                ));
+            }
+            self.trace = generate_trace_path(&trace_fmt);
+            self.trace_fmt = Some(trace_fmt);
+        }
+
+        let (min_size, max_size) = size_range;
+        for (setting, current, other) in [
+            ("min_size", &mut self.min_size, min_size),
+            ("max_size", &mut self.max_size, max_size),
+        ] {
+            if let Some(other) = other {
+                if current.is_some_and(|c| c != other) {
+                    self.conflicts.push(format!(
+                        "{origin:?}: Setting \"{setting}\" overrides a previous value"
+                    ));
+                }
+                *current = Some(other);
+            }
+        }
+
+        let (signing_key, history) = paths;
+        for (setting, current, other) in [
+            ("signing_key", &mut self.signing_key, signing_key),
+            ("history", &mut self.history, history),
+        ] {
+            if let Some(other) = other {
+                if current.as_ref().is_some_and(|c| c != &other) {
+                    self.conflicts.push(format!(
+                        "{origin:?}: Setting \"{setting}\" overrides a previous value"
+                    ));
+                }
+                *current = Some(other);
+            }
+        }
+
+        if let Some(parallel) = parallel {
+            if self.parallel.is_some_and(|p| p != parallel) {
+                self.conflicts.push(format!(
+                    "{origin:?}: Setting \"parallel\" overrides a previous value"
+                ));
+            }
+            self.parallel = Some(parallel);
+        }
+
+        let (debounce, debounce_max) = debounce;
+        for (setting, current, other) in [
+            ("debounce", &mut self.debounce, debounce),
+            ("debounce_max", &mut self.debounce_max, debounce_max),
+        ] {
+            if let Some(other) = other {
+                if current.is_some_and(|c| c != other) {
+                    self.conflicts.push(format!(
+                        "{origin:?}: Setting \"{setting}\" overrides a previous value"
+                    ));
+                }
+                *current = Some(other);
+            }
+        }
+
+        let (poll_interval, heartbeat) = intervals;
+        for (setting, current, other) in [
+            ("poll_interval", &mut self.poll_interval, poll_interval),
+            ("heartbeat", &mut self.heartbeat, heartbeat),
+        ] {
+            if let Some(other) = other {
+                if current.is_some_and(|c| c != other) {
+                    self.conflicts.push(format!(
+                        "{origin:?}: Setting \"{setting}\" overrides a previous value"
+                    ));
+                }
+                *current = Some(other);
+            }
+        }
+
+        if let Some(mqtt) = mqtt {
+            if self.mqtt.as_ref().is_some_and(|m| m != &mqtt) {
+                self.conflicts.push(format!(
+                    "{origin:?}: Setting \"mqtt\" overrides a previous value"
+                ));
+            }
+            self.mqtt = Some(mqtt);
+        }
+
+        for setting in self.overrides.merge(overrides) {
+            self.conflicts.push(format!(
+                "{origin:?}: Setting \"{setting}\" overrides a previous value"
+            ));
+        }
+    }
+}
+
+/// Processes all configuration files and returns the gathered `Settings`.
+///
+/// If a `profile` is given, it is applied on top of every config file declaring it. If `strict`,
+/// or any config file enables it, unknown fields in the config files are an error.
 ///
 fn process_all_configs(
     config_paths: Vec<&PathBuf>,
-) -> Result<(Vec<RootsetConfig>, Option<PathBuf>)> {
+    profile: Option<&str>,
+    strict: bool,
+) -> Result<Settings> {
     let mut success = false;
-    let mut rootsets = Vec::new();
-    let mut trace = None;
+    let mut profile_found = false;
+    let mut unknown = Vec::new();
+    let mut settings = Settings {
+        strict,
+        ..Default::default()
+    };
 
     for config_path in config_paths {
         log::info!("Loading configuration from: {config_path:?}...");
 
         if config_path.exists() {
-            match config::Config::new(config_path) {
-                Ok(config) => {
-                    let keyword = config.keyword.unwrap_or(DEFAULT_KEYWORD.to_owned());
-                    let roots = config
-                        .roots
-                        .into_iter()
-                        .map(|x| {
-                            if x.contains("$") {
-                                unimplemented!("Environment variables not supported yet");
-                            }
-                            let x = PathBuf::from(x);
-                            if x.is_absolute() {
-                                x
-                            } else {
-                                PathBuf::from(config_path.parent().unwrap()).join(x)
-                            }
-                        })
-                        .collect::<Vec<PathBuf>>();
+            match config::Config::load(config_path) {
+                Ok(fragments) => {
+                    for config::Fragment { path, mut config } in fragments {
+                        settings.strict |= config.strict;
+                        if let Err(e) = config.check_unknown() {
+                            unknown.push(format!("{path:?}: {e}"));
+                        }
+
+                        if let Some(profile) = profile {
+                            profile_found |= config.select_profile(profile);
+                        }
+
+                        let keyword = config.keyword.unwrap_or(DEFAULT_KEYWORD.to_owned());
+                        let roots = config
+                            .roots
+                            .into_iter()
+                            .map(|x| {
+                                if x.contains("$") {
+                                    bail!(
+                                        "{path:?}: Environment variables are not supported in roots"
+                                    );
+                                }
+                                let x = PathBuf::from(x);
+                                if x.is_absolute() {
+                                    Ok(x)
+                                } else {
+                                    match config.relative_roots {
+                                        RelativeTo::Config => Ok(path
+                                            .parent()
+                                            .ok_or_else(|| {
+                                                anyhow!("Config path has no parent: {path:?}")
+                                            })?
+                                            .join(x)),
+                                        RelativeTo::Cwd => Ok(std::path::absolute(x)?),
+                                    }
+                                }
+                            })
+                            .collect::<Result<Vec<PathBuf>>>()?;
 
-                    rootsets.push(RootsetConfig { keyword, roots });
+                        // Fragments may only carry settings (or further includes)
+                        if !roots.is_empty() {
+                            settings.add_rootset(
+                                RootsetConfig {
+                                    keyword,
+                                    roots,
+                                    poll: config.poll,
+                                },
+                                &path,
+                            );
+                        }
 
-                    // Yeah, only the trace of the last config file that defines it will prevail
-                    if let Some(trace_fmt) = config.trace {
-                        trace = generate_trace_path(&trace_fmt);
+                        // Relative to the config file declaring them
+                        let parent = path.parent().unwrap_or(Path::new("/"));
+                        let signing_key = config.signing_key.map(|key| parent.join(key));
+                        let history = config.history.map(|history| parent.join(history));
+                        settings.merge_scalars(
+                            config.trace,
+                            config.overrides,
+                            (config.min_size, config.max_size),
+                            (signing_key, history),
+                            config.parallel,
+                            (config.debounce, config.debounce_max),
+                            (config.poll_interval, config.heartbeat),
+                            config.mqtt,
+                            &path,
+                        );
+                        settings.exclude.extend(config.exclude);
+                        settings.trusted_keys.extend(config.trusted_keys);
                     }
                 }
                 Err(e) => {
@@ -96,7 +362,396 @@ fn process_all_configs(
         bail!("No valid configuration file found");
     }
 
-    Ok((rootsets, trace))
+    if settings.strict && !unknown.is_empty() {
+        bail!("{}", unknown.join("\n"));
+    }
+
+    if let Some(profile) = profile {
+        if !profile_found {
+            bail!("Profile \"{profile}\" not found in any configuration file");
+        }
+    }
+
+    for conflict in &settings.conflicts {
+        log::warn!("{conflict}");
+    }
+
+    Ok(settings)
+}
+
+/// Validates the configuration files and the environment they describe.
+///
+/// Every problem found is printed. Fails if there is any.
+///
+fn validate_configs(
+    config_paths: Vec<&PathBuf>,
+    defaulted: bool,
+    profile: Option<&str>,
+    strict: bool,
+) -> Result<()> {
+    let mut issues = Vec::new();
+    let mut valid_paths = Vec::new();
+
+    for config_path in config_paths {
+        if !config_path.exists() {
+            // Missing default config files are expected
+            if !defaulted {
+                issues.push(format!("{config_path:?}: Config not found"));
+            }
+            continue;
+        }
+        match config::Config::load(config_path) {
+            Ok(_) => valid_paths.push(config_path),
+            Err(e) => issues.push(format!("{config_path:?}: Invalid config format: {e}")),
+        }
+    }
+
+    if valid_paths.is_empty() {
+        issues.push("No valid configuration file found".to_owned());
+    } else {
+        match process_all_configs(valid_paths, profile, strict) {
+            Ok(settings) => {
+                issues.extend(settings.conflicts);
+                issues.extend(validate(&settings.rootsets, settings.strict));
+            }
+            Err(e) => issues.push(e.to_string()),
+        }
+    }
+
+    for issue in &issues {
+        println!("{issue}");
+    }
+
+    if !issues.is_empty() {
+        bail!("Found {} problem(s) in the configuration", issues.len());
+    }
+
+    println!("Configuration is valid");
+    Ok(())
+}
+
+/// Options of the subcommands transferring files.
+///
+struct TransferOptions {
+    /// If true, do not perform any filesystem operation
+    dry_run: bool,
+    /// If true, disable the safe move algorithm
+    non_safe: bool,
+    /// Extension of the .wip files overriding the configured one
+    wip_suffix: Option<String>,
+    /// If true, the .wip files are created in a staging folder of the destination slide
+    staging: bool,
+    /// Number of retries in case of a failure
+    retries: u8,
+    /// Whether the retries were given in the command line, hence take precedence over the config files
+    retries_from_cli: bool,
+    /// Number of syncs failing in a row before giving up on a slide, overriding the configured one
+    max_failures: Option<u32>,
+    /// Least seconds between the starts of two syncs of a slide, overriding the configured one
+    min_interval: Option<u64>,
+    /// If true, sync a single time and exit
+    once: bool,
+    /// Exclusion patterns added to the configured ones
+    exclude: Vec<String>,
+    /// Collision policy overriding the configured one
+    collision: Option<CollisionPolicy>,
+    /// Conflict policy overriding the configured one
+    conflict: Option<ConflictPolicy>,
+    /// Comparison of the identical files overriding the configured one, `Some(None)` to disable it
+    skip_identical: Option<Option<Identical>>,
+    /// Checksum algorithm overriding the configured one, `Some(None)` to disable the checksum
+    check: Option<Option<Algorithm>>,
+    /// If true, the copies are read back to check them
+    recheck: bool,
+    /// How much of the copies is read back, overriding the configured mode
+    check_mode: Option<CheckMode>,
+    /// Number of files moved concurrently within a slide
+    jobs: usize,
+    /// Whether the jobs were given in the command line, hence take precedence over the config files
+    jobs_from_cli: bool,
+    /// Number of syncjobs syncing at once, overriding the configured one
+    parallel: Option<usize>,
+    /// Seconds without filesystem events awaited before syncing, overriding the configured ones
+    debounce: Option<u64>,
+    /// Most seconds a burst of filesystem events may postpone a sync, overriding the configured
+    /// ones
+    debounce_max: Option<u64>,
+    /// If true, the slides of every rootset are polled rather than watched
+    poll: bool,
+    /// Seconds between two scans of the slides polled, overriding the configured ones
+    poll_interval: Option<u64>,
+    /// Seconds between two heartbeats of a sync lasting longer, overriding the configured ones
+    heartbeat: Option<u64>,
+    /// If true, show the bytes moved per file and per syncjob
+    progress: bool,
+    /// If true, only the changed blocks of the overwritten files are copied
+    delta: bool,
+    /// If true, the moved files keep their modification and access times
+    preserve_times: bool,
+    /// If true, the moved files keep their permissions, and ownership if allowed
+    preserve_permissions: bool,
+    /// If true, the moved files keep their extended attributes, or alternate data streams
+    preserve_xattrs: bool,
+    /// Bytes per second copied by each syncjob, overriding the configured limit
+    rate_limit: Option<u64>,
+    /// Sanitization of the file names overriding the configured one, `Some(None)` to disable it
+    sanitize: Option<Option<Sanitize>>,
+    /// Use of the trash overriding the configured one, `Some(None)` to delete the files instead
+    use_trash: Option<Option<Trash>>,
+    /// Seconds a file has to go unmodified before it is moved, overriding the configured ones
+    min_age: Option<u64>,
+    /// Size cap of the files, overriding the configured ones
+    max_file_size: Option<u64>,
+    /// Checksum files overriding the configured ones, `Some(None)` to write none
+    sidecar: Option<Option<Sidecar>>,
+    /// Second check of the deliveries overriding the configured one, `Some(None)` to disable it
+    reverify: Option<Option<Reverify>>,
+    /// If true, each sync writes a manifest of the files it delivered to the destination slide
+    delivery_manifest: bool,
+    /// Size range of the files to sync, overriding the configured one
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    /// Socket where to serve the control interface while watching the slides
+    control: Option<PathBuf>,
+}
+
+impl TransferOptions {
+    /// Reads the options from the subcommand matches. Without them, the defaults are used.
+    ///
+    fn new(command: &str, matches: Option<&ArgMatches>) -> Self {
+        match matches {
+            Some(matches) => Self {
+                once: command == "once" || matches.get_flag("once"),
+                collision: matches.get_one::<CollisionPolicy>("collision").cloned(),
+                conflict: matches.get_one::<ConflictPolicy>("conflict").copied(),
+                skip_identical: matches
+                    .get_one::<Option<Identical>>("skip-identical")
+                    .copied(),
+                check: matches.get_one::<Option<Algorithm>>("check").copied(),
+                recheck: matches.get_flag("recheck"),
+                check_mode: matches.get_one::<CheckMode>("check-mode").copied(),
+                jobs: *matches.get_one::<u16>("jobs").unwrap() as usize,
+                jobs_from_cli: matches.value_source("jobs") == Some(ValueSource::CommandLine),
+                parallel: matches
+                    .get_one::<u16>("parallel")
+                    .map(|parallel| *parallel as usize),
+                debounce: matches.get_one::<u64>("debounce").copied(),
+                debounce_max: matches.get_one::<u64>("debounce-max").copied(),
+                poll: matches.get_flag("poll"),
+                poll_interval: matches.get_one::<u64>("poll-interval").copied(),
+                heartbeat: matches.get_one::<u64>("heartbeat").copied(),
+                progress: matches.get_flag("progress"),
+                delta: matches.get_flag("delta"),
+                preserve_times: matches.get_flag("preserve-times"),
+                preserve_permissions: matches.get_flag("preserve-permissions"),
+                preserve_xattrs: matches.get_flag("preserve-xattrs"),
+                rate_limit: matches.get_one::<u64>("rate-limit").copied(),
+                sanitize: matches.get_one::<Option<Sanitize>>("sanitize").copied(),
+                use_trash: matches.get_one::<Option<Trash>>("use-trash").copied(),
+                min_age: matches.get_one::<u64>("min-age").copied(),
+                max_file_size: matches.get_one::<u64>("max-file-size").copied(),
+                sidecar: matches.get_one::<Option<Sidecar>>("sidecar").copied(),
+                reverify: matches.get_one::<Option<Reverify>>("reverify").copied(),
+                delivery_manifest: matches.get_flag("delivery-manifest"),
+                min_size: matches.get_one::<u64>("min-size").copied(),
+                max_size: matches.get_one::<u64>("max-size").copied(),
+                // A daemon always listens, as there is no other way to know what it is up to
+                control: match matches.try_get_one::<PathBuf>("control").ok().flatten() {
+                    Some(control) => Some(control.to_owned()),
+                    None if matches!(matches.try_get_one::<bool>("daemon"), Ok(Some(true))) => {
+                        Some(daemon::default_control_socket())
+                    }
+                    None => None,
+                },
+                // Includes are negated patterns, so they have to come after every exclude
+                exclude: matches
+                    .get_many::<String>("exclude")
+                    .into_iter()
+                    .flatten()
+                    .cloned()
+                    .chain(
+                        matches
+                            .get_many::<String>("include")
+                            .into_iter()
+                            .flatten()
+                            .map(|pattern| format!("!{pattern}")),
+                    )
+                    .collect(),
+                dry_run: matches.get_flag("dry-run"),
+                non_safe: matches.get_flag("non-safe"),
+                wip_suffix: matches.get_one::<String>("wip-suffix").cloned(),
+                staging: matches.get_flag("staging"),
+                retries: *matches.get_one::<u8>("retries").unwrap(),
+                retries_from_cli: matches.value_source("retries") == Some(ValueSource::CommandLine),
+                max_failures: matches.get_one::<u32>("max-failures").copied(),
+                min_interval: matches.get_one::<u64>("min-interval").copied(),
+            },
+            None => Self {
+                dry_run: false,
+                non_safe: false,
+                wip_suffix: None,
+                staging: false,
+                retries: cli::DEFAULT_RETRIES,
+                retries_from_cli: false,
+                max_failures: None,
+                min_interval: None,
+                once: false,
+                exclude: vec![],
+                collision: None,
+                conflict: None,
+                skip_identical: None,
+                check: None,
+                recheck: false,
+                check_mode: None,
+                jobs: cli::default_jobs(),
+                jobs_from_cli: false,
+                parallel: None,
+                debounce: None,
+                debounce_max: None,
+                poll: false,
+                poll_interval: None,
+                heartbeat: None,
+                progress: false,
+                delta: false,
+                preserve_times: false,
+                preserve_permissions: false,
+                preserve_xattrs: false,
+                rate_limit: None,
+                sanitize: None,
+                use_trash: None,
+                min_age: None,
+                max_file_size: None,
+                sidecar: None,
+                reverify: None,
+                delivery_manifest: false,
+                min_size: None,
+                max_size: None,
+                control: None,
+            },
+        }
+    }
+}
+
+/// Builds the library configuration from the gathered settings and the command line options.
+///
+fn global_config(
+    settings: Settings,
+    options: &TransferOptions,
+    output: OutputFormat,
+) -> GlobalConfig {
+    let overrides = settings.overrides;
+    let jobs = match options.jobs_from_cli {
+        true => options.jobs,
+        false => overrides.jobs.unwrap_or(options.jobs).max(1),
+    };
+
+    GlobalConfig {
+        rootsets: settings.rootsets,
+        dry_run: options.dry_run,
+        trace: settings.trace,
+        history: settings.history,
+        check: options
+            .check
+            .or(overrides.check)
+            .unwrap_or(Some(Algorithm::BLAKE)),
+        recheck: options.recheck || overrides.recheck.unwrap_or(false),
+        check_mode: options
+            .check_mode
+            .or(overrides.check_mode)
+            .unwrap_or_default(),
+        collision: options
+            .collision
+            .clone()
+            .or(overrides.collision)
+            .unwrap_or(CollisionPolicy::Fail),
+        conflict: options.conflict.or(overrides.conflict).unwrap_or_default(),
+        skip_identical: options
+            .skip_identical
+            .or(overrides.skip_identical)
+            .flatten(),
+        safe: !options.non_safe && overrides.safe.unwrap_or(true),
+        wip_suffix: options
+            .wip_suffix
+            .clone()
+            .or(overrides.wip_suffix)
+            .unwrap_or(cli::DEFAULT_WIP_SUFFIX.to_owned()),
+        staging: options.staging || overrides.staging.unwrap_or(false),
+        retries: match options.retries_from_cli {
+            true => options.retries,
+            false => overrides.retries.unwrap_or(options.retries),
+        },
+        max_failures: options
+            .max_failures
+            .or(overrides.max_failures)
+            .unwrap_or(cli::DEFAULT_MAX_FAILURES),
+        min_interval: options
+            .min_interval
+            .or(overrides.min_interval)
+            .unwrap_or(cli::DEFAULT_MIN_INTERVAL),
+        delta: options.delta || overrides.delta.unwrap_or(false),
+        preserve_times: options.preserve_times || overrides.preserve_times.unwrap_or(false),
+        preserve_permissions: options.preserve_permissions
+            || overrides.preserve_permissions.unwrap_or(false),
+        preserve_xattrs: options.preserve_xattrs || overrides.preserve_xattrs.unwrap_or(false),
+        rate_limit: options.rate_limit.or(overrides.rate_limit),
+        sanitize: options
+            .sanitize
+            .or(overrides.sanitize)
+            .unwrap_or(Some(Sanitize::Underscore)),
+        use_trash: options.use_trash.or(overrides.use_trash).flatten(),
+        min_age: options.min_age.or(overrides.min_age),
+        max_file_size: options.max_file_size.or(overrides.max_file_size),
+        sidecar: options.sidecar.or(overrides.sidecar).flatten(),
+        reverify: options.reverify.or(overrides.reverify).flatten(),
+        delivery_manifest: options.delivery_manifest
+            || overrides.delivery_manifest.unwrap_or(false),
+        exclude: settings
+            .exclude
+            .into_iter()
+            .chain(options.exclude.iter().cloned())
+            .collect(),
+        strict: settings.strict,
+        once: options.once,
+        output,
+        jobs,
+        parallel: options
+            .parallel
+            .or(settings.parallel)
+            .unwrap_or(jobs)
+            .max(1),
+        debounce: Some(
+            options
+                .debounce
+                .or(settings.debounce)
+                .unwrap_or(cli::DEFAULT_DEBOUNCE),
+        ),
+        debounce_max: Some(
+            options
+                .debounce_max
+                .or(settings.debounce_max)
+                .unwrap_or(cli::DEFAULT_DEBOUNCE_MAX),
+        ),
+        poll: options.poll,
+        poll_interval: options
+            .poll_interval
+            .or(settings.poll_interval)
+            .unwrap_or(cli::DEFAULT_POLL_INTERVAL)
+            .max(1),
+        heartbeat: Some(
+            options
+                .heartbeat
+                .or(settings.heartbeat)
+                .unwrap_or(cli::DEFAULT_HEARTBEAT),
+        )
+        .filter(|heartbeat| *heartbeat > 0),
+        progress: options.progress,
+        min_size: options.min_size.or(settings.min_size),
+        max_size: options.max_size.or(settings.max_size),
+        control: options.control.clone(),
+        signing_key: settings.signing_key,
+        trusted_keys: settings.trusted_keys,
+        mqtt: settings.mqtt,
+    }
 }
 
 /// Main function with arguments.
@@ -110,82 +765,455 @@ async fn main_w_args(
     let matches = cli::cli().get_matches_from(args);
 
     // Get the configuration files
-    let config_files = matches
-        .get_many::<PathBuf>("config")
-        .expect("No configuration file found among provided/defaults.");
+    let (config_files, defaulted) =
+        cli::config_files(&matches, std::env::var_os(cli::CONFIG_ENV_VAR));
+
+    let profile = matches.get_one::<String>("profile").map(|p| p.as_str());
+    let strict = matches.get_flag("strict-config");
+    let output = cli::output_format(&matches);
 
-    let dry_run = matches.get_flag("dry-run");
-    let non_safe = matches.get_flag("non-safe");
-    let retries = matches.get_one::<u8>("retries").unwrap();
+    // Running without a subcommand is the same as `run` with the default options
+    let (command, sub_matches) = match matches.subcommand() {
+        Some((command, sub_matches)) => (command, Some(sub_matches)),
+        None => ("run", None),
+    };
 
     // Initialize the logging framework if not already done
     #[cfg(not(test))]
     {
+        let dry_run = sub_matches
+            .and_then(|m| m.try_get_one::<bool>("dry-run").ok().flatten())
+            .copied()
+            .unwrap_or(false);
         let verbosity = *matches.get_one::<u8>("verbose").unwrap_or(&0);
-        // Initialize the logging framework
-        TermLogger::init(
-            match verbosity {
-                0 => LevelFilter::Error,
-                1 => LevelFilter::Warn,
-                2 => LevelFilter::Info,
-                3 => LevelFilter::Debug,
-                _ => LevelFilter::Trace,
-            },
+        let term_level = match verbosity {
+            0 => LevelFilter::Error,
+            1 => LevelFilter::Warn,
+            2 => LevelFilter::Info,
+            3 => LevelFilter::Debug,
+            _ => LevelFilter::Trace,
+        };
+        let mut loggers: Vec<Box<dyn SharedLogger>> = vec![TermLogger::new(
+            term_level,
             Config::default(),
             TerminalMode::Stderr,
             ColorChoice::Auto,
-        )
-        .map_err(|_| anyhow!("Unable to initialize log"))?;
+        )];
+
+        // The log file, if any, has its own level
+        // A daemon has no terminal, so it always logs to a file
+        let daemon = sub_matches
+            .and_then(|m| m.try_get_one::<bool>("daemon").ok().flatten())
+            .copied()
+            .unwrap_or(false);
+        let log_file = match matches.get_one::<PathBuf>("log-file") {
+            Some(log_file) => Some(log_file.to_owned()),
+            None if daemon => daemon::default_log_file(),
+            None => None,
+        };
+        let mut file_level = LevelFilter::Off;
+        if let Some(log_file) = log_file {
+            file_level = *matches.get_one::<LevelFilter>("log-file-level").unwrap();
+            if let Some(parent) = log_file.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&log_file)
+                .map_err(|e| anyhow!("{log_file:?}: Unable to open the log file: {e}"))?;
+            loggers.push(WriteLogger::new(file_level, Config::default(), file));
+        }
 
-        if dry_run && verbosity < 2 {
+        // Initialize the logging framework
+        CombinedLogger::init(loggers).map_err(|_| anyhow!("Unable to initialize log"))?;
+
+        if dry_run && term_level < LevelFilter::Info && file_level < LevelFilter::Info {
             bail!("Dry-run mode is enabled, but the verbosity level is too low to see the output");
         }
     }
 
-    let (rootsets, trace) = process_all_configs(config_files.into_iter().collect())?;
+    let config_files = config_files.iter().collect::<Vec<_>>();
 
-    let keep_alive = slide(GlobalConfig {
-        rootsets,
-        dry_run,
-        trace,
-        // FIXME: This should be configurable
-        check: Some(Algorithm::BLAKE),
-        // FIXME: This should be configurable
-        collision: CollisionPolicy::Fail,
-        safe: !non_safe,
-        retries: *retries,
-    })
-    .await?;
+    match (command, sub_matches) {
+        ("validate", _) => {
+            validate_configs(config_files, defaulted, profile, strict).context(Status::Config)
+        }
+        ("init", Some(sub_matches)) => {
+            let path = match sub_matches
+                .get_one::<PathBuf>("path")
+                .or(sub_matches.get_one::<PathBuf>("output"))
+            {
+                Some(path) => path.to_owned(),
+                None => cli::user_config_file().ok_or_else(|| {
+                    anyhow!("Unable to find the user config folder, provide a path")
+                })?,
+            };
+            init::init(
+                &path,
+                sub_matches.get_flag("force"),
+                sub_matches.get_flag("probe"),
+            )
+        }
+        ("keygen", Some(sub_matches)) => {
+            let path = sub_matches.get_one::<PathBuf>("path").unwrap();
+            let public_key = generate_key(path, sub_matches.get_flag("force"))?;
+            match output {
+                OutputFormat::Text => println!("{public_key}"),
+                OutputFormat::Json => println!(
+                    "{}",
+                    serde_json::json!({ "signing_key": path, "public_key": public_key })
+                ),
+            }
+            Ok(())
+        }
+        ("plan", _) => {
+            let settings =
+                process_all_configs(config_files, profile, strict).context(Status::Config)?;
+            let config = global_config(settings, &TransferOptions::new(command, None), output);
+            for job in plan(&config)? {
+                match output {
+                    OutputFormat::Text => println!("{job}"),
+                    OutputFormat::Json => println!("{}", serde_json::to_string(&job)?),
+                }
+            }
+            Ok(())
+        }
+        ("list-volumes", _) => {
+            let settings =
+                process_all_configs(config_files, profile, strict).context(Status::Config)?;
+            let config = global_config(settings, &TransferOptions::new(command, None), output);
+            for volume in list_volumes(&config)? {
+                match output {
+                    OutputFormat::Text => println!("{volume}"),
+                    OutputFormat::Json => println!("{}", serde_json::to_string(&volume)?),
+                }
+            }
+            Ok(())
+        }
+        ("explain-routes", _) => {
+            let settings =
+                process_all_configs(config_files, profile, strict).context(Status::Config)?;
+            let config = global_config(settings, &TransferOptions::new(command, None), output);
+            for route in explain_routes(&config)? {
+                match output {
+                    OutputFormat::Text => println!("{route}"),
+                    OutputFormat::Json => println!("{}", serde_json::to_string(&route)?),
+                }
+            }
+            Ok(())
+        }
+        ("verify", Some(sub_matches)) => {
+            let settings =
+                process_all_configs(config_files, profile, strict).context(Status::Config)?;
+            let config = global_config(settings, &TransferOptions::new(command, None), output);
+            let traces = sub_matches
+                .get_many::<PathBuf>("trace")
+                .into_iter()
+                .flatten()
+                .cloned()
+                .collect::<Vec<_>>();
+            let verifications = verify(&config, &traces, sub_matches.get_flag("rehash"))?;
+            let problems = verifications.iter().filter(|v| !v.is_ok()).count();
+            for verification in &verifications {
+                match output {
+                    OutputFormat::Text if !verification.is_ok() => println!("{verification}"),
+                    OutputFormat::Text => {}
+                    OutputFormat::Json => println!("{}", serde_json::to_string(verification)?),
+                }
+            }
+            if output == OutputFormat::Text {
+                println!(
+                    "Verified {} file(s), found {problems} problem(s)",
+                    verifications.len()
+                );
+            }
+            if problems > 0 {
+                bail!("Found {problems} problem(s) in the verified files");
+            }
+            Ok(())
+        }
+        ("status", Some(sub_matches)) => {
+            let socket = sub_matches.get_one::<PathBuf>("control").unwrap();
+            for job in status(socket).await? {
+                match output {
+                    OutputFormat::Text => println!("{job}"),
+                    OutputFormat::Json => println!("{}", serde_json::to_string(&job)?),
+                }
+            }
+            Ok(())
+        }
+        ("pause" | "resume" | "sync-now" | "shutdown", Some(sub_matches)) => {
+            let socket = sub_matches.get_one::<PathBuf>("control").unwrap();
+            let target = sub_matches
+                .try_get_one::<String>("target")
+                .ok()
+                .flatten()
+                .cloned();
+            let (command, done) = match command {
+                "pause" => (ControlCommand::Pause(target), "Paused"),
+                "resume" => (ControlCommand::Resume(target), "Resumed"),
+                "sync-now" => (ControlCommand::SyncNow(target), "Triggered"),
+                _ => (ControlCommand::Shutdown, "Shutting down"),
+            };
+            let jobs = control(socket, &command).await?;
+            if jobs.is_empty() {
+                println!("{done}");
+            }
+            for job in jobs {
+                println!("{done} {job}");
+            }
+            Ok(())
+        }
+        ("history", Some(sub_matches)) => {
+            // The export takes the same filters, but the operation
+            let (export, sub_matches) = match sub_matches.subcommand_matches("export") {
+                Some(export_matches) => (true, export_matches),
+                None => (false, sub_matches),
+            };
+            let path = match sub_matches.get_one::<PathBuf>("db") {
+                Some(path) => path.to_owned(),
+                None => process_all_configs(config_files, profile, strict)
+                    .context(Status::Config)?
+                    .history
+                    .ok_or_else(|| anyhow!("No history configured, provide a database"))?,
+            };
+            let query = HistoryQuery {
+                since: sub_matches.get_one::<NaiveDateTime>("since").copied(),
+                until: sub_matches.get_one::<NaiveDateTime>("until").copied(),
+                volume: sub_matches.get_one::<String>("volume").cloned(),
+                file: sub_matches.get_one::<String>("file").cloned(),
+                operation: match export {
+                    true => None,
+                    false => sub_matches.get_one::<String>("operation").cloned(),
+                },
+            };
+            if export {
+                let format = sub_matches
+                    .get_one::<ExportFormat>("format")
+                    .copied()
+                    .unwrap_or_default();
+                let exported = export_history(&path, &query, format, std::io::stdout().lock())?;
+                log::info!("{exported} file(s) exported from {path:?}");
+                return Ok(());
+            }
+            for event in history(&path, &query)? {
+                match output {
+                    OutputFormat::Text => println!("{event}"),
+                    OutputFormat::Json => println!("{}", serde_json::to_string(&event)?),
+                }
+            }
+            Ok(())
+        }
+        ("undo", Some(sub_matches)) => {
+            let settings =
+                process_all_configs(config_files, profile, strict).context(Status::Config)?;
+            let mut config = global_config(settings, &TransferOptions::new(command, None), output);
+            config.dry_run = sub_matches.get_flag("dry-run");
+            let traces = sub_matches
+                .get_many::<PathBuf>("trace")
+                .into_iter()
+                .flatten()
+                .cloned()
+                .collect::<Vec<_>>();
+            let since = sub_matches.get_one::<NaiveDateTime>("since").copied();
+            let undone = undo(&config, &traces, since).await?;
+            let restored = undone.iter().filter(|u| u.is_restored()).count();
+            let failed = undone
+                .iter()
+                .filter(|u| matches!(u.outcome, UndoOutcome::Failed(_)))
+                .count();
+            for undone in &undone {
+                match output {
+                    OutputFormat::Text => println!("{undone}"),
+                    OutputFormat::Json => println!("{}", serde_json::to_string(undone)?),
+                }
+            }
+            if output == OutputFormat::Text {
+                match config.dry_run {
+                    true => println!("Would move back {restored} of {} file(s)", undone.len()),
+                    false => println!("Moved back {restored} of {} file(s)", undone.len()),
+                }
+            }
+            if failed > 0 {
+                bail!("Failed to move back {failed} file(s)");
+            }
+            Ok(())
+        }
+        ("tidy", Some(sub_matches)) => {
+            let settings =
+                process_all_configs(config_files, profile, strict).context(Status::Config)?;
+            let mut config = global_config(settings, &TransferOptions::new(command, None), output);
+            config.dry_run = sub_matches.get_flag("dry-run");
+            let tidied = tidy_up(&config).await?;
+            if output == OutputFormat::Text {
+                match config.dry_run {
+                    true => println!("Would tidy up {tidied} file(s)"),
+                    false => println!("Tidied up {tidied} file(s)"),
+                }
+            }
+            Ok(())
+        }
+        (command, sub_matches) => {
+            let settings =
+                process_all_configs(config_files, profile, strict).context(Status::Config)?;
+            let config = global_config(
+                settings,
+                &TransferOptions::new(command, sub_matches),
+                output,
+            );
+            let summary = if config.once {
+                // A single pass is over when slide() returns, unless interrupted
+                let mut shutdown_signal = shutdown_signal;
+                tokio::select! {
+                    keep_alive = slide(config) => enough(keep_alive?).await?,
+                    Ok(()) = &mut shutdown_signal => {
+                        return Err(anyhow!("Received a termination signal before the end of the pass"))
+                            .context(Status::Interrupted);
+                    }
+                }
+            } else {
+                let mut keep_alive = slide(config.clone()).await?;
+                let mut rescan = daemon::Rescan::new()?;
+                let mut shutdown_signal = shutdown_signal;
+                // Wait for shutdown signal (either from Ctrl+C handler, the control interface or test)
+                loop {
+                    tokio::select! {
+                        signal = &mut shutdown_signal => break signal?,
+                        () = keep_alive.stopped() => {
+                            log::info!("Shutdown requested, shutting down...");
+                            break;
+                        }
+                        () = rescan.requested() => {
+                            log::info!("Rescan requested, syncing every slide now...");
+                            if let Err(e) = keep_alive.sync_now(None) {
+                                log::warn!("Nothing to sync: {e}");
+                            }
+                        }
+                        () = keep_alive.replanned() => {
+                            log::info!("The volumes changed, rebuilding the syncjobs...");
+                            // The syncjobs running are kept as they are if the new ones fail
+                            if let Err(e) = rebuild(&mut keep_alive, config.clone()).await {
+                                log::error!("Unable to rebuild the syncjobs: {e}");
+                            }
+                        }
+                    }
+                }
+                enough(keep_alive).await?
+            };
 
-    // Wait for shutdown signal (either from Ctrl+C handler or test)
-    shutdown_signal.await?;
+            for job in &summary.jobs {
+                match output {
+                    OutputFormat::Text => println!("{job}"),
+                    OutputFormat::Json => println!("{}", serde_json::to_string(job)?),
+                }
+            }
+            for issue in &summary.issues {
+                match output {
+                    OutputFormat::Text => println!("{issue}"),
+                    OutputFormat::Json => println!("{}", serde_json::to_string(issue)?),
+                }
+            }
+            if output == OutputFormat::Text {
+                println!("{summary}");
+            }
+            exit::check_summary(summary)
+        }
+    }
+}
 
-    enough(keep_alive).await
+/// Returns the PID file if the daemon mode was requested.
+///
+fn daemon_pid_file(args: &[String]) -> Option<PathBuf> {
+    let matches = cli::cli().try_get_matches_from(args).ok()?;
+    let (_, sub_matches) = matches.subcommand()?;
+    if !sub_matches
+        .try_get_one::<bool>("daemon")
+        .ok()
+        .flatten()
+        .copied()
+        .unwrap_or(false)
+    {
+        return None;
+    }
+    sub_matches.get_one::<PathBuf>("pid-file").cloned()
+}
+
+/// Loads and validates the configuration of a daemon before it detaches, so a broken one is
+/// reported on the terminal it is started from.
+///
+fn check_daemon_config(args: &[String]) -> Result<()> {
+    let matches = cli::cli().get_matches_from(args);
+    let (config_files, _) = cli::config_files(&matches, std::env::var_os(cli::CONFIG_ENV_VAR));
+    let profile = matches.get_one::<String>("profile").map(|p| p.as_str());
+    let strict = matches.get_flag("strict-config");
+
+    let settings = process_all_configs(config_files.iter().collect(), profile, strict)?;
+    let issues = validate(&settings.rootsets, settings.strict);
+    if !issues.is_empty() {
+        bail!("Invalid configuration:\n{}", issues.join("\n"));
+    }
+    Ok(())
 }
 
 /// Entry point of the application.
 ///
-#[tokio::main]
-async fn main() -> Result<()> {
+/// The exit code tells the outcome apart, see `exit::Status`.
+///
+fn main() -> ExitCode {
+    let result = start();
+    if let Err(e) = &result {
+        eprintln!("Error: {e:?}");
+    }
+    Status::of(&result).into()
+}
+
+/// Sets up the process and runs the command line given.
+///
+fn start() -> Result<()> {
     // Collect args
     let args = std::env::args().collect::<Vec<_>>();
 
+    // Detach before any thread is spawned, forking a multi-threaded process is not sound
+    let pid_file = daemon_pid_file(&args);
+    // Kept locked until exiting
+    let _pid_lock = match &pid_file {
+        Some(pid_file) => {
+            check_daemon_config(&args).context(Status::Config)?;
+            Some(daemon::daemonize(pid_file)?)
+        }
+        None => None,
+    };
+
     // Create a oneshot channel for shutdown signal
     let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel();
 
-    // Install custom Ctrl+C handler (cross-platform, not using tokio's signal)
+    // Install custom Ctrl+C and SIGTERM handler (cross-platform, not using tokio's signal)
     // Wrap the sender in an Option so we can take it in the FnMut closure
     let shutdown_tx = std::sync::Mutex::new(Some(shutdown_tx));
     ctrlc::set_handler(move || {
-        log::info!("Received Ctrl+C, shutting down...");
+        log::info!("Received a termination signal, shutting down...");
         if let Some(tx) = shutdown_tx.lock().unwrap().take() {
             let _ = tx.send(());
         }
     })
-    .expect("Failed to set Ctrl+C handler");
+    .expect("Failed to set the termination handler");
 
     // Await on main with shutdown signal
-    main_w_args(&args, shutdown_rx).await
+    let runtime = tokio::runtime::Runtime::new()?;
+    let result = runtime.block_on(main_w_args(&args, shutdown_rx));
+    // The moves of an interrupted pass, their syncjobs closed, get some time to stop between two
+    // chunks and clean up after themselves, but not forever
+    runtime.shutdown_timeout(SHUTDOWN_GRACE);
+
+    if let Some(pid_file) = &pid_file {
+        // Without a terminal, the log file is the only place to tell why the daemon stopped
+        if let Err(e) = &result {
+            log::error!("{e:?}");
+        }
+        let _ = std::fs::remove_file(pid_file);
+    }
+    result
 }
 
 #[cfg(test)]
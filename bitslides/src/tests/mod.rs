@@ -130,3 +130,923 @@ trace: "{}/non-existing-folder/bitslides.log"
 
     assert!(result.is_ok(), "Failed with: {}", result.unwrap_err());
 }
+
+#[test]
+fn test_config_include() {
+    let temp_dir = tempdir().unwrap();
+    let main_config = temp_dir.path().join("config.yml");
+    let machines = temp_dir.path().join("machines");
+    std::fs::create_dir(&machines).unwrap();
+
+    std::fs::write(
+        &main_config,
+        r#"
+keyword: "slides"
+roots:
+- "root0"
+include:
+- "machines/*.yml"
+"#,
+    )
+    .unwrap();
+    std::fs::write(
+        machines.join("laptop.yml"),
+        r#"
+roots:
+- "/media"
+"#,
+    )
+    .unwrap();
+    std::fs::write(
+        machines.join("server.yml"),
+        r#"
+keyword: "queues"
+roots:
+- "/mnt"
+"#,
+    )
+    .unwrap();
+
+    let rootsets = crate::process_all_configs(vec![&main_config], None, false)
+        .unwrap()
+        .rootsets;
+
+    // Included fragments come first, the including file last. Rootsets sharing the keyword are merged
+    assert_eq!(rootsets.len(), 2);
+    assert_eq!(rootsets[0].keyword, "slides");
+    assert_eq!(rootsets[0].roots.len(), 2);
+    assert_eq!(rootsets[0].roots[0], std::path::PathBuf::from("/media"));
+    assert!(rootsets[0].roots[1].ends_with("root0"));
+    assert_eq!(rootsets[1].keyword, "queues");
+    assert_eq!(rootsets[1].roots, vec![std::path::PathBuf::from("/mnt")]);
+}
+
+#[test]
+fn test_config_include_cycle() {
+    let temp_dir = tempdir().unwrap();
+    let a = temp_dir.path().join("a.yml");
+    let b = temp_dir.path().join("b.yml");
+    std::fs::write(&a, "roots: [\"root0\"]\ninclude: [\"b.yml\"]\n").unwrap();
+    std::fs::write(&b, "roots: [\"root1\"]\ninclude: [\"a.yml\"]\n").unwrap();
+
+    let result = crate::config::Config::load(&a);
+    assert!(result.is_err());
+    assert!(result
+        .err()
+        .unwrap()
+        .to_string()
+        .contains("Include cycle detected"));
+}
+
+#[tokio::test]
+async fn test_main_validate() {
+    let temp_dir = tempdir().unwrap();
+    let config_file = temp_dir.path().join("config.yml");
+    std::fs::create_dir(temp_dir.path().join("root0")).unwrap();
+    std::fs::write(&config_file, "keyword: \"slides\"\nroots:\n- \"root0\"\n").unwrap();
+
+    let (_shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel();
+    let args = vec!["bitslides", "-c", config_file.to_str().unwrap(), "validate"];
+    assert!(main_w_args(
+        args.into_iter()
+            .map(|x| x.to_owned())
+            .collect::<Vec<String>>()
+            .as_slice(),
+        shutdown_rx,
+    )
+    .await
+    .is_ok());
+
+    // A missing root makes the configuration invalid
+    std::fs::write(&config_file, "keyword: \"slides\"\nroots:\n- \"root1\"\n").unwrap();
+
+    let (_shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel();
+    let args = vec!["bitslides", "validate", "-c", config_file.to_str().unwrap()];
+    assert!(main_w_args(
+        args.into_iter()
+            .map(|x| x.to_owned())
+            .collect::<Vec<String>>()
+            .as_slice(),
+        shutdown_rx,
+    )
+    .await
+    .is_err());
+
+    // Environment variables in the roots are reported rather than panicking
+    std::fs::write(&config_file, "keyword: \"slides\"\nroots:\n- \"$HOME\"\n").unwrap();
+
+    let (_shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel();
+    let args = vec!["bitslides", "validate", "-c", config_file.to_str().unwrap()];
+    assert!(main_w_args(
+        args.into_iter()
+            .map(|x| x.to_owned())
+            .collect::<Vec<String>>()
+            .as_slice(),
+        shutdown_rx,
+    )
+    .await
+    .is_err());
+}
+
+#[tokio::test]
+async fn test_main_subcommands() {
+    let temp_dir = tempdir().unwrap();
+    let config_file = temp_dir.path().join("config.yml");
+    for volume in ["foo", "bar"] {
+        std::fs::create_dir_all(temp_dir.path().join("root0").join(volume).join("slides")).unwrap();
+    }
+    std::fs::create_dir(temp_dir.path().join("root0/foo/slides/bar")).unwrap();
+    std::fs::write(&config_file, "keyword: \"slides\"\nroots:\n- \"root0\"\n").unwrap();
+
+    let run = |subcommand: &'static str| {
+        let args = ["bitslides", "-c", config_file.to_str().unwrap(), subcommand]
+            .into_iter()
+            .map(|x| x.to_owned())
+            .collect::<Vec<String>>();
+        async move {
+            // The sender is kept alive, so only the commands not waiting for it can finish
+            let (_shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel();
+            tokio::time::timeout(
+                tokio::time::Duration::from_secs(2),
+                main_w_args(&args, shutdown_rx),
+            )
+            .await
+        }
+    };
+
+    // A plan doesn't create the missing slides
+    assert!(run("plan").await.unwrap().is_ok());
+    assert!(!temp_dir.path().join("root0/bar/slides/foo").exists());
+
+    // A single pass returns on its own
+    assert!(run("once").await.unwrap().is_ok());
+
+    // Listing the volumes or the routes doesn't create the missing slides either
+    assert!(run("list-volumes").await.unwrap().is_ok());
+    assert!(run("explain-routes").await.unwrap().is_ok());
+    assert!(!temp_dir.path().join("root0/bar/slides/foo").exists());
+
+    // Tidying up without rules leaves everything in place
+    assert!(run("tidy").await.unwrap().is_ok());
+
+    // Run keeps watching until the shutdown signal
+    assert!(run("run").await.is_err());
+}
+
+#[tokio::test]
+async fn test_main_history() {
+    let temp_dir = tempdir().unwrap();
+    let config_file = temp_dir.path().join("config.yml");
+    for volume in ["foo", "bar"] {
+        std::fs::create_dir_all(temp_dir.path().join("root0").join(volume).join("slides")).unwrap();
+    }
+    std::fs::create_dir_all(temp_dir.path().join("root0/foo/slides/bar/docs")).unwrap();
+    std::fs::write(
+        temp_dir.path().join("root0/foo/slides/bar/docs/a.txt"),
+        b"a",
+    )
+    .unwrap();
+
+    let run = |config: &str, args: &[&str]| {
+        std::fs::write(&config_file, config).unwrap();
+        let args = ["bitslides", "-c", config_file.to_str().unwrap()]
+            .iter()
+            .chain(args)
+            .map(|x| x.to_string())
+            .collect::<Vec<String>>();
+        async move {
+            let (_shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel();
+            main_w_args(&args, shutdown_rx).await
+        }
+    };
+    let config = "keyword: \"slides\"\nroots:\n- \"root0\"\nhistory: \"history.db\"\n";
+
+    // The history is relative to the config file, and filled by the syncs
+    assert!(run(config, &["once"]).await.is_ok());
+    let history_path = temp_dir.path().join("history.db");
+    assert!(history_path.exists());
+    assert!(run(
+        config,
+        &["history", "--volume", "bar", "--file", "a.txt", "--since", "1h"]
+    )
+    .await
+    .is_ok());
+    assert!(run(
+        config,
+        &["history", "export", "--format", "csv", "--volume", "bar"]
+    )
+    .await
+    .is_ok());
+
+    // Without a history configured, the database has to be provided
+    let config = "keyword: \"slides\"\nroots:\n- \"root0\"\n";
+    assert!(run(config, &["history"]).await.is_err());
+    assert!(
+        run(config, &["history", "--db", history_path.to_str().unwrap()])
+            .await
+            .is_ok()
+    );
+}
+
+#[tokio::test]
+async fn test_main_undo() {
+    let temp_dir = tempdir().unwrap();
+    let config_file = temp_dir.path().join("config.yml");
+    let trace = temp_dir.path().join("trace.log");
+    let src = temp_dir.path().join("root0/foo/slides/bar/docs/a.txt");
+    let dst = temp_dir.path().join("root0/bar/slides/bar/docs/a.txt");
+    std::fs::create_dir_all(src.parent().unwrap()).unwrap();
+    std::fs::create_dir_all(temp_dir.path().join("root0/bar/slides")).unwrap();
+    std::fs::write(&src, b"a").unwrap();
+    std::fs::write(
+        &config_file,
+        format!("keyword: \"slides\"\nroots:\n- \"root0\"\ntrace: {trace:?}\n"),
+    )
+    .unwrap();
+
+    let run = |args: &[&str]| {
+        let args = ["bitslides", "-c", config_file.to_str().unwrap()]
+            .iter()
+            .chain(args)
+            .map(|x| x.to_string())
+            .collect::<Vec<String>>();
+        async move {
+            let (_shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel();
+            main_w_args(&args, shutdown_rx).await
+        }
+    };
+
+    assert!(run(&["once"]).await.is_ok());
+    assert!(dst.exists());
+
+    // A dry run moves nothing back
+    let trace_arg = trace.to_str().unwrap();
+    assert!(run(&["undo", "--trace", trace_arg, "--dry-run"])
+        .await
+        .is_ok());
+    assert!(dst.exists());
+
+    // The delivered file is moved back to its slide
+    assert!(run(&["undo", "--trace", trace_arg, "--since", "1h"])
+        .await
+        .is_ok());
+    assert!(!dst.exists());
+    assert_eq!(std::fs::read(&src).unwrap(), b"a");
+
+    // A missing trace file is an error
+    let missing = temp_dir.path().join("missing.log");
+    assert!(run(&["undo", "--trace", missing.to_str().unwrap()])
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_main_exclude_include() {
+    let temp_dir = tempdir().unwrap();
+    let config_file = temp_dir.path().join("config.yml");
+    let src = temp_dir.path().join("root0/bar/slides/foo/isos");
+    std::fs::create_dir_all(&src).unwrap();
+    std::fs::create_dir_all(temp_dir.path().join("root0/foo/slides")).unwrap();
+    for file in ["big.iso", "keep.iso", "notes.txt"] {
+        std::fs::write(src.join(file), file).unwrap();
+    }
+    std::fs::write(&config_file, "keyword: \"slides\"\nroots:\n- \"root0\"\n").unwrap();
+
+    let (_shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel();
+    let args = [
+        "bitslides",
+        "-c",
+        config_file.to_str().unwrap(),
+        "once",
+        "--exclude",
+        "*.iso",
+        "--include",
+        "keep.iso",
+    ]
+    .into_iter()
+    .map(|x| x.to_owned())
+    .collect::<Vec<String>>();
+    assert!(main_w_args(&args, shutdown_rx).await.is_ok());
+
+    // Only the excluded file is left behind
+    let dst = temp_dir.path().join("root0/foo/slides/foo/isos");
+    assert!(src.join("big.iso").exists());
+    assert!(!dst.join("big.iso").exists());
+    assert!(dst.join("keep.iso").exists());
+    assert!(dst.join("notes.txt").exists());
+}
+
+#[test]
+fn test_config_relative_roots() {
+    let temp_dir = tempdir().unwrap();
+    let config_file = temp_dir.path().join("config.yml");
+
+    // By default the relative roots are solved from the config file location
+    std::fs::write(&config_file, "roots:\n- \"root0\"\n- \"/media\"\n").unwrap();
+    let rootsets = crate::process_all_configs(vec![&config_file], None, false)
+        .unwrap()
+        .rootsets;
+    assert_eq!(
+        rootsets[0].roots,
+        vec![
+            temp_dir.path().canonicalize().unwrap().join("root0"),
+            std::path::PathBuf::from("/media")
+        ]
+    );
+
+    // Unless the config file opts out
+    std::fs::write(
+        &config_file,
+        "roots:\n- \"root0\"\nrelative_roots: \"cwd\"\n",
+    )
+    .unwrap();
+    let rootsets = crate::process_all_configs(vec![&config_file], None, false)
+        .unwrap()
+        .rootsets;
+    assert_eq!(
+        rootsets[0].roots,
+        vec![std::env::current_dir().unwrap().join("root0")]
+    );
+}
+
+#[test]
+fn test_config_profiles() {
+    let temp_dir = tempdir().unwrap();
+    let config_file = temp_dir.path().join("config.yml");
+    std::fs::write(
+        &config_file,
+        r#"
+keyword: "slides"
+roots:
+- "/media"
+exclude:
+- "*.tmp"
+collision: "skip"
+profiles:
+  home:
+    roots:
+    - "/home/media"
+    trace: "home.log"
+  office:
+    keyword: "queues"
+    exclude:
+    - "*.iso"
+    collision: "overwrite"
+    retries: 1
+    parallel: 1
+    debounce: 10
+"#,
+    )
+    .unwrap();
+
+    // Without profile, the top level settings are used
+    let settings = crate::process_all_configs(vec![&config_file], None, false).unwrap();
+    assert_eq!(settings.rootsets[0].keyword, "slides");
+    assert_eq!(
+        settings.rootsets[0].roots,
+        vec![std::path::PathBuf::from("/media")]
+    );
+    assert_eq!(
+        settings.overrides.collision,
+        Some(bitslideslib::CollisionPolicy::Skip)
+    );
+
+    // The profile settings take precedence
+    let settings = crate::process_all_configs(vec![&config_file], Some("home"), false).unwrap();
+    assert_eq!(settings.rootsets[0].keyword, "slides");
+    assert_eq!(
+        settings.rootsets[0].roots,
+        vec![std::path::PathBuf::from("/home/media")]
+    );
+    assert!(settings.trace.is_some());
+
+    let settings = crate::process_all_configs(vec![&config_file], Some("office"), false).unwrap();
+    assert_eq!(settings.rootsets[0].keyword, "queues");
+    assert_eq!(
+        settings.rootsets[0].roots,
+        vec![std::path::PathBuf::from("/media")]
+    );
+    assert_eq!(settings.exclude, vec!["*.tmp", "*.iso"]);
+    assert_eq!(
+        settings.overrides.collision,
+        Some(bitslideslib::CollisionPolicy::Overwrite)
+    );
+    assert_eq!(settings.overrides.retries, Some(1));
+    assert_eq!(settings.parallel, Some(1));
+    assert_eq!(settings.debounce, Some(10));
+
+    // Unknown profiles are an error
+    assert!(crate::process_all_configs(vec![&config_file], Some("garage"), false).is_err());
+}
+
+#[test]
+fn test_config_signing_key() {
+    let temp_dir = tempdir().unwrap();
+    let config_file = temp_dir.path().join("config.yml");
+    std::fs::write(
+        &config_file,
+        r#"
+keyword: "slides"
+roots:
+- "/media"
+signing_key: "keys/bitslides.key"
+trusted_keys:
+- "0123"
+profiles:
+  office:
+    trusted_keys:
+    - "4567"
+"#,
+    )
+    .unwrap();
+
+    // The key is relative to the config file, and the profiles add trusted keys
+    let settings = crate::process_all_configs(vec![&config_file], Some("office"), false).unwrap();
+    assert_eq!(
+        settings.signing_key,
+        Some(
+            config_file
+                .canonicalize()
+                .unwrap()
+                .with_file_name("keys")
+                .join("bitslides.key")
+        )
+    );
+    assert_eq!(settings.trusted_keys, vec!["0123", "4567"]);
+}
+
+#[tokio::test]
+async fn test_main_init() {
+    let temp_dir = tempdir().unwrap();
+    let config_file = temp_dir.path().join("bitslides").join("config.yml");
+
+    let run = |extra: &[&str]| {
+        let mut args = vec!["bitslides", "init", config_file.to_str().unwrap()];
+        args.extend(extra);
+        let args = args
+            .into_iter()
+            .map(|x| x.to_owned())
+            .collect::<Vec<String>>();
+        async move {
+            let (_shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel();
+            main_w_args(&args, shutdown_rx).await
+        }
+    };
+
+    // The starter config is written and can be loaded
+    assert!(run(&["--probe"]).await.is_ok());
+    assert!(crate::config::Config::load(&config_file).is_ok());
+
+    // Existing configs are not overwritten unless forced
+    std::fs::write(&config_file, "keyword: mine").unwrap();
+    assert!(run(&[]).await.is_err());
+    assert_eq!(
+        std::fs::read_to_string(&config_file).unwrap(),
+        "keyword: mine"
+    );
+    assert!(run(&["--force"]).await.is_ok());
+    assert_ne!(
+        std::fs::read_to_string(&config_file).unwrap(),
+        "keyword: mine"
+    );
+
+    // Only the mount points with volumes mounted on them are probed as roots
+    let media = temp_dir.path().join("media");
+    let mnt = temp_dir.path().join("mnt");
+    std::fs::create_dir_all(media.join("usb").join("Slides")).unwrap();
+    std::fs::create_dir_all(mnt.join("backup").join("Photos")).unwrap();
+    assert_eq!(
+        crate::init::volume_roots(
+            vec![media.clone(), mnt, temp_dir.path().join("Volumes")],
+            "Slides"
+        ),
+        vec![media]
+    );
+}
+
+#[test]
+fn test_config_merge() {
+    let temp_dir = tempdir().unwrap();
+    let first = temp_dir.path().join("first.yml");
+    let second = temp_dir.path().join("second.yml");
+    std::fs::write(
+        &first,
+        r#"
+keyword: "slides"
+roots:
+- "/media"
+- "/mnt"
+collision: "skip"
+retries: 3
+"#,
+    )
+    .unwrap();
+    std::fs::write(
+        &second,
+        r#"
+keyword: "slides"
+roots:
+- "/mnt"
+- "/srv"
+collision: "overwrite"
+retries: 3
+"#,
+    )
+    .unwrap();
+
+    let settings = crate::process_all_configs(vec![&first, &second], None, false).unwrap();
+
+    // Duplicated roots are dropped
+    assert_eq!(settings.rootsets.len(), 1);
+    assert_eq!(
+        settings.rootsets[0].roots,
+        ["/media", "/mnt", "/srv"]
+            .iter()
+            .map(std::path::PathBuf::from)
+            .collect::<Vec<_>>()
+    );
+
+    // Later files override the scalars, and different values are reported
+    assert_eq!(
+        settings.overrides.collision,
+        Some(bitslideslib::CollisionPolicy::Overwrite)
+    );
+    assert_eq!(settings.overrides.retries, Some(3));
+    assert_eq!(settings.conflicts.len(), 1);
+    assert!(settings.conflicts[0].contains("\"collision\" overrides"));
+
+    // The same root with a different keyword is reported
+    std::fs::write(&second, "keyword: \"queues\"\nroots:\n- \"/mnt\"\n").unwrap();
+    let settings = crate::process_all_configs(vec![&first, &second], None, false).unwrap();
+    assert_eq!(settings.rootsets.len(), 2);
+    assert_eq!(settings.conflicts.len(), 1);
+    assert!(settings.conflicts[0].contains("declared with keywords"));
+}
+
+/// Test the strict parsing of the config files
+#[test]
+fn test_strict_config() {
+    // Prerequisite: A config file with a misspelled setting
+    let temp_dir = tempfile::tempdir().unwrap();
+    let config_file = temp_dir.path().join("main.conf");
+    std::fs::write(&config_file, "roots:\n- \"/media\"\ncolision: \"skip\"\n").unwrap();
+
+    // Check: The typo is ignored by default
+    assert!(crate::process_all_configs(vec![&config_file], None, false).is_ok());
+
+    // Check: The typo is rejected with --strict-config
+    let err = crate::process_all_configs(vec![&config_file], None, true)
+        .err()
+        .unwrap();
+    assert!(
+        err.to_string().contains("Unknown field `colision`"),
+        "{err}"
+    );
+
+    // Check: The typo is rejected when the config file enables the strict mode
+    std::fs::write(
+        &config_file,
+        "strict: true\nroots:\n- \"/media\"\ncolision: \"skip\"\n",
+    )
+    .unwrap();
+    assert!(crate::process_all_configs(vec![&config_file], None, false).is_err());
+}
+
+/// Test the MQTT broker setting of the config files
+#[test]
+fn test_mqtt_config() {
+    // Prerequisite: A config file publishing to a broker, and another one to a different broker
+    let temp_dir = tempfile::tempdir().unwrap();
+    let first = temp_dir.path().join("first.conf");
+    let second = temp_dir.path().join("second.conf");
+    std::fs::write(
+        &first,
+        "roots:\n- \"/media\"\nmqtt:\n  host: \"broker.lan\"\n  topic: \"home/bitslides\"\n",
+    )
+    .unwrap();
+    std::fs::write(&second, "mqtt:\n  host: \"other.lan\"\n  port: 8883\n").unwrap();
+
+    // Check: The broker is read, the settings not provided left to their defaults
+    let settings = crate::process_all_configs(vec![&first], None, false).unwrap();
+    let mqtt = settings.mqtt.unwrap();
+    assert_eq!(
+        (mqtt.host.as_str(), mqtt.port, mqtt.topic.as_deref()),
+        ("broker.lan", None, Some("home/bitslides"))
+    );
+
+    // Check: A later file takes precedence, reporting the conflict
+    let settings = crate::process_all_configs(vec![&first, &second], None, false).unwrap();
+    let mqtt = settings.mqtt.unwrap();
+    assert_eq!((mqtt.host.as_str(), mqtt.port), ("other.lan", Some(8883)));
+    assert_eq!(settings.conflicts.len(), 1);
+    assert!(settings.conflicts[0].contains("\"mqtt\" overrides"));
+
+    // Check: A misspelled setting of the broker is rejected in strict mode
+    std::fs::write(&second, "mqtt:\n  host: \"other.lan\"\n  prot: 8883\n").unwrap();
+    let err = crate::process_all_configs(vec![&second], None, true)
+        .err()
+        .unwrap();
+    assert!(
+        err.to_string().contains("Unknown field `mqtt.prot`"),
+        "{err}"
+    );
+}
+
+#[test]
+fn test_cli_collision() {
+    let config = |args: &[&str]| {
+        let matches = crate::cli::cli().get_matches_from(args);
+        let options = crate::TransferOptions::new("once", matches.subcommand_matches("once"));
+        let settings = crate::Settings {
+            overrides: bitslideslib::config::StrategyOverrides {
+                collision: Some(bitslideslib::CollisionPolicy::Skip),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        crate::global_config(settings, &options, Default::default())
+    };
+
+    // The config value is used by default
+    assert_eq!(
+        config(&["bitslides", "once"]).collision,
+        bitslideslib::CollisionPolicy::Skip
+    );
+
+    // The command line takes precedence
+    assert_eq!(
+        config(&["bitslides", "once", "--collision", "rename:old"]).collision,
+        bitslideslib::CollisionPolicy::Rename {
+            suffix: "old".to_owned()
+        }
+    );
+
+    // Unknown policies are rejected
+    assert!(crate::cli::cli()
+        .try_get_matches_from(["bitslides", "once", "--collision", "squash"])
+        .is_err());
+}
+
+#[test]
+fn test_cli_jobs() {
+    let config = |args: &[&str], jobs| {
+        let matches = crate::cli::cli().get_matches_from(args);
+        let options = crate::TransferOptions::new("once", matches.subcommand_matches("once"));
+        let settings = crate::Settings {
+            overrides: bitslideslib::config::StrategyOverrides {
+                jobs,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        crate::global_config(settings, &options, Default::default())
+    };
+
+    // The number of CPUs is used by default, or the config value if any
+    assert_eq!(
+        config(&["bitslides", "once"], None).jobs,
+        crate::cli::default_jobs()
+    );
+    assert_eq!(config(&["bitslides", "once"], Some(3)).jobs, 3);
+
+    // The command line takes precedence
+    assert_eq!(config(&["bitslides", "once", "-j", "2"], Some(3)).jobs, 2);
+
+    // As many syncjobs as files are synced at once by default, or as configured
+    assert_eq!(config(&["bitslides", "once"], Some(3)).parallel, 3);
+    assert_eq!(
+        config(&["bitslides", "once", "--parallel", "1"], Some(3)).parallel,
+        1
+    );
+    let matches = crate::cli::cli().get_matches_from(["bitslides", "once", "-j", "4"]);
+    let options = crate::TransferOptions::new("once", matches.subcommand_matches("once"));
+    let settings = crate::Settings {
+        parallel: Some(2),
+        ..Default::default()
+    };
+    let config = crate::global_config(settings, &options, Default::default());
+    assert_eq!((config.jobs, config.parallel), (4, 2));
+    assert!(crate::cli::cli()
+        .try_get_matches_from(["bitslides", "once", "--parallel", "0"])
+        .is_err());
+}
+
+#[test]
+fn test_cli_debounce() {
+    let debounce = |args: &[&str], configured| {
+        let matches = crate::cli::cli().get_matches_from(args);
+        let options = crate::TransferOptions::new("run", matches.subcommand_matches("run"));
+        let settings = crate::Settings {
+            debounce: configured,
+            ..Default::default()
+        };
+        crate::global_config(settings, &options, Default::default()).debounce
+    };
+
+    // A couple of seconds by default, or the config value if any
+    assert_eq!(
+        debounce(&["bitslides", "run"], None),
+        Some(crate::cli::DEFAULT_DEBOUNCE)
+    );
+    assert_eq!(debounce(&["bitslides", "run"], Some(5)), Some(5));
+
+    // The command line takes precedence
+    assert_eq!(
+        debounce(&["bitslides", "run", "--debounce", "0"], Some(5)),
+        Some(0)
+    );
+}
+
+#[test]
+fn test_cli_check() {
+    let check = |args: &[&str]| {
+        let matches = crate::cli::cli().get_matches_from(args);
+        let options = crate::TransferOptions::new("run", matches.subcommand_matches("run"));
+        crate::global_config(Default::default(), &options, Default::default()).check
+    };
+
+    assert_eq!(
+        check(&["bitslides", "run"]),
+        Some(bitslideslib::Algorithm::BLAKE)
+    );
+    assert_eq!(check(&["bitslides", "run", "--check", "none"]), None);
+    assert_eq!(
+        check(&["bitslides", "run", "--check", "sha256"]),
+        Some(bitslideslib::Algorithm::SHA2256)
+    );
+    assert!(crate::cli::cli()
+        .try_get_matches_from(["bitslides", "run", "--check", "rot13"])
+        .is_err());
+}
+
+#[test]
+fn test_daemon_pid_file() {
+    let temp_dir = tempdir().unwrap();
+    let pid_file = temp_dir.path().join("run").join("bitslides.pid");
+
+    // Only asked for with --daemon
+    let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+    assert_eq!(crate::daemon_pid_file(&args(&["bitslides", "run"])), None);
+    assert_eq!(
+        crate::daemon_pid_file(&args(&[
+            "bitslides",
+            "run",
+            "--daemon",
+            "--pid-file",
+            pid_file.to_str().unwrap()
+        ])),
+        Some(pid_file.clone())
+    );
+    assert!(crate::cli::cli()
+        .try_get_matches_from(["bitslides", "run", "--daemon", "--once"])
+        .is_err());
+    assert!(crate::daemon::default_log_file().is_none_or(|f| f.ends_with("bitslides.log")));
+
+    // No PID file, or a stale one, is fine
+    drop(crate::daemon::lock_pid_file(&pid_file).unwrap());
+    std::fs::write(&pid_file, "garbage").unwrap();
+    let mut held = crate::daemon::lock_pid_file(&pid_file).unwrap();
+    crate::daemon::record_pid(&mut held, &pid_file).unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&pid_file).unwrap(),
+        format!("{}\n", std::process::id())
+    );
+
+    // A live process holding it is not, a second one cannot take it over
+    assert!(crate::daemon::lock_pid_file(&pid_file).is_err());
+
+    // Once let go, it is free again
+    drop(held);
+    drop(crate::daemon::lock_pid_file(&pid_file).unwrap());
+}
+
+#[test]
+fn test_control_socket() {
+    let control = |args: &[&str]| {
+        let matches = crate::cli::cli().get_matches_from(args);
+        let (command, sub_matches) = matches.subcommand().unwrap();
+        crate::TransferOptions::new(command, Some(sub_matches)).control
+    };
+
+    // Only listened on when asked for, or by a daemon
+    assert_eq!(control(&["bitslides", "run"]), None);
+    assert_eq!(control(&["bitslides", "once"]), None);
+    assert_eq!(
+        control(&["bitslides", "run", "--control", "/tmp/b.sock"]),
+        Some(std::path::PathBuf::from("/tmp/b.sock"))
+    );
+    assert_eq!(
+        control(&["bitslides", "run", "--daemon"]),
+        Some(crate::daemon::default_control_socket())
+    );
+
+    // The status command looks for the daemon by default
+    let matches = crate::cli::cli().get_matches_from(["bitslides", "status"]);
+    assert_eq!(
+        matches
+            .subcommand_matches("status")
+            .unwrap()
+            .get_one::<std::path::PathBuf>("control"),
+        Some(&crate::daemon::default_control_socket())
+    );
+    assert!(crate::cli::cli()
+        .try_get_matches_from(["bitslides", "run", "--once", "--control", "/tmp/b.sock"])
+        .is_err());
+}
+
+#[test]
+fn test_config_size_range() {
+    let temp_dir = tempdir().unwrap();
+    let config_file = temp_dir.path().join("config.yml");
+    std::fs::write(
+        &config_file,
+        r#"
+roots:
+- "/media"
+max_size: "50G"
+profiles:
+  docs:
+    min_size: 1024
+"#,
+    )
+    .unwrap();
+
+    // Sizes are accepted as text or as a number of bytes
+    let settings = crate::process_all_configs(vec![&config_file], None, false).unwrap();
+    assert_eq!(settings.min_size, None);
+    assert_eq!(settings.max_size, Some(50 * 1024 * 1024 * 1024));
+    let settings = crate::process_all_configs(vec![&config_file], Some("docs"), false).unwrap();
+    assert_eq!(settings.min_size, Some(1024));
+
+    // The command line takes precedence
+    let matches = crate::cli::cli().get_matches_from(["bitslides", "once", "--max-size", "1M"]);
+    let options = crate::TransferOptions::new("once", matches.subcommand_matches("once"));
+    let config = crate::global_config(settings, &options, Default::default());
+    assert_eq!(config.min_size, Some(1024));
+    assert_eq!(config.max_size, Some(1024 * 1024));
+
+    // Invalid sizes are rejected
+    std::fs::write(&config_file, "roots:\n- \"/media\"\nmax_size: \"big\"\n").unwrap();
+    assert!(crate::process_all_configs(vec![&config_file], None, false).is_err());
+}
+
+#[tokio::test]
+async fn test_exit_status() {
+    use crate::exit::{check_summary, Status};
+    use bitslideslib::Summary;
+
+    let temp_dir = tempdir().unwrap();
+    let config_file = temp_dir.path().join("config.yml");
+    let once = |config_file: &std::path::Path| {
+        let args = ["bitslides", "-c", config_file.to_str().unwrap(), "once"]
+            .into_iter()
+            .map(|x| x.to_owned())
+            .collect::<Vec<String>>();
+        async move {
+            let (_shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel();
+            Status::of(&main_w_args(&args, shutdown_rx).await)
+        }
+    };
+
+    // An unreadable config is a config error
+    std::fs::write(&config_file, "Memento mori").unwrap();
+    assert_eq!(once(&config_file).await, Status::Config);
+
+    // Prerequisite: Two volumes sending a file to each other, one of them colliding
+    std::fs::write(&config_file, "keyword: \"slides\"\nroots:\n- \"root0\"\n").unwrap();
+    let root = temp_dir.path().join("root0");
+    for (src, dst) in [("foo", "bar"), ("bar", "foo")] {
+        let dir = root.join(src).join("slides").join(dst).join("docs");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(format!("{src}.txt")), src).unwrap();
+    }
+    let collision = root.join("bar/slides/bar/docs");
+    std::fs::create_dir_all(&collision).unwrap();
+    std::fs::write(collision.join("foo.txt"), "collision").unwrap();
+
+    // Check: One syncjob failed, the other one went fine
+    assert_eq!(once(&config_file).await, Status::PartialFailure);
+    assert!(root.join("foo/slides/foo/docs/bar.txt").exists());
+
+    // Check: Nothing is left to move but the colliding file
+    assert_eq!(once(&config_file).await, Status::PartialFailure);
+
+    // Check: Nothing to do is fine
+    std::fs::remove_file(collision.join("foo.txt")).unwrap();
+    assert_eq!(once(&config_file).await, Status::Success);
+
+    // The rest of the statuses
+    assert_eq!(
+        Status::of(&check_summary(Summary {
+            syncjobs: 2,
+            failed: 2,
+            ..Default::default()
+        })),
+        Status::AllFailed
+    );
+    assert_eq!(
+        Status::of(&check_summary(Summary::default())),
+        Status::Success
+    );
+    assert_eq!(std::process::ExitCode::from(Status::Interrupted), 5.into());
+}
@@ -0,0 +1,175 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use anyhow::Result;
+use filetime::FileTime;
+use serde::{Deserialize, Serialize};
+
+use crate::{config::Algorithm, volume::Volume};
+
+/// File of the keyword folder of a volume caching the checksums of the files in its slides.
+///
+pub(crate) const CACHE_FILE: &str = ".bitslides-checksums.json";
+
+/// Checksum of a file, along with the metadata it was computed for.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Entry {
+    size: u64,
+    /// Modification time, seconds and nanoseconds since the Unix epoch
+    modified: (i64, u32),
+    algorithm: String,
+    checksum: String,
+}
+
+impl Entry {
+    /// Check if the entry still describes the file with the given metadata.
+    ///
+    fn matches(&self, metadata: &std::fs::Metadata) -> bool {
+        let modified = FileTime::from_last_modification_time(metadata);
+        self.size == metadata.len()
+            && self.modified == (modified.unix_seconds(), modified.nanoseconds())
+    }
+}
+
+/// Cache of the checksums of the files of a volume, so the unchanged ones are not hashed again.
+///
+/// The files are identified by their path relative to the keyword folder of the volume, and an
+/// entry is only valid while the size and the modification time of its file stay the same, and is
+/// dropped once found otherwise. Only the changes are written back, on top of whatever the file
+/// holds by then, so the syncjobs sharing a volume do not undo each other's.
+///
+/// Only the checksums computed from the files themselves belong here, never the ones of the
+/// sources they were copied from, as the cache is trusted to tell what the files hold.
+///
+#[derive(Debug)]
+pub(crate) struct ChecksumCache {
+    /// Keyword folder of the volume
+    root: PathBuf,
+    entries: HashMap<String, Entry>,
+    /// Entries added, or removed if `None`, since the cache was loaded
+    changes: Mutex<HashMap<String, Option<Entry>>>,
+}
+
+impl ChecksumCache {
+    /// Load the cache of the volume whose keyword folder is `root`. A missing or unreadable cache
+    /// starts empty.
+    ///
+    pub(crate) fn load(root: &Path) -> Self {
+        Self {
+            root: root.to_owned(),
+            entries: read_entries(&root.join(CACHE_FILE)),
+            changes: Mutex::default(),
+        }
+    }
+
+    /// Checksum of a file with the given algorithm, if cached and the file is unchanged since.
+    ///
+    pub(crate) fn get(&self, path: &Path, algorithm: Algorithm) -> Option<String> {
+        let key = self.key(path)?;
+        let metadata = std::fs::metadata(path);
+        let mut changes = self.changes.lock().unwrap();
+        let entry = match changes.get(&key) {
+            Some(change) => change.as_ref(),
+            None => self.entries.get(&key),
+        }?;
+
+        if !metadata.is_ok_and(|metadata| entry.matches(&metadata)) {
+            changes.insert(key, None);
+            return None;
+        }
+        (entry.algorithm == format!("{algorithm:?}")).then(|| entry.checksum.clone())
+    }
+
+    /// Record the checksum of a file, as it is now.
+    ///
+    pub(crate) fn insert(&self, path: &Path, algorithm: Algorithm, checksum: &str) {
+        let (Some(key), Ok(metadata)) = (self.key(path), std::fs::metadata(path)) else {
+            return;
+        };
+        let modified = FileTime::from_last_modification_time(&metadata);
+        let entry = Entry {
+            size: metadata.len(),
+            modified: (modified.unix_seconds(), modified.nanoseconds()),
+            algorithm: format!("{algorithm:?}"),
+            checksum: checksum.to_owned(),
+        };
+        self.changes.lock().unwrap().insert(key, Some(entry));
+    }
+
+    /// Write the changes back, if any.
+    ///
+    pub(crate) fn save(&self) -> Result<()> {
+        let changes = std::mem::take(&mut *self.changes.lock().unwrap());
+        if changes.is_empty() {
+            return Ok(());
+        }
+
+        let file = self.root.join(CACHE_FILE);
+        let mut entries = read_entries(&file);
+        for (key, change) in changes {
+            match change {
+                Some(entry) => entries.insert(key, entry),
+                None => entries.remove(&key),
+            };
+        }
+
+        // Written aside first, so a crash never leaves the cache truncated
+        let partial = self.root.join(format!("{CACHE_FILE}.tmp"));
+        std::fs::write(&partial, serde_json::to_vec(&entries)?)?;
+        std::fs::rename(&partial, &file)?;
+        Ok(())
+    }
+
+    /// Key of a file inside the volume. The files elsewhere, or with a name that is not valid
+    /// UTF-8, are not cached.
+    ///
+    fn key(&self, path: &Path) -> Option<String> {
+        let relative = path.strip_prefix(&self.root).ok()?;
+        Some(relative.to_str()?.replace('\\', "/"))
+    }
+}
+
+/// Read the entries of a cache file, none if missing or unreadable.
+///
+fn read_entries(file: &Path) -> HashMap<String, Entry> {
+    let Ok(content) = std::fs::read(file) else {
+        return HashMap::new();
+    };
+    serde_json::from_slice(&content).unwrap_or_else(|e| {
+        log::warn!("{file:?}: Unreadable checksum cache, starting over: {e}");
+        HashMap::new()
+    })
+}
+
+/// The checksum caches of a set of volumes.
+///
+#[derive(Debug, Default)]
+pub(crate) struct Caches(Vec<ChecksumCache>);
+
+impl Caches {
+    pub(crate) fn load(volumes: &[Volume]) -> Self {
+        Self(
+            volumes
+                .iter()
+                .map(|volume| ChecksumCache::load(&volume.path.join(&volume.keyword)))
+                .collect(),
+        )
+    }
+
+    /// Cache of the volume holding a file, if any.
+    ///
+    pub(crate) fn of(&self, path: &Path) -> Option<&ChecksumCache> {
+        self.0.iter().find(|cache| path.starts_with(&cache.root))
+    }
+
+    pub(crate) fn save(&self) -> Result<()> {
+        for cache in &self.0 {
+            cache.save()?;
+        }
+        Ok(())
+    }
+}
@@ -1,8 +1,12 @@
-use anyhow::Result;
-use serde::Deserialize;
-use std::path::{Path, PathBuf};
+use anyhow::{anyhow, bail, Result};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
-pub use checksums::Algorithm;
+pub use crate::hasher::Algorithm;
 
 /// Set of roots
 ///
@@ -14,6 +18,8 @@ pub struct RootsetConfig {
     pub keyword: String,
     /// List of root absolute paths that will contain volumes
     pub roots: Vec<PathBuf>,
+    /// If true, the slides are polled for changes rather than watched
+    pub poll: bool,
 }
 
 /// Policy to apply in case of a file collision
@@ -24,10 +30,853 @@ pub enum CollisionPolicy {
     Overwrite,
     /// Skip the file
     Skip,
-    /// Rename the file
+    /// Rename the file, with a suffix replacing its extension or a template of the new name
     Rename { suffix: String },
     /// Fail the operation
     Fail,
+    /// Overwrite the destination file only if the file is newer. Otherwise, the file is skipped,
+    /// or removed if `remove_older`
+    KeepNewer { remove_older: bool },
+}
+
+impl CollisionPolicy {
+    /// Suffix used by the rename policy when none is given.
+    ///
+    pub const DEFAULT_RENAME_SUFFIX: &str = "conflict";
+
+    /// Placeholders of the templates of the rename policy.
+    ///
+    pub const RENAME_PLACEHOLDERS: [&str; 4] = ["stem", "ext", "n", "timestamp"];
+}
+
+/// Check that the suffix of the rename policy is a single file name, with known placeholders only.
+///
+fn check_rename_suffix(suffix: &str) -> Result<()> {
+    if suffix.contains(['/', '\\']) {
+        bail!("\"{suffix}\" cannot contain path separators");
+    }
+    let mut rest = suffix;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}') else {
+            bail!("\"{suffix}\" has an unclosed placeholder");
+        };
+        let placeholder = &rest[start + 1..start + end];
+        if !CollisionPolicy::RENAME_PLACEHOLDERS.contains(&placeholder) {
+            bail!(
+                "\"{suffix}\" has an unknown placeholder {{{placeholder}}}, expected {{{}}}",
+                CollisionPolicy::RENAME_PLACEHOLDERS.join("}, {")
+            );
+        }
+        rest = &rest[start + end + 1..];
+    }
+    Ok(())
+}
+
+/// Parse a collision policy from its textual form: `overwrite`, `skip`, `fail`, `rename[:suffix]`
+/// or `keep-newer[:remove]`.
+///
+/// The suffix of `rename` can be a template instead, like `rename:{stem} ({n}).{ext}`.
+///
+impl FromStr for CollisionPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (policy, suffix) = match s.split_once(':') {
+            Some((policy, suffix)) => (policy, Some(suffix)),
+            None => (s, None),
+        };
+        match (policy.to_lowercase().as_str(), suffix) {
+            ("overwrite", None) => Ok(Self::Overwrite),
+            ("skip", None) => Ok(Self::Skip),
+            ("fail", None) => Ok(Self::Fail),
+            ("rename", None) => Ok(Self::Rename {
+                suffix: Self::DEFAULT_RENAME_SUFFIX.to_owned(),
+            }),
+            ("rename", Some(suffix)) if !suffix.is_empty() => {
+                check_rename_suffix(suffix)?;
+                Ok(Self::Rename {
+                    suffix: suffix.to_owned(),
+                })
+            }
+            ("keep-newer", None) => Ok(Self::KeepNewer {
+                remove_older: false,
+            }),
+            ("keep-newer", Some(option)) if option.eq_ignore_ascii_case("remove") => {
+                Ok(Self::KeepNewer { remove_older: true })
+            }
+            _ => bail!("\"{s}\" is not a recognised collision policy"),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for CollisionPolicy {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Policy to apply to a file sent both ways between two volumes, that is, found under the same
+/// path in the slides they have for each other
+///
+#[derive(Debug, Clone, Copy, Default, Hash, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Report the conflict, and let both files cross each other
+    #[default]
+    Report,
+    /// Keep the most recently modified file, removing the other one
+    Newer,
+    /// Rename both files after the volume they come from
+    Rename,
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "report" => Ok(Self::Report),
+            "newer" => Ok(Self::Newer),
+            "rename" => Ok(Self::Rename),
+            _ => bail!("\"{s}\" is not a recognised conflict policy"),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ConflictPolicy {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// How the files of a slide are spread over its routes, while its destination is not available
+///
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Balance {
+    /// Deal the files evenly between the routes, by a hash of their path, so a file takes the
+    /// same route at every pass
+    Even,
+    /// Deal the files between the routes in proportion to their free space
+    FreeSpace,
+}
+
+impl FromStr for Balance {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "even" => Ok(Self::Even),
+            "free-space" => Ok(Self::FreeSpace),
+            _ => bail!("\"{s}\" is not a recognised balance"),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Balance {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// How much of the copies is read back to check them
+///
+#[derive(Debug, Clone, Copy, Default, Hash, PartialEq, Eq)]
+pub enum CheckMode {
+    /// Every byte is read back
+    #[default]
+    Full,
+    /// The files over `threshold` bytes only get their first and last `edge` bytes read back,
+    /// and `blocks` blocks picked at random in between
+    Sampled {
+        threshold: u64,
+        edge: u64,
+        blocks: u32,
+    },
+}
+
+impl CheckMode {
+    /// Size of the files sampled when not given.
+    ///
+    pub const DEFAULT_THRESHOLD: u64 = 1024 * 1024 * 1024;
+    /// Bytes read back at each end of the files sampled when not given.
+    ///
+    pub const DEFAULT_EDGE: u64 = 16 * 1024 * 1024;
+    /// Blocks read back in between when not given.
+    ///
+    pub const DEFAULT_BLOCKS: u32 = 64;
+}
+
+/// Parse the check mode from its textual form: `full` or
+/// `sampled[:threshold[:edge[:blocks]]]`, the sizes as in `parse_size`.
+///
+impl FromStr for CheckMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = s.split(':');
+        match parts.next().map(str::to_lowercase).as_deref() {
+            Some("full") if parts.next().is_none() => Ok(Self::Full),
+            Some("sampled") => {
+                let threshold = parts.next().map(parse_size).transpose()?;
+                let edge = parts.next().map(parse_size).transpose()?;
+                let blocks = match parts.next() {
+                    Some(blocks) => Some(blocks.parse().map_err(|_| {
+                        anyhow!("Invalid number of blocks \"{blocks}\" in \"{s}\"")
+                    })?),
+                    None => None,
+                };
+                if parts.next().is_some() {
+                    bail!(
+                        "\"{s}\" has too many fields, expected sampled[:threshold[:edge[:blocks]]]"
+                    );
+                }
+                Ok(Self::Sampled {
+                    threshold: threshold.unwrap_or(Self::DEFAULT_THRESHOLD),
+                    edge: edge.unwrap_or(Self::DEFAULT_EDGE),
+                    blocks: blocks.unwrap_or(Self::DEFAULT_BLOCKS),
+                })
+            }
+            _ => bail!(
+                "Unknown check mode \"{s}\", expected full or sampled[:threshold[:edge[:blocks]]]"
+            ),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for CheckMode {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// How a file is found identical to the one already at its destination
+///
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum Identical {
+    /// Same size and modification time
+    Quick,
+    /// Same contents, compared byte by byte
+    Contents,
+}
+
+/// Parse a comparison from its textual form: `quick` or `contents`.
+///
+impl FromStr for Identical {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "quick" => Ok(Self::Quick),
+            "contents" => Ok(Self::Contents),
+            _ => bail!("Unknown comparison \"{s}\", expected quick, contents or none"),
+        }
+    }
+}
+
+/// Parse the comparison of the identical files. `none` disables it.
+///
+pub fn parse_identical(s: &str) -> Result<Option<Identical>> {
+    match s.eq_ignore_ascii_case("none") {
+        true => Ok(None),
+        false => s.parse().map(Some),
+    }
+}
+
+/// Deserialize an optional comparison setting, where `none` explicitly disables it.
+///
+fn deserialize_identical<'de, D>(
+    deserializer: D,
+) -> std::result::Result<Option<Option<Identical>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    parse_identical(&s)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+/// How the file names a destination filesystem does not allow are rewritten
+///
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum Sanitize {
+    /// The illegal characters are replaced by underscores
+    Underscore,
+    /// The illegal characters are replaced by their Unicode lookalikes
+    Unicode,
+}
+
+/// Parse a sanitization scheme from its textual form: `underscore` or `unicode`.
+///
+impl FromStr for Sanitize {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "underscore" => Ok(Self::Underscore),
+            "unicode" => Ok(Self::Unicode),
+            _ => bail!("Unknown sanitization \"{s}\", expected underscore, unicode or none"),
+        }
+    }
+}
+
+/// Parse the sanitization of the file names. `none` disables it.
+///
+pub fn parse_sanitize(s: &str) -> Result<Option<Sanitize>> {
+    match s.eq_ignore_ascii_case("none") {
+        true => Ok(None),
+        false => s.parse().map(Some),
+    }
+}
+
+/// Deserialize an optional sanitization setting, where `none` explicitly disables it.
+///
+fn deserialize_sanitize<'de, D>(
+    deserializer: D,
+) -> std::result::Result<Option<Option<Sanitize>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    parse_sanitize(&s)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+/// Checksum files written along with the delivered files
+///
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum Sidecar {
+    /// A file next to each delivered file, named after it, like `photo.jpg.sha256`
+    File,
+    /// A file in each folder listing the checksums of the files delivered to it, like
+    /// `SHA256SUMS`
+    Folder,
+}
+
+/// Parse a kind of checksum file from its textual form: `file` or `folder`.
+///
+impl FromStr for Sidecar {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "file" => Ok(Self::File),
+            "folder" => Ok(Self::Folder),
+            _ => bail!("Unknown checksum file \"{s}\", expected file, folder or none"),
+        }
+    }
+}
+
+/// Parse the kind of checksum files written. `none` disables them.
+///
+pub fn parse_sidecar(s: &str) -> Result<Option<Sidecar>> {
+    match s.eq_ignore_ascii_case("none") {
+        true => Ok(None),
+        false => s.parse().map(Some),
+    }
+}
+
+/// Deserialize an optional checksum file setting, where `none` explicitly disables them.
+///
+fn deserialize_sidecar<'de, D>(
+    deserializer: D,
+) -> std::result::Result<Option<Option<Sidecar>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    parse_sidecar(&s)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+/// Files kept in the trash of the slides instead of being deleted
+///
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct Trash {
+    /// If true, the sources removed after their move are kept too, not only the overwritten
+    /// destinations
+    pub sources: bool,
+    /// Days the files are kept in the trash, 0 to keep them forever
+    pub retention: u32,
+}
+
+impl Trash {
+    /// Days the files are kept in the trash when not given.
+    ///
+    pub const DEFAULT_RETENTION: u32 = 30;
+}
+
+/// Parse the use of the trash from its textual form: `overwritten[:days]` or `all[:days]`.
+///
+impl FromStr for Trash {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (files, retention) = match s.split_once(':') {
+            Some((files, days)) => (
+                files,
+                days.parse().map_err(|_| {
+                    anyhow!("Invalid retention \"{days}\", expected a number of days")
+                })?,
+            ),
+            None => (s, Self::DEFAULT_RETENTION),
+        };
+        let sources = match files.to_lowercase().as_str() {
+            "overwritten" => false,
+            "all" => true,
+            _ => {
+                bail!("Unknown trash use \"{s}\", expected overwritten[:days], all[:days] or none")
+            }
+        };
+        Ok(Self { sources, retention })
+    }
+}
+
+/// Parse the use of the trash. `none` deletes the files instead.
+///
+pub fn parse_trash(s: &str) -> Result<Option<Trash>> {
+    match s.eq_ignore_ascii_case("none") {
+        true => Ok(None),
+        false => s.parse().map(Some),
+    }
+}
+
+/// Deserialize an optional trash setting, where `none` explicitly disables it.
+///
+fn deserialize_trash<'de, D>(
+    deserializer: D,
+) -> std::result::Result<Option<Option<Trash>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    parse_trash(&s).map(Some).map_err(serde::de::Error::custom)
+}
+
+/// Second check of the delivered files, once settled, catching the corruption showing up late
+///
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct Reverify {
+    /// Minutes after the delivery the files are hashed again
+    pub after: u32,
+    /// If true, the corrupted files are restored from the copy of their source kept in the trash,
+    /// if any
+    pub restore: bool,
+}
+
+/// Parse the second check from its textual form: `<minutes>` or `<minutes>:restore`.
+///
+impl FromStr for Reverify {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (after, restore) = match s.split_once(':') {
+            Some((after, option)) if option.eq_ignore_ascii_case("restore") => (after, true),
+            Some(_) => bail!("Unknown second check \"{s}\", expected <minutes>[:restore] or none"),
+            None => (s, false),
+        };
+        let after = after
+            .parse()
+            .map_err(|_| anyhow!("Invalid delay \"{after}\", expected a number of minutes"))?;
+        Ok(Self { after, restore })
+    }
+}
+
+/// Parse the second check of the delivered files. `none` disables it.
+///
+pub fn parse_reverify(s: &str) -> Result<Option<Reverify>> {
+    match s.eq_ignore_ascii_case("none") {
+        true => Ok(None),
+        false => s.parse().map(Some),
+    }
+}
+
+/// Deserialize an optional second check setting, where `none` explicitly disables it. The
+/// minutes may be given as a plain number.
+///
+fn deserialize_reverify<'de, D>(
+    deserializer: D,
+) -> std::result::Result<Option<Option<Reverify>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Value {
+        Minutes(u32),
+        Text(String),
+    }
+    match Value::deserialize(deserializer)? {
+        Value::Minutes(after) => Ok(Some(Some(Reverify {
+            after,
+            restore: false,
+        }))),
+        Value::Text(s) => parse_reverify(&s)
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+    }
+}
+
+/// Deserialize the routes of a slide, either a single volume name or a list of them.
+///
+fn deserialize_routes<'de, D>(deserializer: D) -> std::result::Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Value {
+        One(String),
+        Many(Vec<String>),
+    }
+    match Option::<Value>::deserialize(deserializer)? {
+        None => Ok(Vec::new()),
+        Some(Value::One(route)) => Ok(vec![route]),
+        Some(Value::Many(routes)) => Ok(routes),
+    }
+}
+
+/// Parse a checksum algorithm from its textual form. `none` disables the checksum.
+///
+/// The usual short names of the SHA-2 family (ex. `sha256`) are accepted as well.
+///
+pub fn parse_check(s: &str) -> Result<Option<Algorithm>> {
+    if s.eq_ignore_ascii_case("none") {
+        return Ok(None);
+    }
+    let name = match s.to_lowercase().as_str() {
+        "sha224" | "sha256" | "sha384" | "sha512" => format!("sha2-{}", &s[3..]),
+        _ => s.to_owned(),
+    };
+    match Algorithm::from_str(&name) {
+        Ok(algorithm) => Ok(Some(algorithm)),
+        Err(e) => bail!(e),
+    }
+}
+
+/// Deserialize an optional checksum setting, where `none` explicitly disables the checksum.
+///
+fn deserialize_check<'de, D>(
+    deserializer: D,
+) -> std::result::Result<Option<Option<Algorithm>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    parse_check(&s).map(Some).map_err(serde::de::Error::custom)
+}
+
+/// Parse a file size: a number of bytes, optionally followed by a unit (ex. `512K`, `50G`, `1.5GiB`).
+///
+/// Units are powers of 1024, with or without the trailing `B` or `iB`.
+///
+pub fn parse_size(s: &str) -> Result<u64> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number = number
+        .parse::<f64>()
+        .map_err(|_| anyhow!("\"{s}\" is not a valid size"))?;
+    let unit = unit.trim().to_lowercase();
+    let exponent = match unit.trim_end_matches("ib").trim_end_matches('b') {
+        "" => 0,
+        "k" => 1,
+        "m" => 2,
+        "g" => 3,
+        "t" => 4,
+        _ => bail!("\"{s}\" has an unknown size unit, expected K, M, G or T"),
+    };
+    Ok((number * 1024f64.powi(exponent)) as u64)
+}
+
+/// Deserialize an optional file size, given either as a number of bytes or as text (ex. `50G`).
+///
+pub fn deserialize_size<'de, D>(deserializer: D) -> std::result::Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Size {
+        Bytes(u64),
+        Text(String),
+    }
+
+    match Size::deserialize(deserializer)? {
+        Size::Bytes(bytes) => Ok(Some(bytes)),
+        Size::Text(text) => parse_size(&text)
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+    }
+}
+
+/// Format of the information printed to stdout
+///
+#[derive(Debug, Clone, Copy, Default, Hash, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human readable text. Events are only written to the trace file
+    #[default]
+    Text,
+    /// One JSON object per line, for each event and planned job
+    Json,
+}
+
+/// Parse an output format from its textual form: `text` or `json`.
+///
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => bail!("Unknown output format \"{s}\", expected text or json"),
+        }
+    }
+}
+
+/// Format of the transfers exported from the history
+///
+#[derive(Debug, Clone, Copy, Default, Hash, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Comma-separated values, with a header row
+    #[default]
+    Csv,
+}
+
+/// Parse an export format from its textual form: `csv`.
+///
+impl FromStr for ExportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(Self::Csv),
+            _ => bail!("Unknown export format \"{s}\", expected csv"),
+        }
+    }
+}
+
+/// Move strategy overrides
+///
+/// Settings that can be tuned for the files coming out of a single slide. The ones not provided
+/// are taken from the global configuration.
+///
+#[derive(Deserialize, Debug, Clone, Default, Hash, PartialEq, Eq)]
+pub struct StrategyOverrides {
+    /// What to do in case of a file collision
+    pub collision: Option<CollisionPolicy>,
+    /// What to do with the files sent both ways between two volumes
+    pub conflict: Option<ConflictPolicy>,
+    /// How the files identical to the ones at their destination are found, `none` to not look
+    /// for them
+    #[serde(default, deserialize_with = "deserialize_identical")]
+    pub skip_identical: Option<Option<Identical>>,
+    /// Algorithm to use for checksumming, `none` to disable it
+    #[serde(default, deserialize_with = "deserialize_check")]
+    pub check: Option<Option<Algorithm>>,
+    /// If true, the copies are read back to check them
+    pub recheck: Option<bool>,
+    /// How much of the copies is read back to check them
+    pub check_mode: Option<CheckMode>,
+    /// If true, enable a secure algorithm for moving files
+    pub safe: Option<bool>,
+    /// Extension of the .wip files of the secure algorithm
+    pub wip_suffix: Option<String>,
+    /// If true, the .wip files are created in a staging folder of the destination slide
+    pub staging: Option<bool>,
+    /// Number of retries in case of a transient failure (timeout, I/O error, checksum mismatch...)
+    pub retries: Option<u8>,
+    /// Number of syncs failing in a row before giving up on the slide, 0 to never give up
+    pub max_failures: Option<u32>,
+    /// Least seconds between the starts of two syncs of the slide, 0 for no limit
+    pub min_interval: Option<u64>,
+    /// Number of files moved concurrently within a slide
+    pub jobs: Option<usize>,
+    /// If true, only the changed blocks of the overwritten files are copied
+    pub delta: Option<bool>,
+    /// If true, the moved files keep their modification and access times
+    pub preserve_times: Option<bool>,
+    /// If true, the moved files keep their permissions, and ownership if allowed
+    pub preserve_permissions: Option<bool>,
+    /// If true, the moved files keep their extended attributes, or alternate data streams
+    pub preserve_xattrs: Option<bool>,
+    /// Bytes per second copied by all the syncjobs together, or by the syncjob of the slide
+    /// setting it alone, 0 for no limit
+    #[serde(default, deserialize_with = "deserialize_size")]
+    pub rate_limit: Option<u64>,
+    /// How the names a FAT, exFAT or NTFS destination does not allow are rewritten, `none` to
+    /// keep them
+    #[serde(default, deserialize_with = "deserialize_sanitize")]
+    pub sanitize: Option<Option<Sanitize>>,
+    /// Whether the overwritten files, and the removed sources, are kept in the trash of their
+    /// slide, `none` to delete them
+    #[serde(default, deserialize_with = "deserialize_trash")]
+    pub use_trash: Option<Option<Trash>>,
+    /// Seconds a file has to go unmodified before it is moved, 0 to move it at once
+    pub min_age: Option<u64>,
+    /// Files larger than this are left in their slide and reported, 0 for no cap
+    #[serde(default, deserialize_with = "deserialize_size")]
+    pub max_file_size: Option<u64>,
+    /// Checksum files written along with the delivered files, `none` to write none
+    #[serde(default, deserialize_with = "deserialize_sidecar")]
+    pub sidecar: Option<Option<Sidecar>>,
+    /// Second check of the delivered files, `none` to check them only once
+    #[serde(default, deserialize_with = "deserialize_reverify")]
+    pub reverify: Option<Option<Reverify>>,
+    /// If true, each sync writes a manifest of the files it delivered to the destination slide
+    pub delivery_manifest: Option<bool>,
+}
+
+impl StrategyOverrides {
+    /// Merge another set of overrides into this one. The settings provided by `other` win.
+    ///
+    /// Returns the names of the settings whose previous value was replaced by a different one.
+    ///
+    pub fn merge(&mut self, other: StrategyOverrides) -> Vec<&'static str> {
+        /// Replace a setting if provided, recording whether a different value was lost.
+        fn replace<T: PartialEq>(
+            name: &'static str,
+            current: &mut Option<T>,
+            other: Option<T>,
+            conflicts: &mut Vec<&'static str>,
+        ) {
+            if let Some(other) = other {
+                if current.as_ref().is_some_and(|c| c != &other) {
+                    conflicts.push(name);
+                }
+                *current = Some(other);
+            }
+        }
+
+        let mut conflicts = Vec::new();
+        replace(
+            "collision",
+            &mut self.collision,
+            other.collision,
+            &mut conflicts,
+        );
+        replace(
+            "conflict",
+            &mut self.conflict,
+            other.conflict,
+            &mut conflicts,
+        );
+        replace(
+            "skip_identical",
+            &mut self.skip_identical,
+            other.skip_identical,
+            &mut conflicts,
+        );
+        replace("check", &mut self.check, other.check, &mut conflicts);
+        replace("recheck", &mut self.recheck, other.recheck, &mut conflicts);
+        replace(
+            "check_mode",
+            &mut self.check_mode,
+            other.check_mode,
+            &mut conflicts,
+        );
+        replace("safe", &mut self.safe, other.safe, &mut conflicts);
+        replace(
+            "wip_suffix",
+            &mut self.wip_suffix,
+            other.wip_suffix,
+            &mut conflicts,
+        );
+        replace("staging", &mut self.staging, other.staging, &mut conflicts);
+        replace("retries", &mut self.retries, other.retries, &mut conflicts);
+        replace(
+            "max_failures",
+            &mut self.max_failures,
+            other.max_failures,
+            &mut conflicts,
+        );
+        replace(
+            "min_interval",
+            &mut self.min_interval,
+            other.min_interval,
+            &mut conflicts,
+        );
+        replace("jobs", &mut self.jobs, other.jobs, &mut conflicts);
+        replace("delta", &mut self.delta, other.delta, &mut conflicts);
+        replace(
+            "preserve_times",
+            &mut self.preserve_times,
+            other.preserve_times,
+            &mut conflicts,
+        );
+        replace(
+            "preserve_permissions",
+            &mut self.preserve_permissions,
+            other.preserve_permissions,
+            &mut conflicts,
+        );
+        replace(
+            "preserve_xattrs",
+            &mut self.preserve_xattrs,
+            other.preserve_xattrs,
+            &mut conflicts,
+        );
+        replace(
+            "rate_limit",
+            &mut self.rate_limit,
+            other.rate_limit,
+            &mut conflicts,
+        );
+        replace(
+            "sanitize",
+            &mut self.sanitize,
+            other.sanitize,
+            &mut conflicts,
+        );
+        replace(
+            "use_trash",
+            &mut self.use_trash,
+            other.use_trash,
+            &mut conflicts,
+        );
+        replace("min_age", &mut self.min_age, other.min_age, &mut conflicts);
+        replace(
+            "max_file_size",
+            &mut self.max_file_size,
+            other.max_file_size,
+            &mut conflicts,
+        );
+        replace("sidecar", &mut self.sidecar, other.sidecar, &mut conflicts);
+        replace(
+            "reverify",
+            &mut self.reverify,
+            other.reverify,
+            &mut conflicts,
+        );
+        replace(
+            "delivery_manifest",
+            &mut self.delivery_manifest,
+            other.delivery_manifest,
+            &mut conflicts,
+        );
+        conflicts
+    }
 }
 
 /// Global configuration
@@ -42,14 +891,160 @@ pub struct GlobalConfig {
     pub dry_run: bool,
     /// If provided, the path to a file where to write the trace
     pub trace: Option<PathBuf>,
+    /// If provided, the path to a SQLite database where to record the traced events too
+    pub history: Option<PathBuf>,
     /// If provided, the algorithm to use for checksumming
     pub check: Option<Algorithm>,
+    /// If true, the copies are read back to check them
+    pub recheck: bool,
+    /// How much of the copies is read back to check them
+    pub check_mode: CheckMode,
     /// What to do in case of a file collision
     pub collision: CollisionPolicy,
+    /// What to do with the files sent both ways between two volumes
+    pub conflict: ConflictPolicy,
+    /// If provided, how the files identical to the ones at their destination are found
+    pub skip_identical: Option<Identical>,
     /// If true, enable a secure algorithm for moving files
     pub safe: bool,
-    /// Number of retries in case of a failure (checksum mismatch, etc)
+    /// Extension of the .wip files of the secure algorithm
+    pub wip_suffix: String,
+    /// If true, the .wip files are created in a staging folder of the destination slide
+    pub staging: bool,
+    /// Number of retries in case of a transient failure (timeout, I/O error, checksum mismatch...)
     pub retries: u8,
+    /// Number of syncs failing in a row before giving up on a slide, 0 to never give up
+    pub max_failures: u32,
+    /// Least seconds between the starts of two syncs of a slide, 0 for no limit
+    pub min_interval: u64,
+    /// If true, only the changed blocks of the overwritten files are copied
+    pub delta: bool,
+    /// If true, the moved files keep their modification and access times
+    pub preserve_times: bool,
+    /// If true, the moved files keep their permissions, and ownership if allowed
+    pub preserve_permissions: bool,
+    /// If true, the moved files keep their extended attributes, or alternate data streams
+    pub preserve_xattrs: bool,
+    /// If provided, the bytes per second copied by all the syncjobs together
+    pub rate_limit: Option<u64>,
+    /// If provided, how the names a FAT, exFAT or NTFS destination does not allow are rewritten
+    pub sanitize: Option<Sanitize>,
+    /// If provided, the files displaced by the moves are kept in the trash of their slide
+    pub use_trash: Option<Trash>,
+    /// If provided, the seconds a file has to go unmodified before it is moved
+    pub min_age: Option<u64>,
+    /// If provided, the files larger than this many bytes are left in their slide and reported
+    pub max_file_size: Option<u64>,
+    /// If provided, the checksum files written along with the delivered files
+    pub sidecar: Option<Sidecar>,
+    /// If provided, when the delivered files are checked again, and what is done if corrupted
+    pub reverify: Option<Reverify>,
+    /// If true, each sync writes a manifest of the files it delivered to the destination slide
+    pub delivery_manifest: bool,
+    /// Gitignore-style patterns of the slide contents that will never be synced
+    pub exclude: Vec<String>,
+    /// If true, unknown fields in the volume and slide configuration files are an error
+    pub strict: bool,
+    /// If true, sync every slide a single time without watching for changes
+    pub once: bool,
+    /// Format of the information printed to stdout
+    pub output: OutputFormat,
+    /// Number of files moved concurrently within a slide
+    pub jobs: usize,
+    /// Number of syncjobs doing filesystem work at once
+    pub parallel: usize,
+    /// If provided, the seconds without filesystem events awaited before syncing a slide
+    pub debounce: Option<u64>,
+    /// If provided, the most seconds a burst of filesystem events may postpone the sync of a slide
+    pub debounce_max: Option<u64>,
+    /// If true, the slides of every rootset are polled for changes rather than watched
+    pub poll: bool,
+    /// Seconds between two scans of the slides polled, whether they have to or they cannot be
+    /// watched
+    pub poll_interval: u64,
+    /// If provided, the seconds between two heartbeats traced along the syncs lasting longer
+    pub heartbeat: Option<u64>,
+    /// If true, show the bytes moved per file and per syncjob
+    pub progress: bool,
+    /// Files smaller than this many bytes are not synced
+    pub min_size: Option<u64>,
+    /// Files larger than this many bytes are not synced
+    pub max_size: Option<u64>,
+    /// If provided, the socket (named pipe on Windows) where to serve the control interface
+    /// while watching the slides
+    pub control: Option<PathBuf>,
+    /// If provided, the key signing the manifest of the files delivered by each sync
+    pub signing_key: Option<PathBuf>,
+    /// Public keys, hex, whose manifests are trusted when verifying the deliveries
+    pub trusted_keys: Vec<String>,
+    /// If provided, the MQTT broker where the state of the syncjobs is published
+    pub mqtt: Option<MqttConfig>,
+}
+
+/// MQTT broker where the state changes of the syncjobs and their summaries are published.
+///
+#[derive(Deserialize, Clone, Hash, PartialEq, Eq)]
+pub struct MqttConfig {
+    /// Host name or address of the broker
+    pub host: String,
+    /// Port of the broker, 1883 by default
+    pub port: Option<u16>,
+    /// Prefix of the topics published, `bitslides` by default
+    pub topic: Option<String>,
+    /// Client identifier, `bitslides-<process id>` by default
+    pub client_id: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Fields not matching any of the above.
+    #[serde(flatten)]
+    pub unknown: UnknownFields,
+}
+
+/// MqttConfig Debug implementation, leaving the password out of the logs.
+///
+impl std::fmt::Debug for MqttConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MqttConfig")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("topic", &self.topic)
+            .field("client_id", &self.client_id)
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| "<redacted>"))
+            .field("unknown", &self.unknown)
+            .finish()
+    }
+}
+
+/// What a tidy-up rule does with the matching files.
+///
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TidyOperation {
+    /// Move the file to the destination
+    #[serde(alias = "Move")]
+    Move,
+    /// Move the file to a new folder inside the destination, named after the current date
+    #[serde(alias = "Move_to_new_dir")]
+    MoveToNewDir,
+}
+
+/// Tidy-up rule of a volume.
+///
+/// Files delivered to the volume whose path, relative to its slide, matches the `regex` are moved
+/// out of the slide to the `destination`, relative to the volume root.
+///
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TidyRule {
+    /// Regular expression matched against the path of the files, with `/` as separator
+    pub regex: String,
+    /// What to do with the matching files
+    pub operation: TidyOperation,
+    /// Parameters of the operation. `move_to_new_dir` takes the date format of the new folder
+    #[serde(default)]
+    pub params: Vec<String>,
+    /// Folder where the files are moved to, relative to the volume root
+    pub destination: PathBuf,
 }
 
 /// Volume configuration
@@ -62,18 +1057,42 @@ pub struct VolumeConfig {
     pub name: Option<String>,
     /// Optional enable status of the volume. Disabled volumes will be identified but not processed.
     pub disabled: Option<bool>,
+    /// Optional priority of the volume. Syncjobs from higher priority volumes are run first.
+    pub priority: Option<i32>,
+    /// Optional free-form description of the volume.
+    pub description: Option<String>,
+    /// Optional cost of routing files through the volume, 1 by default.
+    pub cost: Option<u32>,
+    /// Optional flag to only take files from the volume, never moving any into it.
+    pub send_only: Option<bool>,
+    /// Optional flag to only move files into the volume, never taking any from it.
+    pub receive_only: Option<bool>,
+    /// Optional list of volumes never exchanging files with this one.
+    pub ignore: Option<Vec<String>>,
+    /// Optional number of seconds between the pulls of the files moved into the volume, instead
+    /// of watching their sources.
+    pub pull: Option<u64>,
+    /// Fields not matching any of the above.
+    #[serde(flatten)]
+    pub unknown: UnknownFields,
 }
 
 impl VolumeConfig {
     /// Read a volume configuration file
     ///
-    pub fn new<P>(file_path: P) -> Result<Self>
+    /// If `strict`, unknown fields are rejected.
+    ///
+    pub fn new<P>(file_path: P, strict: bool) -> Result<Self>
     where
         P: AsRef<Path>,
     {
-        let file_content = std::fs::read_to_string(file_path)?;
-        let config = serde_yaml::from_str(&file_content)?;
-        Ok(config)
+        read_yaml(file_path, strict)
+    }
+}
+
+impl Strict for VolumeConfig {
+    fn unknown_fields(&self) -> Vec<String> {
+        self.unknown.keys().cloned().collect()
     }
 }
 
@@ -83,19 +1102,85 @@ impl VolumeConfig {
 ///
 #[derive(Deserialize, Debug)]
 pub struct SlideConfig {
-    /// Default route for the slide.
-    pub route: Option<String>,
+    /// Default route for the slide. Several routes may be listed, in order of preference.
+    #[serde(default, deserialize_with = "deserialize_routes")]
+    pub route: Vec<String>,
+    /// Spread the files over every route available, rather than having them all follow the first
+    /// one.
+    #[serde(default)]
+    pub balance: Option<Balance>,
+    /// Tidy-up rules, only meaningful in the slide named after its own volume.
+    #[serde(default)]
+    pub rules: Vec<TidyRule>,
+    /// Move strategy overrides for the files coming out of this slide.
+    #[serde(flatten)]
+    pub overrides: StrategyOverrides,
+    /// Fields not matching any of the above.
+    #[serde(flatten)]
+    pub unknown: UnknownFields,
 }
 
 impl SlideConfig {
     /// Read a slide configuration file
     ///
-    pub fn new<P>(file_path: P) -> Result<Self>
+    /// If `strict`, unknown fields are rejected.
+    ///
+    pub fn new<P>(file_path: P, strict: bool) -> Result<Self>
     where
         P: AsRef<Path>,
     {
-        let file_content = std::fs::read_to_string(file_path)?;
-        let config = serde_yaml::from_str(&file_content)?;
-        Ok(config)
+        read_yaml(file_path, strict)
+    }
+}
+
+impl Strict for SlideConfig {
+    fn unknown_fields(&self) -> Vec<String> {
+        self.unknown.keys().cloned().collect()
+    }
+}
+
+/// Configuration that keeps track of the unknown fields found while reading it.
+///
+/// Unknown fields are usually typos (ex. `rout:` instead of `route:`), silently ignored unless
+/// running in strict mode.
+///
+/// They are captured rather than refused with `#[serde(deny_unknown_fields)]`, which serde does not
+/// support along with the flattened overrides, and which could not depend on the `strict` setting
+/// read from the very same file.
+///
+pub trait Strict {
+    /// Names of the unknown fields, as dotted paths (ex. `profiles.usb.rout`).
+    ///
+    fn unknown_fields(&self) -> Vec<String>;
+
+    /// Fail if any unknown field was found.
+    ///
+    fn check_unknown(&self) -> Result<()> {
+        match self.unknown_fields().as_slice() {
+            [] => Ok(()),
+            [field] => bail!("Unknown field `{field}`"),
+            fields => bail!("Unknown fields `{}`", fields.join("`, `")),
+        }
+    }
+}
+
+/// Fields not matching any setting, captured by `#[serde(flatten)]`.
+///
+pub type UnknownFields = BTreeMap<String, serde_yaml::Value>;
+
+/// Read a YAML configuration file.
+///
+/// If `strict`, the presence of unknown fields is an error.
+///
+fn read_yaml<T, P>(file_path: P, strict: bool) -> Result<T>
+where
+    T: DeserializeOwned + Strict,
+    P: AsRef<Path>,
+{
+    let file_content = std::fs::read_to_string(file_path)?;
+    let config: T = serde_yaml::from_str(&file_content)?;
+    if strict {
+        config.check_unknown()?;
     }
+    Ok(config)
 }
@@ -0,0 +1,830 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail, Result};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    sync::{mpsc::WeakSender, watch, Notify},
+    task::JoinHandle,
+};
+
+use crate::{
+    fs::Transferred,
+    issues::{Issue, IssueKind},
+    mqtt::Publisher,
+    progress::{JobProgress, Phase},
+};
+
+/// State of a syncjob, as reported by the control interface.
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JobStatus {
+    /// Syncjob, as in the logs and the trace
+    pub job: String,
+    /// If true, the slide is being synced right now
+    pub syncing: bool,
+    /// If true, the syncjob waits to be resumed before syncing again
+    #[serde(default)]
+    pub paused: bool,
+    /// Local time the last sync ended at, if any
+    pub last_sync: Option<String>,
+    /// Milliseconds the last sync took, if any
+    #[serde(default)]
+    pub last_duration_ms: Option<u64>,
+    /// Number of syncs run since the start
+    #[serde(default)]
+    pub syncs: u64,
+    /// Number of files moved since the start
+    pub files: u64,
+    /// Number of bytes moved since the start
+    pub bytes: u64,
+    /// Number of failed attempts to move a file retried since the start
+    #[serde(default)]
+    pub retries: u64,
+    /// Number of files left in the slides, or found at their destination already, since the start
+    #[serde(default)]
+    pub skipped: u64,
+    /// Number of syncs ended by an error since the start
+    #[serde(default)]
+    pub failures: u64,
+    /// Watcher triggers waiting for the syncjob
+    pub pending: usize,
+    /// Error of the last sync if it failed, like the one that ended the syncjob
+    pub error: Option<String>,
+}
+
+/// JobStatus Display implementation.
+///
+impl Display for JobStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = match (&self.error, self.paused, self.syncing) {
+            (Some(_), _, _) => "failed",
+            (None, true, true) => "pausing",
+            (None, true, false) => "paused",
+            (None, false, true) => "syncing",
+            (None, false, false) => "idle",
+        };
+        write!(
+            f,
+            "{}: {state}, last sync {}",
+            self.job,
+            self.last_sync.as_deref().unwrap_or("never"),
+        )?;
+        if let Some(duration) = self.last_duration_ms {
+            write!(f, " (took {:?})", Duration::from_millis(duration))?;
+        }
+        write!(
+            f,
+            ", {} file(s) and {} byte(s) moved in {} sync(s), {} skipped, {} retry(ies), {} failure(s), {} pending trigger(s)",
+            self.files,
+            self.bytes,
+            self.syncs,
+            self.skipped,
+            self.retries,
+            self.failures,
+            self.pending
+        )?;
+        if let Some(error) = &self.error {
+            write!(f, ", {error}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Command understood by the control interface.
+///
+/// The target of a command is either a syncjob, as printed by `status`, or a volume, meaning every
+/// syncjob from, via or to it. Without target, every syncjob is affected.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ControlCommand {
+    /// Report the state of every syncjob
+    Status,
+    /// Stop syncing the target. Replies once its ongoing sync, if any, is cut short
+    Pause(Option<String>),
+    /// Sync the target again when triggered
+    Resume(Option<String>),
+    /// Sync the target right away, or as soon as it is resumed
+    SyncNow(Option<String>),
+    /// Stop the instance, as a termination signal would
+    Shutdown,
+}
+
+/// ControlCommand Display implementation, the line sent to the control interface.
+///
+impl Display for ControlCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (command, target) = match self {
+            Self::Status => ("status", None),
+            Self::Pause(target) => ("pause", target.as_ref()),
+            Self::Resume(target) => ("resume", target.as_ref()),
+            Self::SyncNow(target) => ("sync-now", target.as_ref()),
+            Self::Shutdown => ("shutdown", None),
+        };
+        match target {
+            Some(target) => write!(f, "{command} {target}"),
+            None => write!(f, "{command}"),
+        }
+    }
+}
+
+impl FromStr for ControlCommand {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let (command, target) = match s.split_once(' ') {
+            Some((command, target)) => (command, Some(target.trim().to_owned())),
+            None => (s, None),
+        };
+        match (command, target) {
+            ("status", None) => Ok(Self::Status),
+            ("pause", target) => Ok(Self::Pause(target)),
+            ("resume", target) => Ok(Self::Resume(target)),
+            ("sync-now", target) => Ok(Self::SyncNow(target)),
+            ("shutdown", None) => Ok(Self::Shutdown),
+            _ => bail!("Unknown command \"{s}\""),
+        }
+    }
+}
+
+/// What a syncjob is allowed to do.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Gate {
+    /// Sync whenever triggered
+    Open,
+    /// Wait to be resumed
+    Paused,
+    /// End, the instance is stopping
+    Closed,
+}
+
+/// A syncjob known to the registry.
+///
+struct Entry {
+    status: JobStatus,
+    /// Source, proxy and destination volumes
+    volumes: [String; 3],
+    /// Watcher trigger of the syncjob. Weak so it does not keep the syncjob alive
+    trigger: Option<WeakSender<()>>,
+    gate: watch::Sender<Gate>,
+    /// When the ongoing sync started, if any
+    started: Option<Instant>,
+    /// Files of the ongoing sync not moved yet, along with their size and the bytes copied so far,
+    /// None until their copy starts
+    moving: HashMap<PathBuf, (u64, Option<u64>)>,
+    /// Files and bytes moved by the ongoing sync, or the last one
+    moved: (u64, u64),
+}
+
+impl Entry {
+    /// Check if the syncjob is the `target`, or involves the `target` volume.
+    ///
+    fn matches(&self, target: Option<&str>) -> bool {
+        match target {
+            Some(target) => self.status.job == target || self.volumes.iter().any(|v| v == target),
+            None => true,
+        }
+    }
+
+    /// Current state of the syncjob.
+    ///
+    fn status(&self) -> JobStatus {
+        JobStatus {
+            pending: self
+                .trigger
+                .as_ref()
+                .and_then(|trigger| trigger.upgrade())
+                .map(|trigger| trigger.max_capacity() - trigger.capacity())
+                .unwrap_or(0),
+            ..self.status.clone()
+        }
+    }
+}
+
+/// State of the running syncjobs, shared between their tasks and the control server.
+///
+#[derive(Clone, Default)]
+pub(crate) struct Registry {
+    jobs: Arc<Mutex<Vec<Entry>>>,
+    /// State of the syncjobs unregistered, picked up if they are registered again
+    carried: Arc<Mutex<Vec<JobStatus>>>,
+    /// Problems the syncjobs went through without stopping, in the order they happened
+    issues: Arc<Mutex<Vec<Issue>>>,
+    /// Where the changes of state of the syncjobs are published, if anywhere
+    publisher: Option<Arc<Publisher>>,
+}
+
+impl Registry {
+    /// Publish the changes of state of the syncjobs through the `publisher`, if any.
+    ///
+    pub(crate) fn publishing(self, publisher: Option<Arc<Publisher>>) -> Self {
+        Self { publisher, ..self }
+    }
+
+    /// Add a syncjob, in the order they are run.
+    ///
+    /// Returns the gate the syncjob has to wait for before each sync.
+    ///
+    pub(crate) fn register(
+        &self,
+        job: String,
+        volumes: [String; 3],
+        trigger: Option<WeakSender<()>>,
+    ) -> watch::Receiver<Gate> {
+        let carried = {
+            let mut carried = self.carried.lock().unwrap();
+            carried
+                .iter()
+                .position(|status| status.job == job)
+                .map(|index| carried.remove(index))
+        };
+        let status = match carried {
+            Some(carried) => JobStatus {
+                syncing: false,
+                pending: 0,
+                error: None,
+                ..carried
+            },
+            None => JobStatus {
+                job,
+                ..Default::default()
+            },
+        };
+        let (gate, rx) = watch::channel(if status.paused {
+            Gate::Paused
+        } else {
+            Gate::Open
+        });
+        let mut jobs = self.jobs.lock().unwrap();
+        let job = status.job.clone();
+        jobs.push(Entry {
+            status,
+            volumes,
+            trigger,
+            gate,
+            started: None,
+            moving: HashMap::new(),
+            moved: (0, 0),
+        });
+        self.publish(&jobs, &job, true);
+        rx
+    }
+
+    /// Publish the state of the syncjob named `job`, and whether its volumes are being synced if
+    /// `volumes`.
+    ///
+    fn publish(&self, jobs: &[Entry], job: &str, volumes: bool) {
+        let Some(publisher) = &self.publisher else {
+            return;
+        };
+        let Some(entry) = jobs.iter().find(|entry| entry.status.job == job) else {
+            return;
+        };
+        publisher.job(&entry.volumes, &entry.status());
+        if volumes {
+            let mut names = entry.volumes.to_vec();
+            names.dedup();
+            for name in names {
+                let syncing = jobs
+                    .iter()
+                    .any(|other| other.status.syncing && other.volumes.contains(&name));
+                publisher.volume(&name, syncing);
+            }
+        }
+    }
+
+    /// Publish the `report` of the last sync of the slide.
+    ///
+    pub(crate) fn summed_up(&self, job: &str, report: &str) {
+        let Some(publisher) = &self.publisher else {
+            return;
+        };
+        let jobs = self.jobs.lock().unwrap();
+        if let Some(entry) = jobs.iter().find(|entry| entry.status.job == job) {
+            publisher.sync_summary(&entry.volumes, report);
+        }
+    }
+
+    /// Apply `f` to the syncjob named `job`, if registered.
+    ///
+    fn with_job(&self, job: &str, f: impl FnOnce(&mut Entry)) {
+        let mut jobs = self.jobs.lock().unwrap();
+        if let Some(entry) = jobs.iter_mut().find(|entry| entry.status.job == job) {
+            f(entry);
+        }
+    }
+
+    /// Apply `f` to the syncjobs matching the `target`, returning their names.
+    ///
+    fn apply(&self, target: Option<&str>, mut f: impl FnMut(&mut Entry)) -> Result<Vec<String>> {
+        let mut jobs = self.jobs.lock().unwrap();
+        let matched = jobs
+            .iter_mut()
+            .filter(|entry| entry.matches(target))
+            .map(|entry| {
+                f(entry);
+                entry.status.job.clone()
+            })
+            .collect::<Vec<_>>();
+        if matched.is_empty() {
+            bail!("No syncjob matches \"{}\"", target.unwrap_or_default());
+        }
+        Ok(matched)
+    }
+
+    /// Record the start of a sync of the slide.
+    ///
+    /// Returns false, recording nothing, if the syncjob was paused or closed meanwhile.
+    ///
+    pub(crate) fn started(&self, job: &str) -> bool {
+        let mut jobs = self.jobs.lock().unwrap();
+        match jobs.iter_mut().find(|entry| entry.status.job == job) {
+            Some(entry) if *entry.gate.borrow() != Gate::Open => false,
+            Some(entry) => {
+                entry.status.syncing = true;
+                entry.started = Some(Instant::now());
+                entry.moving.clear();
+                entry.moved = (0, 0);
+                self.publish(&jobs, job, true);
+                true
+            }
+            None => true,
+        }
+    }
+
+    /// Record the end of a sync of the slide, along with what it moved or its error.
+    ///
+    pub(crate) fn finished(&self, job: &str, result: &Result<Transferred>) {
+        let mut jobs = self.jobs.lock().unwrap();
+        let Some(entry) = jobs.iter_mut().find(|entry| entry.status.job == job) else {
+            return;
+        };
+        let status = &mut entry.status;
+        status.syncing = false;
+        status.syncs += 1;
+        status.last_sync = Some(Local::now().format("%Y-%m-%d %H:%M:%S").to_string());
+        status.last_duration_ms = entry
+            .started
+            .take()
+            .map(|started| started.elapsed().as_millis() as u64);
+        // The moves of a sync ended by an error may never report back
+        entry.moving.clear();
+        match result {
+            Ok(transferred) => {
+                status.files += transferred.files;
+                status.bytes += transferred.bytes;
+                status.retries += transferred.retries;
+                status.skipped += transferred.skipped;
+                status.error = None;
+            }
+            Err(e) => {
+                status.failures += 1;
+                status.error = Some(e.to_string());
+            }
+        }
+        self.publish(&jobs, job, true);
+    }
+
+    /// Current state of every syncjob.
+    ///
+    pub(crate) fn snapshot(&self) -> Vec<JobStatus> {
+        self.jobs
+            .lock()
+            .unwrap()
+            .iter()
+            .map(Entry::status)
+            .collect()
+    }
+
+    /// Record a file found by the ongoing sync of the slide, to be moved.
+    ///
+    pub(crate) fn pending(&self, job: &str, file: &Path, size: u64) {
+        self.with_job(job, |entry| {
+            entry.moving.insert(file.to_owned(), (size, None));
+        });
+    }
+
+    /// Record some more bytes of a file copied.
+    ///
+    pub(crate) fn copied(&self, job: &str, file: &Path, bytes: u64) {
+        self.with_job(job, |entry| {
+            if let Some((_, copied)) = entry.moving.get_mut(file) {
+                *copied = Some(copied.unwrap_or(0) + bytes);
+            }
+        });
+    }
+
+    /// Record a copy of a file started over, the bytes of the failed attempt not counting.
+    ///
+    pub(crate) fn restarted(&self, job: &str, file: &Path) {
+        self.with_job(job, |entry| {
+            if let Some((_, copied)) = entry.moving.get_mut(file) {
+                *copied = Some(0);
+            }
+        });
+    }
+
+    /// Record a file no longer pending, whether it was `delivered` or left in place.
+    ///
+    pub(crate) fn done(&self, job: &str, file: &Path, delivered: bool) {
+        self.with_job(job, |entry| {
+            if let Some((size, _)) = entry.moving.remove(file).filter(|_| delivered) {
+                entry.moved.0 += 1;
+                entry.moved.1 += size;
+            }
+        });
+    }
+
+    /// Record a problem the syncjob went through without stopping.
+    ///
+    /// A problem already recorded for the same syncjob and file is only kept once, the latest
+    /// time it happened, so a file failing sync after sync does not pile up issues.
+    ///
+    pub(crate) fn report(&self, job: &str, kind: IssueKind, path: Option<&Path>, detail: String) {
+        let issue = Issue::new(job, kind, path, detail);
+        let mut issues = self.issues.lock().unwrap();
+        issues.retain(|other| {
+            (&other.job, other.kind, &other.path) != (&issue.job, kind, &issue.path)
+        });
+        issues.push(issue);
+    }
+
+    /// Problems the syncjobs went through so far.
+    ///
+    pub(crate) fn issues(&self) -> Vec<Issue> {
+        self.issues.lock().unwrap().clone()
+    }
+
+    /// Progress of every syncjob.
+    ///
+    pub(crate) fn progress(&self) -> Vec<JobProgress> {
+        self.jobs
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|entry| {
+                let status = &entry.status;
+                let phase = match (&status.error, status.paused, status.syncing) {
+                    (Some(_), _, false) => Phase::Failed,
+                    (_, true, false) => Phase::Paused,
+                    (_, _, true) if !entry.moving.is_empty() => Phase::Moving,
+                    (_, _, true) => Phase::Scanning,
+                    (None, false, false) => Phase::Idle,
+                };
+                let copied = entry
+                    .moving
+                    .values()
+                    .filter_map(|(_, copied)| *copied)
+                    .sum::<u64>();
+                let mut current = entry.moving.keys().cloned().collect::<Vec<_>>();
+                current.sort();
+                JobProgress {
+                    job: status.job.clone(),
+                    phase,
+                    files_pending: entry.moving.len() as u64,
+                    bytes_pending: entry
+                        .moving
+                        .values()
+                        .map(|(size, copied)| size.saturating_sub(copied.unwrap_or(0)))
+                        .sum(),
+                    files_moved: entry.moved.0,
+                    bytes_moved: entry.moved.1 + copied,
+                    current,
+                }
+            })
+            .collect()
+    }
+
+    /// Files of the ongoing sync of the slide being copied, along with their size and the bytes
+    /// copied so far.
+    ///
+    pub(crate) fn copying(&self, job: &str) -> Vec<(PathBuf, u64, u64)> {
+        let mut copying = Vec::new();
+        self.with_job(job, |entry| {
+            copying.extend(
+                entry
+                    .moving
+                    .iter()
+                    .filter_map(|(file, (size, copied))| Some((file.clone(), *size, (*copied)?))),
+            );
+        });
+        copying.sort();
+        copying
+    }
+
+    /// Pause or resume the syncjobs matching the `target`.
+    ///
+    /// A resumed syncjob syncs right away, catching up with the changes missed while paused.
+    ///
+    fn set_paused(&self, target: Option<&str>, paused: bool) -> Result<Vec<String>> {
+        let matched = self.apply(target, |entry| {
+            entry.status.paused = paused;
+            let resumed = entry.gate.send_if_modified(|gate| {
+                let next = match (*gate, paused) {
+                    (Gate::Closed, _) => Gate::Closed,
+                    (_, true) => Gate::Paused,
+                    (_, false) => Gate::Open,
+                };
+                std::mem::replace(gate, next) != next
+            }) && !paused;
+            if resumed {
+                if let Some(trigger) = entry.trigger.as_ref().and_then(|t| t.upgrade()) {
+                    // A full channel means a sync is already pending
+                    let _ = trigger.try_send(());
+                }
+            }
+        })?;
+        let jobs = self.jobs.lock().unwrap();
+        for job in &matched {
+            self.publish(&jobs, job, false);
+        }
+        Ok(matched)
+    }
+
+    /// Trigger the syncjobs matching the `target`, as the watcher would.
+    ///
+    pub(crate) fn trigger(&self, target: Option<&str>) -> Result<Vec<String>> {
+        self.apply(target, |entry| {
+            if let Some(trigger) = entry.trigger.as_ref().and_then(|t| t.upgrade()) {
+                // A full channel means a sync is already pending
+                let _ = trigger.try_send(());
+            }
+        })
+    }
+
+    /// Check if any of the given syncjobs is still syncing.
+    ///
+    fn is_syncing(&self, jobs: &[String]) -> bool {
+        self.jobs
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|entry| entry.status.syncing && jobs.contains(&entry.status.job))
+    }
+
+    /// Let every syncjob end, paused ones included.
+    ///
+    pub(crate) fn close(&self) {
+        for entry in self.jobs.lock().unwrap().iter() {
+            entry.gate.send_replace(Gate::Closed);
+        }
+    }
+
+    /// Let the syncjobs named `jobs` end, paused ones included. The ones waiting for a trigger are
+    /// woken up to find their gate closed.
+    ///
+    pub(crate) fn close_jobs(&self, jobs: &[String]) {
+        for entry in self.jobs.lock().unwrap().iter() {
+            if !jobs.contains(&entry.status.job) {
+                continue;
+            }
+            entry.gate.send_replace(Gate::Closed);
+            if let Some(trigger) = entry.trigger.as_ref().and_then(|t| t.upgrade()) {
+                // A full channel means a sync is already pending
+                let _ = trigger.try_send(());
+            }
+        }
+    }
+
+    /// Forget the syncjobs named `jobs`, once they ended. The ones registered again keep their
+    /// statistics, and stay paused if they were.
+    ///
+    pub(crate) fn unregister(&self, jobs: &[String]) {
+        let mut entries = self.jobs.lock().unwrap();
+        let (gone, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut *entries)
+            .into_iter()
+            .partition(|entry| jobs.contains(&entry.status.job));
+        *entries = kept;
+        self.carried
+            .lock()
+            .unwrap()
+            .extend(gone.iter().map(Entry::status));
+    }
+
+    /// Close the registry once the returned guard is dropped, even by a future cancelled.
+    ///
+    pub(crate) fn closing(&self) -> Closing {
+        Closing(self.clone())
+    }
+}
+
+/// Guard closing a registry when dropped.
+///
+pub(crate) struct Closing(Registry);
+
+impl Drop for Closing {
+    fn drop(&mut self) {
+        self.0.close();
+    }
+}
+
+/// Control server of a running instance.
+///
+/// Each client sends a command per connection, as a line, and the server replies with a line per
+/// item. A line starting with `error: ` reports a failed command.
+///
+pub(crate) struct Server {
+    path: PathBuf,
+    handle: JoinHandle<()>,
+    registry: Registry,
+    /// Notified when a client requests a shutdown
+    shutdown: Arc<Notify>,
+}
+
+impl Server {
+    /// Listen on the socket (named pipe on Windows) at `path`.
+    ///
+    /// A socket left behind by an instance no longer running is replaced, but nothing else.
+    ///
+    pub(crate) async fn bind(path: &Path, registry: Registry) -> Result<Self> {
+        let shutdown = Arc::new(Notify::new());
+        let handle = listen(path, registry.clone(), shutdown.clone()).await?;
+        log::info!("Control interface listening on {path:?}");
+        Ok(Self {
+            path: path.to_owned(),
+            handle,
+            registry,
+            shutdown,
+        })
+    }
+
+    /// Wait for a client to request a shutdown.
+    ///
+    pub(crate) async fn stopped(&self) {
+        self.shutdown.notified().await
+    }
+
+    /// Stop serving, and remove the socket. The paused syncjobs are let go.
+    ///
+    pub(crate) fn shutdown(self) {
+        self.handle.abort();
+        self.registry.close();
+        #[cfg(unix)]
+        let _ = std::fs::remove_file(&self.path);
+        log::debug!("Control interface at {:?} closed", self.path);
+    }
+}
+
+#[cfg(unix)]
+async fn listen(path: &Path, registry: Registry, shutdown: Arc<Notify>) -> Result<JoinHandle<()>> {
+    use std::os::unix::fs::FileTypeExt;
+    use tokio::net::{UnixListener, UnixStream};
+
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        // Anything else there is not ours to replace
+        if !metadata.file_type().is_socket() {
+            bail!("{path:?}: Not a socket, refusing to replace it");
+        }
+        if UnixStream::connect(path).await.is_ok() {
+            bail!("{path:?}: Another instance is already listening");
+        }
+        std::fs::remove_file(path)?;
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let listener = UnixListener::bind(path)
+        .map_err(|e| anyhow!("{path:?}: Unable to listen for control commands: {e}"))?;
+
+    Ok(tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(serve(stream, registry.clone(), shutdown.clone()));
+                }
+                Err(e) => log::warn!("Unable to accept a control connection: {e}"),
+            }
+        }
+    }))
+}
+
+#[cfg(windows)]
+async fn listen(path: &Path, registry: Registry, shutdown: Arc<Notify>) -> Result<JoinHandle<()>> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let path = path.to_owned();
+    let mut server = ServerOptions::new()
+        .first_pipe_instance(true)
+        .create(&path)
+        .map_err(|e| anyhow!("{path:?}: Unable to listen for control commands: {e}"))?;
+
+    Ok(tokio::spawn(async move {
+        loop {
+            if let Err(e) = server.connect().await {
+                log::warn!("Unable to accept a control connection: {e}");
+                continue;
+            }
+            // A new instance of the pipe has to be waiting for the next client
+            let next = match ServerOptions::new().create(&path) {
+                Ok(next) => next,
+                Err(e) => {
+                    log::error!("{path:?}: Unable to listen for control commands: {e}");
+                    return;
+                }
+            };
+            tokio::spawn(serve(
+                std::mem::replace(&mut server, next),
+                registry.clone(),
+                shutdown.clone(),
+            ));
+        }
+    }))
+}
+
+/// Serve the command of a client.
+///
+async fn serve<S: AsyncRead + AsyncWrite>(stream: S, registry: Registry, shutdown: Arc<Notify>) {
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut command = String::new();
+    if BufReader::new(reader)
+        .read_line(&mut command)
+        .await
+        .is_err()
+    {
+        return;
+    }
+
+    let reply = match execute(&command, &registry, &shutdown).await {
+        Ok(lines) => lines,
+        Err(e) => vec![format!("error: {e}")],
+    };
+    for line in reply {
+        if writer
+            .write_all(format!("{line}\n").as_bytes())
+            .await
+            .is_err()
+        {
+            return;
+        }
+    }
+    let _ = writer.shutdown().await;
+}
+
+/// Execute a control command, returning the lines of the reply.
+///
+async fn execute(command: &str, registry: &Registry, shutdown: &Notify) -> Result<Vec<String>> {
+    const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+    let command = command.parse::<ControlCommand>()?;
+    log::info!("Control command: {command}");
+    match command {
+        ControlCommand::Status => registry
+            .snapshot()
+            .iter()
+            .map(|status| Ok(serde_json::to_string(status)?))
+            .collect(),
+        ControlCommand::Pause(target) => {
+            let jobs = registry.set_paused(target.as_deref(), true)?;
+            // Only reply once nothing is being moved, so the drives can be unplugged
+            while registry.is_syncing(&jobs) {
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+            Ok(jobs)
+        }
+        ControlCommand::Resume(target) => registry.set_paused(target.as_deref(), false),
+        ControlCommand::SyncNow(target) => registry.trigger(target.as_deref()),
+        ControlCommand::Shutdown => {
+            shutdown.notify_one();
+            Ok(vec![])
+        }
+    }
+}
+
+/// Send a command to the instance listening at `path`, returning the lines of the reply.
+///
+pub(crate) async fn request(path: &Path, command: &ControlCommand) -> Result<Vec<String>> {
+    let not_running =
+        |e: std::io::Error| anyhow!("{path:?}: Unable to reach a running instance: {e}");
+
+    #[cfg(unix)]
+    let stream = tokio::net::UnixStream::connect(path)
+        .await
+        .map_err(not_running)?;
+    #[cfg(windows)]
+    let stream = tokio::net::windows::named_pipe::ClientOptions::new()
+        .open(path)
+        .map_err(not_running)?;
+
+    let (reader, mut writer) = tokio::io::split(stream);
+    writer.write_all(format!("{command}\n").as_bytes()).await?;
+
+    let mut lines = BufReader::new(reader).lines();
+    let mut reply = Vec::new();
+    while let Some(line) = lines.next_line().await? {
+        if let Some(error) = line.strip_prefix("error: ") {
+            bail!("{error}");
+        }
+        reply.push(line);
+    }
+    Ok(reply)
+}
@@ -0,0 +1,161 @@
+use anyhow::Result;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::{path::Path, sync::Arc};
+
+/// File holding the exclusion patterns of a folder of a slide, and of its contents.
+///
+pub(crate) const IGNORE_FILE: &str = ".slideignore";
+
+/// Content filter.
+///
+/// Decides which entries of a slide are shipped between volumes. The exclusion patterns follow
+/// the gitignore syntax, and are matched against the path of each entry relative to the slide.
+/// Files may also be held back by their size.
+///
+#[derive(Debug, Clone)]
+pub struct Filter {
+    excludes: Arc<Gitignore>,
+    /// Patterns of the ignore files of the folders descended, the innermost last
+    nested: Vec<Arc<Gitignore>>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    /// Files taken, if the slide is spread over several routes
+    share: Option<Share>,
+}
+
+/// Share of the files of a slide taken by one of the syncjobs spreading them over several routes.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Share {
+    /// Route taken by the syncjob
+    pub(crate) index: usize,
+    /// Weight of every route
+    pub(crate) weights: Arc<Vec<u64>>,
+}
+
+impl Share {
+    /// Check if the file at `path`, relative to the slide, falls in this share. A file always
+    /// falls in the same share, as long as the weights are the same, whichever syncjob looks.
+    ///
+    pub(crate) fn takes(&self, path: &Path) -> bool {
+        let total = self.weights.iter().sum::<u64>();
+        if total == 0 {
+            return self.index == 0;
+        }
+        // FNV-1a, stable across runs and platforms
+        let mut hash = 0xcbf29ce484222325u64;
+        for component in path.components() {
+            for byte in component
+                .as_os_str()
+                .to_string_lossy()
+                .bytes()
+                .chain([b'/'])
+            {
+                hash = (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3);
+            }
+        }
+        let mut spot = hash % total;
+        for (index, weight) in self.weights.iter().enumerate() {
+            if spot < *weight {
+                return index == self.index;
+            }
+            spot -= weight;
+        }
+        false
+    }
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Self {
+            excludes: Arc::new(Gitignore::empty()),
+            nested: vec![],
+            min_size: None,
+            max_size: None,
+            share: None,
+        }
+    }
+}
+
+impl Filter {
+    /// Create a new filter from a list of gitignore-style exclusion patterns.
+    ///
+    pub fn new(excludes: &[String]) -> Result<Self> {
+        let mut builder = GitignoreBuilder::new("");
+        for pattern in excludes {
+            builder.add_line(None, pattern)?;
+        }
+        Ok(Self {
+            excludes: Arc::new(builder.build()?),
+            ..Default::default()
+        })
+    }
+
+    /// Add the patterns of the ignore file of a folder, if it has one.
+    ///
+    /// `dir` is the path of the folder relative to the `slide`. Its patterns are matched against
+    /// the paths relative to the folder, and take precedence over the ones of the parent folders
+    /// and the configured ones, like nested .gitignore files do. Returns `None` without an ignore
+    /// file.
+    ///
+    pub(crate) fn with_ignore_file(&self, slide: &Path, dir: &Path) -> Result<Option<Self>> {
+        let file = slide.join(dir).join(IGNORE_FILE);
+        if !file.is_file() {
+            return Ok(None);
+        }
+
+        let mut builder = GitignoreBuilder::new(dir);
+        // The valid patterns are still honoured
+        if let Some(e) = builder.add(&file) {
+            log::warn!("{file:?}: {e}");
+        }
+        let mut filter = self.clone();
+        filter.nested.push(Arc::new(builder.build()?));
+        Ok(Some(filter))
+    }
+
+    /// Hold back the files whose size, in bytes, is out of the given bounds. Both are inclusive.
+    ///
+    pub fn with_size_range(self, min_size: Option<u64>, max_size: Option<u64>) -> Self {
+        Self {
+            min_size,
+            max_size,
+            ..self
+        }
+    }
+
+    /// Only take the files falling in the `share`, if any.
+    ///
+    pub(crate) fn with_share(self, share: Option<Share>) -> Self {
+        Self { share, ..self }
+    }
+
+    /// Check if the filter holds back files by their size.
+    ///
+    pub fn has_size_range(&self) -> bool {
+        self.min_size.is_some() || self.max_size.is_some()
+    }
+
+    /// Check if a file has to be left untouched because of its `size`.
+    ///
+    pub fn is_out_of_range(&self, size: u64) -> bool {
+        self.min_size.is_some_and(|min| size < min) || self.max_size.is_some_and(|max| size > max)
+    }
+
+    /// Check if an entry has to be left untouched.
+    ///
+    /// The `path` is relative to the slide folder.
+    ///
+    pub fn is_excluded(&self, path: &Path, is_dir: bool) -> bool {
+        if !is_dir && self.share.as_ref().is_some_and(|share| !share.takes(path)) {
+            return true;
+        }
+        for excludes in self.nested.iter().rev() {
+            let matched = excludes.matched(path, is_dir);
+            if !matched.is_none() {
+                return matched.is_ignore();
+            }
+        }
+        self.excludes.matched(path, is_dir).is_ignore()
+    }
+}
@@ -1,27 +1,485 @@
 use anyhow::{bail, Result};
-use std::path::{Path, PathBuf};
+use chrono::Local;
+use filetime::FileTime;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    ffi::{OsStr, OsString},
+    hash::{DefaultHasher, Hash, Hasher},
+    io::Read,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
+    sync::watch,
+    task::JoinSet,
+};
 
-use crate::tracer::Tracer;
+use crate::{
+    cache::ChecksumCache,
+    control::Gate,
+    filter::{Filter, IGNORE_FILE},
+    hasher::hash_reader,
+    issues::{IssueKind, Reported},
+    progress::Progress,
+    tracer::Tracer,
+};
+use conflict::rename_new;
+use journal::Journal;
+use prehash::{Prehash, Prehashed};
+use retry::Failure;
+use reverify::Reverification;
+use sample::Sample;
+use throttle::Throttle;
+use trash::Bin;
 
-use super::config::{Algorithm, CollisionPolicy};
+use super::config::{
+    Algorithm, CheckMode, CollisionPolicy, ConflictPolicy, Identical, Reverify, Sanitize, Sidecar,
+    StrategyOverrides, Trash,
+};
+
+/// Folder of a slide where the .wip files are staged, when enabled. It is never synced.
+///
+pub(crate) const STAGING_DIR: &str = ".bitslides-tmp";
+
+/// Folder of a slide where the displaced files are kept, when enabled. It is never synced.
+///
+pub(crate) const TRASH_DIR: &str = ".bitslides-trash";
+
+/// Folder of a slide where the copies in flight to it are recorded. It is never synced.
+///
+pub(crate) const JOURNAL_DIR: &str = ".bitslides-journal";
+
+/// Folder of a slide where the signed manifests of the deliveries to it are written, when enabled.
+/// It is never synced.
+///
+pub(crate) const MANIFEST_DIR: &str = ".bitslides-manifests";
+
+/// Start of the name of the manifests written at the root of a slide, each listing the files
+/// delivered to it by a sync, when enabled. They are never synced.
+///
+pub(crate) const DELIVERY_MANIFEST_PREFIX: &str = ".bitslides-manifest-";
+
+/// File of a slide listing the files left in it for being over the size cap. It is never synced.
+///
+pub(crate) const OVERSIZE_REPORT: &str = ".bitslides-oversize.txt";
+
+/// File of a slide naming the volume its files started to be staged on, while their destination
+/// is away.
+///
+pub(crate) const ROUTE_FILE: &str = ".bitslides-route";
+
+/// Check if an entry is one of the folders, or files, bitslides keeps at the root of the slides.
+///
+pub(crate) fn is_internal(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| {
+        [
+            STAGING_DIR,
+            TRASH_DIR,
+            JOURNAL_DIR,
+            MANIFEST_DIR,
+            OVERSIZE_REPORT,
+            ROUTE_FILE,
+        ]
+        .map(OsStr::new)
+        .contains(&name)
+            || name.to_string_lossy().starts_with(DELIVERY_MANIFEST_PREFIX)
+    })
+}
+
+/// Check if a path within a `slide` is one bitslides writes along its syncs rather than a file to
+/// deliver: the internal folders and files at the root of the slide, and the .wip files with
+/// any of the `wip_suffixes`.
+///
+pub(crate) fn is_own(slide: &Path, path: &Path, wip_suffixes: &[String]) -> bool {
+    let Ok(relative) = path.strip_prefix(slide) else {
+        return false;
+    };
+    let internal = relative
+        .components()
+        .next()
+        .is_some_and(|first| is_internal(Path::new(first.as_os_str())));
+    let wip = path.file_name().is_some_and(|name| {
+        let name = name.to_string_lossy();
+        name.starts_with('.')
+            && wip_suffixes
+                .iter()
+                .any(|suffix| name.ends_with(&format!(".{suffix}")))
+    });
+    internal || wip
+}
+
+/// Check if an entry of a `slide` is left untouched by the syncs, so that its changes do not need
+/// one: the entries excluded by the `filter` or by the ignore files of the folders holding them,
+/// the ones in an internal folder, and the temporary files of the editors.
+///
+pub(crate) fn is_held_back(slide: &Path, path: &Path, is_dir: bool, filter: &Filter) -> bool {
+    let Ok(relative) = path.strip_prefix(slide) else {
+        return false;
+    };
+    if !is_dir && path.file_name().is_some_and(is_editor_temp) {
+        return true;
+    }
+
+    // The ignore files are read the same way the syncs descend the slide
+    let mut filter = filter.clone();
+    let mut dir = PathBuf::new();
+    let mut components = relative.components().peekable();
+    while let Some(component) = components.next() {
+        if !dir.as_os_str().is_empty() {
+            if let Ok(Some(nested)) = filter.with_ignore_file(slide, &dir) {
+                filter = nested;
+            }
+        }
+        dir.push(component);
+        let is_dir = is_dir || components.peek().is_some();
+        if (is_dir && is_internal(&dir)) || filter.is_excluded(&dir, is_dir) {
+            return true;
+        }
+    }
+    false
+}
+
+/// Check if a file name is one of the temporary files the editors keep along the files being
+/// edited: swap, backup and lock files.
+///
+fn is_editor_temp(name: &OsStr) -> bool {
+    let name = name.to_string_lossy();
+    [".swp", ".swo", ".swx", "~"]
+        .iter()
+        .any(|suffix| name.ends_with(suffix))
+        || [".~lock.", ".#"].iter().any(|prefix| name.starts_with(prefix))
+        || (name.len() > 1 && name.starts_with('#') && name.ends_with('#'))
+        // Written by vim to check it can create files in the folder
+        || name == "4913"
+}
+
+/// Write the oversize report of a slide, listing the size and the path, relative to the slide, of
+/// each file left in it for being over the size cap. The report is removed once there are none,
+/// and left untouched while the files listed do not change, not to wake up the watchers.
+///
+pub(crate) async fn report_oversize(slide: &Path, oversize: &[(PathBuf, u64)]) -> Result<()> {
+    let report = slide.join(OVERSIZE_REPORT);
+    if oversize.is_empty() {
+        if report.exists() {
+            tokio::fs::remove_file(&report).await?;
+        }
+        return Ok(());
+    }
+
+    let mut lines = oversize
+        .iter()
+        .map(|(path, size)| {
+            let relative = path.strip_prefix(slide).unwrap_or(path);
+            format!("{size}\t{}\n", relative.display())
+        })
+        .collect::<Vec<_>>();
+    lines.sort();
+    let contents = lines.concat();
+    if tokio::fs::read_to_string(&report)
+        .await
+        .is_ok_and(|reported| reported == contents)
+    {
+        return Ok(());
+    }
+    log::warn!("{} file(s) over the size cap, see {report:?}", lines.len());
+    tokio::fs::write(&report, contents).await?;
+    Ok(())
+}
+
+/// Check if the moves have to stop, leaving the files not moved yet in place, once their syncjob
+/// is paused or the instance is stopping.
+///
+pub(crate) fn is_cancelled(gate: Option<&watch::Receiver<Gate>>) -> bool {
+    gate.is_some_and(|gate| *gate.borrow() != Gate::Open)
+}
+
+/// Volume the files of a slide started to be staged on, if any.
+///
+pub(crate) fn sticky_route(slide: &Path) -> Option<String> {
+    let via = std::fs::read_to_string(slide.join(ROUTE_FILE)).ok()?;
+    let via = via.trim();
+    (!via.is_empty()).then(|| via.to_owned())
+}
+
+/// Record the volume the files of a slide are staged on, unless some already were on another one,
+/// or forget it once the files reach their destination.
+///
+pub(crate) async fn stick_route(slide: &Path, via: Option<&str>) -> Result<()> {
+    let file = slide.join(ROUTE_FILE);
+    match via {
+        Some(via) if !file.exists() => tokio::fs::write(&file, format!("{via}\n")).await?,
+        Some(_) => {}
+        None if file.exists() => tokio::fs::remove_file(&file).await?,
+        None => {}
+    }
+    Ok(())
+}
 
 /// Move request parameters.
 ///
+/// The bytes copied are not reported through a callback set here, which would keep the strategy
+/// from being hashed and compared, but through the [`Progress`] given along with it.
+///
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 pub struct MoveStrategy {
     /// What to do in case of a file collision
     pub collision: CollisionPolicy,
+    /// What to do with the files found under the same path in the slide sending files back the
+    /// other way
+    pub conflict: ConflictPolicy,
+    /// If provided, the files identical to the ones at their destination are not moved but
+    /// removed, whatever the collision policy
+    pub skip_identical: Option<Identical>,
     /// If true, create a .wip file in the destination and move the file there
     pub safe: bool,
-    /// If true, perform a checksum with the provided algorithm of the file before and after moving it
+    /// Extension of the .wip files
+    pub wip_suffix: String,
+    /// If true, the .wip files are created in the staging folder of the destination slide,
+    /// instead of next to their destination
+    pub staging: bool,
+    /// If true, perform a checksum with the provided algorithm of the file while moving it
     pub check: Option<Algorithm>,
-    /// Number of retries in case of a failure (checksum mismatch, etc)
+    /// If true, read the copies back to compare their checksum to the one of the original files
+    pub recheck: bool,
+    /// How much of the copies is read back, trading certainty for time on the largest files
+    pub check_mode: CheckMode,
+    /// Number of retries in case of a transient failure (timeout, I/O error, checksum mismatch...)
     pub retries: u8,
+    /// Number of syncs failing in a row before giving up on the slide, 0 to never give up
+    pub max_failures: u32,
+    /// Least seconds between the starts of two syncs of the slide, 0 for no limit
+    pub min_interval: u64,
+    /// Number of files moved concurrently
+    pub jobs: usize,
+    /// If true, only the blocks that changed are copied over a file being overwritten
+    pub delta: bool,
+    /// If true, the copies get the modification and access times of the original files
+    pub preserve_times: bool,
+    /// If true, the copies get the permissions, and ownership if allowed, of the original files
+    pub preserve_permissions: bool,
+    /// If true, the copies get the extended attributes (alternate data streams on Windows) of the
+    /// original files
+    pub preserve_xattrs: bool,
+    /// If provided, the bytes per second copied by all the syncjobs together, or by the syncjob of
+    /// a slide overriding it alone. 0 means no limit
+    pub rate_limit: Option<u64>,
+    /// If provided, how the names a FAT, exFAT or NTFS destination does not allow are rewritten
+    pub sanitize: Option<Sanitize>,
+    /// If provided, the overwritten files, and the removed sources if asked to, are kept in the
+    /// trash of their slide
+    pub use_trash: Option<Trash>,
+    /// If provided, the files modified within the last seconds are left for a later sync, as
+    /// they may still be being written. 0 means no wait
+    pub min_age: Option<u64>,
+    /// If provided, the files larger than this many bytes are left in their slide, and reported
+    /// in its oversize report. 0 means no cap
+    pub max_file_size: Option<u64>,
+    /// If provided, the checksum of each file delivered is also written to a checksum file, for
+    /// the tools checking them. Needs a `check` algorithm
+    pub sidecar: Option<Sidecar>,
+    /// If provided, the checksummed files delivered are hashed again once settled, to catch the
+    /// corruption showing up late
+    pub reverify: Option<Reverify>,
+    /// If true, each sync writes a manifest of the files it delivered at the root of the
+    /// destination slide, listing where they came from
+    pub delivery_manifest: bool,
+}
+
+impl MoveStrategy {
+    /// Compose a new strategy, replacing the settings provided by the `overrides`.
+    ///
+    pub fn with_overrides(&self, overrides: &StrategyOverrides) -> Self {
+        Self {
+            collision: overrides
+                .collision
+                .clone()
+                .unwrap_or_else(|| self.collision.clone()),
+            conflict: overrides.conflict.unwrap_or(self.conflict),
+            skip_identical: overrides.skip_identical.unwrap_or(self.skip_identical),
+            safe: overrides.safe.unwrap_or(self.safe),
+            wip_suffix: overrides
+                .wip_suffix
+                .clone()
+                .unwrap_or_else(|| self.wip_suffix.clone()),
+            staging: overrides.staging.unwrap_or(self.staging),
+            check: overrides.check.unwrap_or(self.check),
+            recheck: overrides.recheck.unwrap_or(self.recheck),
+            check_mode: overrides.check_mode.unwrap_or(self.check_mode),
+            retries: overrides.retries.unwrap_or(self.retries),
+            max_failures: overrides.max_failures.unwrap_or(self.max_failures),
+            min_interval: overrides.min_interval.unwrap_or(self.min_interval),
+            jobs: overrides.jobs.unwrap_or(self.jobs),
+            delta: overrides.delta.unwrap_or(self.delta),
+            preserve_times: overrides.preserve_times.unwrap_or(self.preserve_times),
+            preserve_permissions: overrides
+                .preserve_permissions
+                .unwrap_or(self.preserve_permissions),
+            preserve_xattrs: overrides.preserve_xattrs.unwrap_or(self.preserve_xattrs),
+            rate_limit: overrides.rate_limit.or(self.rate_limit),
+            sanitize: overrides.sanitize.unwrap_or(self.sanitize),
+            use_trash: overrides.use_trash.unwrap_or(self.use_trash),
+            min_age: overrides.min_age.or(self.min_age),
+            max_file_size: overrides.max_file_size.or(self.max_file_size),
+            sidecar: overrides.sidecar.unwrap_or(self.sidecar),
+            reverify: overrides.reverify.unwrap_or(self.reverify),
+            delivery_manifest: overrides
+                .delivery_manifest
+                .unwrap_or(self.delivery_manifest),
+        }
+    }
+}
+
+/// Files and bytes delivered by a sync.
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Transferred {
+    /// Number of files delivered
+    pub files: u64,
+    /// Number of bytes delivered
+    pub bytes: u64,
+    /// Number of failed attempts retried before delivering the files
+    pub retries: u64,
+    /// Number of files left in the slide, or not delivered for being at their destination already
+    pub skipped: u64,
+    /// If any file was left for being modified too recently, the time until the first one settles
+    pub settling: Option<Duration>,
+    /// Files left for being over the size cap, along with their size
+    pub oversize: Vec<(PathBuf, u64)>,
+    /// Deliveries to check again once settled
+    pub(crate) reverify: Vec<Reverification>,
+    /// Files delivered
+    pub(crate) deliveries: Vec<Delivery>,
+    /// Records of the files delivered, along with where they came from
+    pub(crate) transfers: Vec<Transfer>,
+}
+
+impl std::ops::AddAssign for Transferred {
+    fn add_assign(&mut self, other: Self) {
+        self.files += other.files;
+        self.bytes += other.bytes;
+        self.retries += other.retries;
+        self.skipped += other.skipped;
+        self.settling = match (self.settling, other.settling) {
+            (Some(settling), Some(other)) => Some(settling.min(other)),
+            (settling, other) => settling.or(other),
+        };
+        self.oversize.extend(other.oversize);
+        self.reverify.extend(other.reverify);
+        self.deliveries.extend(other.deliveries);
+        self.transfers.extend(other.transfers);
+    }
+}
+
+/// A file delivered at its destination.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Delivery {
+    /// Final location of the file, which differs from the requested one if renamed on collision
+    pub path: PathBuf,
+    /// Size of the file, in bytes
+    pub size: u64,
+    /// Checksum of the file, if checked
+    pub checksum: Option<(Algorithm, String)>,
+    /// If true, the checksum was computed from the delivered file itself, read back whole, rather
+    /// than from its source
+    pub read_back: bool,
+    /// Copy of the source kept in the trash of its slide, if any
+    pub kept: Option<PathBuf>,
+    /// Number of failed attempts retried before delivering it
+    pub retries: u8,
+    /// What became of the file already at the destination, if any
+    pub collision: Collision,
+}
+
+/// What became of the file found at the destination of a delivery.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Collision {
+    /// There was no file at the destination
+    #[default]
+    None,
+    /// It was replaced by the delivered file
+    Overwritten,
+    /// The file was delivered next to it, under another name
+    Renamed,
+    /// It was identical to the delivered file, so the file was not copied
+    Identical,
+}
+
+/// Record of a delivery, traced as the details of its `DELIVERED` entry.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Transfer {
+    /// Where the file was moved from
+    pub src: PathBuf,
+    /// Where the file was delivered
+    pub dst: PathBuf,
+    /// Size of the file, in bytes
+    pub size: u64,
+    /// Milliseconds the move took, retries included
+    pub duration_ms: u64,
+    /// Bytes moved per second, if the move took long enough to tell
+    pub throughput: Option<u64>,
+    /// Checksum of the file as `<algorithm>:<checksum>`, if checked
+    pub checksum: Option<String>,
+    /// Number of failed attempts retried before delivering the file
+    pub retries: u8,
+    /// What became of the file already at the destination, if any
+    pub collision: Collision,
+}
+
+impl Delivery {
+    /// Record of the delivery of `src_file`, which took `took`.
+    ///
+    pub fn transfer(&self, src_file: &Path, took: Duration) -> Transfer {
+        let checksum = self.checksum.as_ref().map(|(algorithm, checksum)| {
+            format!("{}:{checksum}", format!("{algorithm:?}").to_lowercase())
+        });
+        Transfer {
+            src: src_file.to_owned(),
+            dst: self.path.clone(),
+            size: self.size,
+            duration_ms: took.as_millis() as u64,
+            throughput: (took.as_millis() > 0)
+                .then(|| (self.size as f64 / took.as_secs_f64()) as u64),
+            checksum,
+            retries: self.retries,
+            collision: self.collision,
+        }
+    }
+}
+
+impl Transfer {
+    /// Details traced for the delivery, the record as JSON.
+    ///
+    /// This is the format the `verify` command reads back from the trace. The paths not valid
+    /// UTF-8, which JSON cannot hold, are traced as text instead: the source and destination
+    /// paths quoted, and the checksum if any.
+    ///
+    pub fn trace_details(&self) -> String {
+        match serde_json::to_string(self) {
+            Ok(details) => details,
+            Err(_) => {
+                let mut details = format!("{:?} -> {:?}", self.src, self.dst);
+                if let Some(checksum) = &self.checksum {
+                    details.push_str(&format!(" {checksum}"));
+                }
+                details
+            }
+        }
+    }
 }
 
 /// Delete all empty folders inside a path, leave the path root untouched.
 ///
-async fn delete_empty_folders(root: &Path) -> Result<()> {
+pub(crate) async fn delete_empty_folders(root: &Path) -> Result<()> {
     /// Recursively delete empty folders, including the root folder.
     ///
     async fn try_delete_empty_folders(root: &Path) -> Result<()> {
@@ -112,38 +570,118 @@ async fn delete_empty_folders(root: &Path) -> Result<()> {
 
 /// Recursively move the contents of one directory to another.
 ///
+/// Entries excluded by the `filter` are left untouched. The filter is matched against the paths
+/// relative to the parent of `from`, that is, the slide folder. The checksums of the files
+/// delivered are kept in the `cache` of the destination volume when given. The moves stop between
+/// two chunks once the `gate` of the syncjob, if any, is no longer open. The copies wait for the
+/// `throttle`, if any, shared with the other copies it limits. Returns the files and bytes
+/// delivered, none on a dry run.
+///
+#[allow(clippy::too_many_arguments)]
 pub async fn sync<U: AsRef<Path>, V: AsRef<Path>>(
     from: U,
     to: V,
     dry_run: bool,
     tracer: &Tracer,
+    progress: &Progress,
+    gate: Option<&watch::Receiver<Gate>>,
     request: &MoveStrategy,
-) -> Result<()> {
+    filter: &Filter,
+    cache: Option<&Arc<ChecksumCache>>,
+    throttle: Option<&Arc<Throttle>>,
+) -> Result<Transferred> {
     let from = PathBuf::from(from.as_ref());
     let to = PathBuf::from(to.as_ref());
 
+    let base = from.parent().unwrap_or(Path::new("")).to_owned();
+    // The ignore files stay along with the folders they hold back
+    let is_excluded = |filter: &Filter, path: &Path, is_dir: bool| {
+        let excluded = (is_dir && is_internal(path))
+            || (!is_dir && path.file_name().is_some_and(|name| name == IGNORE_FILE))
+            || filter.is_excluded(path.strip_prefix(&base).unwrap_or(path), is_dir);
+        if excluded {
+            log::debug!("excluded: {:?}", path);
+        }
+        excluded
+    };
+
+    if is_excluded(filter, &from, true) {
+        return Ok(Transferred::default());
+    }
+
     // dbg!(&from, &to);
 
+    // Only the filesystems that cannot hold some names get them rewritten
+    let sanitize = request.sanitize.filter(|_| sanitize::is_restrictive(&to));
+    let rename = |name: &OsStr| -> OsString {
+        match sanitize.and_then(|scheme| sanitize::sanitize(name, scheme)) {
+            Some(sanitized) => sanitized,
+            None => name.to_owned(),
+        }
+    };
+
     let input_root_length = from.components().count();
-    let output_root = to;
+    let output_root = match to.file_name() {
+        Some(name) => to.with_file_name(rename(name)),
+        None => to.clone(),
+    };
+    // The staging folder is at the root of the destination slide, so renaming the .wip files
+    // never crosses filesystems
+    let staging = match output_root.parent() {
+        Some(slide) if request.staging && !dry_run => {
+            let staging = slide.join(STAGING_DIR);
+            tokio::fs::create_dir_all(&staging).await?;
+            Some(staging)
+        }
+        _ => None,
+    };
+
+    let bin = match (request.use_trash, output_root.parent()) {
+        (Some(trash), Some(slide)) if !dry_run => Some(Arc::new(Bin::new(&base, slide, trash))),
+        _ => None,
+    };
+    let journal = output_root
+        .parent()
+        .map(|slide| Arc::new(Journal::new(slide)));
 
-    let mut jobs = vec![from.clone()];
+    // 0 disables them
+    let max_file_size = request.max_file_size.filter(|max| *max > 0);
+    let min_age = request.min_age.filter(|min_age| *min_age > 0);
 
-    while let Some(job) = jobs.pop() {
+    // Each folder along with the filter of its contents
+    let mut jobs = vec![(from.clone(), filter.clone())];
+    // Files being moved. The folders are created beforehand, so they can be moved in any order
+    let mut moves = JoinSet::new();
+    let mut transferred = Transferred::default();
+
+    'jobs: while let Some((job, filter)) = jobs.pop() {
         log::debug!("process: {:?}", &job);
 
+        let filter = match filter.with_ignore_file(&base, job.strip_prefix(&base)?)? {
+            Some(nested) => nested,
+            None => filter,
+        };
+
         // Compose the destination
         let dst = {
             let src = job
                 .components()
                 .skip(input_root_length)
                 .collect::<PathBuf>();
+            let dst = src
+                .components()
+                .fold(output_root.clone(), |dst, component| {
+                    dst.join(rename(component.as_os_str()))
+                });
 
-            if src.components().count() == 0 {
-                output_root.clone()
-            } else {
-                output_root.join(&src)
+            // The parents were reported already
+            if dst.file_name() != to.join(&src).file_name() {
+                log::info!("Sanitize: {:?} -> {:?}", &job, &dst);
+                tracer
+                    .async_log("SANITIZE", &format!("{:?} -> {:?}", &job, &dst))
+                    .await?;
             }
+            dst
         };
 
         // Check if the destination exists, otherwise create it
@@ -157,14 +695,132 @@ pub async fn sync<U: AsRef<Path>, V: AsRef<Path>>(
         }
 
         log::debug!("read_dir: {:?}", &job);
-        let mut read_dir = tokio::fs::read_dir(job).await?;
-        while let Some(entry) = read_dir.next_entry().await? {
+        let mut read_dir = match tokio::fs::read_dir(&job).await {
+            Ok(read_dir) => read_dir,
+            // The rest of the slide is synced anyway
+            Err(e) if job != from => {
+                log::warn!("{job:?}: Unable to read the folder, leaving it: {e}");
+                progress.issue(IssueKind::Unreadable, Some(&job), &e);
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        loop {
+            let entry = match read_dir.next_entry().await {
+                Ok(Some(entry)) => entry,
+                Ok(None) => break,
+                Err(e) => {
+                    log::warn!("{job:?}: Unable to read the folder, leaving the rest of it: {e}");
+                    progress.issue(IssueKind::Unreadable, Some(&job), &e);
+                    break;
+                }
+            };
             log::debug!("item: {:?}", &entry);
 
+            // The files not moved yet are left for the next sync
+            if is_cancelled(gate) {
+                log::info!("Sync of {:?} cancelled", &from);
+                break 'jobs;
+            }
+
             let src = entry.path();
 
             if src.is_dir() {
-                jobs.push(src);
+                if !is_excluded(&filter, &src, true) {
+                    jobs.push((src, filter.clone()));
+                }
+                continue;
+            }
+
+            if is_excluded(&filter, &src, false) {
+                continue;
+            }
+
+            let metadata = match entry.metadata().await {
+                Ok(metadata) => metadata,
+                Err(e) => {
+                    log::warn!("{src:?}: Unable to read the file, leaving it: {e}");
+                    progress.issue(IssueKind::Unreadable, Some(&src), &e);
+                    continue;
+                }
+            };
+
+            if filter.has_size_range() {
+                let size = metadata.len();
+                if filter.is_out_of_range(size) {
+                    log::info!("Skip: {:?} ({size} bytes, out of the size range)", &src);
+                    tracer
+                        .async_log(
+                            "SKIP",
+                            &format!("{:?}: {size} bytes, out of the size range", &src),
+                        )
+                        .await?;
+                    transferred.skipped += 1;
+                    continue;
+                }
+            }
+
+            // Likely dropped by mistake, so they are reported rather than silently left
+            if let Some(max_file_size) = max_file_size {
+                let size = metadata.len();
+                if size > max_file_size {
+                    log::warn!(
+                        "Oversize: {:?} ({size} bytes, over the {max_file_size} bytes cap)",
+                        &src
+                    );
+                    tracer
+                        .async_log(
+                            "OVERSIZE",
+                            &format!(
+                                "{:?}: {size} bytes, over the {max_file_size} bytes cap",
+                                &src
+                            ),
+                        )
+                        .await?;
+                    progress.issue(
+                        IssueKind::Oversize,
+                        Some(&src),
+                        format!("{size} bytes, over the {max_file_size} bytes cap"),
+                    );
+                    transferred.oversize.push((src, size));
+                    transferred.skipped += 1;
+                    continue;
+                }
+            }
+
+            // The files still being written are left until they settle
+            if let Some(min_age) = min_age {
+                // A modification time in the future counts as just modified
+                let age = metadata.modified()?.elapsed().unwrap_or_default();
+                let min_age = Duration::from_secs(min_age);
+                if age < min_age {
+                    log::info!(
+                        "Skip: {:?} (modified {}s ago, settling)",
+                        &src,
+                        age.as_secs()
+                    );
+                    tracer
+                        .async_log(
+                            "SKIP",
+                            &format!("{:?}: modified {}s ago, settling", &src, age.as_secs()),
+                        )
+                        .await?;
+                    transferred += Transferred {
+                        skipped: 1,
+                        settling: Some(min_age - age),
+                        ..Default::default()
+                    };
+                    continue;
+                }
+            }
+
+            if lock::is_locked(&src) {
+                log::info!("Skip: {:?} (locked by another program)", &src);
+                tracer
+                    .async_log("SKIP", &format!("{:?}: locked by another program", &src))
+                    .await?;
+                progress.issue(IssueKind::Locked, Some(&src), "locked by another program");
+                transferred.skipped += 1;
                 continue;
             }
 
@@ -175,9 +831,100 @@ pub async fn sync<U: AsRef<Path>, V: AsRef<Path>>(
                         .async_log("MV", &format!("{:?} -> {:?}", &src, &dst))
                         .await?;
 
-                    let dst = dst.join(filename);
+                    let dst = dst.join(rename(filename));
+                    if dst.file_name() != Some(filename) {
+                        log::info!("Sanitize: {:?} -> {:?}", &src, &dst);
+                        tracer
+                            .async_log("SANITIZE", &format!("{:?} -> {:?}", &src, &dst))
+                            .await?;
+                    }
                     if !dry_run {
-                        move_file(&src, &dst, request, checksums::hash_file).await?;
+                        let size = metadata.len();
+                        progress.pending(&src, size);
+                        // Hashed while waiting for its turn, unless merely renamed
+                        let prehash = match request.check {
+                            Some(algorithm)
+                                if moves.len() >= request.jobs.max(1)
+                                    && !same_device(&src, &dst)
+                                    && size <= prehash::MAX_SIZE =>
+                            {
+                                Some(Prehash::start(&src, algorithm, hash_reader))
+                            }
+                            _ => None,
+                        };
+                        while moves.len() >= request.jobs.max(1) {
+                            transferred += join_move(&mut moves).await?;
+                        }
+                        let request = request.clone();
+                        let progress = progress.clone();
+                        let gate = gate.cloned();
+                        let tracer = tracer.clone();
+                        let staging = staging.clone();
+                        let throttle = throttle.cloned();
+                        let bin = bin.clone();
+                        let journal = journal.clone();
+                        let cache = cache.cloned();
+                        moves.spawn(async move {
+                            let started = Instant::now();
+                            let delivery = move_file(
+                                &src,
+                                &dst,
+                                staging.as_deref(),
+                                &request,
+                                &progress,
+                                gate.as_ref(),
+                                throttle.as_deref(),
+                                bin.as_deref(),
+                                journal.as_deref(),
+                                cache.as_deref(),
+                                prehash,
+                                hash_reader,
+                            )
+                            .await;
+                            let elapsed = started.elapsed();
+                            progress.done(&src, matches!(delivery, Ok(Some(_))));
+                            let Some(delivery) = delivery? else {
+                                return Ok(Transferred {
+                                    skipped: 1,
+                                    ..Default::default()
+                                });
+                            };
+                            // The checksum of a source says nothing of the bytes of its copy
+                            if let (Some(cache), Some((algorithm, checksum)), true) =
+                                (&cache, &delivery.checksum, delivery.read_back)
+                            {
+                                cache.insert(&delivery.path, *algorithm, checksum);
+                            }
+                            if let Some(sidecar) = request.sidecar {
+                                write_sidecar(
+                                    &delivery,
+                                    &request,
+                                    cache.clone(),
+                                    sidecar,
+                                    &progress,
+                                )
+                                .await?;
+                            }
+                            let transfer = delivery.transfer(&src, elapsed);
+                            tracer
+                                .async_log("DELIVERED", &transfer.trace_details())
+                                .await?;
+                            let reverify = match (request.reverify, &delivery.checksum) {
+                                (Some(_), Some(_)) => {
+                                    vec![Reverification::new(src, delivery.clone()).await?]
+                                }
+                                _ => vec![],
+                            };
+                            Ok(Transferred {
+                                files: 1,
+                                bytes: delivery.size,
+                                retries: u64::from(delivery.retries),
+                                reverify,
+                                deliveries: vec![delivery],
+                                transfers: vec![transfer],
+                                ..Default::default()
+                            })
+                        });
                     }
                 }
                 None => {
@@ -187,113 +934,1221 @@ pub async fn sync<U: AsRef<Path>, V: AsRef<Path>>(
         }
     }
 
+    while !moves.is_empty() {
+        transferred += join_move(&mut moves).await?;
+    }
+
+    if request.sidecar == Some(Sidecar::Folder) {
+        compact_sidecars(&transferred.deliveries, request).await;
+    }
+
     if !dry_run {
-        delete_empty_folders(&from).await
-    } else {
-        Ok(())
+        delete_empty_folders(&from).await?;
+    }
+
+    Ok(transferred)
+}
+
+/// Compact the folder checksum files the deliveries were appended to, once the sync is over rather
+/// than along with each delivery. Failing to do so leaves stale lines behind, which are ignored
+/// anyway, so it is only logged.
+///
+async fn compact_sidecars(deliveries: &[Delivery], request: &MoveStrategy) {
+    let files: HashSet<PathBuf> = deliveries
+        .iter()
+        .filter_map(|delivery| {
+            let algorithm = delivery
+                .checksum
+                .as_ref()
+                .map(|(algorithm, _)| *algorithm)
+                .or(request.check)?;
+            Some(
+                delivery
+                    .path
+                    .parent()?
+                    .join(sidecar::folder_file(algorithm)),
+            )
+        })
+        .collect();
+    for file in files {
+        let compacted = tokio::task::spawn_blocking({
+            let file = file.clone();
+            move || sidecar::compact(&file)
+        })
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|compacted| Ok(compacted?));
+        if let Err(e) = compacted {
+            log::warn!("{file:?}: Unable to compact the checksum file: {e}");
+        }
+    }
+}
+
+/// Write the checksum file of a delivery, hashing the delivered file if it was not checksummed
+/// along the way, like the files found identical at their destination. Failing to write it does not
+/// undo the delivery, so it is only logged and reported as an issue.
+///
+async fn write_sidecar(
+    delivery: &Delivery,
+    request: &MoveStrategy,
+    cache: Option<Arc<ChecksumCache>>,
+    sidecar: Sidecar,
+    progress: &Progress,
+) -> Result<()> {
+    let Some((algorithm, checksum)) = delivery
+        .checksum
+        .clone()
+        .map(|(algorithm, checksum)| (algorithm, Some(checksum)))
+        .or(request.check.map(|algorithm| (algorithm, None)))
+    else {
+        return Ok(());
+    };
+    let path = delivery.path.clone();
+    let written = tokio::task::spawn_blocking(move || {
+        let checksum = match checksum {
+            Some(checksum) => checksum,
+            None => {
+                let checksum = hash_reader(&mut std::fs::File::open(&path)?, algorithm)?;
+                if let Some(cache) = cache {
+                    cache.insert(&path, algorithm, &checksum);
+                }
+                checksum
+            }
+        };
+        sidecar::write(&path, algorithm, &checksum, sidecar)
+    })
+    .await?;
+    if let Err(e) = written {
+        log::warn!(
+            "{:?}: Unable to write the checksum file: {e}",
+            delivery.path
+        );
+        progress.issue(IssueKind::Sidecar, Some(&delivery.path), &e);
+    }
+    Ok(())
+}
+
+/// Wait for one of the moves in flight to finish, returning what it delivered.
+///
+/// On failure, the rest of the moves are awaited before returning the error, so no file is left
+/// half-moved.
+///
+async fn join_move(moves: &mut JoinSet<Result<Transferred>>) -> Result<Transferred> {
+    match moves.join_next().await {
+        Some(result) => match result.map_err(anyhow::Error::from).and_then(|r| r) {
+            Ok(transferred) => Ok(transferred),
+            Err(e) => {
+                while moves.join_next().await.is_some() {}
+                Err(e)
+            }
+        },
+        None => Ok(Transferred::default()),
     }
 }
 
 /// Move a single file from one location to another.
 ///
+/// Returns where the file was delivered, or `None` if it was left in place. The .wip file, if
+/// any, is created in the `staging` folder when given. The copy, if any, is limited by the
+/// `throttle`. The files displaced go to the `bin` when given. The copy, if any, is recorded in
+/// the `journal` when given. The checksums of the files delivered before are taken from the
+/// `cache` when given. The checksum of the source is taken from the `prehash` when given and
+/// still valid.
+///
+#[allow(clippy::too_many_arguments)]
 async fn move_file<F>(
     src_file: &PathBuf,
-    mut dst_file: &PathBuf,
+    dst_file: &PathBuf,
+    staging: Option<&Path>,
     request: &MoveStrategy,
-    hash_file: F,
-) -> Result<()>
+    progress: &Progress,
+    gate: Option<&watch::Receiver<Gate>>,
+    throttle: Option<&Throttle>,
+    bin: Option<&Bin>,
+    journal: Option<&Journal>,
+    cache: Option<&ChecksumCache>,
+    prehash: Option<Prehash>,
+    hash: F,
+) -> Result<Option<Delivery>>
 where
-    F: Fn(&Path, Algorithm) -> String,
+    F: Fn(&mut dyn Read, Algorithm) -> std::io::Result<String> + Clone + Send + 'static,
 {
-    let mut dst_ = None;
+    let mut dst_;
+    let mut delta;
+    let mut collision;
+    let mut clone;
+    let prehashed = match prehash {
+        Some(prehash) => prehash.wait(src_file).await,
+        None => None,
+    };
 
-    /* Handle a possible collision */
-    {
-        if dst_file.exists() {
-            match request.collision {
-                CollisionPolicy::Skip => {
-                    return Ok(());
+    // A file showing up at the destination in the meantime goes through the collision policy again
+    loop {
+        dst_ = None;
+        delta = false;
+        collision = Collision::None;
+
+        /* Handle a possible collision */
+        {
+            // A previous run may have delivered the file already, without removing it
+            if let Some(identical) = request.skip_identical.filter(|_| dst_file.exists()) {
+                // The checksum of the file delivered before spares reading it again
+                let cached = match (identical, request.check, cache) {
+                    (Identical::Contents, Some(algorithm), Some(cache))
+                        if algorithm.identifies_contents() =>
+                    {
+                        cache
+                            .get(dst_file, algorithm)
+                            .map(|checksum| (algorithm, checksum))
+                    }
+                    _ => None,
+                };
+                let (src, dst, hash) = (src_file.clone(), dst_file.clone(), hash.clone());
+                let prehashed = prehashed.as_ref().map(|p| p.checksum.clone());
+                let found = tokio::task::spawn_blocking(move || match cached {
+                    Some((algorithm, checksum)) => {
+                        if std::fs::metadata(&src)?.len() != std::fs::metadata(&dst)?.len() {
+                            return Ok(None);
+                        }
+                        let checksum_src = match prehashed {
+                            Some(checksum_src) => checksum_src,
+                            None => hash(&mut std::fs::File::open(&src)?, algorithm)?,
+                        };
+                        Ok::<_, std::io::Error>(
+                            checksum_src
+                                .eq_ignore_ascii_case(&checksum)
+                                .then_some(Some((algorithm, checksum_src))),
+                        )
+                    }
+                    None => Ok(is_identical(&src, &dst, identical)?.then_some(None)),
+                })
+                .await??;
+                if let Some(checksum) = found {
+                    log::info!("Identical: {src_file:?} is already at {dst_file:?}");
+                    let size = tokio::fs::metadata(src_file).await?.len();
+                    let kept = remove_source(src_file, bin).await?;
+                    return Ok(Some(Delivery {
+                        path: dst_file.to_owned(),
+                        size,
+                        checksum,
+                        read_back: false,
+                        kept,
+                        retries: 0,
+                        collision: Collision::Identical,
+                    }));
+                }
+            }
+
+            if dst_file.exists() {
+                match request.collision {
+                    CollisionPolicy::Skip => {
+                        return Ok(None);
+                    }
+                    CollisionPolicy::Fail => {
+                        bail!("File already exists: {:?}", dst_file);
+                    }
+                    CollisionPolicy::Rename { ref suffix } => {
+                        dst_ = Some(renamed(dst_file, suffix));
+                        collision = Collision::Renamed;
+                    }
+                    CollisionPolicy::Overwrite => {
+                        // The file will be overwritten by the copy operation, reusing its
+                        // unchanged blocks if asked to
+                        delta = request.delta;
+                        collision = Collision::Overwritten;
+                    }
+                    CollisionPolicy::KeepNewer { remove_older } => {
+                        let src_modified = tokio::fs::metadata(src_file).await?.modified()?;
+                        let dst_modified = tokio::fs::metadata(dst_file).await?.modified()?;
+                        // As recent, neither one is the newer, so both are kept
+                        if src_modified == dst_modified {
+                            log::warn!("Skip: {src_file:?} and {dst_file:?} are as recent");
+                            return Ok(None);
+                        }
+                        if src_modified < dst_modified {
+                            if remove_older {
+                                log::info!("Remove: {src_file:?}, {dst_file:?} is newer");
+                                remove_source(src_file, bin).await?;
+                            } else {
+                                log::info!("Skip: {src_file:?}, {dst_file:?} is newer");
+                            }
+                            return Ok(None);
+                        }
+                        delta = request.delta;
+                        collision = Collision::Overwritten;
+                    }
+                }
+            }
+        }
+        let dst_file = dst_.as_ref().unwrap_or(dst_file);
+
+        // Within the same filesystem, a rename moves the file at once without touching its data.
+        // Failing that, the copy may still share the data of the file, as between the subvolumes
+        // of a btrfs filesystem, which do not share their device
+        clone = dst_file
+            .parent()
+            .is_some_and(|dst_dir| may_clone(src_file, dst_dir));
+        if same_device(src_file, dst_file) {
+            let size = tokio::fs::metadata(src_file).await?.len();
+            let renamed = match collision {
+                Collision::Overwritten => {
+                    if let Some(bin) = bin.filter(|_| dst_file.exists()) {
+                        bin.discard_destination(dst_file).await?;
+                    }
+                    tokio::fs::rename(src_file, dst_file).await
                 }
-                CollisionPolicy::Fail => {
-                    bail!("File already exists: {:?}", dst_file);
+                // Never over a file showing up since its collision was handled
+                _ => rename_new(src_file, dst_file),
+            };
+            match renamed {
+                Ok(()) => {
+                    // Nothing was copied to compare. The file delivered is only read for its
+                    // checksum when the sidecar or the second check need it, unless hashed ahead
+                    let (checksum, read_back) = match (request.check, &prehashed) {
+                        (Some(algorithm), Some(prehashed)) => {
+                            (Some((algorithm, prehashed.checksum.clone())), false)
+                        }
+                        (Some(algorithm), None)
+                            if request.sidecar.is_some() || request.reverify.is_some() =>
+                        {
+                            match hash_path(&hash, dst_file, algorithm).await {
+                                Ok(checksum) => (Some((algorithm, checksum)), true),
+                                Err(e) => {
+                                    log::warn!(
+                                        "{dst_file:?}: Unable to hash the file delivered: {e}"
+                                    );
+                                    progress.issue(IssueKind::Unreadable, Some(dst_file), &e);
+                                    (None, false)
+                                }
+                            }
+                        }
+                        _ => (None, false),
+                    };
+                    return Ok(Some(Delivery {
+                        path: dst_file.to_owned(),
+                        size,
+                        checksum,
+                        read_back,
+                        kept: None,
+                        retries: 0,
+                        collision,
+                    }));
                 }
-                CollisionPolicy::Rename { ref suffix } => {
-                    dst_ = Some({
-                        let mut new_dst = dst_file.to_path_buf();
-                        new_dst.set_extension(suffix);
-                        new_dst
-                    });
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    log::info!("{dst_file:?} showed up meanwhile, handling the collision again");
+                    continue;
                 }
-                CollisionPolicy::Overwrite => {
-                    // Do nothing. The file will be overwritten by the copy operation
+                Err(e) => {
+                    log::debug!("Unable to rename {src_file:?}, copying it: {e}");
+                    clone = true;
                 }
             }
         }
+        break;
+    }
+
+    let dst_file = dst_.as_ref().unwrap_or(dst_file);
+    let delivery = copy_file(
+        src_file, dst_file, staging, delta, clone, request, progress, gate, throttle, bin, journal,
+        prehashed, hash,
+    )
+    .await?;
+    Ok(delivery.map(|delivery| Delivery {
+        collision,
+        ..delivery
+    }))
+}
 
-        if let Some(ref new_dst) = dst_ {
-            dst_file = new_dst;
+/// Remove a moved file, or send it to the `bin` when given. Returns where it is kept, if it is.
+///
+async fn remove_source(src_file: &Path, bin: Option<&Bin>) -> Result<Option<PathBuf>> {
+    match bin {
+        Some(bin) => bin.discard_source(src_file).await,
+        None => {
+            tokio::fs::remove_file(src_file).await?;
+            Ok(None)
         }
     }
+}
 
-    let checksum_src = if let Some(algorithm) = request.check {
-        let checksum_src = hash_file(src_file, algorithm);
-        log::debug!("Checksum(src): {:?}", checksum_src);
-        Some((algorithm, checksum_src))
+/// Copy a file to its destination, checking it if requested, and remove the source.
+///
+/// With `delta`, the existing destination file is used as the base of a delta copy. Otherwise, with
+/// `clone`, the file is cloned if the filesystem supports it, and copied if not. The
+/// destination file replaced, if any, goes to the `bin` when given. The copy is recorded in the
+/// `journal` while in flight, when given. The checksum of the source is taken from the
+/// `prehashed` one when given, rather than computed along the copy. Returns `None` if the copy was
+/// cancelled, leaving the source in place.
+///
+#[allow(clippy::too_many_arguments)]
+async fn copy_file<F>(
+    src_file: &PathBuf,
+    dst_file: &PathBuf,
+    staging: Option<&Path>,
+    delta: bool,
+    clone: bool,
+    request: &MoveStrategy,
+    progress: &Progress,
+    gate: Option<&watch::Receiver<Gate>>,
+    throttle: Option<&Throttle>,
+    bin: Option<&Bin>,
+    journal: Option<&Journal>,
+    prehashed: Option<Prehashed>,
+    hash: F,
+) -> Result<Option<Delivery>>
+where
+    F: Fn(&mut dyn Read, Algorithm) -> std::io::Result<String> + Clone + Send + 'static,
+{
+    // Taken before hashing the file, which may update its access time
+    let (metadata, mut prehashed) = match prehashed {
+        Some(Prehashed { metadata, checksum }) => (metadata, Some(checksum)),
+        None => (tokio::fs::metadata(src_file).await?, None),
+    };
+    let times = if request.preserve_times {
+        Some((
+            FileTime::from_last_access_time(&metadata),
+            FileTime::from_last_modification_time(&metadata),
+        ))
     } else {
         None
     };
 
     // Generate WIP filename if safe mode is enabled
-    // For photo.jpg, this creates .photo.jpg.wip (a hidden sidecar file)
-    // This preserves the full filename and avoids extension loss and collisions
+    // A delta copy always needs one, as the file being overwritten is read meanwhile
     let wip_path;
-    let wip = if request.safe {
-        wip_path = {
-            let parent = dst_file.parent().unwrap_or_else(|| Path::new("."));
-            let filename = dst_file
-                .file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or("file");
-            parent.join(format!(".{}.wip", filename))
-        };
+    let wip = if request.safe || delta {
+        wip_path = wip_file(dst_file, staging, &request.wip_suffix)?;
         &wip_path
     } else {
         dst_file
     };
 
+    // Fail before filling up the destination, rather than after with a truncated copy
+    let size = metadata.len();
+    let dst_dir = wip.parent().unwrap_or(Path::new("."));
+    if let Some(available) = free_space(dst_dir) {
+        // Overwriting the file in place frees its space
+        let freed = match wip == dst_file {
+            true => tokio::fs::metadata(dst_file).await.map_or(0, |m| m.len()),
+            false => 0,
+        };
+        if size > available + freed {
+            bail!(
+                "Destination full: {src_file:?} needs {size} bytes, {} available in {dst_dir:?}",
+                available + freed
+            );
+        }
+    }
+
+    // Copying in place overwrites the file, so it has to be put away first
+    if let Some(bin) = bin.filter(|_| wip == dst_file && dst_file.exists()) {
+        bin.discard_destination(dst_file).await?;
+    }
+
+    // Left behind on a failure, so the .wip file is rolled back on the next start
+    let entry = match journal {
+        Some(journal) => Some((journal, journal.record(src_file, wip, dst_file).await?)),
+        None => None,
+    };
+
+    // Copying in place only replaces the destination once it is open for writing, so it is left
+    // as it was by a failure before that
+    let written = AtomicBool::new(wip != dst_file);
+
     let mut retry_count = 0;
-    // <= because the first attempt is not a retry
-    while retry_count <= request.retries {
+    let (size, checksum_src, read_back) = loop {
         log::debug!("Moving {:?} -> {:?}", src_file, wip);
 
-        // TODO: Optimize this copy to be able to resume the copy if it fails
-        tokio::fs::copy(src_file, wip).await?;
+        let attempt = async {
+            // TODO: Optimize this copy to be able to resume the copy if it fails
+            // The source is hashed while copied, but for the clones and the delta copies, which
+            // do not read all of it, and the files hashed ahead
+            let (size, checksum) = if delta {
+                let (src, base, dst) = (src_file.clone(), dst_file.clone(), wip.clone());
+                let delta =
+                    tokio::task::spawn_blocking(move || delta::copy(&src, &base, &dst)).await??;
+                log::debug!(
+                    "Delta: reused {} of {} bytes of {:?}",
+                    delta.reused,
+                    delta.size,
+                    dst_file
+                );
+                let checksum = match (request.check, &prehashed) {
+                    (Some(_), Some(checksum)) => Some(checksum.clone()),
+                    (Some(algorithm), None) => Some(hash_path(&hash, src_file, algorithm).await?),
+                    (None, _) => None,
+                };
+                (delta.size, checksum)
+            } else if let Some(size) = match clone {
+                true => clone_file(src_file, wip).await,
+                false => None,
+            } {
+                let checksum = match (request.check, &prehashed) {
+                    (Some(_), Some(checksum)) => Some(checksum.clone()),
+                    (Some(algorithm), None) => Some(hash_path(&hash, src_file, algorithm).await?),
+                    (None, _) => None,
+                };
+                (size, checksum)
+            } else {
+                let hasher = match prehashed {
+                    Some(_) => None,
+                    None => request.check.map(|algorithm| (algorithm, hash.clone())),
+                };
+                match copy_chunked(src_file, wip, &written, progress, gate, throttle, hasher)
+                    .await?
+                {
+                    Some((size, None)) => (size, prehashed.clone()),
+                    Some(copied) => copied,
+                    None => return Ok(Attempt::Cancelled),
+                }
+            };
+            let checksum_src = request.check.zip(checksum);
+            log::debug!("Checksum(src): {:?}", checksum_src);
+
+            // Read the copy back to check it, if requested
+            let mut read_back = false;
+            if let Some((algorithm, ref checksum_src)) =
+                checksum_src.as_ref().filter(|_| request.recheck)
+            {
+                // Only the same parts of the source compare to a sample of the copy
+                let (checksum_src, checksum_wip) = match Sample::new(size, request.check_mode) {
+                    Some(sample) => {
+                        log::debug!("Sampled: reading {} of {size} bytes", sample.len());
+                        (
+                            hash_sample(&hash, src_file, *algorithm, &sample).await?,
+                            hash_sample(&hash, wip, *algorithm, &sample).await?,
+                        )
+                    }
+                    None => {
+                        read_back = true;
+                        (
+                            checksum_src.clone(),
+                            hash_path(&hash, wip, *algorithm).await?,
+                        )
+                    }
+                };
+                log::debug!("Checksum(wip): {:?}", checksum_wip);
+                if checksum_src != checksum_wip {
+                    return Ok(Attempt::Mismatch);
+                }
+            }
+
+            Ok(Attempt::Copied(size, checksum_src, read_back))
+        }
+        .await;
+
+        let mismatched = matches!(attempt, Ok(Attempt::Mismatch));
+        let (failure, e) = match attempt {
+            Ok(Attempt::Copied(size, checksum_src, read_back)) => {
+                break (size, checksum_src, read_back)
+            }
+            Ok(Attempt::Cancelled) => {
+                log::info!("Cancelled: {:?}", src_file);
+                tokio::fs::remove_file(wip).await?;
+                if let Some((journal, entry)) = &entry {
+                    journal.clear(entry).await?;
+                }
+                return Ok(None);
+            }
+            // A bad read on either side, worth another try
+            Ok(Attempt::Mismatch) => (
+                Failure::Transient,
+                anyhow::anyhow!("The copy {wip:?} does not match its source"),
+            ),
+            Err(e) => (Failure::of(&e), e),
+        };
+
+        // Only the transient failures are retried, the others would just fail again
+        if failure == Failure::Transient && retry_count < request.retries {
+            retry_count += 1;
+            // The checksum taken ahead may be the bad read
+            prehashed = None;
+            let delay = retry::backoff(retry_count);
+            log::warn!(
+                "Retrying {src_file:?} in {delay:?} ({retry_count}/{}): {e}",
+                request.retries
+            );
+            tokio::time::sleep(delay).await;
+            continue;
+        }
+
+        if written.load(Ordering::Relaxed) && wip.exists() {
+            tokio::fs::remove_file(wip).await?;
+        }
+        if let Some((journal, entry)) = &entry {
+            journal.clear(entry).await?;
+        }
+        // The sync fails along, but the file is named in the issues of the run
+        if failure != Failure::Locked {
+            let kind = match mismatched {
+                true => IssueKind::Checksum,
+                false => IssueKind::Move,
+            };
+            progress.issue(kind, Some(src_file), &e);
+        }
+        match failure {
+            Failure::Transient => {
+                return Err(Reported(format!(
+                    "Failed to move file {src_file:?} after maximum retries: {e}"
+                ))
+                .into())
+            }
+            Failure::Permission => {
+                return Err(Reported(format!("Permission denied moving {src_file:?}: {e}")).into())
+            }
+            Failure::DiskFull => {
+                return Err(Reported(format!("Destination full moving {src_file:?}: {e}")).into())
+            }
+            Failure::Permanent => {
+                return Err(Reported(format!("Unable to move {src_file:?}: {e}")).into())
+            }
+            // Not an error of the sync, the file is just not ready
+            Failure::Locked => {
+                log::warn!("Locked: {src_file:?} is in use by another program: {e}");
+                progress.issue(IssueKind::Locked, Some(src_file), &e);
+                return Ok(None);
+            }
+        }
+    };
+
+    if let Some((atime, mtime)) = times {
+        let wip = wip.clone();
+        tokio::task::spawn_blocking(move || filetime::set_file_times(wip, atime, mtime)).await??;
+    }
+
+    // Done before the permissions, which may make the file read-only
+    if request.preserve_xattrs {
+        let (src, wip) = (src_file.clone(), wip.clone());
+        if let Err(e) = tokio::task::spawn_blocking(move || copy_xattrs(&src, &wip)).await? {
+            log::warn!("Unable to copy the extended attributes of {src_file:?}: {e}");
+        }
+    }
+
+    if request.preserve_permissions {
+        let (src, wip) = (src_file.clone(), wip.clone());
+        tokio::task::spawn_blocking(move || copy_permissions(&src, &wip)).await??;
+    }
+
+    if wip != dst_file {
+        if let Some(bin) = bin.filter(|_| dst_file.exists()) {
+            bin.discard_destination(dst_file).await?;
+        }
+        tokio::fs::rename(wip, dst_file).await?;
+    }
+
+    let kept = remove_source(src_file, bin).await?;
+    if let Some((journal, entry)) = &entry {
+        journal.clear(entry).await?;
+    }
+
+    Ok(Some(Delivery {
+        path: dst_file.to_owned(),
+        size,
+        checksum: checksum_src,
+        read_back,
+        kept,
+        retries: retry_count,
+        collision: Collision::None,
+    }))
+}
+
+/// Outcome of an attempt to copy a file.
+///
+enum Attempt {
+    /// The file was copied, with its size and checksum if any, and whether the copy was read back
+    /// whole
+    Copied(u64, Option<(Algorithm, String)>, bool),
+    /// The copy read back does not match the source
+    Mismatch,
+    /// The syncjob was paused or stopped meanwhile
+    Cancelled,
+}
+
+/// Check if two files are identical, as told by the given comparison.
+///
+fn is_identical(src_file: &Path, dst_file: &Path, identical: Identical) -> std::io::Result<bool> {
+    const CHUNK_SIZE: usize = 64 * 1024;
+
+    let (src_metadata, dst_metadata) = (std::fs::metadata(src_file)?, std::fs::metadata(dst_file)?);
+    if src_metadata.len() != dst_metadata.len() {
+        return Ok(false);
+    }
+    if identical == Identical::Quick {
+        return Ok(src_metadata.modified()? == dst_metadata.modified()?);
+    }
+
+    let mut src = std::io::BufReader::new(std::fs::File::open(src_file)?);
+    let mut dst = std::io::BufReader::new(std::fs::File::open(dst_file)?);
+    let (mut src_chunk, mut dst_chunk) = (vec![0; CHUNK_SIZE], vec![0; CHUNK_SIZE]);
+    loop {
+        let read = src.read(&mut src_chunk)?;
+        if read == 0 {
+            return Ok(true);
+        }
+        dst.read_exact(&mut dst_chunk[..read])?;
+        if src_chunk[..read] != dst_chunk[..read] {
+            return Ok(false);
+        }
+    }
+}
+
+/// Name a file colliding with `dst_file` is delivered with instead, by the rename policy.
+///
+/// A `suffix` without placeholders replaces the extension of the file. Otherwise, it is a template
+/// of the new name, where `{stem}` is the name of the file without its extension, `{ext}` the
+/// extension, `{n}` a counter and `{timestamp}` the current local time. The first name not taken
+/// is used: `{n}` counts up from 1, and without it ` (2)`, ` (3)`... are added before the
+/// extension of the new name.
+///
+pub(crate) fn renamed(dst_file: &Path, suffix: &str) -> PathBuf {
+    let stem = dst_file
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let ext = dst_file
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_string());
+    let template = match suffix.contains('{') {
+        true => suffix.to_owned(),
+        false => format!("{{stem}}.{suffix}"),
+    };
+    // Without extension, the dot before it goes too
+    let template = match ext {
+        Some(_) => template,
+        None => template.replace(".{ext}", ""),
+    };
+    let counted = template.contains("{n}");
+    let timestamp = Local::now().format("%Y%m%d-%H%M%S").to_string();
+
+    (1u64..)
+        .map(|n| {
+            let mut name = String::new();
+            let mut rest = template.as_str();
+            while let Some(start) = rest.find('{') {
+                name.push_str(&rest[..start]);
+                let end = rest[start..]
+                    .find('}')
+                    .map_or(rest.len(), |end| start + end + 1);
+                match &rest[start..end] {
+                    "{stem}" => name.push_str(&stem),
+                    "{ext}" => name.push_str(ext.as_deref().unwrap_or_default()),
+                    "{n}" => name.push_str(&n.to_string()),
+                    "{timestamp}" => name.push_str(&timestamp),
+                    unknown => name.push_str(unknown),
+                }
+                rest = &rest[end..];
+            }
+            name.push_str(rest);
+            if !counted && n > 1 {
+                let name = Path::new(&name);
+                let stem = name.file_stem().unwrap_or_default().to_string_lossy();
+                return dst_file.with_file_name(match name.extension() {
+                    Some(ext) => format!("{stem} ({n}).{}", ext.to_string_lossy()),
+                    None => format!("{stem} ({n})"),
+                });
+            }
+            dst_file.with_file_name(name)
+        })
+        .find(|candidate| !candidate.exists())
+        .unwrap()
+}
+
+/// Where a file is written before being renamed to its destination.
+///
+/// For photo.jpg, this is .photo.jpg.wip, a hidden sidecar file keeping the full filename, so
+/// neither the extension is lost nor another photo.wip file is hit. In the `staging` folder, the
+/// files with the same name going to different folders are told apart by a hash of their
+/// destination.
+///
+pub(crate) fn wip_file(dst_file: &Path, staging: Option<&Path>, suffix: &str) -> Result<PathBuf> {
+    if suffix.is_empty() || suffix.contains(['/', '\\']) {
+        bail!("Invalid suffix of the .wip files: {suffix:?}");
+    }
+    let filename = dst_file
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or("file".into());
+
+    Ok(match staging {
+        Some(staging) => {
+            let mut hasher = DefaultHasher::new();
+            dst_file.hash(&mut hasher);
+            staging.join(format!(".{filename}.{:016x}.{suffix}", hasher.finish()))
+        }
+        None => dst_file
+            .parent()
+            .unwrap_or(Path::new("."))
+            .join(format!(".{filename}.{suffix}")),
+    })
+}
 
-        // Check that the file was copied correctly
-        if let Some((algorithm, ref checksum_src)) = checksum_src {
-            let checksum_wip = hash_file(wip, algorithm);
-            log::debug!("Checksum(wip): {:?}", checksum_wip);
-            if checksum_src != &checksum_wip {
-                retry_count += 1;
+/// Bytes of the files waiting in a slide, the folders internal to it left out.
+///
+pub(crate) fn pending_size(slide: &Path) -> u64 {
+    let mut size = 0;
+    let mut pending = vec![(slide.to_owned(), true)];
+    while let Some((dir, top)) = pending.pop() {
+        let Ok(entries) = dir.read_dir() else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(metadata) = entry.metadata() else {
                 continue;
+            };
+            let path = entry.path();
+            if metadata.is_dir() {
+                if !is_internal(&path) {
+                    pending.push((path, false));
+                }
+            }
+            // Only the folders of the slide are synced, not the files at its top
+            else if !top {
+                size += metadata.len();
             }
         }
+    }
+    size
+}
 
-        if request.safe {
-            tokio::fs::rename(wip, dst_file).await?;
+/// Space available to the process in the filesystem of a folder, if known.
+///
+pub(crate) fn free_space(dir: &Path) -> Option<u64> {
+    #[cfg(unix)]
+    {
+        let stats = nix::sys::statvfs::statvfs(dir).ok()?;
+        Some(stats.blocks_available() as u64 * stats.fragment_size() as u64)
+    }
+    #[cfg(windows)]
+    {
+        let mut available = 0u64;
+        unsafe {
+            windows::Win32::Storage::FileSystem::GetDiskFreeSpaceExW(
+                &windows::core::HSTRING::from(dir.as_os_str()),
+                Some(&mut available),
+                None,
+                None,
+            )
         }
+        .ok()?;
+        Some(available)
+    }
+}
 
-        tokio::fs::remove_file(src_file).await?;
+/// Check if a file and the folder of its destination belong to the same filesystem.
+///
+/// Compares the device on Unix, and the serial number of the volume on Windows.
+///
+fn same_device(src_file: &Path, dst_file: &Path) -> bool {
+    let Some(dst_dir) = dst_file.parent() else {
+        return false;
+    };
+    match (device_id(src_file), device_id(dst_dir)) {
+        (Some(src), Some(dst)) => src == dst,
+        _ => false,
+    }
+}
 
-        return Ok(());
+/// Identifier of the filesystem holding a path, if available.
+///
+fn device_id(path: &Path) -> Option<u64> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        std::fs::metadata(path).ok().map(|metadata| metadata.dev())
     }
+    #[cfg(windows)]
+    {
+        use windows::{core::HSTRING, Win32::Storage::FileSystem};
 
-    if wip.exists() {
-        tokio::fs::remove_file(wip).await?;
+        const MAX_PATH: usize = 261;
+        let mut root = [0u16; MAX_PATH];
+        let mut serial = 0u32;
+        unsafe {
+            FileSystem::GetVolumePathNameW(&HSTRING::from(path.as_os_str()), &mut root).ok()?;
+            FileSystem::GetVolumeInformationW(
+                windows::core::PCWSTR(root.as_ptr()),
+                None,
+                Some(&mut serial),
+                None,
+                None,
+                None,
+            )
+            .ok()?;
+        }
+        Some(serial as u64)
     }
+}
 
-    bail!("Failed to move file {:?} after maximum retries", src_file);
+/// Give a file the permissions of another one.
+///
+/// On Unix, these are the mode bits, and the owner and group as long as the process is allowed to
+/// change them, typically when running as root. On Windows, the read-only and hidden attributes.
+///
+fn copy_permissions(src_file: &Path, dst_file: &Path) -> std::io::Result<()> {
+    let metadata = std::fs::metadata(src_file)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        // Changing the owner clears the setuid and setgid bits, so it goes first
+        match std::os::unix::fs::chown(dst_file, Some(metadata.uid()), Some(metadata.gid())) {
+            Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+                log::debug!("Unable to change the owner of {dst_file:?}: {e}")
+            }
+            result => result?,
+        }
+        std::fs::set_permissions(dst_file, metadata.permissions())
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+        use windows::{core::HSTRING, Win32::Storage::FileSystem};
+
+        let kept = (FileSystem::FILE_ATTRIBUTE_READONLY | FileSystem::FILE_ATTRIBUTE_HIDDEN).0;
+        let attributes = (std::fs::metadata(dst_file)?.file_attributes() & !kept)
+            | (metadata.file_attributes() & kept);
+        unsafe {
+            FileSystem::SetFileAttributesW(
+                &HSTRING::from(dst_file.as_os_str()),
+                FileSystem::FILE_FLAGS_AND_ATTRIBUTES(attributes),
+            )
+        }
+        .map_err(std::io::Error::from)
+    }
+}
+
+/// Copy the extended attributes of a file to another one.
+///
+/// On Windows, these are the alternate data streams. The attributes the process is not allowed to
+/// set, like the `security` and `trusted` namespaces of Linux when not running as root, are left
+/// out.
+///
+fn copy_xattrs(src_file: &Path, dst_file: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        let names = match xattr::list(src_file) {
+            Ok(names) => names,
+            Err(e) if e.kind() == std::io::ErrorKind::Unsupported => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        for name in names {
+            let Some(value) = xattr::get(src_file, &name)? else {
+                continue;
+            };
+            match xattr::set(dst_file, &name, &value) {
+                Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+                    log::debug!("Unable to set the attribute {name:?} of {dst_file:?}: {e}")
+                }
+                result => result?,
+            }
+        }
+        Ok(())
+    }
+    #[cfg(windows)]
+    {
+        use windows::{core::HSTRING, Win32::Storage::FileSystem};
+
+        let mut data = FileSystem::WIN32_FIND_STREAM_DATA::default();
+        // Fails if there is no stream at all, as for folders
+        let Ok(find) = (unsafe {
+            FileSystem::FindFirstStreamW(
+                &HSTRING::from(src_file.as_os_str()),
+                FileSystem::FindStreamInfoStandard,
+                &mut data as *mut _ as *mut std::ffi::c_void,
+                0,
+            )
+        }) else {
+            return Ok(());
+        };
+
+        let mut result = Ok(());
+        loop {
+            let name = String::from_utf16_lossy(&data.cStreamName);
+            // Named streams look like ":name:$DATA", the unnamed one holds the file contents
+            if let Some(stream) = name.trim_end_matches('\0').strip_suffix(":$DATA") {
+                if !stream.is_empty() && stream != ":" {
+                    let mut src = src_file.as_os_str().to_owned();
+                    src.push(stream);
+                    let mut dst = dst_file.as_os_str().to_owned();
+                    dst.push(stream);
+                    if let Err(e) = std::fs::copy(&src, &dst) {
+                        result = Err(e);
+                        break;
+                    }
+                }
+            }
+            if unsafe {
+                FileSystem::FindNextStreamW(find, &mut data as *mut _ as *mut std::ffi::c_void)
+            }
+            .is_err()
+            {
+                break;
+            }
+        }
+        unsafe {
+            let _ = FileSystem::FindClose(find);
+        }
+        result
+    }
 }
 
+/// Check if a file may be cloned into the folder of its destination on another device: both have to
+/// be on btrfs, whose subvolumes do not share their device. The copy-on-write filesystems of the
+/// other platforms only clone within a volume.
+///
+fn may_clone(src_file: &Path, dst_dir: &Path) -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        use nix::sys::statfs::{statfs, BTRFS_SUPER_MAGIC};
+
+        match (statfs(src_file), statfs(dst_dir)) {
+            (Ok(src), Ok(dst)) => [src, dst]
+                .iter()
+                .all(|fs| fs.filesystem_type() == BTRFS_SUPER_MAGIC),
+            _ => false,
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = (src_file, dst_dir);
+        false
+    }
+}
+
+/// Clone a file, so both share their data blocks until one of them is modified.
+///
+/// Only copy-on-write filesystems (btrfs, XFS, APFS, ReFS...) support it, and just within the
+/// same filesystem. Returns the size of the file, or `None` if it could not be cloned. `dst_file`
+/// must not exist.
+///
+async fn clone_file(src_file: &Path, dst_file: &Path) -> Option<u64> {
+    let (src, dst) = (src_file.to_owned(), dst_file.to_owned());
+    let cloned = tokio::task::spawn_blocking(move || {
+        reflink_copy::reflink(&src, &dst)?;
+        // Like a copy, the clone gets the permissions of the source
+        let metadata = std::fs::metadata(&src)?;
+        std::fs::set_permissions(&dst, metadata.permissions())?;
+        Ok::<_, std::io::Error>(metadata.len())
+    })
+    .await;
+
+    match cloned {
+        Ok(Ok(size)) => Some(size),
+        Ok(Err(e)) => {
+            log::debug!("Unable to clone {src_file:?}, copying it: {e}");
+            None
+        }
+        Err(_) => None,
+    }
+}
+
+/// Blocks of zeros of this size are not written to the copies, but left as holes.
+///
+const HOLE_SIZE: usize = 4096;
+
+/// Copy a file in chunks, reporting the bytes copied to the progress display.
+///
+/// The copy stops between two chunks if `progress` tells it to, returning `None`. The partial
+/// copy is left for the caller to remove. `written` is set once the destination file is created,
+/// or truncated. Each chunk read waits for the `throttle`, if any.
+///
+/// If a `hasher` is given, the chunks are hashed as they are copied, so the file is read just once,
+/// and the checksum returned along with the number of bytes copied. The blocks of zeros are
+/// skipped rather than written, so the filesystem leaves holes in their place, and sparse files
+/// like the disk images of virtual machines stay sparse. Like `tokio::fs::copy`, the permissions
+/// of the source file are copied too.
+///
+async fn copy_chunked<F>(
+    src_file: &Path,
+    dst_file: &Path,
+    written: &AtomicBool,
+    progress: &Progress,
+    gate: Option<&watch::Receiver<Gate>>,
+    throttle: Option<&Throttle>,
+    hasher: Option<(Algorithm, F)>,
+) -> Result<Option<(u64, Option<String>)>>
+where
+    F: Fn(&mut dyn Read, Algorithm) -> std::io::Result<String> + Send + 'static,
+{
+    const CHUNK_SIZE: usize = 1024 * 1024;
+
+    let mut reader = tokio::fs::File::open(src_file).await?;
+    let metadata = reader.metadata().await?;
+    let mut writer = tokio::fs::File::create(dst_file).await?;
+    written.store(true, Ordering::Relaxed);
+
+    // The hashing functions pull the data, so they run apart, fed through a channel
+    let (chunks, hashing) = match hasher {
+        Some((algorithm, hash)) => {
+            let (tx, rx) = tokio::sync::mpsc::channel(4);
+            let hashing =
+                tokio::task::spawn_blocking(move || hash(&mut ChunkReader::new(rx), algorithm));
+            (Some(tx), Some(hashing))
+        }
+        None => (None, None),
+    };
+
+    let started = Instant::now();
+    progress.start(src_file, metadata.len()).await;
+    let result = async {
+        let mut buffer = vec![0; CHUNK_SIZE];
+        let mut copied = 0;
+        loop {
+            if is_cancelled(gate) {
+                return Ok(None);
+            }
+            let read = reader.read(&mut buffer).await?;
+            if read == 0 {
+                break;
+            }
+            if let Some(throttle) = throttle {
+                throttle.acquire(read as u64).await;
+            }
+            if let Some(chunks) = &chunks {
+                // The hashing function may be done early, and no longer care
+                let _ = chunks.send(buffer[..read].to_vec()).await;
+            }
+            write_sparse(&mut writer, &buffer[..read]).await?;
+            copied += read as u64;
+            progress.advance(src_file, read as u64).await;
+        }
+        // A trailing hole is only part of the file once its length is set
+        writer.set_len(copied).await?;
+        writer.flush().await?;
+        writer.set_permissions(metadata.permissions()).await?;
+        Ok::<_, anyhow::Error>(Some(copied))
+    }
+    .await;
+    progress
+        .finish(src_file, matches!(result, Ok(Some(_))))
+        .await;
+
+    drop(chunks);
+    let checksum = match hashing {
+        Some(hashing) => Some(hashing.await??),
+        None => None,
+    };
+    let Some(copied) = result? else {
+        return Ok(None);
+    };
+
+    let elapsed = started.elapsed();
+    log::debug!(
+        "Copied {copied} bytes of {src_file:?} in {elapsed:.2?} ({:.0} bytes/s)",
+        copied as f64 / elapsed.as_secs_f64().max(f64::EPSILON)
+    );
+    Ok(Some((copied, checksum)))
+}
+
+/// Reader of the chunks of a file sent through a channel.
+///
+struct ChunkReader {
+    rx: tokio::sync::mpsc::Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    position: usize,
+}
+
+impl ChunkReader {
+    fn new(rx: tokio::sync::mpsc::Receiver<Vec<u8>>) -> Self {
+        Self {
+            rx,
+            chunk: Vec::new(),
+            position: 0,
+        }
+    }
+}
+
+impl Read for ChunkReader {
+    fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+        while self.position == self.chunk.len() {
+            match self.rx.blocking_recv() {
+                Some(chunk) => {
+                    self.chunk = chunk;
+                    self.position = 0;
+                }
+                None => return Ok(0),
+            }
+        }
+        let read = buffer.len().min(self.chunk.len() - self.position);
+        buffer[..read].copy_from_slice(&self.chunk[self.position..self.position + read]);
+        self.position += read;
+        Ok(read)
+    }
+}
+
+/// Hash a file with the given hashing function.
+///
+/// Hashing a large file takes a while, so it runs on a blocking thread, letting the other
+/// syncjobs make progress meanwhile.
+///
+async fn hash_path<F>(hash: &F, path: &Path, algorithm: Algorithm) -> Result<String>
+where
+    F: Fn(&mut dyn Read, Algorithm) -> std::io::Result<String> + Clone + Send + 'static,
+{
+    let (hash, path) = (hash.clone(), path.to_owned());
+    tokio::task::spawn_blocking(move || {
+        let mut file = std::fs::File::open(path)?;
+        Ok(hash(&mut file, algorithm)?)
+    })
+    .await?
+}
+
+/// Hash the parts of a file in a sample, with the given hashing function.
+///
+async fn hash_sample<F>(
+    hash: &F,
+    path: &Path,
+    algorithm: Algorithm,
+    sample: &Sample,
+) -> Result<String>
+where
+    F: Fn(&mut dyn Read, Algorithm) -> std::io::Result<String> + Clone + Send + 'static,
+{
+    let (hash, path, sample) = (hash.clone(), path.to_owned(), sample.clone());
+    tokio::task::spawn_blocking(move || {
+        let file = std::fs::File::open(path)?;
+        Ok(hash(&mut sample.reader(file), algorithm)?)
+    })
+    .await?
+}
+
+/// Write data, seeking over its blocks of zeros instead of writing them.
+///
+async fn write_sparse(writer: &mut tokio::fs::File, data: &[u8]) -> Result<()> {
+    let is_hole = |block: &[u8]| block.len() == HOLE_SIZE && block.iter().all(|byte| *byte == 0);
+
+    let mut blocks = data.chunks(HOLE_SIZE).peekable();
+    let mut offset = 0;
+    while let Some(block) = blocks.next() {
+        // Take the whole run of blocks of the same kind at once
+        let hole = is_hole(block);
+        let mut end = offset + block.len();
+        while let Some(next) = blocks.next_if(|next| is_hole(next) == hole) {
+            end += next.len();
+        }
+        if hole {
+            writer
+                .seek(std::io::SeekFrom::Current((end - offset) as i64))
+                .await?;
+        } else {
+            writer.write_all(&data[offset..end]).await?;
+        }
+        offset = end;
+    }
+    Ok(())
+}
+
+pub(crate) mod conflict;
+mod delta;
+pub(crate) mod journal;
+mod lock;
+mod prehash;
+mod retry;
+pub(crate) mod reverify;
+mod sample;
+mod sanitize;
+pub(crate) mod sidecar;
+pub(crate) mod throttle;
+pub(crate) mod trash;
+
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,179 @@
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use tokio::sync::Mutex;
+
+use super::{is_internal, renamed, trash::Bin, MoveStrategy};
+use crate::{config::ConflictPolicy, filter::Filter, tracer::Tracer};
+
+/// Held while conflicts are resolved, as the syncjobs of two volumes sending files to each other
+/// find the same ones.
+///
+static RESOLVING: Mutex<()> = Mutex::const_new(());
+
+/// A file sent both ways between two volumes within the same sync.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Conflict {
+    /// File in the slide being synced
+    pub(crate) ours: PathBuf,
+    /// File under the same path in the slide sending files back the other way
+    pub(crate) theirs: PathBuf,
+}
+
+/// Find the files of a slide found under the same path in the `reciprocal` one, the slide its
+/// destination volume has for its source volume. The excluded files are left out, as they are
+/// not sent.
+///
+pub(crate) fn find(slide: &Path, reciprocal: &Path, filter: &Filter) -> Vec<Conflict> {
+    let mut conflicts = Vec::new();
+    let mut pending = vec![PathBuf::new()];
+    while let Some(relative) = pending.pop() {
+        let Ok(entries) = slide.join(&relative).read_dir() else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            let path = relative.join(entry.file_name());
+            // Only the folders of the slide are synced, not the files at its top
+            if relative.as_os_str().is_empty() && !file_type.is_dir() {
+                continue;
+            }
+            if (file_type.is_dir() && is_internal(&path))
+                || filter.is_excluded(&path, file_type.is_dir())
+            {
+                continue;
+            }
+            if file_type.is_dir() {
+                pending.push(path);
+            } else if reciprocal.join(&path).is_file() {
+                conflicts.push(Conflict {
+                    ours: slide.join(&path),
+                    theirs: reciprocal.join(&path),
+                });
+            }
+        }
+    }
+    conflicts.sort_by(|a, b| a.ours.cmp(&b.ours));
+    conflicts
+}
+
+/// Resolve the conflicts between the slides two volumes have for each other, as the conflict
+/// policy of the `request` says, tracing each of them. Returns the number of conflicts found.
+///
+/// `volumes` are the names of the source and the destination volumes of the slide synced. The
+/// syncjobs both ways resolve them one after the other, so the later one finds what the earlier one
+/// left, and only the one from the first volume by name reports them.
+///
+pub(crate) async fn resolve(
+    slide: &Path,
+    reciprocal: &Path,
+    volumes: (&str, &str),
+    request: &MoveStrategy,
+    filter: &Filter,
+    dry_run: bool,
+    tracer: &Tracer,
+) -> Result<usize> {
+    let (src, dst) = volumes;
+    if request.conflict == ConflictPolicy::Report && src > dst {
+        return Ok(0);
+    }
+    let _resolving = RESOLVING.lock().await;
+    let conflicts = find(slide, reciprocal, filter);
+    for Conflict { ours, theirs } in &conflicts {
+        let resolution = match request.conflict {
+            ConflictPolicy::Report => {
+                log::warn!("{ours:?} and {theirs:?} are sent both ways");
+                "reported".to_owned()
+            }
+            ConflictPolicy::Newer => {
+                let modified = |file: &Path| file.metadata().and_then(|m| m.modified()).ok();
+                let older = match (modified(ours), modified(theirs)) {
+                    (Some(a), Some(b)) if a < b => ours,
+                    (Some(a), Some(b)) if a > b => theirs,
+                    _ => {
+                        log::warn!("{ours:?} and {theirs:?} are as recent, both are kept");
+                        continue;
+                    }
+                };
+                if !dry_run {
+                    let slide = if older == ours { slide } else { reciprocal };
+                    match request.use_trash {
+                        Some(trash) => {
+                            Bin::new(slide, slide, trash)
+                                .discard_destination(older)
+                                .await?
+                        }
+                        None => tokio::fs::remove_file(older).await?,
+                    }
+                }
+                format!("removed older {older:?}")
+            }
+            ConflictPolicy::Rename => {
+                let mut renames = Vec::new();
+                for (file, volume) in [(ours, src), (theirs, dst)] {
+                    let suffix = format!("{{stem}}.{volume}.{{ext}}");
+                    let to = match dry_run {
+                        true => renamed(file, &suffix),
+                        false => {
+                            let file = file.clone();
+                            tokio::task::spawn_blocking(move || rename_aside(&file, &suffix))
+                                .await??
+                        }
+                    };
+                    renames.push(format!("{file:?} -> {to:?}"));
+                }
+                format!("renamed {}", renames.join(", "))
+            }
+        };
+        tracer
+            .async_log(
+                "CONFLICT",
+                &format!("{ours:?} <-> {theirs:?}: {resolution}"),
+            )
+            .await?;
+    }
+    Ok(conflicts.len())
+}
+
+/// Rename a file next to itself as the `suffix` says, never over another file, even one showing up
+/// in the meantime. Returns its new path.
+///
+fn rename_aside(file: &Path, suffix: &str) -> std::io::Result<PathBuf> {
+    loop {
+        let to = renamed(file, suffix);
+        match rename_new(file, &to) {
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            result => return result.map(|()| to),
+        }
+    }
+}
+
+/// Rename a file, failing with [`ErrorKind::AlreadyExists`] rather than replacing the destination.
+///
+/// Only Linux does it at once, on the filesystems supporting it. Elsewhere the destination is
+/// checked right before.
+///
+pub(crate) fn rename_new(from: &Path, to: &Path) -> std::io::Result<()> {
+    #[cfg(all(target_os = "linux", target_env = "gnu"))]
+    {
+        use nix::{
+            errno::Errno,
+            fcntl::{renameat2, RenameFlags, AT_FDCWD},
+        };
+
+        match renameat2(AT_FDCWD, from, AT_FDCWD, to, RenameFlags::RENAME_NOREPLACE) {
+            Err(Errno::EINVAL) => {}
+            result => return result.map_err(std::io::Error::from),
+        }
+    }
+    match to.symlink_metadata() {
+        Ok(_) => Err(ErrorKind::AlreadyExists.into()),
+        Err(_) => std::fs::rename(from, to),
+    }
+}
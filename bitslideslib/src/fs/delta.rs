@@ -0,0 +1,188 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, BufWriter, Read, Result, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+/// Size of the blocks looked for in the previous version of a file.
+///
+const BLOCK_SIZE: usize = 64 * 1024;
+
+/// Literal data is written out once this much is pending, so it is never held whole in memory.
+///
+const MAX_LITERAL: usize = 16 * BLOCK_SIZE;
+
+/// Outcome of a delta copy.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Delta {
+    /// Size of the new file
+    pub size: u64,
+    /// Bytes taken from the previous version instead of the source
+    pub reused: u64,
+}
+
+/// Weak checksum of a block, cheap to roll one byte at a time, as the one of rsync.
+///
+struct Rolling {
+    a: u32,
+    b: u32,
+    len: u32,
+}
+
+impl Rolling {
+    fn new(block: &[u8]) -> Self {
+        let len = block.len() as u32;
+        let (mut a, mut b) = (0u32, 0u32);
+        for (i, &byte) in block.iter().enumerate() {
+            a = a.wrapping_add(byte as u32);
+            b = b.wrapping_add((len - i as u32).wrapping_mul(byte as u32));
+        }
+        Self { a, b, len }
+    }
+
+    fn digest(&self) -> u32 {
+        (self.a & 0xffff) | (self.b << 16)
+    }
+
+    /// Slide the block one byte forward, dropping `out` and taking `next`.
+    ///
+    fn roll(&mut self, out: u8, next: u8) {
+        self.a = self.a.wrapping_sub(out as u32).wrapping_add(next as u32);
+        self.b = self
+            .b
+            .wrapping_sub(self.len.wrapping_mul(out as u32))
+            .wrapping_add(self.a);
+    }
+}
+
+/// Strong checksum of a block, to confirm the matches of the weak one. A cryptographic hash, as a
+/// collision would silently write the wrong block.
+///
+fn strong(block: &[u8]) -> blake3::Hash {
+    blake3::hash(block)
+}
+
+/// Checksums of the blocks of the previous version of a file, by weak checksum.
+///
+struct Signature {
+    blocks: HashMap<u32, Vec<(blake3::Hash, u64)>>,
+}
+
+impl Signature {
+    fn new(base: &Path) -> Result<Self> {
+        let mut reader = BufReader::new(File::open(base)?);
+        let mut blocks = HashMap::<u32, Vec<(blake3::Hash, u64)>>::new();
+        let mut block = vec![0; BLOCK_SIZE];
+        let mut offset = 0;
+        // The trailing partial block, if any, is not worth looking for
+        while read_full(&mut reader, &mut block)? == BLOCK_SIZE {
+            blocks
+                .entry(Rolling::new(&block).digest())
+                .or_default()
+                .push((strong(&block), offset));
+            offset += BLOCK_SIZE as u64;
+        }
+        Ok(Self { blocks })
+    }
+
+    /// Offset of a block with the same contents in the previous version, if any.
+    ///
+    fn find(&self, weak: u32, block: &[u8]) -> Option<u64> {
+        let candidates = self.blocks.get(&weak)?;
+        let strong = strong(block);
+        candidates
+            .iter()
+            .find(|(candidate, _)| *candidate == strong)
+            .map(|(_, offset)| *offset)
+    }
+}
+
+/// Read until the buffer is full or the end of the input, returning the bytes read.
+///
+fn read_full(reader: &mut impl Read, buffer: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..])? {
+            0 => break,
+            read => filled += read,
+        }
+    }
+    Ok(filled)
+}
+
+/// Write `dst` with the contents of `src`, taking the blocks unchanged from `base`, the previous
+/// version of the file, instead of from `src`.
+///
+/// The blocks are found wherever they moved to, as rsync does, so data inserted or removed does
+/// not spoil the rest of the file. `dst` must not be `base`. The permissions of `src` are copied.
+///
+pub(crate) fn copy(src: &Path, base: &Path, dst: &Path) -> Result<Delta> {
+    let signature = Signature::new(base)?;
+    let mut base = File::open(base)?;
+    let source = File::open(src)?;
+    let permissions = source.metadata()?.permissions();
+    let mut reader = BufReader::new(source);
+    let mut writer = BufWriter::new(File::create(dst)?);
+
+    let mut delta = Delta { size: 0, reused: 0 };
+    // Bytes of the source read but not written yet. The block being checked starts at `start`,
+    // and the literal data, to be written as is, goes from the beginning to `start`
+    let mut buffer = Vec::new();
+    let mut start = 0;
+    let mut rolling: Option<Rolling> = None;
+    let mut eof = false;
+    let mut block = vec![0; BLOCK_SIZE];
+
+    loop {
+        if start >= MAX_LITERAL {
+            writer.write_all(&buffer[..start])?;
+            buffer.drain(..start);
+            start = 0;
+        }
+
+        // Have the block and the byte after it at hand, to roll forward
+        if !eof && buffer.len() < start + BLOCK_SIZE + 1 {
+            let read = read_full(&mut reader, &mut block)?;
+            buffer.extend_from_slice(&block[..read]);
+            eof = read < BLOCK_SIZE;
+            continue;
+        }
+        if buffer.len() < start + BLOCK_SIZE {
+            break;
+        }
+
+        let window = &buffer[start..start + BLOCK_SIZE];
+        let weak = rolling.get_or_insert_with(|| Rolling::new(window)).digest();
+        if let Some(offset) = signature.find(weak, window) {
+            writer.write_all(&buffer[..start])?;
+            base.seek(SeekFrom::Start(offset))?;
+            base.read_exact(&mut block)?;
+            writer.write_all(&block)?;
+            delta.reused += BLOCK_SIZE as u64;
+            buffer.drain(..start + BLOCK_SIZE);
+            start = 0;
+            rolling = None;
+            continue;
+        }
+
+        match buffer.get(start + BLOCK_SIZE) {
+            Some(&next) => {
+                if let Some(rolling) = rolling.as_mut() {
+                    rolling.roll(buffer[start], next);
+                }
+                start += 1;
+            }
+            None => break,
+        }
+    }
+
+    writer.write_all(&buffer)?;
+    writer.flush()?;
+    let file = writer.into_inner().map_err(|e| e.into_error())?;
+    delta.size = file.metadata()?.len();
+    file.set_permissions(permissions)?;
+
+    Ok(delta)
+}
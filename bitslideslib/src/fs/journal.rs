@@ -0,0 +1,99 @@
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+
+use super::JOURNAL_DIR;
+
+/// A move in flight, as recorded in the journal.
+///
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    src: PathBuf,
+    /// File being written, the destination itself when not in safe mode
+    wip: PathBuf,
+    dst: PathBuf,
+}
+
+/// Journal of the copies in flight to a slide.
+///
+/// Each copy gets its own entry, a small file written before the copy starts and removed once
+/// the source is gone. The entries left behind by a crash tell what to roll back.
+///
+#[derive(Debug)]
+pub(crate) struct Journal {
+    dir: PathBuf,
+}
+
+impl Journal {
+    pub(crate) fn new(dst_slide: &Path) -> Self {
+        Self {
+            dir: dst_slide.join(JOURNAL_DIR),
+        }
+    }
+
+    /// Record a copy about to start, flushing it to the disk. Returns the entry to clear.
+    ///
+    pub(crate) async fn record(&self, src: &Path, wip: &Path, dst: &Path) -> Result<PathBuf> {
+        let mut hasher = DefaultHasher::new();
+        dst.hash(&mut hasher);
+        let path = self.dir.join(format!("{:016x}.json", hasher.finish()));
+
+        let entry = Entry {
+            src: src.to_owned(),
+            wip: wip.to_owned(),
+            dst: dst.to_owned(),
+        };
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let mut file = tokio::fs::File::create(&path).await?;
+        file.write_all(&serde_json::to_vec(&entry)?).await?;
+        file.sync_all().await?;
+        Ok(path)
+    }
+
+    /// Remove the entry of a copy done, or cancelled without leftovers.
+    ///
+    pub(crate) async fn clear(&self, entry: &Path) -> Result<()> {
+        tokio::fs::remove_file(entry).await?;
+        // Left in place while other copies are in flight
+        let _ = tokio::fs::remove_dir(&self.dir).await;
+        Ok(())
+    }
+}
+
+/// Roll back the copies to a slide interrupted by a crash, as recorded in its journal.
+///
+/// The .wip file of each copy whose source is still there is removed, so the file is moved from
+/// scratch by the next sync. Without safe mode, that is the destination file itself. The copies
+/// whose source is gone were complete, and need nothing. Returns the number of copies rolled back.
+///
+pub(crate) async fn recover(dst_slide: &Path) -> Result<usize> {
+    let dir = dst_slide.join(JOURNAL_DIR);
+    if !dir.is_dir() {
+        return Ok(0);
+    }
+
+    let mut rolled_back = 0;
+    let mut entries = tokio::fs::read_dir(&dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        // A crash while recording leaves the entry truncated, before the copy started
+        match serde_json::from_slice::<Entry>(&tokio::fs::read(&path).await?) {
+            Ok(Entry { src, wip, dst }) if src.exists() && wip.exists() => {
+                log::warn!("Rollback: {src:?} -> {dst:?} was interrupted, removing {wip:?}");
+                tokio::fs::remove_file(&wip).await?;
+                rolled_back += 1;
+            }
+            Ok(_) => {}
+            Err(e) => log::warn!("{path:?}: Unreadable journal entry, ignored: {e}"),
+        }
+        tokio::fs::remove_file(&path).await?;
+    }
+
+    let _ = tokio::fs::remove_dir(&dir).await;
+    Ok(rolled_back)
+}
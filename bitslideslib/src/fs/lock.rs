@@ -0,0 +1,72 @@
+use std::{
+    fs::{File, TryLockError},
+    path::Path,
+};
+
+/// Check if another program is holding a file, as it is likely being written.
+///
+/// On Unix, these are the files with a `flock` or `fcntl` lock other than a shared one, which
+/// the programs writing them take on purpose. On Windows, these are also the files open by a
+/// program not letting others read them, or writing them without letting others write too. A file
+/// that cannot be opened for any other reason is left for the move to report.
+///
+pub(crate) fn is_locked(path: &Path) -> bool {
+    #[cfg(windows)]
+    let file = {
+        use std::os::windows::fs::OpenOptionsExt;
+        use windows::Win32::Storage::FileSystem::FILE_SHARE_READ;
+
+        // Asking to share the reads only clashes with the handles open for writing
+        match std::fs::OpenOptions::new()
+            .read(true)
+            .share_mode(FILE_SHARE_READ.0)
+            .open(path)
+        {
+            Ok(file) => file,
+            Err(e) => return is_violation(&e),
+        }
+    };
+    #[cfg(not(windows))]
+    let Ok(file) = File::open(path) else {
+        return false;
+    };
+
+    #[cfg(unix)]
+    if has_record_lock(&file) {
+        return true;
+    }
+
+    // The shared lock is released along with the file
+    matches!(file.try_lock_shared(), Err(TryLockError::WouldBlock))
+}
+
+/// Check if an I/O error comes from a file locked, or open without sharing it, by another
+/// program. Only Windows reports them.
+///
+pub(crate) fn is_violation(e: &std::io::Error) -> bool {
+    // ERROR_SHARING_VIOLATION and ERROR_LOCK_VIOLATION
+    cfg!(windows) && matches!(e.raw_os_error(), Some(32 | 33))
+}
+
+/// Check if a `fcntl` write lock is held on any part of a file.
+///
+#[cfg(unix)]
+fn has_record_lock(file: &File) -> bool {
+    use nix::{
+        fcntl::{fcntl, FcntlArg},
+        libc,
+    };
+
+    // A zeroed request covers the whole file
+    let mut lock: libc::flock = unsafe { std::mem::zeroed() };
+    lock.l_type = libc::F_RDLCK as _;
+    lock.l_whence = libc::SEEK_SET as _;
+
+    // The open file description locks also see the ones of this same process
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    let result = fcntl(file, FcntlArg::F_OFD_GETLK(&mut lock));
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    let result = fcntl(file, FcntlArg::F_GETLK(&mut lock));
+
+    result.is_ok() && libc::c_int::from(lock.l_type) != libc::F_UNLCK
+}
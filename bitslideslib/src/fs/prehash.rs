@@ -0,0 +1,71 @@
+use std::{fs::Metadata, io::Read, path::Path};
+
+use filetime::FileTime;
+use tokio::task::JoinHandle;
+
+use crate::config::Algorithm;
+
+/// Largest file hashed ahead of its copy.
+///
+/// Hashing a file ahead reads it twice, once to hash it and once to copy it, which only pays off
+/// while the second read comes from the page cache.
+///
+pub(crate) const MAX_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Checksum of a source file being computed ahead of its copy.
+///
+/// While the files before it are being copied, a file waiting for its turn is hashed on the
+/// blocking pool, so hashing it no longer holds back its own copy.
+///
+pub(crate) struct Prehash {
+    hashing: JoinHandle<std::io::Result<Prehashed>>,
+}
+
+/// Checksum of a source file computed ahead of its copy.
+///
+#[derive(Debug)]
+pub(crate) struct Prehashed {
+    /// Metadata of the file as it was before hashing it, which may have updated its access time
+    pub(crate) metadata: Metadata,
+    pub(crate) checksum: String,
+}
+
+impl Prehash {
+    /// Start hashing a file with the given hashing function.
+    ///
+    pub(crate) fn start<F>(path: &Path, algorithm: Algorithm, hash: F) -> Self
+    where
+        F: Fn(&mut dyn Read, Algorithm) -> std::io::Result<String> + Send + 'static,
+    {
+        let path = path.to_owned();
+        let hashing = tokio::task::spawn_blocking(move || {
+            let mut file = std::fs::File::open(path)?;
+            let metadata = file.metadata()?;
+            let checksum = hash(&mut file, algorithm)?;
+            Ok(Prehashed { metadata, checksum })
+        });
+        Self { hashing }
+    }
+
+    /// Wait for the checksum of the file. None if it could not be hashed, or changed since, so
+    /// it is hashed again along its copy.
+    ///
+    pub(crate) async fn wait(self, path: &Path) -> Option<Prehashed> {
+        let prehashed = match self.hashing.await.map_err(std::io::Error::from) {
+            Ok(Ok(prehashed)) => prehashed,
+            Ok(Err(e)) | Err(e) => {
+                log::debug!("Unable to hash {path:?} ahead: {e}");
+                return None;
+            }
+        };
+
+        let metadata = tokio::fs::metadata(path).await.ok()?;
+        let unchanged = metadata.len() == prehashed.metadata.len()
+            && FileTime::from_last_modification_time(&metadata)
+                == FileTime::from_last_modification_time(&prehashed.metadata);
+        if !unchanged {
+            log::debug!("{path:?}: Modified since hashed ahead, hashing it again");
+        }
+        unchanged.then_some(prehashed)
+    }
+}
@@ -0,0 +1,78 @@
+use std::{
+    hash::{BuildHasher, RandomState},
+    io::ErrorKind,
+    time::Duration,
+};
+
+/// Delay before the first retry, doubled for each of the following ones.
+///
+const BASE_DELAY: Duration = Duration::from_millis(200);
+
+/// Longest delay between two attempts.
+///
+const MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// Kind of failure of an attempt to copy a file, telling whether it is worth retrying.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Failure {
+    /// Likely to go away on its own, like a timeout or a flaky device
+    Transient,
+    /// The file or the destination cannot be accessed
+    Permission,
+    /// The destination has no room left
+    DiskFull,
+    /// The file is locked, or open without sharing it, by another program
+    Locked,
+    /// Any other failure, bound to happen again
+    Permanent,
+}
+
+impl Failure {
+    /// Classify an error by the I/O error causing it. Errors not caused by I/O are permanent.
+    ///
+    pub(crate) fn of(e: &anyhow::Error) -> Self {
+        let Some(e) = e
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<std::io::Error>())
+        else {
+            return Self::Permanent;
+        };
+        if super::lock::is_violation(e) {
+            return Self::Locked;
+        }
+        match e.kind() {
+            ErrorKind::PermissionDenied | ErrorKind::ReadOnlyFilesystem => Self::Permission,
+            ErrorKind::StorageFull | ErrorKind::QuotaExceeded | ErrorKind::FileTooLarge => {
+                Self::DiskFull
+            }
+            ErrorKind::NotFound
+            | ErrorKind::AlreadyExists
+            | ErrorKind::InvalidInput
+            | ErrorKind::InvalidData
+            | ErrorKind::InvalidFilename
+            | ErrorKind::IsADirectory
+            | ErrorKind::NotADirectory
+            | ErrorKind::DirectoryNotEmpty
+            | ErrorKind::CrossesDevices
+            | ErrorKind::Unsupported
+            | ErrorKind::OutOfMemory => Self::Permanent,
+            // Timeouts, interruptions, busy or unplugged devices, lost network shares...
+            _ => Self::Transient,
+        }
+    }
+}
+
+/// Delay before the given retry, counting from 1.
+///
+/// The delay doubles with each retry, up to a maximum, and a random half of it is dropped so the
+/// copies failing at once do not retry in lockstep.
+///
+pub(crate) fn backoff(retry: u8) -> Duration {
+    let delay = BASE_DELAY
+        .saturating_mul(1 << retry.saturating_sub(1).min(16))
+        .min(MAX_DELAY);
+    // Each RandomState is seeded differently, which is random enough for a jitter
+    let jitter = (RandomState::new().hash_one(retry) % 1000) as u32;
+    delay / 2 + delay / 2 * jitter / 1000
+}
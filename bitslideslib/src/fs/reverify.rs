@@ -0,0 +1,133 @@
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::{bail, Result};
+
+use super::{wip_file, Delivery};
+use crate::{
+    config::Algorithm, hasher::hash_reader, issues::IssueKind, progress::Progress, tracer::Tracer,
+};
+
+/// A delivery waiting to be checked again, once settled.
+///
+/// Flaky enclosures and cables may corrupt a copy after it was found sound, as the file read
+/// back right away can come from a cache rather than from the disk.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Reverification {
+    /// Where the file was delivered from
+    src: PathBuf,
+    delivery: Delivery,
+    /// Modification time of the file once delivered, telling a later edit from a corruption
+    modified: SystemTime,
+}
+
+impl Reverification {
+    pub(crate) async fn new(src: PathBuf, delivery: Delivery) -> Result<Self> {
+        let modified = tokio::fs::metadata(&delivery.path).await?.modified()?;
+        Ok(Self {
+            src,
+            delivery,
+            modified,
+        })
+    }
+
+    /// Hash the delivered file again, tracing the outcome and reporting a corruption as an issue.
+    /// If `restore`, a corrupted file is replaced by the copy of its source kept in the trash, as
+    /// long as that one is sound.
+    ///
+    /// The files removed or modified since their delivery are not checked, as they are no longer
+    /// the files delivered.
+    ///
+    pub(crate) async fn run(
+        self,
+        restore: bool,
+        tracer: &Tracer,
+        progress: &Progress,
+    ) -> Result<()> {
+        let path = &self.delivery.path;
+        let Some((algorithm, expected)) = &self.delivery.checksum else {
+            return Ok(());
+        };
+        let modified = tokio::fs::metadata(path)
+            .await
+            .and_then(|metadata| metadata.modified());
+        if modified.ok() != Some(self.modified) {
+            log::info!("{path:?}: Removed or modified since its delivery, not checked again");
+            return Ok(());
+        }
+
+        let problem = match hash(path, *algorithm).await {
+            Ok(actual) if actual.eq_ignore_ascii_case(expected) => {
+                return tracer.async_log("REVERIFIED", &format!("{path:?}")).await;
+            }
+            Ok(actual) => format!("expected {expected}, found {actual}"),
+            Err(e) => format!("unreadable, {e}"),
+        };
+        log::error!(
+            "{path:?}: Corrupted since its delivery from {:?}, {problem}",
+            self.src
+        );
+        tracer
+            .async_log("CORRUPTED", &format!("{path:?}: {problem}"))
+            .await?;
+        progress.issue(IssueKind::Corrupted, Some(path), &problem);
+
+        match (&self.delivery.kept, restore) {
+            (Some(kept), true) => {
+                if let Err(e) = restore_from(kept, path, *algorithm, expected).await {
+                    log::error!("{path:?}: Unable to restore it from {kept:?}: {e}");
+                    return Ok(());
+                }
+                log::warn!("{path:?}: Restored from {kept:?}");
+                tracer
+                    .async_log("RESTORED", &format!("{kept:?} -> {path:?}"))
+                    .await
+            }
+            (None, true) => {
+                log::error!("{path:?}: No copy of its source was kept to restore it from");
+                Ok(())
+            }
+            (_, false) => Ok(()),
+        }
+    }
+}
+
+/// Replace a corrupted file by a sound copy of it, checking both the copy and the result.
+///
+async fn restore_from(
+    kept: &Path,
+    path: &Path,
+    algorithm: Algorithm,
+    expected: &str,
+) -> Result<()> {
+    let actual = hash(kept, algorithm).await?;
+    if !actual.eq_ignore_ascii_case(expected) {
+        bail!("The copy is corrupted too, found {actual}");
+    }
+
+    let wip = wip_file(path, None, "restore")?;
+    tokio::fs::copy(kept, &wip).await?;
+    match hash(&wip, algorithm).await {
+        Ok(actual) if actual.eq_ignore_ascii_case(expected) => {
+            Ok(tokio::fs::rename(&wip, path).await?)
+        }
+        result => {
+            let _ = tokio::fs::remove_file(&wip).await;
+            match result {
+                Ok(actual) => bail!("The restored file is corrupted, found {actual}"),
+                Err(e) => Err(e.into()),
+            }
+        }
+    }
+}
+
+/// Hash a file without blocking the runtime.
+///
+async fn hash(path: &Path, algorithm: Algorithm) -> std::io::Result<String> {
+    let path = path.to_owned();
+    tokio::task::spawn_blocking(move || hash_reader(&mut std::fs::File::open(path)?, algorithm))
+        .await?
+}
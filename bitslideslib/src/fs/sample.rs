@@ -0,0 +1,105 @@
+use std::{
+    hash::{BuildHasher, RandomState},
+    io::{ErrorKind, Read, Seek, SeekFrom},
+};
+
+use crate::config::CheckMode;
+
+/// Size of the blocks read back at random.
+///
+const BLOCK_SIZE: u64 = 1024 * 1024;
+
+/// Parts of a file read back to check it, as offset and length, in order.
+///
+/// A copy is checked by hashing the same parts of it and of its source, so a sample is drawn
+/// once per check and used for both.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Sample(Vec<(u64, u64)>);
+
+impl Sample {
+    /// Draw the parts of a file of the given size to read back with the given mode. None if the
+    /// whole file is to be read.
+    ///
+    pub(crate) fn new(size: u64, mode: CheckMode) -> Option<Self> {
+        let CheckMode::Sampled {
+            threshold,
+            edge,
+            blocks,
+        } = mode
+        else {
+            return None;
+        };
+        // Reading the sample would take about as long as reading the whole file
+        let sampled = edge
+            .saturating_mul(2)
+            .saturating_add(u64::from(blocks).saturating_mul(BLOCK_SIZE));
+        if size <= threshold || size <= sampled {
+            return None;
+        }
+
+        // The blocks in between may overlap, which only costs reading some bytes twice
+        let (start, end) = (edge, size - edge);
+        let state = RandomState::new();
+        let mut offsets = (0..blocks)
+            .map(|block| start + state.hash_one(block) % (end - start - BLOCK_SIZE + 1))
+            .collect::<Vec<_>>();
+        offsets.sort_unstable();
+
+        let mut parts = vec![(0, edge)];
+        parts.extend(offsets.into_iter().map(|offset| (offset, BLOCK_SIZE)));
+        parts.push((end, edge));
+        Some(Self(parts))
+    }
+
+    /// Number of bytes read back.
+    ///
+    pub(crate) fn len(&self) -> u64 {
+        self.0.iter().map(|(_, len)| len).sum()
+    }
+
+    /// Read the parts of a file, one after the other.
+    ///
+    pub(crate) fn reader<R: Read + Seek>(&self, inner: R) -> SampleReader<'_, R> {
+        SampleReader {
+            inner,
+            parts: &self.0,
+            position: 0,
+        }
+    }
+}
+
+/// Reader of the parts of a sample of a file.
+///
+/// A file shorter than the sample fails with an unexpected end of file.
+///
+pub(crate) struct SampleReader<'a, R> {
+    inner: R,
+    /// Parts left to read, the first one being read
+    parts: &'a [(u64, u64)],
+    /// Bytes of the first part already read
+    position: u64,
+}
+
+impl<R: Read + Seek> Read for SampleReader<'_, R> {
+    fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+        while let Some((offset, len)) = self.parts.first() {
+            if self.position == *len {
+                self.parts = &self.parts[1..];
+                self.position = 0;
+                continue;
+            }
+            if self.position == 0 {
+                self.inner.seek(SeekFrom::Start(*offset))?;
+            }
+            let wanted = buffer.len().min((len - self.position) as usize);
+            let read = self.inner.read(&mut buffer[..wanted])?;
+            if read == 0 && wanted > 0 {
+                return Err(ErrorKind::UnexpectedEof.into());
+            }
+            self.position += read as u64;
+            return Ok(read);
+        }
+        Ok(0)
+    }
+}
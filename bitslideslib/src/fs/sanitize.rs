@@ -0,0 +1,100 @@
+use std::{
+    ffi::{OsStr, OsString},
+    path::Path,
+};
+
+use crate::config::Sanitize;
+
+/// Characters Windows does not allow in file names, on top of the control ones (below space).
+///
+const ILLEGAL: &[char] = &['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+/// Names Windows reserves for its devices, whatever their extension.
+///
+const RESERVED: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Check if the filesystem holding a path only takes the names Windows allows.
+///
+/// That is FAT, exFAT and NTFS, as long as the OS tells them apart. The path does not need to
+/// exist, its closest existing ancestor is checked instead.
+///
+pub(crate) fn is_restrictive(path: &Path) -> bool {
+    let Some(path) = path.ancestors().find(|ancestor| ancestor.exists()) else {
+        return false;
+    };
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        use nix::sys::statfs::{statfs, FsType, MSDOS_SUPER_MAGIC};
+
+        const EXFAT_SUPER_MAGIC: FsType = FsType(0x2011_bab0);
+        const NTFS_SB_MAGIC: FsType = FsType(0x5346_544e);
+        const NTFS3_SUPER_MAGIC: FsType = FsType(0x7366_746e);
+
+        statfs(path).is_ok_and(|stats| {
+            [
+                MSDOS_SUPER_MAGIC,
+                EXFAT_SUPER_MAGIC,
+                NTFS_SB_MAGIC,
+                NTFS3_SUPER_MAGIC,
+            ]
+            .contains(&stats.filesystem_type())
+        })
+    }
+    #[cfg(target_os = "macos")]
+    {
+        nix::sys::statfs::statfs(path)
+            .is_ok_and(|stats| ["msdos", "exfat", "ntfs"].contains(&stats.filesystem_type_name()))
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos")))]
+    {
+        // Every filesystem goes through the rules of Windows on it
+        cfg!(windows)
+    }
+}
+
+/// Rewrite a file name Windows does not allow, following the given scheme.
+///
+/// Returns `None` if the name is fine as is. The illegal characters, and the dots and spaces
+/// ending the name, are replaced, and the reserved names get an underscore after their stem.
+///
+pub(crate) fn sanitize(name: &OsStr, scheme: Sanitize) -> Option<OsString> {
+    let original = name.to_string_lossy();
+
+    let replace = |c: char| match scheme {
+        Sanitize::Underscore => '_',
+        // Their full-width lookalikes, or the symbols of the control characters
+        Sanitize::Unicode => match c {
+            '\u{1}'..='\u{1f}' => char::from_u32(0x2400 + c as u32).unwrap_or('_'),
+            ' ' => '\u{2420}',
+            c => char::from_u32(c as u32 - 0x20 + 0xff00).unwrap_or('_'),
+        },
+    };
+    let mut sanitized = original
+        .chars()
+        .map(|c| match c < ' ' || ILLEGAL.contains(&c) {
+            true => replace(c),
+            false => c,
+        })
+        .collect::<String>();
+
+    let kept = sanitized.trim_end_matches(['.', ' ']).len();
+    let trailing = sanitized.split_off(kept);
+    sanitized.extend(trailing.chars().map(replace));
+
+    let stem = sanitized.split('.').next().unwrap_or_default().trim_end();
+    if RESERVED
+        .iter()
+        .any(|reserved| stem.eq_ignore_ascii_case(reserved))
+    {
+        sanitized.insert(stem.len(), '_');
+    }
+
+    match name.to_str() == Some(sanitized.as_str()) {
+        true => None,
+        false => Some(sanitized.into()),
+    }
+}
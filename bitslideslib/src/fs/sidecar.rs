@@ -0,0 +1,166 @@
+use std::{
+    collections::HashSet,
+    fs::OpenOptions,
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+};
+
+use crate::config::{parse_check, Algorithm, Sidecar};
+
+/// Held while a folder checksum file is written, as the moves in flight may share one.
+///
+static FOLDER_FILES: Mutex<()> = Mutex::new(());
+
+/// Name of the checksum file listing the files of a folder, like `SHA256SUMS`.
+///
+pub(crate) fn folder_file(algorithm: Algorithm) -> String {
+    format!("{}SUMS", algorithm.extension().to_uppercase())
+}
+
+/// Name of the checksum file of a single file, like `photo.jpg.sha256`.
+///
+pub(crate) fn file_file(name: &str, algorithm: Algorithm) -> String {
+    format!("{name}.{}", algorithm.extension())
+}
+
+/// Write the checksum of a delivered file to its checksum file, in the format of the
+/// `sha256sum`-like tools so they can check it too.
+///
+/// The line is appended to the folder checksum file, so a file delivered again is listed twice
+/// until the file is compacted, see `compact`. The files with a name that is not valid UTF-8 are
+/// left out, as those tools would not find them.
+///
+pub(crate) fn write(
+    path: &Path,
+    algorithm: Algorithm,
+    checksum: &str,
+    sidecar: Sidecar,
+) -> std::io::Result<()> {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name().and_then(|n| n.to_str())) else {
+        log::warn!("{path:?}: No checksum file for a file name that is not valid UTF-8");
+        return Ok(());
+    };
+    let line = format_line(&checksum.to_lowercase(), name);
+
+    match sidecar {
+        Sidecar::File => std::fs::write(dir.join(file_file(name, algorithm)), line),
+        Sidecar::Folder => {
+            let _guard = FOLDER_FILES.lock().unwrap_or_else(PoisonError::into_inner);
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(dir.join(folder_file(algorithm)))?
+                .write_all(line.as_bytes())
+        }
+    }
+}
+
+/// Drop the lines of a folder checksum file superseded by a later one for the same file, left by
+/// the files delivered again. The file is only rewritten if there are any.
+///
+pub(crate) fn compact(path: &Path) -> std::io::Result<()> {
+    let _guard = FOLDER_FILES.lock().unwrap_or_else(PoisonError::into_inner);
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    let lines = latest(&content);
+    if lines.len() == content.lines().count() {
+        return Ok(());
+    }
+
+    // Written aside first, so a crash never leaves the file truncated
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let partial = path.with_file_name(format!(".{name}.tmp"));
+    std::fs::write(
+        &partial,
+        lines.iter().map(|l| format!("{l}\n")).collect::<String>(),
+    )?;
+    std::fs::rename(&partial, path)
+}
+
+/// Lines of a checksum file, but those superseded by a later one for the same file.
+///
+fn latest(content: &str) -> Vec<&str> {
+    let mut listed = HashSet::new();
+    let mut lines: Vec<&str> = content
+        .lines()
+        .rev()
+        .filter(|l| parse_line(l).is_none_or(|(_, name)| listed.insert(name)))
+        .collect();
+    lines.reverse();
+    lines
+}
+
+/// Algorithm of a checksum file written by `write`, or None if the path is not one.
+///
+/// A file named after an algorithm extension is only taken as a checksum file if the file it
+/// names is next to it, as it may well be a regular file otherwise.
+///
+pub(crate) fn algorithm_of(path: &Path) -> Option<Algorithm> {
+    let name = path.file_name()?.to_str()?;
+    if let Some(prefix) = name.strip_suffix("SUMS") {
+        let algorithm = parse_check(prefix).ok()??;
+        return (folder_file(algorithm) == name).then_some(algorithm);
+    }
+    let (stem, extension) = name.rsplit_once('.')?;
+    let algorithm = parse_check(extension).ok()??;
+    (algorithm.extension() == extension && path.with_file_name(stem).is_file()).then_some(algorithm)
+}
+
+/// Read the files listed in a checksum file, along with their checksums, the latest one of those
+/// listed more than once.
+///
+pub(crate) fn read(path: &Path) -> std::io::Result<Vec<(PathBuf, String)>> {
+    let dir = path.parent().unwrap_or(Path::new(""));
+    Ok(latest(&std::fs::read_to_string(path)?)
+        .into_iter()
+        .filter_map(parse_line)
+        .map(|(checksum, name)| (dir.join(name), checksum))
+        .collect())
+}
+
+/// Format a line of a checksum file. The names with a backslash or a line break get them
+/// escaped, and the line is flagged with a leading backslash, like the `sha256sum`-like tools do.
+///
+fn format_line(checksum: &str, name: &str) -> String {
+    match name.contains(['\\', '\n']) {
+        true => format!(
+            "\\{checksum}  {}\n",
+            name.replace('\\', "\\\\").replace('\n', "\\n")
+        ),
+        false => format!("{checksum}  {name}\n"),
+    }
+}
+
+/// Parse a line of a checksum file into the checksum and the name of the file, in text or
+/// binary mode.
+///
+fn parse_line(line: &str) -> Option<(String, String)> {
+    let (escaped, line) = match line.strip_prefix('\\') {
+        Some(line) => (true, line),
+        None => (false, line),
+    };
+    let (checksum, rest) = line.split_once(' ')?;
+    let name = rest.strip_prefix([' ', '*'])?;
+    if checksum.is_empty() || name.is_empty() {
+        return None;
+    }
+    if !escaped {
+        return Some((checksum.to_owned(), name.to_owned()));
+    }
+    let mut unescaped = String::new();
+    let mut chars = name.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next()? {
+                'n' => unescaped.push('\n'),
+                other => unescaped.push(other),
+            },
+            c => unescaped.push(c),
+        }
+    }
+    Some((checksum.to_owned(), unescaped))
+}
@@ -3,11 +3,12 @@ use crate::tracer;
 use super::*;
 
 use std::fs::{self, File};
-use std::io::Write;
+use std::io::{Read, Write};
 use tempfile::{tempdir, TempDir};
 
 /// Test that a empty folder structure is completely removed, but for the root folder.
 #[tokio::test]
+#[allow(clippy::needless_borrow, clippy::needless_borrows_for_generic_args)]
 async fn test_delete_empty_folders_everything_to_ashes() {
     let temp_dir = tempdir().unwrap();
     // Prerequisite: Create directory structure
@@ -40,6 +41,7 @@ async fn test_delete_empty_folders_everything_to_ashes() {
 
 /// Test that an almost empty folder structure can't be completely removed.
 #[tokio::test]
+#[allow(clippy::needless_borrow, clippy::needless_borrows_for_generic_args)]
 async fn test_delete_empty_folders_but_cant_completely() {
     let temp_dir = tempdir().unwrap();
     // Prerequisite: Create directory structure
@@ -152,7 +154,9 @@ async fn test_sync_directory() {
 
     let (tracer, handle) = {
         let trace_path = temp_dir.path().join("test.trace");
-        let (tracer, handle) = tracer::Tracer::new(&Some(&trace_path)).await.unwrap();
+        let (tracer, handle) = tracer::Tracer::new(&Some(&trace_path), &None, Default::default())
+            .await
+            .unwrap();
         (
             tracer.annotate_author("test_sync_directory".to_owned()),
             handle.expect("Should have a handle"),
@@ -163,28 +167,112 @@ async fn test_sync_directory() {
         MoveStrategy {
             collision: CollisionPolicy::Fail,
             safe: false,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
             check: None,
+            recheck: false,
+            check_mode: CheckMode::Full,
             retries: 1,
+            jobs: 1,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
         },
         MoveStrategy {
             collision: CollisionPolicy::Overwrite,
             safe: false,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
             check: Some(Algorithm::CRC32),
+            recheck: false,
+            check_mode: CheckMode::Full,
             retries: 1,
+            jobs: 1,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
         },
         MoveStrategy {
             collision: CollisionPolicy::Skip,
             safe: true,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
             check: None,
+            recheck: false,
+            check_mode: CheckMode::Full,
             retries: 1,
+            jobs: 1,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
         },
         MoveStrategy {
             collision: CollisionPolicy::Rename {
                 suffix: "bro".to_owned(),
             },
             safe: true,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
             check: Some(Algorithm::CRC64),
+            recheck: false,
+            check_mode: CheckMode::Full,
             retries: 1,
+            jobs: 1,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
         },
     ];
 
@@ -196,9 +284,20 @@ async fn test_sync_directory() {
         writeln!(src_file, "Hello, world!").unwrap();
 
         // Perform copy
-        sync(&src_dir, &dest_dir, false, &tracer, request)
-            .await
-            .unwrap();
+        sync(
+            &src_dir,
+            &dest_dir,
+            false,
+            &tracer,
+            &Progress::default(),
+            None,
+            request,
+            &Filter::default(),
+            None,
+            None,
+        )
+        .await
+        .unwrap();
 
         // Verify destination directory structure
         let dest_file_path = dest_dir.join("test.txt");
@@ -231,7 +330,9 @@ async fn test_sync_empty_directory() {
 
     let (tracer, handle) = {
         let trace_path = temp_dir.path().join("test.trace");
-        let (tracer, handle) = tracer::Tracer::new(&Some(&trace_path)).await.unwrap();
+        let (tracer, handle) = tracer::Tracer::new(&Some(&trace_path), &None, Default::default())
+            .await
+            .unwrap();
         (
             tracer.annotate_author("test_sync_empty_directory".to_owned()),
             handle.expect("Should have a handle"),
@@ -250,12 +351,38 @@ async fn test_sync_empty_directory() {
         &dest_dir,
         false,
         &tracer,
+        &Progress::default(),
+        None,
         &MoveStrategy {
             collision: CollisionPolicy::Fail,
             safe: false,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
             check: Some(Algorithm::CRC32),
+            recheck: false,
+            check_mode: CheckMode::Full,
             retries: 5,
+            jobs: 1,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
         },
+        &Filter::default(),
+        None,
+        None,
     )
     .await
     .unwrap();
@@ -278,6 +405,85 @@ async fn test_sync_empty_directory() {
     }
 }
 
+/// Test that many files are moved when several of them are moved concurrently.
+#[tokio::test]
+async fn test_sync_concurrent_moves() {
+    // root
+    // ├── src
+    // │   ├── file_0..file_31
+    // │   └── nested
+    // │       └── file_0..file_31
+    // └── dest
+    let temp_dir = tempdir().unwrap();
+    let src_dir = temp_dir.path().join("src");
+    let dest_dir = temp_dir.path().join("dest");
+
+    // Prerequisite: Create the source files
+    fs::create_dir_all(src_dir.join("nested")).unwrap();
+    for folder in [src_dir.clone(), src_dir.join("nested")] {
+        for i in 0..32 {
+            fs::write(folder.join(format!("file_{i}")), format!("content {i}")).unwrap();
+        }
+    }
+
+    // Action: Sync, moving 4 files at a time
+    let (tracer, _) = tracer::Tracer::new(&None, &None, Default::default())
+        .await
+        .unwrap();
+    sync(
+        &src_dir,
+        &dest_dir,
+        false,
+        &tracer.annotate_author("test_sync_concurrent_moves".to_owned()),
+        &Progress::default(),
+        None,
+        &MoveStrategy {
+            collision: CollisionPolicy::Fail,
+            safe: true,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
+            check: Some(Algorithm::CRC32),
+            recheck: false,
+            check_mode: CheckMode::Full,
+            retries: 5,
+            jobs: 4,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
+        },
+        &Filter::default(),
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+
+    // Check: Every file was moved, with its content
+    for folder in [dest_dir.clone(), dest_dir.join("nested")] {
+        for i in 0..32 {
+            assert_eq!(
+                fs::read_to_string(folder.join(format!("file_{i}"))).unwrap(),
+                format!("content {i}")
+            );
+        }
+    }
+    assert!(!src_dir.join("nested").exists());
+    assert!(!src_dir.join("file_0").exists());
+}
+
 /// Test that a file belonging to a nested directory is copied from the source to the destination directory.
 #[tokio::test]
 async fn test_sync_nested_directories() {
@@ -293,7 +499,9 @@ async fn test_sync_nested_directories() {
 
     let (tracer, handle) = {
         let trace_path = temp_dir.path().join("test.trace");
-        let (tracer, handle) = tracer::Tracer::new(&Some(&trace_path)).await.unwrap();
+        let (tracer, handle) = tracer::Tracer::new(&Some(&trace_path), &None, Default::default())
+            .await
+            .unwrap();
         (
             tracer.annotate_author("test_sync_nested_directories".to_owned()),
             handle.expect("Should have a handle"),
@@ -312,12 +520,38 @@ async fn test_sync_nested_directories() {
         &dest_dir,
         false,
         &tracer,
+        &Progress::default(),
+        None,
         &MoveStrategy {
             collision: CollisionPolicy::Fail,
             safe: false,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
             check: Some(Algorithm::CRC32),
+            recheck: false,
+            check_mode: CheckMode::Full,
             retries: 5,
+            jobs: 1,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
         },
+        &Filter::default(),
+        None,
+        None,
     )
     .await
     .unwrap();
@@ -350,6 +584,210 @@ async fn test_sync_nested_directories() {
     }
 }
 
+/// Test that excluded files and folders are left untouched in the source directory.
+#[tokio::test]
+async fn test_sync_excluded() {
+    // root
+    // ├── src
+    // │   ├── keep.txt
+    // │   ├── junk.tmp
+    // │   ├── Thumbs.db
+    // │   └── node_modules
+    // │       └── lib.js
+    // └── dest
+    let temp_dir = tempdir().unwrap();
+    let src_dir = temp_dir.path().join("src");
+    let dest_dir = temp_dir.path().join("dest");
+
+    let (tracer, handle) = {
+        let trace_path = temp_dir.path().join("test.trace");
+        let (tracer, handle) = tracer::Tracer::new(&Some(&trace_path), &None, Default::default())
+            .await
+            .unwrap();
+        (
+            tracer.annotate_author("test_sync_excluded".to_owned()),
+            handle.expect("Should have a handle"),
+        )
+    };
+
+    // Prerequisite: Create the source directory structure
+    fs::create_dir_all(src_dir.join("node_modules")).unwrap();
+    for file in ["keep.txt", "junk.tmp", "Thumbs.db", "node_modules/lib.js"] {
+        write!(File::create(src_dir.join(file)).unwrap(), "{file}").unwrap();
+    }
+
+    // Action: Sync with some exclusion patterns
+    let filter = Filter::new(&[
+        "*.tmp".to_owned(),
+        "node_modules/".to_owned(),
+        "Thumbs.db".to_owned(),
+    ])
+    .unwrap();
+    sync(
+        &src_dir,
+        &dest_dir,
+        false,
+        &tracer,
+        &Progress::default(),
+        None,
+        &MoveStrategy {
+            collision: CollisionPolicy::Fail,
+            safe: false,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
+            check: None,
+            recheck: false,
+            check_mode: CheckMode::Full,
+            retries: 5,
+            jobs: 1,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
+        },
+        &filter,
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+
+    // Check: Only the non excluded file was moved
+    assert!(dest_dir.join("keep.txt").exists());
+    assert!(!src_dir.join("keep.txt").exists());
+    for file in ["junk.tmp", "Thumbs.db", "node_modules/lib.js"] {
+        assert!(src_dir.join(file).exists(), "{file} should remain");
+        assert!(!dest_dir.join(file).exists(), "{file} should not be synced");
+    }
+
+    // Clean up tracer
+    {
+        // Drop the tx channel to allow the tracer to finish
+        drop(tracer);
+        // Wait for the tracer task to finish
+        handle.await.unwrap();
+    }
+}
+
+/// Test that the entries held back by the ignore files of the folders are not synced.
+#[tokio::test]
+async fn test_sync_ignore_files() {
+    let temp_dir = tempdir().unwrap();
+    let src_dir = temp_dir.path().join("src");
+    let dest_dir = temp_dir.path().join("dest");
+
+    let (tracer, handle) = {
+        let trace_path = temp_dir.path().join("test.trace");
+        let (tracer, handle) = tracer::Tracer::new(&Some(&trace_path), &None, Default::default())
+            .await
+            .unwrap();
+        (
+            tracer.annotate_author("test_sync_ignore_files".to_owned()),
+            handle.expect("Should have a handle"),
+        )
+    };
+
+    // Prerequisite: Create the source directory structure, with an ignore file in a subfolder
+    // overriding the one of the root
+    fs::create_dir_all(src_dir.join("do-not-ship")).unwrap();
+    fs::create_dir_all(src_dir.join("sub").join("deeper")).unwrap();
+    fs::write(src_dir.join(IGNORE_FILE), "do-not-ship/\n*.bak\n").unwrap();
+    fs::write(
+        src_dir.join("sub").join(IGNORE_FILE),
+        "!*.bak\n/local.txt\n",
+    )
+    .unwrap();
+    for file in [
+        "keep.txt",
+        "old.bak",
+        "do-not-ship/notes.txt",
+        "sub/new.bak",
+        "sub/local.txt",
+        "sub/deeper/local.txt",
+    ] {
+        fs::write(src_dir.join(file), file).unwrap();
+    }
+
+    // Action: Sync without any configured exclusion
+    sync(
+        &src_dir,
+        &dest_dir,
+        false,
+        &tracer,
+        &Progress::default(),
+        None,
+        &MoveStrategy {
+            collision: CollisionPolicy::Fail,
+            safe: false,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
+            check: None,
+            recheck: false,
+            check_mode: CheckMode::Full,
+            retries: 5,
+            jobs: 1,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
+        },
+        &Filter::default(),
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+
+    // Check: The files held back by the innermost matching ignore file, and the ignore files
+    // themselves, stay
+    for file in ["keep.txt", "sub/new.bak", "sub/deeper/local.txt"] {
+        assert!(!src_dir.join(file).exists(), "{file} should be synced");
+        assert!(dest_dir.join(file).exists(), "{file} should be synced");
+    }
+    for file in [
+        IGNORE_FILE,
+        "old.bak",
+        "do-not-ship/notes.txt",
+        "sub/.slideignore",
+        "sub/local.txt",
+    ] {
+        assert!(src_dir.join(file).exists(), "{file} should remain");
+        assert!(!dest_dir.join(file).exists(), "{file} should not be synced");
+    }
+
+    // Clean up tracer
+    {
+        // Drop the tx channel to allow the tracer to finish
+        drop(tracer);
+        // Wait for the tracer task to finish
+        handle.await.unwrap();
+    }
+}
+
 /// Setup the environment for testing all move_file permutations.
 fn setup_move_file() -> (TempDir, PathBuf, PathBuf) {
     let tmp_dir = tempdir().unwrap();
@@ -369,6 +807,7 @@ fn setup_move_file() -> (TempDir, PathBuf, PathBuf) {
 
 /// Test move_file if there is a collision and the policy is set to fail.
 #[tokio::test]
+#[allow(clippy::cmp_owned)]
 async fn test_move_file_collision_fail() {
     // Prerequisite: Setup environment
     let (_tmp_dir, src_file, dst_file) = setup_move_file();
@@ -380,13 +819,42 @@ async fn test_move_file_collision_fail() {
     let result = move_file(
         &src_file,
         &dst_file,
+        None,
         &MoveStrategy {
             collision: CollisionPolicy::Fail,
             safe: false,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
             check: None,
+            recheck: false,
+            check_mode: CheckMode::Full,
             retries: 5,
+            jobs: 1,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
         },
-        checksums::hash_file,
+        &Progress::default(),
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        hash_reader,
     )
     .await;
 
@@ -412,6 +880,7 @@ async fn test_move_file_collision_fail() {
 
 /// Test move_file if there is a collision and the policy is set to skip.
 #[tokio::test]
+#[allow(clippy::cmp_owned)]
 async fn test_move_file_collision_skip() {
     // Prerequisite: Setup environment
     let (_tmp_dir, src_file, dst_file) = setup_move_file();
@@ -423,13 +892,42 @@ async fn test_move_file_collision_skip() {
     let result = move_file(
         &src_file,
         &dst_file,
+        None,
         &MoveStrategy {
             collision: CollisionPolicy::Skip,
             safe: false,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
             check: None,
+            recheck: false,
+            check_mode: CheckMode::Full,
             retries: 5,
+            jobs: 1,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
         },
-        checksums::hash_file,
+        &Progress::default(),
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        hash_reader,
     )
     .await;
 
@@ -449,6 +947,7 @@ async fn test_move_file_collision_skip() {
 
 /// Test move_file if there is a collision and the policy is set to overwrite.
 #[tokio::test]
+#[allow(clippy::cmp_owned)]
 async fn test_move_file_collision_overwrite() {
     // Prerequisite: Setup environment
     let (_tmp_dir, src_file, dst_file) = setup_move_file();
@@ -460,13 +959,42 @@ async fn test_move_file_collision_overwrite() {
     let result = move_file(
         &src_file,
         &dst_file,
+        None,
         &MoveStrategy {
             collision: CollisionPolicy::Overwrite,
             safe: false,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
             check: None,
+            recheck: false,
+            check_mode: CheckMode::Full,
             retries: 5,
+            jobs: 1,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
         },
-        checksums::hash_file,
+        &Progress::default(),
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        hash_reader,
     )
     .await;
 
@@ -480,173 +1008,3248 @@ async fn test_move_file_collision_overwrite() {
     );
 }
 
-/// Test move_file if there is a collision and the policy is set to rename.
+/// Test copy_file in place leaving the destination as it was when the source cannot be read.
 #[tokio::test]
-async fn test_move_file_collision_rename() {
-    // Prerequisite: Setup environment
+async fn test_copy_file_unreadable_source() {
+    // Prerequisite: Setup environment, with a file in the destination directory
     let (_tmp_dir, src_file, dst_file) = setup_move_file();
+    fs::write(&dst_file, "destination").unwrap();
 
-    // Prerequisite: Create a file in the destination directory
-    File::create(&dst_file).unwrap();
+    // Prerequisite: The source is gone once hashed, so it cannot be opened to be copied
+    let prehashed = Prehashed {
+        metadata: fs::metadata(&src_file).unwrap(),
+        checksum: "checksum".to_owned(),
+    };
+    fs::remove_file(&src_file).unwrap();
 
-    // Action: Move file with collision policy set to rename
-    let result = move_file(
+    // Action: Copy file over the destination, not in safe mode
+    let result = copy_file(
         &src_file,
         &dst_file,
+        None,
+        false,
+        false,
         &MoveStrategy {
-            collision: CollisionPolicy::Rename {
-                suffix: "test".to_owned(),
-            },
+            collision: CollisionPolicy::Overwrite,
             safe: false,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
             check: None,
+            recheck: false,
+            check_mode: CheckMode::Full,
             retries: 5,
+            jobs: 1,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
         },
-        checksums::hash_file,
+        &Progress::default(),
+        None,
+        None,
+        None,
+        None,
+        Some(prehashed),
+        hash_reader,
     )
     .await;
 
-    // Check: The operation succeeded and the destination file was untouched but another file was created
-    assert!(result.is_ok());
-    assert!(!src_file.exists());
-    assert!(
-        dst_file.exists() && fs::read_to_string(&dst_file).unwrap() == "".to_owned(),
-        "dst_file contents: {:?}",
-        fs::read_to_string(&dst_file).unwrap()
-    );
-    let new_dst_file = {
-        let mut new_dst = dst_file.to_path_buf();
-        new_dst.set_extension("test");
-        new_dst
-    };
-    assert!(
-        new_dst_file.exists() && fs::read_to_string(&new_dst_file).unwrap() == "source".to_owned(),
-        "new_dst_file contents: {:?}",
-        fs::read_to_string(&new_dst_file).unwrap()
-    );
+    // Check: The copy failed, and the destination file was never touched
+    assert!(result.is_err());
+    assert_eq!(fs::read_to_string(&dst_file).unwrap(), "destination");
 }
 
-/// Test move_file if the safe flag is set.
+/// Test that the overwritten files, and the removed sources if asked to, are kept in the trash.
 #[tokio::test]
-async fn test_move_file_safe() {
-    // Prerequisite: Setup environment
-    let (_tmp_dir, src_file, dst_file) = setup_move_file();
+async fn test_move_file_trash() {
+    let strategy = |safe| MoveStrategy {
+        collision: CollisionPolicy::Overwrite,
+        safe,
+        wip_suffix: "wip".to_owned(),
+        staging: false,
+        check: None,
+        recheck: false,
+        check_mode: CheckMode::Full,
+        retries: 5,
+        jobs: 1,
+        delta: false,
+        preserve_times: false,
+        preserve_permissions: false,
+        preserve_xattrs: false,
+        rate_limit: None,
+        sanitize: None,
+        use_trash: None,
+        min_age: None,
+        max_file_size: None,
+        sidecar: None,
+        reverify: None,
+        delivery_manifest: false,
+        conflict: ConflictPolicy::Report,
+        max_failures: 5,
+        min_interval: 0,
+        skip_identical: None,
+    };
+    // Folder of the only sync in the trash of a slide
+    let batch = |slide: &Path| {
+        let mut batches = fs::read_dir(slide.join(TRASH_DIR)).unwrap();
+        let batch = batches.next().unwrap().unwrap().path();
+        assert!(batches.next().is_none());
+        batch
+    };
+    let (tmp_dir, src_file, dst_file) = setup_move_file();
+    let (src_slide, dst_slide) = (tmp_dir.path().join("src"), tmp_dir.path().join("dst"));
+
+    // Prerequisite: A file in the destination directory
+    fs::write(&dst_file, "destination").unwrap();
 
-    // Action: Move file with safe mode enabled
+    // Action: Move file, keeping the displaced files
+    let trash = Trash {
+        sources: true,
+        retention: 30,
+    };
+    let bin = Bin::new(&src_slide, &dst_slide, trash);
     let result = move_file(
         &src_file,
         &dst_file,
-        &MoveStrategy {
-            collision: CollisionPolicy::Fail,
-            safe: true,
-            check: None,
-            retries: 5,
-        },
-        checksums::hash_file,
+        None,
+        &strategy(false),
+        &Progress::default(),
+        None,
+        None,
+        Some(&bin),
+        None,
+        None,
+        None,
+        hash_reader,
     )
     .await;
 
-    // FIXME: Find a way to test that the WIP file was created
+    // Check: The destination file was overwritten, and kept in the trash. The source was renamed
+    assert!(result.unwrap().is_some());
+    assert_eq!(fs::read_to_string(&dst_file).unwrap(), "source");
+    let trashed = batch(&dst_slide).join("test.txt");
+    assert_eq!(fs::read_to_string(&trashed).unwrap(), "destination");
 
-    // Check: The operation succeeded
-    assert!(result.is_ok());
-    assert!(!src_file.exists());
-    assert!(
-        dst_file.exists() && fs::read_to_string(&dst_file).unwrap() == "source".to_owned(),
-        "dst_file contents: {:?}",
-        fs::read_to_string(&dst_file).unwrap()
-    );
-}
+    // Prerequisite: The file to move again
+    fs::write(&src_file, "again").unwrap();
 
-static TEST_HASH_FILE_COUNT: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+    // Action: Copy file, in place and through a .wip file
+    for safe in [false, true] {
+        let result = copy_file(
+            &src_file,
+            &dst_file,
+            None,
+            false,
+            false,
+            &strategy(safe),
+            &Progress::default(),
+            None,
+            None,
+            Some(&bin),
+            None,
+            None,
+            hash_reader,
+        )
+        .await;
+        assert!(result.unwrap().is_some());
+        fs::write(&src_file, "again").unwrap();
+    }
 
-fn test_hash_file_count(path: &Path, _algo: Algorithm) -> String {
-    println!("Hashing: {:?}", path);
-    TEST_HASH_FILE_COUNT.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
-    "test".to_owned()
+    // Check: Every overwritten file and removed source is in the trash, under a free name
+    let batch_dir = batch(&dst_slide);
+    assert_eq!(
+        fs::read_to_string(batch_dir.join("test (1).txt")).unwrap(),
+        "source"
+    );
+    assert_eq!(
+        fs::read_to_string(batch_dir.join("test (2).txt")).unwrap(),
+        "again"
+    );
+    let batch_dir = batch(&src_slide);
+    assert_eq!(
+        fs::read_to_string(batch_dir.join("test.txt")).unwrap(),
+        "again"
+    );
+    assert_eq!(
+        fs::read_to_string(batch_dir.join("test (1).txt")).unwrap(),
+        "again"
+    );
 }
 
-/// Test move_file if checksum is requested.
+/// Test that the copies are recorded in the journal while in flight only.
 #[tokio::test]
-async fn test_move_file_check() {
-    // Prerequisite: Setup environment
-    let (_tmp_dir, src_file, dst_file) = setup_move_file();
-
-    // Prerequisite: Reset the number of calls to the hashing function
-    TEST_HASH_FILE_COUNT.store(0, std::sync::atomic::Ordering::SeqCst);
+async fn test_copy_file_journal() {
+    let (tmp_dir, src_file, dst_file) = setup_move_file();
+    let slide = tmp_dir.path().join("dst");
+    let journal = Journal::new(&slide);
 
-    // Action: Move file with safe mode enabled
-    let result = move_file(
+    // Action: Copy file, looking at the journal while the file is hashed
+    let entries = slide.join(JOURNAL_DIR);
+    let result = copy_file(
         &src_file,
         &dst_file,
+        None,
+        false,
+        false,
         &MoveStrategy {
             collision: CollisionPolicy::Fail,
-            safe: false,
+            safe: true,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
             check: Some(Algorithm::MD5),
-            retries: 0,
+            recheck: false,
+            check_mode: CheckMode::Full,
+            retries: 5,
+            jobs: 1,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
+        },
+        &Progress::default(),
+        None,
+        None,
+        None,
+        Some(&journal),
+        None,
+        move |reader: &mut dyn Read, algorithm| {
+            // Check: The copy is recorded meanwhile
+            assert_eq!(fs::read_dir(&entries).unwrap().count(), 1);
+            hash_reader(reader, algorithm)
         },
-        test_hash_file_count,
     )
     .await;
 
-    // Check: The hashing function was called
-    assert!(
-        TEST_HASH_FILE_COUNT.load(std::sync::atomic::Ordering::SeqCst) == 2,
-        "Actual: {}. Expected: 2",
-        TEST_HASH_FILE_COUNT.load(std::sync::atomic::Ordering::SeqCst)
-    );
-
-    // Check: The operation succeeded
-    assert!(result.is_ok());
+    // Check: The entry is gone along with the source
+    assert!(result.unwrap().is_some());
     assert!(!src_file.exists());
-    assert!(
-        dst_file.exists() && fs::read_to_string(&dst_file).unwrap() == "source".to_owned(),
-        "dst_file contents: {:?}",
-        fs::read_to_string(&dst_file).unwrap()
-    );
+    assert!(!slide.join(JOURNAL_DIR).exists());
 }
 
-static TEST_HASH_FILE_NASTY_RESULTS: std::sync::atomic::AtomicU32 =
-    std::sync::atomic::AtomicU32::new(0);
+/// Test the rollback of the copies interrupted by a crash.
+#[tokio::test]
+async fn test_journal_recover() {
+    let (tmp_dir, src_file, dst_file) = setup_move_file();
+    let slide = tmp_dir.path().join("dst");
+    let journal = Journal::new(&slide);
 
-fn test_hash_file_nasty_results(_path: &Path, _algo: Algorithm) -> String {
-    let result = TEST_HASH_FILE_NASTY_RESULTS
-        .load(std::sync::atomic::Ordering::SeqCst)
-        .to_string();
-    TEST_HASH_FILE_NASTY_RESULTS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
-    result
+    // Prerequisite: An interrupted copy, a complete one, and a truncated entry
+    let wip_file = wip_file(&dst_file, None, "wip").unwrap();
+    fs::write(&wip_file, "sou").unwrap();
+    journal
+        .record(&src_file, &wip_file, &dst_file)
+        .await
+        .unwrap();
+    let done = slide.join("done.txt");
+    fs::write(&done, "done").unwrap();
+    journal
+        .record(&tmp_dir.path().join("src").join("done.txt"), &done, &done)
+        .await
+        .unwrap();
+    fs::write(slide.join(JOURNAL_DIR).join("truncated.json"), "{\"src\":").unwrap();
+
+    // Action: Recover the journal
+    let rolled_back = journal::recover(&slide).await.unwrap();
+
+    // Check: Only the .wip file of the interrupted copy was removed, along with the journal
+    assert_eq!(rolled_back, 1);
+    assert!(!wip_file.exists());
+    assert!(src_file.exists());
+    assert_eq!(fs::read_to_string(&done).unwrap(), "done");
+    assert!(!slide.join(JOURNAL_DIR).exists());
 }
 
-/// Test move_file if the operation fails and retries are requested.
+/// Test that the trash keeps the files for their retention only.
 #[tokio::test]
-async fn test_move_file_check_failed() {
-    // Prerequisite: Setup environment
+async fn test_purge_trash() {
+    let temp_dir = tempdir().unwrap();
+    let trash = temp_dir.path().join(TRASH_DIR);
+
+    // Prerequisite: The files of an old sync, of a recent one, and of someone else
+    let recent = chrono::Local::now().format("%Y%m%d-%H%M%S").to_string();
+    for batch in ["20000101-000000", recent.as_str(), "mine"] {
+        fs::create_dir_all(trash.join(batch)).unwrap();
+        File::create(trash.join(batch).join("file.txt")).unwrap();
+    }
+
+    // Action: Purge the trash, keeping the files forever
+    let purged = trash::purge(temp_dir.path(), 0).await.unwrap();
+
+    // Check: Nothing was deleted
+    assert_eq!(purged, 0);
+    assert!(trash.join("20000101-000000").exists());
+
+    // Action: Purge the trash
+    let purged = trash::purge(temp_dir.path(), 30).await.unwrap();
+
+    // Check: Only the files of the old sync were deleted
+    assert_eq!(purged, 1);
+    assert!(!trash.join("20000101-000000").exists());
+    assert!(trash.join(&recent).join("file.txt").exists());
+    assert!(trash.join("mine").join("file.txt").exists());
+}
+
+/// Test move_file if there is a collision and the policy is set to keep the newer file.
+#[tokio::test]
+async fn test_move_file_collision_keep_newer() {
+    let strategy = |remove_older| MoveStrategy {
+        collision: CollisionPolicy::KeepNewer { remove_older },
+        safe: false,
+        wip_suffix: "wip".to_owned(),
+        staging: false,
+        check: None,
+        recheck: false,
+        check_mode: CheckMode::Full,
+        retries: 5,
+        jobs: 1,
+        delta: false,
+        preserve_times: false,
+        preserve_permissions: false,
+        preserve_xattrs: false,
+        rate_limit: None,
+        sanitize: None,
+        use_trash: None,
+        min_age: None,
+        max_file_size: None,
+        sidecar: None,
+        reverify: None,
+        delivery_manifest: false,
+        conflict: ConflictPolicy::Report,
+        max_failures: 5,
+        min_interval: 0,
+        skip_identical: None,
+    };
     let (_tmp_dir, src_file, dst_file) = setup_move_file();
+    let old = filetime::FileTime::from_unix_time(1_000_000_000, 0);
+    let new = filetime::FileTime::from_unix_time(1_500_000_000, 0);
 
-    // Prerequisite: Reset the number of calls to the hashing function
-    TEST_HASH_FILE_NASTY_RESULTS.store(0, std::sync::atomic::Ordering::SeqCst);
+    // Prerequisite: A newer file in the destination directory
+    fs::write(&dst_file, "destination").unwrap();
+    filetime::set_file_mtime(&src_file, old).unwrap();
+    filetime::set_file_mtime(&dst_file, new).unwrap();
 
-    // Action: Move file with safe mode enabled
+    // Action: Move file, leaving the older ones in place
     let result = move_file(
         &src_file,
         &dst_file,
-        &MoveStrategy {
-            collision: CollisionPolicy::Fail,
-            safe: false,
-            check: Some(Algorithm::MD5),
-            retries: 5,
-        },
-        test_hash_file_nasty_results,
+        None,
+        &strategy(false),
+        &Progress::default(),
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        hash_reader,
     )
     .await;
 
-    // Check: The operation failed and nothing changed
-    assert!(result.is_err() && result.unwrap_err().to_string().contains("maximum retries"));
+    // Check: Nothing changed
+    assert!(result.unwrap().is_none());
+    assert_eq!(fs::read_to_string(&src_file).unwrap(), "source");
+    assert_eq!(fs::read_to_string(&dst_file).unwrap(), "destination");
+
+    // Action: Move file, removing the older ones
+    let result = move_file(
+        &src_file,
+        &dst_file,
+        None,
+        &strategy(true),
+        &Progress::default(),
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        hash_reader,
+    )
+    .await;
+
+    // Check: The source file was removed, the destination file kept
+    assert!(result.unwrap().is_none());
+    assert!(!src_file.exists());
+    assert_eq!(fs::read_to_string(&dst_file).unwrap(), "destination");
+
+    // Prerequisite: A file as recent in the source directory
+    fs::write(&src_file, "source").unwrap();
+    filetime::set_file_mtime(&src_file, new).unwrap();
+
+    // Action: Move file, removing the older ones
+    let result = move_file(
+        &src_file,
+        &dst_file,
+        None,
+        &strategy(true),
+        &Progress::default(),
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        hash_reader,
+    )
+    .await;
+
+    // Check: Neither is older, so both are kept
+    assert!(result.unwrap().is_none());
+    assert_eq!(fs::read_to_string(&src_file).unwrap(), "source");
+    assert_eq!(fs::read_to_string(&dst_file).unwrap(), "destination");
+
+    // Prerequisite: A newer file in the source directory
+    fs::write(&src_file, "source").unwrap();
+    filetime::set_file_mtime(&src_file, new).unwrap();
+    filetime::set_file_mtime(&dst_file, old).unwrap();
+
+    // Action: Move file
+    let result = move_file(
+        &src_file,
+        &dst_file,
+        None,
+        &strategy(false),
+        &Progress::default(),
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        hash_reader,
+    )
+    .await;
+
+    // Check: The destination file was overwritten
+    assert!(result.unwrap().is_some());
+    assert!(!src_file.exists());
+    assert_eq!(fs::read_to_string(&dst_file).unwrap(), "source");
+}
+
+/// Test move_file removing the files already delivered, whatever the collision policy.
+#[tokio::test]
+async fn test_move_file_skip_identical() {
+    let strategy = |skip_identical| MoveStrategy {
+        collision: CollisionPolicy::Fail,
+        conflict: ConflictPolicy::Report,
+        max_failures: 5,
+        min_interval: 0,
+        skip_identical,
+        safe: false,
+        wip_suffix: "wip".to_owned(),
+        staging: false,
+        check: None,
+        recheck: false,
+        check_mode: CheckMode::Full,
+        retries: 5,
+        jobs: 1,
+        delta: false,
+        preserve_times: false,
+        preserve_permissions: false,
+        preserve_xattrs: false,
+        rate_limit: None,
+        sanitize: None,
+        use_trash: None,
+        min_age: None,
+        max_file_size: None,
+        sidecar: None,
+        reverify: None,
+        delivery_manifest: false,
+    };
+    let (_tmp_dir, src_file, dst_file) = setup_move_file();
+    let time = filetime::FileTime::from_unix_time(1_000_000_000, 0);
+
+    // Prerequisite: A file of the same size, but different contents and time, at the destination
+    fs::write(&dst_file, "SOURCE").unwrap();
+    filetime::set_file_mtime(&dst_file, time).unwrap();
+
+    // Check: It is not identical by any comparison, so the collision policy applies
+    for identical in [None, Some(Identical::Quick), Some(Identical::Contents)] {
+        let result = move_file(
+            &src_file,
+            &dst_file,
+            None,
+            &strategy(identical),
+            &Progress::default(),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            hash_reader,
+        )
+        .await;
+        assert!(result.is_err());
+        assert!(src_file.exists());
+    }
+
+    // Prerequisite: Same modification time
+    filetime::set_file_mtime(&src_file, time).unwrap();
+
+    // Check: It is identical by a quick comparison only
+    assert!(is_identical(&src_file, &dst_file, Identical::Quick).unwrap());
+    assert!(!is_identical(&src_file, &dst_file, Identical::Contents).unwrap());
+
+    // Prerequisite: Same contents
+    fs::write(&dst_file, "source").unwrap();
+
+    // Action: Move file comparing the contents
+    let result = move_file(
+        &src_file,
+        &dst_file,
+        None,
+        &strategy(Some(Identical::Contents)),
+        &Progress::default(),
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        hash_reader,
+    )
+    .await;
+
+    // Check: The source file was removed, and counted as delivered
+    let delivery = result.unwrap().unwrap();
+    assert_eq!(delivery.path, dst_file);
+    assert_eq!(delivery.size, 6);
+    assert!(!src_file.exists());
+    assert_eq!(fs::read_to_string(&dst_file).unwrap(), "source");
+}
+
+/// Test move_file taking the checksum of the file already delivered from the cache.
+#[tokio::test]
+async fn test_move_file_skip_identical_cached() {
+    let strategy = MoveStrategy {
+        collision: CollisionPolicy::Fail,
+        conflict: ConflictPolicy::Report,
+        max_failures: 5,
+        min_interval: 0,
+        skip_identical: Some(Identical::Contents),
+        safe: false,
+        wip_suffix: "wip".to_owned(),
+        staging: false,
+        check: Some(Algorithm::SHA2256),
+        recheck: false,
+        check_mode: CheckMode::Full,
+        retries: 5,
+        jobs: 1,
+        delta: false,
+        preserve_times: false,
+        preserve_permissions: false,
+        preserve_xattrs: false,
+        rate_limit: None,
+        sanitize: None,
+        use_trash: None,
+        min_age: None,
+        max_file_size: None,
+        sidecar: None,
+        reverify: None,
+        delivery_manifest: false,
+    };
+    let (tmp_dir, src_file, dst_file) = setup_move_file();
+    let calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let counted = calls.clone();
+    let hash = move |reader: &mut dyn Read, algorithm| {
+        counted.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        hash_reader(reader, algorithm)
+    };
+
+    // Prerequisite: The same file at the destination, with its checksum cached
+    fs::copy(&src_file, &dst_file).unwrap();
+    let checksum = hash_reader(&mut File::open(&dst_file).unwrap(), Algorithm::SHA2256).unwrap();
+    let cache = ChecksumCache::load(tmp_dir.path());
+    cache.insert(&dst_file, Algorithm::SHA2256, &checksum);
+
+    // Action: Move file comparing the contents
+    let result = move_file(
+        &src_file,
+        &dst_file,
+        None,
+        &strategy,
+        &Progress::default(),
+        None,
+        None,
+        None,
+        None,
+        Some(&cache),
+        None,
+        hash,
+    )
+    .await;
+
+    // Check: Only the source file was hashed, and it was taken as delivered with its checksum
+    let delivery = result.unwrap().unwrap();
+    assert_eq!(delivery.checksum, Some((Algorithm::SHA2256, checksum)));
+    assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+    assert!(!src_file.exists());
+}
+
+/// Test the delta copy of a file over an older version of it.
+#[tokio::test]
+async fn test_copy_file_delta() {
+    // Prerequisite: Setup environment
+    let (_tmp_dir, src_file, dst_file) = setup_move_file();
+
+    // Prerequisite: A destination file of a few blocks, and a source file with some bytes
+    // inserted at the beginning and changed in the middle
+    let mut seed = 42u32;
+    let old = (0..1024 * 1024)
+        .map(|_| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (seed >> 16) as u8
+        })
+        .collect::<Vec<_>>();
+    let mut new = b"inserted".to_vec();
+    new.extend_from_slice(&old);
+    new[500_000..500_100].fill(0);
+    fs::write(&dst_file, &old).unwrap();
+    fs::write(&src_file, &new).unwrap();
+
+    // Action: Copy the source over the destination reusing its blocks
+    let wip = dst_file.with_extension("wip");
+    let delta = delta::copy(&src_file, &dst_file, &wip).unwrap();
+
+    // Check: The copy has the new contents, most of them taken from the old ones
+    assert_eq!(fs::read(&wip).unwrap(), new);
+    assert_eq!(delta.size, new.len() as u64);
     assert!(
-        src_file.exists() && fs::read_to_string(&src_file).unwrap() == "source".to_owned(),
-        "src_file contents: {:?}",
-        fs::read_to_string(&src_file).unwrap()
+        delta.reused >= old.len() as u64 - 3 * 64 * 1024,
+        "reused: {}",
+        delta.reused
     );
-    assert!(!dst_file.exists());
+    fs::remove_file(&wip).unwrap();
+
+    // Action: Copy file over the destination in delta mode, as done across filesystems
+    let result = copy_file(
+        &src_file,
+        &dst_file,
+        None,
+        true,
+        false,
+        &MoveStrategy {
+            collision: CollisionPolicy::Overwrite,
+            safe: false,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
+            check: Some(Algorithm::BLAKE),
+            recheck: false,
+            check_mode: CheckMode::Full,
+            retries: 0,
+            jobs: 1,
+            delta: true,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
+        },
+        &Progress::default(),
+        None,
+        None,
+        None,
+        None,
+        None,
+        hash_reader,
+    )
+    .await;
+
+    // Check: The operation succeeded and the destination file was overwritten
+    assert_eq!(result.unwrap().unwrap().size, new.len() as u64);
+    assert!(!src_file.exists());
+    assert_eq!(fs::read(&dst_file).unwrap(), new);
+    assert_eq!(fs::read_dir(dst_file.parent().unwrap()).unwrap().count(), 1);
 }
 
-//TODO: Check that after moving a file inside a folder and leaving the folder empty, the folder is removed
+/// Test move_file within the same filesystem, where the file is just renamed.
+#[tokio::test]
+async fn test_move_file_same_device() {
+    // Prerequisite: Setup environment
+    let (_tmp_dir, src_file, dst_file) = setup_move_file();
+    let hashed = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let read = hashed.clone();
+    let hash = move |reader: &mut dyn Read, algorithm| {
+        let mut content = String::new();
+        reader.read_to_string(&mut content)?;
+        read.lock().unwrap().push(content.clone());
+        hash_reader(&mut content.as_bytes(), algorithm)
+    };
+
+    // Action: Move file with checksum requested
+    let result = move_file(
+        &src_file,
+        &dst_file,
+        None,
+        &MoveStrategy {
+            collision: CollisionPolicy::Fail,
+            safe: true,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
+            check: Some(Algorithm::MD5),
+            recheck: false,
+            check_mode: CheckMode::Full,
+            retries: 0,
+            jobs: 1,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
+        },
+        &Progress::default(),
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        hash.clone(),
+    )
+    .await;
+
+    // Check: The file was renamed, without reading it for a checksum nobody asked for
+    let delivery = result.unwrap().unwrap();
+    assert_eq!(delivery.path, dst_file);
+    assert_eq!(delivery.size, 6);
+    assert!(hashed.lock().unwrap().is_empty());
+    assert_eq!(delivery.checksum, None);
+    assert!(!delivery.read_back);
+    assert!(!src_file.exists());
+
+    // Action: Move another file, with a checksum file asked for
+    fs::write(&src_file, "source").unwrap();
+    let dst_file = dst_file.with_file_name("sidecar.txt");
+    let result = move_file(
+        &src_file,
+        &dst_file,
+        None,
+        &MoveStrategy {
+            collision: CollisionPolicy::Fail,
+            safe: true,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
+            check: Some(Algorithm::MD5),
+            recheck: false,
+            check_mode: CheckMode::Full,
+            retries: 0,
+            jobs: 1,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: Some(Sidecar::File),
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
+        },
+        &Progress::default(),
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        hash,
+    )
+    .await;
+
+    // Check: The file was renamed, then hashed once delivered for its checksum
+    let delivery = result.unwrap().unwrap();
+    assert_eq!(delivery.path, dst_file);
+    assert_eq!(*hashed.lock().unwrap(), vec!["source".to_owned()]);
+    assert_eq!(
+        delivery.checksum,
+        Some((
+            Algorithm::MD5,
+            hash_reader(&mut "source".as_bytes(), Algorithm::MD5).unwrap()
+        ))
+    );
+    assert!(delivery.read_back);
+    assert!(!src_file.exists());
+    assert_eq!(fs::read_to_string(&dst_file).unwrap(), "source");
+}
+
+/// Test clone_file, whether the filesystem supports cloning or not.
+#[tokio::test]
+async fn test_clone_file() {
+    // Prerequisite: Setup environment
+    let (_tmp_dir, src_file, dst_file) = setup_move_file();
+
+    // Action: Clone the file
+    let cloned = clone_file(&src_file, &dst_file).await;
+
+    // Check: Either the clone has the same contents, or nothing is left behind for the copy
+    match cloned {
+        Some(size) => {
+            assert_eq!(size, 6);
+            assert_eq!(fs::read_to_string(&dst_file).unwrap(), "source");
+        }
+        None => assert!(!dst_file.exists()),
+    }
+    assert!(src_file.exists());
+
+    // Action: Clone the file over an existing one
+    fs::write(&dst_file, "destination").unwrap();
+    let cloned = clone_file(&src_file, &dst_file).await;
+
+    // Check: The existing file is left untouched
+    assert_eq!(cloned, None);
+    assert_eq!(fs::read_to_string(&dst_file).unwrap(), "destination");
+}
+
+/// Test copy_file allowed to clone the file, as done once a rename fails.
+#[tokio::test]
+async fn test_copy_file_clone() {
+    // Prerequisite: Setup environment
+    let (_tmp_dir, src_file, dst_file) = setup_move_file();
+
+    // Action: Copy file checked, cloning it if the filesystem supports it
+    let delivery = copy_file(
+        &src_file,
+        &dst_file,
+        None,
+        false,
+        true,
+        &MoveStrategy {
+            collision: CollisionPolicy::Fail,
+            safe: true,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
+            check: Some(Algorithm::MD5),
+            recheck: false,
+            check_mode: CheckMode::Full,
+            retries: 0,
+            jobs: 1,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
+        },
+        &Progress::default(),
+        None,
+        None,
+        None,
+        None,
+        None,
+        hash_reader,
+    )
+    .await
+    .unwrap()
+    .unwrap();
+
+    // Check: Cloned or copied, the file was delivered with the checksum of its source
+    assert!(!src_file.exists());
+    assert_eq!(fs::read_to_string(&dst_file).unwrap(), "source");
+    assert_eq!(
+        delivery.checksum,
+        Some((
+            Algorithm::MD5,
+            hash_reader(&mut "source".as_bytes(), Algorithm::MD5).unwrap()
+        ))
+    );
+}
+
+/// Test move_file if there is a collision and the policy is set to rename.
+#[tokio::test]
+#[allow(clippy::cmp_owned)]
+async fn test_move_file_collision_rename() {
+    // Prerequisite: Setup environment
+    let (_tmp_dir, src_file, dst_file) = setup_move_file();
+
+    // Prerequisite: Create a file in the destination directory
+    File::create(&dst_file).unwrap();
+
+    // Action: Move file with collision policy set to rename
+    let result = move_file(
+        &src_file,
+        &dst_file,
+        None,
+        &MoveStrategy {
+            collision: CollisionPolicy::Rename {
+                suffix: "test".to_owned(),
+            },
+            safe: false,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
+            check: None,
+            recheck: false,
+            check_mode: CheckMode::Full,
+            retries: 5,
+            jobs: 1,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
+        },
+        &Progress::default(),
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        hash_reader,
+    )
+    .await;
+
+    // Check: The operation succeeded and the destination file was untouched but another file was created
+    assert!(result.is_ok());
+    assert!(!src_file.exists());
+    assert!(
+        dst_file.exists() && fs::read_to_string(&dst_file).unwrap() == "".to_owned(),
+        "dst_file contents: {:?}",
+        fs::read_to_string(&dst_file).unwrap()
+    );
+    let new_dst_file = {
+        let mut new_dst = dst_file.to_path_buf();
+        new_dst.set_extension("test");
+        new_dst
+    };
+    assert!(
+        new_dst_file.exists() && fs::read_to_string(&new_dst_file).unwrap() == "source".to_owned(),
+        "new_dst_file contents: {:?}",
+        fs::read_to_string(&new_dst_file).unwrap()
+    );
+}
+
+/// Test the names given to the files renamed on collision.
+#[test]
+fn test_renamed() {
+    let temp_dir = tempdir().unwrap();
+    let dst_file = temp_dir.path().join("photo.jpg");
+    let name = |path: PathBuf| path.file_name().unwrap().to_string_lossy().to_string();
+
+    // Check: A plain suffix replaces the extension, and gets a counter once taken
+    assert_eq!(name(renamed(&dst_file, "old")), "photo.old");
+    File::create(temp_dir.path().join("photo.old")).unwrap();
+    assert_eq!(name(renamed(&dst_file, "old")), "photo (2).old");
+
+    // Check: The counter of a template goes up until a free name is found
+    let template = "{stem} ({n}).{ext}";
+    assert_eq!(name(renamed(&dst_file, template)), "photo (1).jpg");
+    File::create(temp_dir.path().join("photo (1).jpg")).unwrap();
+    File::create(temp_dir.path().join("photo (2).jpg")).unwrap();
+    assert_eq!(name(renamed(&dst_file, template)), "photo (3).jpg");
+
+    // Check: The timestamp is the local time
+    let renamed_file = name(renamed(&dst_file, "{stem}-{timestamp}.{ext}"));
+    let today = chrono::Local::now().format("%Y%m%d").to_string();
+    assert!(
+        renamed_file.starts_with(&format!("photo-{today}-")) && renamed_file.ends_with(".jpg"),
+        "{renamed_file}"
+    );
+
+    // Check: Files without extension lose the dot before it
+    let dst_file = temp_dir.path().join("README");
+    assert_eq!(name(renamed(&dst_file, template)), "README (1)");
+}
+
+/// Test the rewriting of the file names Windows does not allow.
+#[test]
+fn test_sanitize() {
+    let underscore = |name: &str| {
+        sanitize::sanitize(OsStr::new(name), Sanitize::Underscore)
+            .map(|name| name.to_string_lossy().to_string())
+    };
+    let unicode = |name: &str| {
+        sanitize::sanitize(OsStr::new(name), Sanitize::Unicode)
+            .map(|name| name.to_string_lossy().to_string())
+    };
+
+    // Check: The names allowed are left as they are
+    assert_eq!(underscore("photo.jpg"), None);
+    assert_eq!(underscore(".hidden"), None);
+    assert_eq!(underscore("CONSOLE.txt"), None);
+
+    // Check: The illegal characters, and the dots and spaces ending the name, are replaced
+    assert_eq!(underscore("a:b?.txt").as_deref(), Some("a_b_.txt"));
+    assert_eq!(underscore("tab\tname").as_deref(), Some("tab_name"));
+    assert_eq!(underscore("name. ").as_deref(), Some("name__"));
+    assert_eq!(
+        unicode("a:b?.txt").as_deref(),
+        Some("a\u{ff1a}b\u{ff1f}.txt")
+    );
+    assert_eq!(unicode("name. ").as_deref(), Some("name\u{ff0e}\u{2420}"));
+
+    // Check: The reserved names get an underscore after their stem
+    assert_eq!(underscore("CON.txt").as_deref(), Some("CON_.txt"));
+    assert_eq!(underscore("lpt1").as_deref(), Some("lpt1_"));
+    assert_eq!(unicode("nul.tar.gz").as_deref(), Some("nul_.tar.gz"));
+}
+
+/// Test that the usual filesystems of the temporary folders are not taken as restrictive.
+#[cfg(target_os = "linux")]
+#[test]
+fn test_is_restrictive() {
+    let temp_dir = tempdir().unwrap();
+
+    // Check: Both existing and missing paths are checked
+    assert!(!sanitize::is_restrictive(temp_dir.path()));
+    assert!(!sanitize::is_restrictive(
+        &temp_dir.path().join("a").join("b")
+    ));
+}
+
+/// Test move_file if the safe flag is set, copying the file as across filesystems.
+#[tokio::test]
+#[allow(clippy::cmp_owned)]
+async fn test_move_file_safe() {
+    // Prerequisite: Setup environment
+    let (_tmp_dir, src_file, dst_file) = setup_move_file();
+
+    // Action: Copy file with safe mode enabled
+    let result = copy_file(
+        &src_file,
+        &dst_file,
+        None,
+        false,
+        false,
+        &MoveStrategy {
+            collision: CollisionPolicy::Fail,
+            safe: true,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
+            check: None,
+            recheck: false,
+            check_mode: CheckMode::Full,
+            retries: 5,
+            jobs: 1,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
+        },
+        &Progress::default(),
+        None,
+        None,
+        None,
+        None,
+        None,
+        hash_reader,
+    )
+    .await;
+
+    // FIXME: Find a way to test that the WIP file was created
+
+    // Check: The operation succeeded
+    assert!(result.is_ok());
+    assert!(!src_file.exists());
+    assert!(
+        dst_file.exists() && fs::read_to_string(&dst_file).unwrap() == "source".to_owned(),
+        "dst_file contents: {:?}",
+        fs::read_to_string(&dst_file).unwrap()
+    );
+}
+
+/// Test copy_file telling apart the checksums of the copies read back from the ones of the sources.
+#[tokio::test]
+async fn test_copy_file_read_back() {
+    for recheck in [false, true] {
+        // Prerequisite: Setup environment
+        let (_tmp_dir, src_file, dst_file) = setup_move_file();
+
+        // Action: Copy file checked, reading the copy back or not
+        let delivery = copy_file(
+            &src_file,
+            &dst_file,
+            None,
+            false,
+            false,
+            &MoveStrategy {
+                collision: CollisionPolicy::Fail,
+                safe: true,
+                wip_suffix: "wip".to_owned(),
+                staging: false,
+                check: Some(Algorithm::MD5),
+                recheck,
+                check_mode: CheckMode::Full,
+                retries: 5,
+                jobs: 1,
+                delta: false,
+                preserve_times: false,
+                preserve_permissions: false,
+                preserve_xattrs: false,
+                rate_limit: None,
+                sanitize: None,
+                use_trash: None,
+                min_age: None,
+                max_file_size: None,
+                sidecar: None,
+                reverify: None,
+                delivery_manifest: false,
+                conflict: ConflictPolicy::Report,
+                max_failures: 5,
+                min_interval: 0,
+                skip_identical: None,
+            },
+            &Progress::default(),
+            None,
+            None,
+            None,
+            None,
+            None,
+            hash_reader,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        // Check: Only the checksum of the copy read back describes the delivered file
+        let checksum = hash_reader(&mut File::open(&dst_file).unwrap(), Algorithm::MD5).unwrap();
+        assert_eq!(delivery.checksum, Some((Algorithm::MD5, checksum)));
+        assert_eq!(delivery.read_back, recheck);
+    }
+}
+
+/// Test copy_file keeping the times of the original file.
+#[tokio::test]
+async fn test_copy_file_preserve_times() {
+    // Prerequisite: Setup environment, with a file last modified long ago
+    let (_tmp_dir, src_file, dst_file) = setup_move_file();
+    let atime = filetime::FileTime::from_unix_time(1_000_000_000, 0);
+    let mtime = filetime::FileTime::from_unix_time(978_307_200, 500);
+    filetime::set_file_times(&src_file, atime, mtime).unwrap();
+
+    // Action: Copy file with the times preserved, and checked so it is read meanwhile
+    let result = copy_file(
+        &src_file,
+        &dst_file,
+        None,
+        false,
+        false,
+        &MoveStrategy {
+            collision: CollisionPolicy::Fail,
+            safe: true,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
+            check: Some(Algorithm::MD5),
+            recheck: false,
+            check_mode: CheckMode::Full,
+            retries: 0,
+            jobs: 1,
+            delta: false,
+            preserve_times: true,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
+        },
+        &Progress::default(),
+        None,
+        None,
+        None,
+        None,
+        None,
+        hash_reader,
+    )
+    .await;
+
+    // Check: The copy has the times of the original file
+    assert!(result.is_ok(), "{result:?}");
+    let metadata = fs::metadata(&dst_file).unwrap();
+    assert_eq!(
+        filetime::FileTime::from_last_modification_time(&metadata),
+        mtime
+    );
+    assert_eq!(filetime::FileTime::from_last_access_time(&metadata), atime);
+}
+
+/// Test copy_file keeping the permissions of the original file.
+#[cfg(unix)]
+#[tokio::test]
+async fn test_copy_file_preserve_permissions() {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+    // Prerequisite: Setup environment, with an executable file
+    let (_tmp_dir, src_file, dst_file) = setup_move_file();
+    fs::set_permissions(&src_file, fs::Permissions::from_mode(0o751)).unwrap();
+    let src_metadata = fs::metadata(&src_file).unwrap();
+
+    // Action: Copy file with the permissions preserved
+    let result = copy_file(
+        &src_file,
+        &dst_file,
+        None,
+        false,
+        false,
+        &MoveStrategy {
+            collision: CollisionPolicy::Fail,
+            safe: true,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
+            check: None,
+            recheck: false,
+            check_mode: CheckMode::Full,
+            retries: 0,
+            jobs: 1,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: true,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
+        },
+        &Progress::default(),
+        None,
+        None,
+        None,
+        None,
+        None,
+        hash_reader,
+    )
+    .await;
+
+    // Check: The copy has the mode and the owner of the original file
+    assert!(result.is_ok(), "{result:?}");
+    let metadata = fs::metadata(&dst_file).unwrap();
+    assert_eq!(metadata.permissions().mode() & 0o7777, 0o751);
+    assert_eq!(
+        (metadata.uid(), metadata.gid()),
+        (src_metadata.uid(), src_metadata.gid())
+    );
+}
+
+/// Test copy_file keeping the extended attributes of the original file.
+#[cfg(unix)]
+#[tokio::test]
+async fn test_copy_file_preserve_xattrs() {
+    // Prerequisite: Setup environment, with a tagged file
+    let (_tmp_dir, src_file, dst_file) = setup_move_file();
+    if let Err(e) = xattr::set(&src_file, "user.bitslides.test", b"tagged") {
+        eprintln!("Extended attributes not supported here, skipping: {e}");
+        return;
+    }
+
+    // Action: Copy file with the extended attributes preserved
+    let result = copy_file(
+        &src_file,
+        &dst_file,
+        None,
+        false,
+        false,
+        &MoveStrategy {
+            collision: CollisionPolicy::Fail,
+            safe: true,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
+            check: None,
+            recheck: false,
+            check_mode: CheckMode::Full,
+            retries: 0,
+            jobs: 1,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: true,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
+        },
+        &Progress::default(),
+        None,
+        None,
+        None,
+        None,
+        None,
+        hash_reader,
+    )
+    .await;
+
+    // Check: The copy has the attribute of the original file
+    assert!(result.is_ok(), "{result:?}");
+    assert_eq!(
+        xattr::get(&dst_file, "user.bitslides.test").unwrap(),
+        Some(b"tagged".to_vec())
+    );
+}
+
+/// Test copy_file with a sparse file, which stays sparse.
+#[tokio::test]
+async fn test_copy_file_sparse() {
+    // Prerequisite: Setup environment, with a file mostly made of holes
+    let (_tmp_dir, src_file, dst_file) = setup_move_file();
+    {
+        use std::io::Seek;
+        let mut file = File::create(&src_file).unwrap();
+        file.write_all(b"head").unwrap();
+        file.set_len(4 * 1024 * 1024).unwrap();
+        file.seek(std::io::SeekFrom::Start(2 * 1024 * 1024 + 100))
+            .unwrap();
+        file.write_all(b"middle").unwrap();
+        file.set_len(8 * 1024 * 1024).unwrap();
+    }
+    let content = fs::read(&src_file).unwrap();
+
+    // Action: Copy file
+    let result = copy_file(
+        &src_file,
+        &dst_file,
+        None,
+        false,
+        false,
+        &MoveStrategy {
+            collision: CollisionPolicy::Fail,
+            safe: true,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
+            check: Some(Algorithm::MD5),
+            recheck: false,
+            check_mode: CheckMode::Full,
+            retries: 0,
+            jobs: 1,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
+        },
+        &Progress::default(),
+        None,
+        None,
+        None,
+        None,
+        None,
+        hash_reader,
+    )
+    .await;
+
+    // Check: The copy has the same contents, trailing hole included
+    assert_eq!(result.unwrap().unwrap().size, 8 * 1024 * 1024);
+    assert_eq!(fs::read(&dst_file).unwrap(), content);
+
+    // Check: The holes were not written, where the filesystem supports them
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let allocated = fs::metadata(&dst_file).unwrap().blocks() * 512;
+        assert!(allocated < 1024 * 1024, "allocated: {allocated}");
+    }
+}
+
+/// Test the names of the .wip files.
+#[test]
+fn test_wip_file() {
+    let dst_file = Path::new("dst").join("photos").join("photo.jpg");
+    let staging = Path::new("dst").join(STAGING_DIR);
+
+    // Check: The .wip file is a hidden sidecar keeping the full name
+    assert_eq!(
+        wip_file(&dst_file, None, "wip").unwrap(),
+        Path::new("dst").join("photos").join(".photo.jpg.wip")
+    );
+    assert_eq!(
+        wip_file(&dst_file, None, "part").unwrap(),
+        Path::new("dst").join("photos").join(".photo.jpg.part")
+    );
+
+    // Check: In the staging folder, files with the same name going to different folders differ
+    let staged = wip_file(&dst_file, Some(&staging), "wip").unwrap();
+    let other = wip_file(
+        &Path::new("dst").join("other").join("photo.jpg"),
+        Some(&staging),
+        "wip",
+    )
+    .unwrap();
+    assert_eq!(staged.parent(), Some(staging.as_path()));
+    let name = staged.file_name().unwrap().to_string_lossy().into_owned();
+    assert!(
+        name.starts_with(".photo.jpg.") && name.ends_with(".wip"),
+        "{name}"
+    );
+    assert_ne!(staged, other);
+
+    // Check: Suffixes leading out of the folder are rejected
+    assert!(wip_file(&dst_file, None, "").is_err());
+    assert!(wip_file(&dst_file, None, "wip/../x").is_err());
+}
+
+/// Test telling the files written along the syncs from the ones to deliver.
+#[test]
+fn test_is_own() {
+    let slide = Path::new("src").join("slides").join("dst");
+    let suffixes = ["wip".to_owned(), "part".to_owned()];
+
+    // Check: The internal folders and files of the slide, and what they hold, are own
+    assert!(is_own(&slide, &slide.join(STAGING_DIR), &suffixes));
+    assert!(is_own(
+        &slide,
+        &slide.join(TRASH_DIR).join("photos").join("photo.jpg"),
+        &suffixes
+    ));
+    assert!(is_own(&slide, &slide.join(OVERSIZE_REPORT), &suffixes));
+
+    // Check: The .wip files are own, whatever the suffix in use
+    let dst_file = slide.join("photos").join("photo.jpg");
+    assert!(is_own(
+        &slide,
+        &wip_file(&dst_file, None, "wip").unwrap(),
+        &suffixes
+    ));
+    assert!(is_own(
+        &slide,
+        &wip_file(&dst_file, None, "part").unwrap(),
+        &suffixes
+    ));
+
+    // Check: The files to deliver are not, even named like the internal ones deeper in the slide
+    assert!(!is_own(&slide, &dst_file, &suffixes));
+    assert!(!is_own(
+        &slide,
+        &slide.join("photos").join("photo.wip"),
+        &suffixes
+    ));
+    assert!(!is_own(
+        &slide,
+        &slide.join("photos").join(TRASH_DIR),
+        &suffixes
+    ));
+    assert!(!is_own(
+        &slide,
+        &Path::new("elsewhere").join(STAGING_DIR),
+        &suffixes
+    ));
+}
+
+/// Test telling the entries of a slide the syncs leave in place.
+#[test]
+fn test_is_held_back() {
+    // Prerequisite: A slide holding back the .tmp files, and the drafts of a folder
+    let temp_dir = tempdir().unwrap();
+    let slide = temp_dir.path().join("bar");
+    fs::create_dir_all(slide.join("docs")).unwrap();
+    fs::write(slide.join("docs").join(IGNORE_FILE), "drafts/\n").unwrap();
+    let filter = Filter::new(&["*.tmp".to_owned()]).unwrap();
+    let held_back = |path: PathBuf, is_dir| is_held_back(&slide, &path, is_dir, &filter);
+
+    // Check: The excluded entries, and the ones in excluded folders, are held back
+    assert!(held_back(slide.join("docs").join("scratch.tmp"), false));
+    assert!(held_back(slide.join("docs").join("drafts"), true));
+    assert!(held_back(
+        slide.join("docs").join("drafts").join("draft.txt"),
+        false
+    ));
+
+    // Check: The ignore files only apply to their folder
+    assert!(!held_back(slide.join("drafts").join("draft.txt"), false));
+
+    // Check: The internal folders and the temporary files of the editors are held back
+    assert!(held_back(
+        slide.join("docs").join(TRASH_DIR).join("a.txt"),
+        false
+    ));
+    for name in [
+        ".notes.txt.swp",
+        "notes.txt~",
+        ".~lock.notes.odt#",
+        "#notes#",
+        "4913",
+    ] {
+        assert!(held_back(slide.join("docs").join(name), false), "{name}");
+    }
+
+    // Check: The files to deliver are not
+    assert!(!held_back(slide.join("docs").join("notes.txt"), false));
+    assert!(!held_back(slide.join("docs"), true));
+}
+
+/// Test copy_file staging the .wip file in a separate folder.
+#[tokio::test]
+async fn test_copy_file_staging() {
+    // Prerequisite: Setup environment, with a staging folder
+    let (tmp_dir, src_file, dst_file) = setup_move_file();
+    let staging = tmp_dir.path().join(STAGING_DIR);
+    fs::create_dir_all(&staging).unwrap();
+
+    // Action: Copy file in safe mode, with a custom suffix
+    let result = copy_file(
+        &src_file,
+        &dst_file,
+        Some(&staging),
+        false,
+        false,
+        &MoveStrategy {
+            collision: CollisionPolicy::Fail,
+            safe: true,
+            wip_suffix: "part".to_owned(),
+            staging: true,
+            check: Some(Algorithm::MD5),
+            recheck: false,
+            check_mode: CheckMode::Full,
+            retries: 0,
+            jobs: 1,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
+        },
+        &Progress::default(),
+        None,
+        None,
+        None,
+        None,
+        None,
+        hash_reader,
+    )
+    .await;
+
+    // Check: The file was delivered, and nothing is left behind
+    assert!(result.is_ok(), "{result:?}");
+    assert_eq!(fs::read_to_string(&dst_file).unwrap(), "source");
+    assert_eq!(fs::read_dir(&staging).unwrap().count(), 0);
+    assert_eq!(fs::read_dir(dst_file.parent().unwrap()).unwrap().count(), 1);
+}
+
+/// Test that sync creates the staging folder in the destination slide, and never syncs one.
+#[tokio::test]
+async fn test_sync_staging() {
+    // root
+    // ├── src
+    // │   └── job
+    // │       ├── test.txt
+    // │       └── .bitslides-tmp
+    // │           └── .test.txt.0123456789abcdef.wip
+    // └── dst
+    let temp_dir = tempdir().unwrap();
+    let src_dir = temp_dir.path().join("src").join("job");
+    let dst_dir = temp_dir.path().join("dst").join("job");
+    fs::create_dir_all(src_dir.join(STAGING_DIR)).unwrap();
+    fs::write(src_dir.join("test.txt"), "test").unwrap();
+    fs::write(
+        src_dir
+            .join(STAGING_DIR)
+            .join(".test.txt.0123456789abcdef.wip"),
+        "partial",
+    )
+    .unwrap();
+    let (tracer, _) = tracer::Tracer::new(&None, &None, Default::default())
+        .await
+        .unwrap();
+
+    // Action: Sync with the staging enabled
+    let transferred = sync(
+        &src_dir,
+        &dst_dir,
+        false,
+        &tracer,
+        &Progress::default(),
+        None,
+        &MoveStrategy {
+            collision: CollisionPolicy::Fail,
+            safe: true,
+            wip_suffix: "wip".to_owned(),
+            staging: true,
+            check: None,
+            recheck: false,
+            check_mode: CheckMode::Full,
+            retries: 0,
+            jobs: 1,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
+        },
+        &Filter::default(),
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+
+    // Check: The file was delivered, and the staging folder of the destination slide created
+    assert_eq!(transferred.files, 1);
+    assert!(dst_dir.join("test.txt").exists());
+    assert!(temp_dir.path().join("dst").join(STAGING_DIR).is_dir());
+
+    // Check: The staging folder of the source was left alone
+    assert!(!dst_dir.join(STAGING_DIR).exists());
+    assert!(src_dir
+        .join(STAGING_DIR)
+        .join(".test.txt.0123456789abcdef.wip")
+        .exists());
+}
+
+/// Test copy_file with a file larger than the space left in the destination.
+#[tokio::test]
+async fn test_copy_file_destination_full() {
+    // Prerequisite: Setup environment, with a sparse file larger than the free space
+    let (_tmp_dir, src_file, dst_file) = setup_move_file();
+    let available = free_space(dst_file.parent().unwrap()).expect("Free space should be known");
+    if File::options()
+        .write(true)
+        .open(&src_file)
+        .and_then(|file| file.set_len(available + 1024 * 1024 * 1024))
+        .is_err()
+    {
+        eprintln!("Unable to create a file that large here, skipping");
+        return;
+    }
+
+    // Action: Copy file
+    let result = copy_file(
+        &src_file,
+        &dst_file,
+        None,
+        false,
+        false,
+        &MoveStrategy {
+            collision: CollisionPolicy::Fail,
+            safe: true,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
+            check: Some(Algorithm::MD5),
+            recheck: false,
+            check_mode: CheckMode::Full,
+            retries: 5,
+            jobs: 1,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
+        },
+        &Progress::default(),
+        None,
+        None,
+        None,
+        None,
+        None,
+        |_: &mut dyn Read, _| -> std::io::Result<String> { unreachable!("The file is not hashed") },
+    )
+    .await;
+
+    // Check: The copy failed before writing anything
+    let error = result.unwrap_err().to_string();
+    assert!(error.contains("Destination full"), "{error}");
+    assert!(src_file.exists());
+    assert_eq!(fs::read_dir(dst_file.parent().unwrap()).unwrap().count(), 0);
+}
+
+static TEST_HASH_FILE_COUNT: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+
+fn test_hash_file_count(reader: &mut dyn Read, _algo: Algorithm) -> std::io::Result<String> {
+    let read = std::io::copy(reader, &mut std::io::sink()).unwrap();
+    println!("Hashing: {read} bytes");
+    TEST_HASH_FILE_COUNT.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    Ok("test".to_owned())
+}
+
+/// Test move_file if checksum is requested, copying the file as across filesystems.
+#[tokio::test]
+#[allow(clippy::cmp_owned)]
+async fn test_move_file_check() {
+    // Prerequisite: Setup environment
+    let (_tmp_dir, src_file, dst_file) = setup_move_file();
+
+    // Prerequisite: Reset the number of calls to the hashing function
+    TEST_HASH_FILE_COUNT.store(0, std::sync::atomic::Ordering::SeqCst);
+
+    // Action: Copy file with the copy read back
+    let result = copy_file(
+        &src_file,
+        &dst_file,
+        None,
+        false,
+        false,
+        &MoveStrategy {
+            collision: CollisionPolicy::Fail,
+            safe: false,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
+            check: Some(Algorithm::MD5),
+            recheck: true,
+            check_mode: CheckMode::Full,
+            retries: 0,
+            jobs: 1,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
+        },
+        &Progress::default(),
+        None,
+        None,
+        None,
+        None,
+        None,
+        test_hash_file_count,
+    )
+    .await;
+
+    // Check: The hashing function was called while copying, and for reading the copy back
+    assert!(
+        TEST_HASH_FILE_COUNT.load(std::sync::atomic::Ordering::SeqCst) == 2,
+        "Actual: {}. Expected: 2",
+        TEST_HASH_FILE_COUNT.load(std::sync::atomic::Ordering::SeqCst)
+    );
+
+    // Check: The operation succeeded
+    assert!(result.is_ok());
+    assert!(!src_file.exists());
+    assert!(
+        dst_file.exists() && fs::read_to_string(&dst_file).unwrap() == "source".to_owned(),
+        "dst_file contents: {:?}",
+        fs::read_to_string(&dst_file).unwrap()
+    );
+}
+
+/// Test copy_file hashing the file while copying it, without reading the copy back.
+#[tokio::test]
+async fn test_copy_file_check_streamed() {
+    // Prerequisite: Setup environment, with a file spanning several chunks
+    let (_tmp_dir, src_file, dst_file) = setup_move_file();
+    let content = (0..3 * 1024 * 1024 + 100)
+        .map(|i| (i % 251) as u8)
+        .collect::<Vec<_>>();
+    fs::write(&src_file, &content).unwrap();
+    let expected = hash_reader(&mut File::open(&src_file).unwrap(), Algorithm::MD5).unwrap();
+
+    // Action: Copy file with checksum requested
+    let result = copy_file(
+        &src_file,
+        &dst_file,
+        None,
+        false,
+        false,
+        &MoveStrategy {
+            collision: CollisionPolicy::Fail,
+            safe: true,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
+            check: Some(Algorithm::MD5),
+            recheck: false,
+            check_mode: CheckMode::Full,
+            retries: 0,
+            jobs: 1,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
+        },
+        &Progress::default(),
+        None,
+        None,
+        None,
+        None,
+        None,
+        hash_reader,
+    )
+    .await;
+
+    // Check: The checksum of the whole file was computed along the copy
+    let delivery = result.unwrap().unwrap();
+    assert_eq!(delivery.checksum, Some((Algorithm::MD5, expected)));
+    assert_eq!(fs::read(&dst_file).unwrap(), content);
+}
+
+/// Test copy_file reading the copy back without blocking the other tasks meanwhile.
+#[tokio::test(flavor = "current_thread")]
+async fn test_copy_file_check_offloaded() {
+    // Prerequisite: Setup environment
+    let (_tmp_dir, src_file, dst_file) = setup_move_file();
+
+    // Prerequisite: Another task ticking on the only thread of the runtime
+    let ticks = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let ticker = tokio::spawn({
+        let ticks = ticks.clone();
+        async move {
+            loop {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                ticks.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            }
+        }
+    });
+
+    // Action: Copy file with the copy read back, slowly
+    let calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let result = copy_file(
+        &src_file,
+        &dst_file,
+        None,
+        false,
+        false,
+        &MoveStrategy {
+            collision: CollisionPolicy::Fail,
+            safe: true,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
+            check: Some(Algorithm::MD5),
+            recheck: true,
+            check_mode: CheckMode::Full,
+            retries: 0,
+            jobs: 1,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
+        },
+        &Progress::default(),
+        None,
+        None,
+        None,
+        None,
+        None,
+        move |reader: &mut dyn Read, algorithm| {
+            // The first call hashes the file while copying it, the second one reads the copy back
+            if calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst) == 1 {
+                std::thread::sleep(std::time::Duration::from_millis(300));
+            }
+            hash_reader(reader, algorithm)
+        },
+    )
+    .await;
+    ticker.abort();
+
+    // Check: The operation succeeded
+    assert!(result.is_ok());
+    assert!(dst_file.exists() && !src_file.exists());
+
+    // Check: The other task kept running while reading the copy back
+    let ticks = ticks.load(std::sync::atomic::Ordering::SeqCst);
+    assert!(ticks >= 10, "Actual: {ticks}. Expected: 10 or more");
+}
+
+/// Test copy_chunked stopping when its syncjob is paused.
+#[tokio::test]
+async fn test_copy_chunked_cancelled() {
+    // Prerequisite: Setup environment
+    let (_tmp_dir, src_file, dst_file) = setup_move_file();
+
+    // Prerequisite: The syncjob is paused
+    let (_gate, rx) = tokio::sync::watch::channel(crate::control::Gate::Paused);
+
+    // Action: Copy the file
+    let copied = copy_chunked(
+        &src_file,
+        &dst_file,
+        &AtomicBool::new(false),
+        &Progress::default(),
+        Some(&rx),
+        None,
+        Some((Algorithm::MD5, hash_reader)),
+    )
+    .await
+    .unwrap();
+
+    // Check: Nothing was copied, and the source is intact
+    assert_eq!(copied, None);
+    assert_eq!(fs::read_to_string(&src_file).unwrap(), "source");
+}
+
+/// Test the throttle making the copies wait once its bucket is empty.
+#[tokio::test]
+async fn test_throttle() {
+    // Prerequisite: No limit is set for a rate of 0
+    assert!(Throttle::new(0).is_none());
+    let throttle = Throttle::new(1000).unwrap();
+
+    // Action: Take the whole bucket
+    let started = std::time::Instant::now();
+    throttle.acquire(1000).await;
+
+    // Check: It was taken right away
+    assert!(started.elapsed() < std::time::Duration::from_millis(100));
+
+    // Action: Take some more
+    throttle.acquire(500).await;
+
+    // Check: It took the time needed to refill the bucket
+    let elapsed = started.elapsed();
+    assert!(
+        elapsed >= std::time::Duration::from_millis(450),
+        "Actual: {elapsed:?}. Expected: 500ms"
+    );
+}
+
+/// Test copy_chunked limited by a throttle.
+#[tokio::test]
+async fn test_copy_chunked_throttled() {
+    // Prerequisite: Setup environment, with a file larger than the bucket
+    let (_tmp_dir, src_file, dst_file) = setup_move_file();
+    let content = (0..96 * 1024).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    fs::write(&src_file, &content).unwrap();
+    let throttle = Throttle::new(64 * 1024).unwrap();
+
+    // Action: Copy the file
+    let started = std::time::Instant::now();
+    let copied = copy_chunked(
+        &src_file,
+        &dst_file,
+        &AtomicBool::new(false),
+        &Progress::default(),
+        None,
+        Some(&throttle),
+        None::<(
+            Algorithm,
+            fn(&mut dyn Read, Algorithm) -> std::io::Result<String>,
+        )>,
+    )
+    .await
+    .unwrap();
+
+    // Check: The file was copied at the given rate
+    let elapsed = started.elapsed();
+    assert_eq!(copied, Some((content.len() as u64, None)));
+    assert_eq!(fs::read(&dst_file).unwrap(), content);
+    assert!(
+        elapsed >= std::time::Duration::from_millis(450),
+        "Actual: {elapsed:?}. Expected: 500ms"
+    );
+}
+
+/// Test sync leaving the files in place when its syncjob is closed.
+#[tokio::test]
+async fn test_sync_cancelled() {
+    let temp_dir = tempdir().unwrap();
+    let src_dir = temp_dir.path().join("src").join("job");
+    let dst_dir = temp_dir.path().join("dst").join("job");
+    fs::create_dir_all(&src_dir).unwrap();
+    fs::write(src_dir.join("test.txt"), "test").unwrap();
+    let (tracer, _) = tracer::Tracer::new(&None, &None, Default::default())
+        .await
+        .unwrap();
+
+    // Prerequisite: The instance is stopping
+    let (_gate, rx) = tokio::sync::watch::channel(crate::control::Gate::Closed);
+
+    // Action: Sync
+    let transferred = sync(
+        &src_dir,
+        &dst_dir,
+        false,
+        &tracer,
+        &Progress::default(),
+        Some(&rx),
+        &MoveStrategy {
+            collision: CollisionPolicy::Fail,
+            safe: true,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
+            check: None,
+            recheck: false,
+            check_mode: CheckMode::Full,
+            retries: 0,
+            jobs: 1,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
+        },
+        &Filter::default(),
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+
+    // Check: Nothing was delivered, the file is left for the next sync
+    assert_eq!(transferred, Transferred::default());
+    assert!(src_dir.join("test.txt").exists());
+    assert!(!dst_dir.join("test.txt").exists());
+}
+
+static TEST_HASH_FILE_NASTY_RESULTS: std::sync::atomic::AtomicU32 =
+    std::sync::atomic::AtomicU32::new(0);
+
+fn test_hash_file_nasty_results(
+    _reader: &mut dyn Read,
+    _algo: Algorithm,
+) -> std::io::Result<String> {
+    let result = TEST_HASH_FILE_NASTY_RESULTS
+        .load(std::sync::atomic::Ordering::SeqCst)
+        .to_string();
+    TEST_HASH_FILE_NASTY_RESULTS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    Ok(result)
+}
+
+/// Test move_file if the operation fails and retries are requested, copying the file as across
+/// filesystems.
+#[tokio::test]
+#[allow(clippy::cmp_owned)]
+async fn test_move_file_check_failed() {
+    // Prerequisite: Setup environment
+    let (_tmp_dir, src_file, dst_file) = setup_move_file();
+
+    // Prerequisite: Reset the number of calls to the hashing function
+    TEST_HASH_FILE_NASTY_RESULTS.store(0, std::sync::atomic::Ordering::SeqCst);
+
+    // Action: Copy file with the copy read back
+    let result = copy_file(
+        &src_file,
+        &dst_file,
+        None,
+        false,
+        false,
+        &MoveStrategy {
+            collision: CollisionPolicy::Fail,
+            safe: false,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
+            check: Some(Algorithm::MD5),
+            recheck: true,
+            check_mode: CheckMode::Full,
+            retries: 5,
+            jobs: 1,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
+        },
+        &Progress::default(),
+        None,
+        None,
+        None,
+        None,
+        None,
+        test_hash_file_nasty_results,
+    )
+    .await;
+
+    // Check: The operation failed and nothing changed
+    assert!(result.is_err() && result.unwrap_err().to_string().contains("maximum retries"));
+    assert!(
+        src_file.exists() && fs::read_to_string(&src_file).unwrap() == "source".to_owned(),
+        "src_file contents: {:?}",
+        fs::read_to_string(&src_file).unwrap()
+    );
+    assert!(!dst_file.exists());
+}
+
+/// Test the classification of the failures of the copies.
+#[test]
+fn test_failure_of() {
+    use std::io::{Error, ErrorKind};
+
+    let failure = |kind: ErrorKind| Failure::of(&anyhow::Error::from(Error::from(kind)));
+
+    // Check: Only the failures that may go away on their own are transient
+    assert_eq!(failure(ErrorKind::TimedOut), Failure::Transient);
+    assert_eq!(failure(ErrorKind::Interrupted), Failure::Transient);
+    assert_eq!(failure(ErrorKind::Other), Failure::Transient);
+    assert_eq!(failure(ErrorKind::PermissionDenied), Failure::Permission);
+    assert_eq!(failure(ErrorKind::StorageFull), Failure::DiskFull);
+    assert_eq!(failure(ErrorKind::NotFound), Failure::Permanent);
+
+    // Check: The I/O error is found behind the context, and the rest are permanent
+    let e = anyhow::Error::from(Error::from(ErrorKind::StorageFull)).context("Copying");
+    assert_eq!(Failure::of(&e), Failure::DiskFull);
+    assert_eq!(Failure::of(&anyhow::anyhow!("Bad")), Failure::Permanent);
+
+    // Check: The sharing violations tell the file is in use
+    #[cfg(windows)]
+    assert_eq!(
+        Failure::of(&anyhow::Error::from(Error::from_raw_os_error(32))),
+        Failure::Locked
+    );
+}
+
+/// Test the delays between the retries of the copies.
+#[test]
+fn test_backoff() {
+    // Check: The delays double, with up to half of them dropped, until the maximum
+    for (retry, max) in [
+        (1, 200),
+        (2, 400),
+        (3, 800),
+        (8, 25_600),
+        (9, 30_000),
+        (200, 30_000),
+    ] {
+        let delay = retry::backoff(retry);
+        let max = std::time::Duration::from_millis(max);
+        assert!(delay >= max / 2 && delay <= max, "{retry}: {delay:?}");
+    }
+}
+
+//TODO: Check that after moving a file inside a folder and leaving the folder empty, the folder is removed
+
+/// Test copy_file reporting the progress, so the file is copied in chunks.
+#[tokio::test]
+async fn test_copy_file_progress() {
+    // Prerequisite: Setup environment, with a file spanning several chunks
+    let (_tmp_dir, src_file, dst_file) = setup_move_file();
+    let content = (0..3 * 1024 * 1024)
+        .map(|i| (i % 251) as u8)
+        .collect::<Vec<_>>();
+    fs::write(&src_file, &content).unwrap();
+
+    // Action: Copy file with the progress display enabled
+    let (progress, handle) = Progress::new(true);
+    let result = copy_file(
+        &src_file,
+        &dst_file,
+        None,
+        false,
+        false,
+        &MoveStrategy {
+            collision: CollisionPolicy::Fail,
+            safe: true,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
+            check: Some(Algorithm::MD5),
+            recheck: false,
+            check_mode: CheckMode::Full,
+            retries: 0,
+            jobs: 1,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
+        },
+        &progress.annotate_job("test_move_file_progress".to_owned()),
+        None,
+        None,
+        None,
+        None,
+        None,
+        hash_reader,
+    )
+    .await;
+    drop(progress);
+    handle.unwrap().await.unwrap();
+
+    // Check: The file was moved untouched
+    assert!(result.is_ok(), "{result:?}");
+    assert!(!src_file.exists());
+    assert_eq!(fs::read(&dst_file).unwrap(), content);
+}
+
+/// Test that files out of the size range are left untouched in the source directory.
+#[tokio::test]
+async fn test_sync_size_range() {
+    // root
+    // ├── src
+    // │   ├── empty.txt
+    // │   ├── small.txt
+    // │   └── large.iso
+    // └── dest
+    let temp_dir = tempdir().unwrap();
+    let src_dir = temp_dir.path().join("src");
+    let dest_dir = temp_dir.path().join("dest");
+    let trace_path = temp_dir.path().join("test.trace");
+
+    let (tracer, handle) = {
+        let (tracer, handle) = tracer::Tracer::new(&Some(&trace_path), &None, Default::default())
+            .await
+            .unwrap();
+        (
+            tracer.annotate_author("test_sync_size_range".to_owned()),
+            handle.expect("Should have a handle"),
+        )
+    };
+
+    // Prerequisite: Create the source directory structure
+    fs::create_dir_all(&src_dir).unwrap();
+    File::create(src_dir.join("empty.txt")).unwrap();
+    fs::write(src_dir.join("small.txt"), [0; 10]).unwrap();
+    fs::write(src_dir.join("large.iso"), [0; 100]).unwrap();
+
+    // Action: Sync only the files from 1 to 50 bytes
+    sync(
+        &src_dir,
+        &dest_dir,
+        false,
+        &tracer,
+        &Progress::default(),
+        None,
+        &MoveStrategy {
+            collision: CollisionPolicy::Fail,
+            safe: false,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
+            check: None,
+            recheck: false,
+            check_mode: CheckMode::Full,
+            retries: 5,
+            jobs: 1,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
+        },
+        &Filter::default().with_size_range(Some(1), Some(50)),
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    drop(tracer);
+    handle.await.unwrap();
+
+    // Check: Only the file in range was moved, and the others were reported
+    assert!(dest_dir.join("small.txt").exists());
+    assert!(!src_dir.join("small.txt").exists());
+    let trace = fs::read_to_string(&trace_path).unwrap();
+    for file in ["empty.txt", "large.iso"] {
+        assert!(src_dir.join(file).exists(), "{file} should remain");
+        assert!(!dest_dir.join(file).exists(), "{file} should not be synced");
+        assert!(
+            trace
+                .lines()
+                .any(|l| l.contains("SKIP") && l.contains(file)),
+            "{file} should be traced"
+        );
+    }
+}
+
+/// Test that files modified too recently are left until they settle.
+#[tokio::test]
+async fn test_sync_min_age() {
+    // root
+    // ├── src
+    // │   ├── fresh.txt
+    // │   └── settled.txt
+    // └── dest
+    let temp_dir = tempdir().unwrap();
+    let src_dir = temp_dir.path().join("src");
+    let dest_dir = temp_dir.path().join("dest");
+    let trace_path = temp_dir.path().join("test.trace");
+
+    let (tracer, handle) = {
+        let (tracer, handle) = tracer::Tracer::new(&Some(&trace_path), &None, Default::default())
+            .await
+            .unwrap();
+        (
+            tracer.annotate_author("test_sync_min_age".to_owned()),
+            handle.expect("Should have a handle"),
+        )
+    };
+
+    // Prerequisite: Create a file just written, and another one last modified two minutes ago
+    fs::create_dir_all(&src_dir).unwrap();
+    fs::write(src_dir.join("fresh.txt"), "fresh").unwrap();
+    fs::write(src_dir.join("settled.txt"), "settled").unwrap();
+    let two_minutes_ago = FileTime::from_unix_time(FileTime::now().unix_seconds() - 120, 0);
+    filetime::set_file_mtime(src_dir.join("settled.txt"), two_minutes_ago).unwrap();
+
+    // Action: Sync only the files unmodified for a minute
+    let transferred = sync(
+        &src_dir,
+        &dest_dir,
+        false,
+        &tracer,
+        &Progress::default(),
+        None,
+        &MoveStrategy {
+            collision: CollisionPolicy::Fail,
+            safe: false,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
+            check: None,
+            recheck: false,
+            check_mode: CheckMode::Full,
+            retries: 5,
+            jobs: 1,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: Some(60),
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
+        },
+        &Filter::default(),
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    drop(tracer);
+    handle.await.unwrap();
+
+    // Check: Only the settled file was moved, and the other one was reported
+    assert_eq!(transferred.files, 1);
+    assert!(dest_dir.join("settled.txt").exists());
+    assert!(!src_dir.join("settled.txt").exists());
+    assert!(src_dir.join("fresh.txt").exists());
+    assert!(!dest_dir.join("fresh.txt").exists());
+    let trace = fs::read_to_string(&trace_path).unwrap();
+    assert!(trace
+        .lines()
+        .any(|l| l.contains("SKIP") && l.contains("fresh.txt")));
+
+    // Check: The sync tells when the fresh file settles
+    let settling = transferred.settling.expect("Should be settling");
+    assert!(settling > Duration::from_secs(50) && settling <= Duration::from_secs(60));
+}
+
+/// Test that the files locked by another program are detected.
+#[test]
+fn test_is_locked() {
+    let temp_dir = tempdir().unwrap();
+    let path = temp_dir.path().join("file.txt");
+
+    // Prerequisite: Create a file nobody holds
+    fs::write(&path, "content").unwrap();
+
+    // Check: It is not locked
+    assert!(!lock::is_locked(&path));
+    assert!(!lock::is_locked(&temp_dir.path().join("missing.txt")));
+
+    // Action: Take a shared lock on it, as readers do
+    let file = File::open(&path).unwrap();
+    file.lock_shared().unwrap();
+
+    // Check: It is not locked either
+    assert!(!lock::is_locked(&path));
+    drop(file);
+
+    // Action: Take an exclusive lock on it, as writers do
+    let file = File::open(&path).unwrap();
+    file.lock().unwrap();
+
+    // Check: It is locked until released
+    assert!(lock::is_locked(&path));
+    file.unlock().unwrap();
+    assert!(!lock::is_locked(&path));
+    drop(file);
+
+    // Action: Take a write lock on part of it, as databases do
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        use nix::{
+            fcntl::{fcntl, FcntlArg},
+            libc,
+        };
+
+        let file = fs::OpenOptions::new().write(true).open(&path).unwrap();
+        let mut request: libc::flock = unsafe { std::mem::zeroed() };
+        request.l_type = libc::F_WRLCK as _;
+        request.l_whence = libc::SEEK_SET as _;
+        request.l_len = 1;
+        fcntl(&file, FcntlArg::F_OFD_SETLK(&request)).unwrap();
+
+        // Check: It is locked until closed
+        assert!(lock::is_locked(&path));
+        drop(file);
+        assert!(!lock::is_locked(&path));
+    }
+}
+
+/// Test that the files locked by another program are left in the source directory.
+#[tokio::test]
+async fn test_sync_locked() {
+    // root
+    // ├── src
+    // │   ├── free.txt
+    // │   └── locked.txt
+    // └── dest
+    let temp_dir = tempdir().unwrap();
+    let src_dir = temp_dir.path().join("src");
+    let dest_dir = temp_dir.path().join("dest");
+    let trace_path = temp_dir.path().join("test.trace");
+
+    let (tracer, handle) = {
+        let (tracer, handle) = tracer::Tracer::new(&Some(&trace_path), &None, Default::default())
+            .await
+            .unwrap();
+        (
+            tracer.annotate_author("test_sync_locked".to_owned()),
+            handle.expect("Should have a handle"),
+        )
+    };
+
+    // Prerequisite: Create two files, and lock one of them as a writer would
+    fs::create_dir_all(&src_dir).unwrap();
+    fs::write(src_dir.join("free.txt"), "free").unwrap();
+    fs::write(src_dir.join("locked.txt"), "locked").unwrap();
+    let locked = File::open(src_dir.join("locked.txt")).unwrap();
+    locked.lock().unwrap();
+
+    // Action: Sync the files
+    let transferred = sync(
+        &src_dir,
+        &dest_dir,
+        false,
+        &tracer,
+        &Progress::default(),
+        None,
+        &MoveStrategy {
+            collision: CollisionPolicy::Fail,
+            safe: false,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
+            check: None,
+            recheck: false,
+            check_mode: CheckMode::Full,
+            retries: 5,
+            jobs: 1,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
+        },
+        &Filter::default(),
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    drop(tracer);
+    handle.await.unwrap();
+
+    // Check: Only the free file was moved, and the locked one was reported
+    assert_eq!(transferred.files, 1);
+    assert!(dest_dir.join("free.txt").exists());
+    assert!(src_dir.join("locked.txt").exists());
+    assert!(!dest_dir.join("locked.txt").exists());
+    let trace = fs::read_to_string(&trace_path).unwrap();
+    assert!(trace
+        .lines()
+        .any(|l| l.contains("SKIP") && l.contains("locked.txt")));
+}
+
+/// Test that files over the size cap are left in their slide and reported.
+#[tokio::test]
+async fn test_sync_max_file_size() {
+    // root
+    // ├── slide
+    // │   └── photos
+    // │       ├── small.jpg
+    // │       └── disk.img
+    // └── dest
+    let temp_dir = tempdir().unwrap();
+    let slide_dir = temp_dir.path().join("slide");
+    let src_dir = slide_dir.join("photos");
+    let dest_dir = temp_dir.path().join("dest").join("photos");
+    let trace_path = temp_dir.path().join("test.trace");
+
+    let (tracer, handle) = {
+        let (tracer, handle) = tracer::Tracer::new(&Some(&trace_path), &None, Default::default())
+            .await
+            .unwrap();
+        (
+            tracer.annotate_author("test_sync_max_file_size".to_owned()),
+            handle.expect("Should have a handle"),
+        )
+    };
+
+    // Prerequisite: Create a small file and a large one
+    fs::create_dir_all(&src_dir).unwrap();
+    fs::write(src_dir.join("small.jpg"), [0; 10]).unwrap();
+    fs::write(src_dir.join("disk.img"), [0; 100]).unwrap();
+
+    // Action: Sync the files up to 50 bytes, and report the rest
+    let transferred = sync(
+        &src_dir,
+        &dest_dir,
+        false,
+        &tracer,
+        &Progress::default(),
+        None,
+        &MoveStrategy {
+            collision: CollisionPolicy::Fail,
+            safe: false,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
+            check: None,
+            recheck: false,
+            check_mode: CheckMode::Full,
+            retries: 5,
+            jobs: 1,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: Some(50),
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
+        },
+        &Filter::default(),
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    drop(tracer);
+    handle.await.unwrap();
+    report_oversize(&slide_dir, &transferred.oversize)
+        .await
+        .unwrap();
+
+    // Check: Only the small file was moved, and the large one was traced and reported
+    assert!(dest_dir.join("small.jpg").exists());
+    assert!(src_dir.join("disk.img").exists());
+    assert!(!dest_dir.join("disk.img").exists());
+    assert_eq!(transferred.oversize, vec![(src_dir.join("disk.img"), 100)]);
+    let trace = fs::read_to_string(&trace_path).unwrap();
+    assert!(trace
+        .lines()
+        .any(|l| l.contains("OVERSIZE") && l.contains("disk.img")));
+    let report = slide_dir.join(OVERSIZE_REPORT);
+    assert_eq!(
+        fs::read_to_string(&report).unwrap(),
+        format!("100\t{}\n", Path::new("photos").join("disk.img").display())
+    );
+
+    // Action: Report the same file again
+    let reported = filetime::FileTime::from_unix_time(1_000_000_000, 0);
+    filetime::set_file_mtime(&report, reported).unwrap();
+    report_oversize(&slide_dir, &transferred.oversize)
+        .await
+        .unwrap();
+
+    // Check: The report was left untouched
+    assert_eq!(
+        filetime::FileTime::from_last_modification_time(&fs::metadata(&report).unwrap()),
+        reported
+    );
+
+    // Action: Report a pass without oversize files
+    report_oversize(&slide_dir, &[]).await.unwrap();
+
+    // Check: The report is gone
+    assert!(!report.exists());
+}
+
+/// Test the checksum files written along with the delivered files.
+#[tokio::test]
+async fn test_sync_sidecar() {
+    // root
+    // ├── slide
+    // │   └── photos
+    // │       ├── a.jpg
+    // │       └── b.jpg
+    // └── dest
+    let temp_dir = tempdir().unwrap();
+    let src_dir = temp_dir.path().join("slide").join("photos");
+    let dest_dir = temp_dir.path().join("dest").join("photos");
+    let trace_path = temp_dir.path().join("test.trace");
+
+    let (tracer, handle) = {
+        let (tracer, handle) = tracer::Tracer::new(&Some(&trace_path), &None, Default::default())
+            .await
+            .unwrap();
+        (
+            tracer.annotate_author("test_sync_sidecar".to_owned()),
+            handle.expect("Should have a handle"),
+        )
+    };
+    let strategy = |sidecar| MoveStrategy {
+        collision: CollisionPolicy::Fail,
+        safe: false,
+        wip_suffix: "wip".to_owned(),
+        staging: false,
+        check: Some(Algorithm::SHA2256),
+        recheck: false,
+        check_mode: CheckMode::Full,
+        retries: 5,
+        jobs: 2,
+        delta: false,
+        preserve_times: false,
+        preserve_permissions: false,
+        preserve_xattrs: false,
+        rate_limit: None,
+        sanitize: None,
+        use_trash: None,
+        min_age: None,
+        max_file_size: None,
+        sidecar: Some(sidecar),
+        reverify: None,
+        delivery_manifest: false,
+        conflict: ConflictPolicy::Report,
+        max_failures: 5,
+        min_interval: 0,
+        skip_identical: None,
+    };
+    let sha256 = |path: &Path| {
+        hash_reader(&mut File::open(path).unwrap(), Algorithm::SHA2256)
+            .unwrap()
+            .to_lowercase()
+    };
+
+    // Prerequisite: Create a couple of files
+    fs::create_dir_all(&src_dir).unwrap();
+    fs::write(src_dir.join("a.jpg"), "a").unwrap();
+    fs::write(src_dir.join("b.jpg"), "b").unwrap();
+
+    // Action: Sync the files, listing their checksums in a file per folder
+    sync(
+        &src_dir,
+        &dest_dir,
+        false,
+        &tracer,
+        &Progress::default(),
+        None,
+        &strategy(Sidecar::Folder),
+        &Filter::default(),
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+
+    // Check: Both files are listed, in the format of sha256sum
+    let sums = dest_dir.join("SHA256SUMS");
+    assert_eq!(sidecar::algorithm_of(&sums), Some(Algorithm::SHA2256));
+    let mut listed = sidecar::read(&sums).unwrap();
+    listed.sort();
+    assert_eq!(
+        listed,
+        ["a.jpg", "b.jpg"].map(|name| (dest_dir.join(name), sha256(&dest_dir.join(name))))
+    );
+    let content = fs::read_to_string(&sums).unwrap();
+    assert!(content
+        .lines()
+        .any(|l| l == format!("{}  a.jpg", sha256(&dest_dir.join("a.jpg")))));
+
+    // Action: Deliver a file again, with another content
+    fs::write(dest_dir.join("a.jpg"), "another").unwrap();
+    let checksum = sha256(&dest_dir.join("a.jpg"));
+    sidecar::write(
+        &dest_dir.join("a.jpg"),
+        Algorithm::SHA2256,
+        &checksum,
+        Sidecar::Folder,
+    )
+    .unwrap();
+
+    // Check: The line is appended, and only the latest one is read
+    assert_eq!(fs::read_to_string(&sums).unwrap().lines().count(), 3);
+    assert!(sidecar::read(&sums)
+        .unwrap()
+        .contains(&(dest_dir.join("a.jpg"), checksum.clone())));
+    assert_eq!(sidecar::read(&sums).unwrap().len(), 2);
+
+    // Action: Compact the checksum file
+    sidecar::compact(&sums).unwrap();
+
+    // Check: The stale line is gone
+    let content = fs::read_to_string(&sums).unwrap();
+    assert_eq!(content.lines().count(), 2);
+    assert!(content.lines().any(|l| l == format!("{checksum}  a.jpg")));
+
+    // Action: Sync another file, with its checksum next to it
+    fs::create_dir_all(&src_dir).unwrap();
+    fs::write(src_dir.join("c.jpg"), "c").unwrap();
+    sync(
+        &src_dir,
+        &dest_dir,
+        false,
+        &tracer,
+        &Progress::default(),
+        None,
+        &strategy(Sidecar::File),
+        &Filter::default(),
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    drop(tracer);
+    handle.await.unwrap();
+
+    // Check: The checksum file is named after the file, and only taken as one next to it
+    let sidecar_file = dest_dir.join("c.jpg.sha256");
+    assert_eq!(
+        sidecar::read(&sidecar_file).unwrap(),
+        vec![(dest_dir.join("c.jpg"), sha256(&dest_dir.join("c.jpg")))]
+    );
+    assert_eq!(
+        sidecar::algorithm_of(&sidecar_file),
+        Some(Algorithm::SHA2256)
+    );
+    assert_eq!(sidecar::algorithm_of(&dest_dir.join("d.jpg.sha256")), None);
+
+    // Check: The names with a backslash or a line break are escaped, and read back
+    #[cfg(unix)]
+    {
+        let odd = dest_dir.join("back\\slash\nbreak.jpg");
+        fs::write(&odd, "odd").unwrap();
+        sidecar::write(&odd, Algorithm::SHA2256, &sha256(&odd), Sidecar::Folder).unwrap();
+        let listed = sidecar::read(&sums).unwrap();
+        assert!(listed.contains(&(odd.clone(), sha256(&odd))));
+        assert_eq!(listed.len(), 3);
+    }
+}
+
+/// Test the second check of a delivery, restoring it once found corrupted.
+#[tokio::test]
+async fn test_reverify() {
+    let temp_dir = tempdir().unwrap();
+    let trace_path = temp_dir.path().join("test.trace");
+    let (tracer, handle) = {
+        let (tracer, handle) = tracer::Tracer::new(&Some(&trace_path), &None, Default::default())
+            .await
+            .unwrap();
+        (
+            tracer.annotate_author("test_reverify".to_owned()),
+            handle.expect("Should have a handle"),
+        )
+    };
+
+    // Prerequisite: A delivered file, with its source kept in the trash
+    let delivered = temp_dir.path().join("photo.jpg");
+    let kept = temp_dir.path().join("kept.jpg");
+    fs::write(&delivered, "photo").unwrap();
+    fs::write(&kept, "photo").unwrap();
+    let delivery = |kept: Option<&Path>| Delivery {
+        path: delivered.clone(),
+        size: 5,
+        checksum: Some((
+            Algorithm::BLAKE3,
+            hash_reader(&mut "photo".as_bytes(), Algorithm::BLAKE3).unwrap(),
+        )),
+        read_back: false,
+        kept: kept.map(Path::to_owned),
+        retries: 0,
+        collision: Default::default(),
+    };
+    let reverification =
+        Reverification::new(temp_dir.path().join("src.jpg"), delivery(Some(&kept)))
+            .await
+            .unwrap();
+
+    // Action: Check it while sound
+    reverification
+        .clone()
+        .run(true, &tracer, &Progress::default())
+        .await
+        .unwrap();
+
+    // Prerequisite: The file corrupted, keeping its modification time
+    let modified = filetime::FileTime::from_last_modification_time(&delivered.metadata().unwrap());
+    fs::write(&delivered, "phoXo").unwrap();
+    filetime::set_file_mtime(&delivered, modified).unwrap();
+
+    // Action: Check it again, restoring it
+    reverification
+        .run(true, &tracer, &Progress::default())
+        .await
+        .unwrap();
+
+    // Check: The file was restored from the kept copy
+    assert_eq!(fs::read_to_string(&delivered).unwrap(), "photo");
+    assert!(kept.exists());
+
+    // Prerequisite: The file edited after its delivery
+    let reverification = Reverification::new(temp_dir.path().join("src.jpg"), delivery(None))
+        .await
+        .unwrap();
+    fs::write(&delivered, "edited").unwrap();
+    filetime::set_file_mtime(
+        &delivered,
+        filetime::FileTime::from_unix_time(1_000_000_000, 0),
+    )
+    .unwrap();
+
+    // Action: Check it again
+    reverification
+        .run(true, &tracer, &Progress::default())
+        .await
+        .unwrap();
+    drop(tracer);
+    handle.await.unwrap();
+
+    // Check: The outcomes were traced, and the edited file left alone
+    let trace = fs::read_to_string(&trace_path).unwrap();
+    let operations = trace
+        .lines()
+        .filter_map(|l| {
+            ["REVERIFIED", "CORRUPTED", "RESTORED"]
+                .into_iter()
+                .find(|operation| l.contains(operation))
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(operations, ["REVERIFIED", "CORRUPTED", "RESTORED"]);
+    assert_eq!(fs::read_to_string(&delivered).unwrap(), "edited");
+}
+
+/// Test the sample of a file read back to check it.
+#[test]
+fn test_sample() {
+    let mode = CheckMode::Sampled {
+        threshold: 1024,
+        edge: 100,
+        blocks: 3,
+    };
+    let size = 4 * 1024 * 1024;
+    let data = (0..size).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+
+    // Check: The small files and the full mode read the whole file
+    assert_eq!(sample::Sample::new(1024, mode), None);
+    assert_eq!(sample::Sample::new(3 * 1024 * 1024, mode), None);
+    assert_eq!(sample::Sample::new(size, CheckMode::Full), None);
+
+    // Action: Read a sample of a large file
+    let sample = sample::Sample::new(size, mode).unwrap();
+    let mut read = Vec::new();
+    sample
+        .reader(std::io::Cursor::new(&data))
+        .read_to_end(&mut read)
+        .unwrap();
+
+    // Check: Both ends and the blocks were read
+    assert_eq!(sample.len(), 200 + 3 * 1024 * 1024);
+    assert_eq!(read.len() as u64, sample.len());
+    assert_eq!(read[..100], data[..100]);
+    assert_eq!(read[read.len() - 100..], data[data.len() - 100..]);
+
+    // Check: A file shorter than the sample fails to be read
+    let result = sample
+        .reader(std::io::Cursor::new(&data[..size as usize / 2]))
+        .read_to_end(&mut Vec::new());
+    assert_eq!(
+        result.unwrap_err().kind(),
+        std::io::ErrorKind::UnexpectedEof
+    );
+}
+
+/// Test copy_file reading back only a sample of a large copy.
+#[tokio::test]
+async fn test_copy_file_sampled() {
+    // Prerequisite: Setup environment, with a large source file
+    let (_tmp_dir, src_file, dst_file) = setup_move_file();
+    let data = (0..4 * 1024 * 1024u32)
+        .map(|i| (i % 251) as u8)
+        .collect::<Vec<_>>();
+    fs::write(&src_file, &data).unwrap();
+    let hashed = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let recorded = hashed.clone();
+    let hash = move |reader: &mut dyn Read, algorithm| {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        recorded.lock().unwrap().push(data.len());
+        hash_reader(&mut &data[..], algorithm)
+    };
+
+    // Action: Copy file with a sample of the copy read back
+    let result = copy_file(
+        &src_file,
+        &dst_file,
+        None,
+        false,
+        false,
+        &MoveStrategy {
+            collision: CollisionPolicy::Fail,
+            safe: false,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
+            check: Some(Algorithm::XXH64),
+            recheck: true,
+            check_mode: CheckMode::Sampled {
+                threshold: 1024,
+                edge: 4096,
+                blocks: 2,
+            },
+            retries: 0,
+            jobs: 1,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
+        },
+        &Progress::default(),
+        None,
+        None,
+        None,
+        None,
+        None,
+        hash,
+    )
+    .await;
+
+    // Check: The whole file was hashed while copied, and only the sample of both files after
+    let sampled = 2 * 4096 + 2 * 1024 * 1024;
+    assert!(result.unwrap().is_some());
+    assert_eq!(*hashed.lock().unwrap(), [data.len(), sampled, sampled]);
+    assert_eq!(fs::read(&dst_file).unwrap(), data);
+}
+
+/// Test copy_file with the checksum of the source computed ahead.
+#[tokio::test]
+async fn test_copy_file_prehashed() {
+    // Prerequisite: Setup environment, with the source hashed ahead
+    let (_tmp_dir, src_file, dst_file) = setup_move_file();
+    let checksum = hash_reader(&mut File::open(&src_file).unwrap(), Algorithm::XXH64).unwrap();
+    let prehashed = Prehash::start(&src_file, Algorithm::XXH64, hash_reader)
+        .wait(&src_file)
+        .await;
+    assert_eq!(
+        prehashed.as_ref().map(|prehashed| &prehashed.checksum),
+        Some(&checksum)
+    );
+
+    // Action: Copy file
+    let result = copy_file(
+        &src_file,
+        &dst_file,
+        None,
+        false,
+        false,
+        &MoveStrategy {
+            collision: CollisionPolicy::Fail,
+            safe: true,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
+            check: Some(Algorithm::XXH64),
+            recheck: false,
+            check_mode: CheckMode::Full,
+            retries: 0,
+            jobs: 1,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
+        },
+        &Progress::default(),
+        None,
+        None,
+        None,
+        None,
+        prehashed,
+        |_: &mut dyn Read, _| -> std::io::Result<String> { unreachable!("The file is not hashed") },
+    )
+    .await;
+
+    // Check: The checksum taken ahead was delivered, without hashing the file again
+    let delivery = result.unwrap().unwrap();
+    assert_eq!(delivery.checksum, Some((Algorithm::XXH64, checksum)));
+    assert!(dst_file.exists());
+    assert!(!src_file.exists());
+}
+
+/// Test that the checksum computed ahead is dropped if the file changed since.
+#[tokio::test]
+async fn test_prehash_modified() {
+    // Prerequisite: Setup environment, with the source hashed ahead
+    let (_tmp_dir, src_file, _dst_file) = setup_move_file();
+    let (hashed, hashing) = std::sync::mpsc::channel();
+    let prehash = Prehash::start(&src_file, Algorithm::XXH64, move |reader, algorithm| {
+        let checksum = hash_reader(reader, algorithm);
+        hashed.send(()).unwrap();
+        checksum
+    });
+
+    // Action: Modify the source once hashed
+    hashing.recv().unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(10));
+    fs::write(&src_file, "Modified contents, longer than before").unwrap();
+
+    // Check: The checksum taken ahead is no longer used
+    assert!(prehash.wait(&src_file).await.is_none());
+}
+
+/// Test the copies sharing a throttle holding its rate all together.
+#[tokio::test]
+async fn test_throttle_shared() {
+    // Prerequisite: A throttle of 1 KiB/s, shared by two copies
+    let throttle = Arc::new(throttle::Throttle::new(1024).unwrap());
+    assert!(throttle::Throttle::new(0).is_none());
+
+    // Action: Have each copy take a second worth of bytes at once
+    let started = std::time::Instant::now();
+    let copies = (0..2).map(|_| {
+        let throttle = throttle.clone();
+        tokio::spawn(async move { throttle.acquire(1024).await })
+    });
+    for copy in copies.collect::<Vec<_>>() {
+        copy.await.unwrap();
+    }
+
+    // Check: The second one waited for the first one's bytes to be paid for
+    assert!(started.elapsed() >= std::time::Duration::from_millis(900));
+}
@@ -0,0 +1,59 @@
+use std::time::{Duration, Instant};
+
+use tokio::sync::Mutex;
+
+/// Token bucket limiting the bytes copied per second.
+///
+/// Shared by the files copied concurrently, so the limit holds for all of them together. Up to a
+/// second worth of bytes can be copied at once after a pause.
+///
+pub(crate) struct Throttle {
+    /// Bytes per second
+    rate: u64,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    /// Bytes that can be copied right away. Negative when owed by a chunk larger than the bucket
+    tokens: f64,
+    /// Last time the bucket was filled
+    filled: Instant,
+}
+
+impl Throttle {
+    /// Limit the copies to `rate` bytes per second. No limit is set for a rate of 0.
+    ///
+    pub(crate) fn new(rate: u64) -> Option<Self> {
+        if rate == 0 {
+            return None;
+        }
+        Some(Self {
+            rate,
+            bucket: Mutex::new(Bucket {
+                tokens: rate as f64,
+                filled: Instant::now(),
+            }),
+        })
+    }
+
+    /// Wait until `bytes` more bytes can be copied.
+    ///
+    /// The bucket is held while waiting, so the copies take turns instead of starving each other.
+    ///
+    pub(crate) async fn acquire(&self, bytes: u64) {
+        let mut bucket = self.bucket.lock().await;
+        let rate = self.rate as f64;
+
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.filled).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(rate);
+        bucket.filled = now;
+
+        bucket.tokens -= bytes as f64;
+        if bucket.tokens < 0.0 {
+            tokio::time::sleep(Duration::from_secs_f64(-bucket.tokens / rate)).await;
+            bucket.tokens = 0.0;
+            bucket.filled = Instant::now();
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use chrono::{Local, NaiveDateTime, TimeDelta};
+
+use super::{renamed, TRASH_DIR};
+use crate::config::Trash;
+
+/// Name format of the folders holding the files discarded by each sync.
+///
+const BATCH_FORMAT: &str = "%Y%m%d-%H%M%S";
+
+/// Trash of the slides of a sync, where the displaced files are moved instead of being deleted.
+///
+/// The files discarded by the sync go to a folder named after its start, keeping their path
+/// relative to the slide.
+///
+#[derive(Debug)]
+pub(crate) struct Bin {
+    /// Slide the files are moved to
+    destination: PathBuf,
+    /// Slide the files are moved from, if its removed files are kept too
+    source: Option<PathBuf>,
+    /// Folder of this sync inside the trash
+    batch: String,
+}
+
+impl Bin {
+    pub(crate) fn new(src_slide: &Path, dst_slide: &Path, trash: Trash) -> Self {
+        Self {
+            destination: dst_slide.to_owned(),
+            source: trash.sources.then(|| src_slide.to_owned()),
+            batch: Local::now().format(BATCH_FORMAT).to_string(),
+        }
+    }
+
+    /// Move a file about to be overwritten to the trash of the destination slide.
+    ///
+    pub(crate) async fn discard_destination(&self, file: &Path) -> Result<()> {
+        discard(file, &self.destination, &self.batch).await?;
+        Ok(())
+    }
+
+    /// Remove a moved file from the source slide, keeping it in its trash if asked to. Returns
+    /// where it is kept, if it is.
+    ///
+    pub(crate) async fn discard_source(&self, file: &Path) -> Result<Option<PathBuf>> {
+        match &self.source {
+            Some(slide) => discard(file, slide, &self.batch).await.map(Some),
+            None => {
+                tokio::fs::remove_file(file).await?;
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Move a file to the trash of a slide, renaming it if the name is already taken. Returns where
+/// it was moved.
+///
+async fn discard(file: &Path, slide: &Path, batch: &str) -> Result<PathBuf> {
+    let relative = match file.strip_prefix(slide) {
+        Ok(relative) => relative,
+        Err(_) => Path::new(file.file_name().unwrap_or_default()),
+    };
+    let mut trashed = slide.join(TRASH_DIR).join(batch).join(relative);
+    if trashed.exists() {
+        trashed = renamed(&trashed, "{stem} ({n}).{ext}");
+    }
+    if let Some(parent) = trashed.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    log::info!("Trash: {file:?} -> {trashed:?}");
+    // The trash may be on another filesystem if the slide has mount points inside
+    if tokio::fs::rename(file, &trashed).await.is_err() {
+        if let Err(e) = tokio::fs::copy(file, &trashed).await {
+            let _ = tokio::fs::remove_file(&trashed).await;
+            bail!("Unable to move {file:?} to the trash: {e}");
+        }
+        tokio::fs::remove_file(file).await?;
+    }
+    Ok(trashed)
+}
+
+/// Delete the files kept in the trash of a slide for longer than `retention` days.
+///
+/// Nothing is deleted with a retention of 0. Returns the number of syncs whose files were
+/// deleted.
+///
+pub(crate) async fn purge(slide: &Path, retention: u32) -> Result<usize> {
+    let trash = slide.join(TRASH_DIR);
+    if retention == 0 || !trash.is_dir() {
+        return Ok(0);
+    }
+
+    let expiry = Local::now().naive_local() - TimeDelta::days(retention.into());
+    let mut purged = 0;
+    let mut entries = tokio::fs::read_dir(&trash).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        // Anything not named after a sync was put there by someone else
+        let name = entry.file_name();
+        let Ok(started) = NaiveDateTime::parse_from_str(&name.to_string_lossy(), BATCH_FORMAT)
+        else {
+            continue;
+        };
+        if started < expiry {
+            log::info!("Purge: {:?}", entry.path());
+            tokio::fs::remove_dir_all(entry.path()).await?;
+            purged += 1;
+        }
+    }
+
+    let _ = tokio::fs::remove_dir(&trash).await;
+    Ok(purged)
+}
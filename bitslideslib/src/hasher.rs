@@ -0,0 +1,351 @@
+use std::{
+    io::Read,
+    str::FromStr,
+    sync::mpsc::{self, Receiver, SyncSender},
+    thread::JoinHandle,
+};
+
+use xxhash_rust::{xxh3::Xxh3Default, xxh64::Xxh64};
+
+/// Size of the chunks read from the files being hashed.
+///
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Checksum algorithm.
+///
+/// The algorithms of the `checksums` crate, under the same names, and the fast non-cryptographic
+/// ones of the xxHash family. The checksums are written as uppercase hex strings.
+///
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Algorithm {
+    SHA1,
+    /// SHA2-224
+    SHA2224,
+    /// SHA2-256
+    SHA2256,
+    /// SHA2-384
+    SHA2384,
+    /// SHA2-512
+    SHA2512,
+    /// SHA3-256
+    SHA3256,
+    /// SHA3-512
+    SHA3512,
+    BLAKE,
+    BLAKE2B,
+    BLAKE2S,
+    BLAKE3,
+    CRC64,
+    CRC32,
+    /// CRC-32-Castagnoli
+    CRC32C,
+    CRC16,
+    CRC8,
+    MD5,
+    /// MD6-128
+    MD6128,
+    /// MD6-256
+    MD6256,
+    /// MD6-512
+    MD6512,
+    WHIRLPOOL,
+    XOR8,
+    /// xxHash, 64 bits
+    XXH64,
+    /// XXH3, 128 bits
+    XXH128,
+}
+
+impl FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let algorithm = match s.replace('_', "-").to_lowercase().as_str() {
+            "xxh64" | "xxhash64" => return Ok(Self::XXH64),
+            "xxh128" | "xxh3-128" | "xxhash128" => return Ok(Self::XXH128),
+            _ => checksums::Algorithm::from_str(s)?,
+        };
+        Ok(match algorithm {
+            checksums::Algorithm::SHA1 => Self::SHA1,
+            checksums::Algorithm::SHA2224 => Self::SHA2224,
+            checksums::Algorithm::SHA2256 => Self::SHA2256,
+            checksums::Algorithm::SHA2384 => Self::SHA2384,
+            checksums::Algorithm::SHA2512 => Self::SHA2512,
+            checksums::Algorithm::SHA3256 => Self::SHA3256,
+            checksums::Algorithm::SHA3512 => Self::SHA3512,
+            checksums::Algorithm::BLAKE => Self::BLAKE,
+            checksums::Algorithm::BLAKE2B => Self::BLAKE2B,
+            checksums::Algorithm::BLAKE2S => Self::BLAKE2S,
+            checksums::Algorithm::BLAKE3 => Self::BLAKE3,
+            checksums::Algorithm::CRC64 => Self::CRC64,
+            checksums::Algorithm::CRC32 => Self::CRC32,
+            checksums::Algorithm::CRC32C => Self::CRC32C,
+            checksums::Algorithm::CRC16 => Self::CRC16,
+            checksums::Algorithm::CRC8 => Self::CRC8,
+            checksums::Algorithm::MD5 => Self::MD5,
+            checksums::Algorithm::MD6128 => Self::MD6128,
+            checksums::Algorithm::MD6256 => Self::MD6256,
+            checksums::Algorithm::MD6512 => Self::MD6512,
+            checksums::Algorithm::WHIRLPOOL => Self::WHIRLPOOL,
+            checksums::Algorithm::XOR8 => Self::XOR8,
+        })
+    }
+}
+
+impl Algorithm {
+    /// Check if the checksums are wide enough for two files with the same one to be taken as
+    /// identical, barring a deliberate collision.
+    ///
+    pub fn identifies_contents(self) -> bool {
+        !matches!(
+            self,
+            Self::CRC32 | Self::CRC32C | Self::CRC16 | Self::CRC8 | Self::XOR8
+        )
+    }
+
+    /// Extension of the checksum files of this algorithm, as used by the `sha256sum`-like tools.
+    ///
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::SHA1 => "sha1",
+            Self::SHA2224 => "sha224",
+            Self::SHA2256 => "sha256",
+            Self::SHA2384 => "sha384",
+            Self::SHA2512 => "sha512",
+            Self::SHA3256 => "sha3-256",
+            Self::SHA3512 => "sha3-512",
+            Self::BLAKE => "blake",
+            Self::BLAKE2B => "blake2b",
+            Self::BLAKE2S => "blake2s",
+            Self::BLAKE3 => "blake3",
+            Self::CRC64 => "crc64",
+            Self::CRC32 => "crc32",
+            Self::CRC32C => "crc32c",
+            Self::CRC16 => "crc16",
+            Self::CRC8 => "crc8",
+            Self::MD5 => "md5",
+            Self::MD6128 => "md6-128",
+            Self::MD6256 => "md6-256",
+            Self::MD6512 => "md6-512",
+            Self::WHIRLPOOL => "whirlpool",
+            Self::XOR8 => "xor8",
+            Self::XXH64 => "xxh64",
+            Self::XXH128 => "xxh128",
+        }
+    }
+
+    /// Create a hasher computing this checksum.
+    ///
+    pub fn hasher(self) -> Box<dyn Hasher> {
+        match (self, self.pulled()) {
+            (_, Some(algorithm)) => Box::new(Pulled::new(algorithm)),
+            (Self::BLAKE3, None) => Box::new(blake3::Hasher::new()),
+            (Self::XXH64, None) => Box::new(Xxh64::new(0)),
+            // XXH128, the only one left
+            (_, None) => Box::new(Xxh128(Xxh3Default::new())),
+        }
+    }
+
+    /// Algorithm of the `checksums` crate computing this checksum, if left to it.
+    ///
+    fn pulled(self) -> Option<checksums::Algorithm> {
+        Some(match self {
+            Self::SHA1 => checksums::Algorithm::SHA1,
+            Self::SHA2224 => checksums::Algorithm::SHA2224,
+            Self::SHA2256 => checksums::Algorithm::SHA2256,
+            Self::SHA2384 => checksums::Algorithm::SHA2384,
+            Self::SHA2512 => checksums::Algorithm::SHA2512,
+            Self::SHA3256 => checksums::Algorithm::SHA3256,
+            Self::SHA3512 => checksums::Algorithm::SHA3512,
+            Self::BLAKE => checksums::Algorithm::BLAKE,
+            Self::BLAKE2B => checksums::Algorithm::BLAKE2B,
+            Self::BLAKE2S => checksums::Algorithm::BLAKE2S,
+            Self::CRC64 => checksums::Algorithm::CRC64,
+            Self::CRC32 => checksums::Algorithm::CRC32,
+            Self::CRC32C => checksums::Algorithm::CRC32C,
+            Self::CRC16 => checksums::Algorithm::CRC16,
+            Self::CRC8 => checksums::Algorithm::CRC8,
+            Self::MD5 => checksums::Algorithm::MD5,
+            Self::MD6128 => checksums::Algorithm::MD6128,
+            Self::MD6256 => checksums::Algorithm::MD6256,
+            Self::MD6512 => checksums::Algorithm::MD6512,
+            Self::WHIRLPOOL => checksums::Algorithm::WHIRLPOOL,
+            Self::XOR8 => checksums::Algorithm::XOR8,
+            Self::BLAKE3 | Self::XXH64 | Self::XXH128 => return None,
+        })
+    }
+}
+
+/// Streaming checksum of some data.
+///
+/// The data is fed in pieces of any size, so it can be hashed while being copied.
+///
+pub trait Hasher: Send {
+    /// Feed the next piece of the data.
+    ///
+    fn update(&mut self, data: &[u8]);
+
+    /// Checksum of all the data fed.
+    ///
+    fn finish(self: Box<Self>) -> std::io::Result<String>;
+}
+
+impl Hasher for blake3::Hasher {
+    fn update(&mut self, data: &[u8]) {
+        blake3::Hasher::update(self, data);
+    }
+
+    fn finish(self: Box<Self>) -> std::io::Result<String> {
+        Ok(checksums::hash_string(self.finalize().as_bytes()))
+    }
+}
+
+impl Hasher for Xxh64 {
+    fn update(&mut self, data: &[u8]) {
+        Xxh64::update(self, data);
+    }
+
+    fn finish(self: Box<Self>) -> std::io::Result<String> {
+        Ok(format!("{:016X}", self.digest()))
+    }
+}
+
+/// XXH3 hasher, giving the 128 bits checksum.
+///
+struct Xxh128(Xxh3Default);
+
+impl Hasher for Xxh128 {
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    fn finish(self: Box<Self>) -> std::io::Result<String> {
+        Ok(format!("{:032X}", self.0.digest128()))
+    }
+}
+
+/// Hasher of the `checksums` crate.
+///
+/// Those pull the data themselves, so they run on a thread of their own, fed through a channel.
+/// Only needed when fed in pieces, as `hash_reader` lets them pull from the reader.
+///
+struct Pulled {
+    chunks: SyncSender<Vec<u8>>,
+    hashing: JoinHandle<String>,
+}
+
+impl Pulled {
+    fn new(algorithm: checksums::Algorithm) -> Self {
+        let (chunks, rx) = mpsc::sync_channel(4);
+        let hashing = std::thread::spawn(move || {
+            checksums::hash_reader(&mut ChannelReader::new(rx), algorithm)
+        });
+        Self { chunks, hashing }
+    }
+}
+
+impl Hasher for Pulled {
+    fn update(&mut self, data: &[u8]) {
+        // The thread only ends once the channel is closed
+        let _ = self.chunks.send(data.to_vec());
+    }
+
+    fn finish(self: Box<Self>) -> std::io::Result<String> {
+        let Self { chunks, hashing } = *self;
+        drop(chunks);
+        hashing
+            .join()
+            .map_err(|_| std::io::Error::other("The hashing thread panicked"))
+    }
+}
+
+/// Reader of the pieces of data sent through a channel.
+///
+struct ChannelReader {
+    rx: Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    position: usize,
+}
+
+impl ChannelReader {
+    fn new(rx: Receiver<Vec<u8>>) -> Self {
+        Self {
+            rx,
+            chunk: Vec::new(),
+            position: 0,
+        }
+    }
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+        while self.position == self.chunk.len() {
+            match self.rx.recv() {
+                Ok(chunk) => {
+                    self.chunk = chunk;
+                    self.position = 0;
+                }
+                Err(_) => return Ok(0),
+            }
+        }
+        let read = buffer.len().min(self.chunk.len() - self.position);
+        buffer[..read].copy_from_slice(&self.chunk[self.position..self.position + read]);
+        self.position += read;
+        Ok(read)
+    }
+}
+
+/// Reader passing the data of another one, but ending at its first error, kept aside.
+///
+/// The hashers of the `checksums` crate take a failure to read as the end of the data, so the
+/// error is only known this way.
+///
+struct Failing<'a> {
+    reader: &'a mut dyn Read,
+    error: Option<std::io::Error>,
+}
+
+impl Read for Failing<'_> {
+    fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            match self.reader.read(buffer) {
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    self.error = Some(e);
+                    return Ok(0);
+                }
+                read => return read,
+            }
+        }
+    }
+}
+
+/// Hash everything a reader gives, with the given algorithm.
+///
+pub fn hash_reader(reader: &mut dyn Read, algorithm: Algorithm) -> std::io::Result<String> {
+    if let Some(algorithm) = algorithm.pulled() {
+        let mut failing = Failing {
+            reader,
+            error: None,
+        };
+        let checksum = checksums::hash_reader(&mut failing, algorithm);
+        return match failing.error {
+            Some(e) => Err(e),
+            None => Ok(checksum),
+        };
+    }
+
+    let mut hasher = algorithm.hasher();
+    let mut buffer = vec![0; CHUNK_SIZE];
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        hasher.update(&buffer[..read]);
+    }
+    hasher.finish()
+}
@@ -0,0 +1,330 @@
+use std::{
+    fmt::Display,
+    io::Write,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{Local, NaiveDate, NaiveDateTime, TimeDelta};
+use rusqlite::{params, Connection, OpenFlags, ToSql};
+use serde::Serialize;
+
+use crate::{
+    config::ExportFormat,
+    fs::{Collision, Transfer},
+};
+
+/// Format of the times recorded, the same as in the trace file. Sorts as text.
+///
+pub(crate) const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// How long a writer waits for another one holding the database.
+///
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS events (
+        id INTEGER PRIMARY KEY,
+        time TEXT NOT NULL,
+        author TEXT NOT NULL,
+        src TEXT,
+        via TEXT,
+        dst TEXT,
+        operation TEXT NOT NULL,
+        details TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS events_time ON events (time);
+";
+
+/// An event recorded in the history.
+///
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct HistoryEvent {
+    /// Local time of the event
+    pub time: String,
+    /// Syncjob, or part of the program, the event comes from
+    pub author: String,
+    pub operation: String,
+    pub details: String,
+}
+
+impl Display for HistoryEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{}] [{}] {} {}",
+            self.time, self.author, self.operation, self.details
+        )
+    }
+}
+
+/// A file delivered, as recorded in the history. One row of the transfers exported.
+///
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct TransferRecord {
+    /// Local time of the delivery
+    pub time: String,
+    /// Syncjob that delivered the file
+    pub job: String,
+    /// Volume the file was delivered from
+    pub source: Option<String>,
+    /// Volume the file was delivered to, on its way to the destination one
+    pub via: Option<String>,
+    /// Volume the file is meant for
+    pub destination: Option<String>,
+    /// Where the file was moved from
+    pub src: PathBuf,
+    /// Where the file was delivered
+    pub dst: PathBuf,
+    /// Size of the file, in bytes
+    pub size: u64,
+    /// Milliseconds the move took, retries included
+    pub duration_ms: u64,
+    /// Bytes moved per second, if the move took long enough to tell
+    pub throughput: Option<u64>,
+    /// Checksum of the file as `<algorithm>:<checksum>`, if checked
+    pub checksum: Option<String>,
+    /// Number of failed attempts retried before delivering the file
+    pub retries: u8,
+    /// What became of the file already at the destination, if any
+    pub collision: Collision,
+}
+
+impl TransferRecord {
+    /// Record of the delivery traced by the `event`, if it is one traced as a structured record.
+    ///
+    fn from_event(event: &HistoryEvent) -> Option<Self> {
+        if event.operation != "DELIVERED" {
+            return None;
+        }
+        let transfer = serde_json::from_str::<Transfer>(&event.details).ok()?;
+        let (source, via, destination) = match parse_author(&event.author) {
+            Some((src, via, dst)) => (Some(src), Some(via), Some(dst)),
+            None => (None, None, None),
+        };
+        Some(Self {
+            time: event.time.clone(),
+            job: event.author.clone(),
+            source: source.map(str::to_owned),
+            via: via.map(str::to_owned),
+            destination: destination.map(str::to_owned),
+            src: transfer.src,
+            dst: transfer.dst,
+            size: transfer.size,
+            duration_ms: transfer.duration_ms,
+            throughput: transfer.throughput,
+            checksum: transfer.checksum,
+            retries: transfer.retries,
+            collision: transfer.collision,
+        })
+    }
+}
+
+/// Filters of the events looked up in the history. Every event matches the default one.
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HistoryQuery {
+    /// If provided, only the events from then on
+    pub since: Option<NaiveDateTime>,
+    /// If provided, only the events up to then
+    pub until: Option<NaiveDateTime>,
+    /// If provided, only the events of the syncjobs from, through or to this volume
+    pub volume: Option<String>,
+    /// If provided, only the events mentioning this text in their details, ex. a file name
+    pub file: Option<String>,
+    /// If provided, only the events of this operation (MV, MKDIR, FAILED...), in any case
+    pub operation: Option<String>,
+}
+
+/// SQLite database where the traced events are recorded, so they can be queried later on.
+///
+pub(crate) struct History {
+    conn: Connection,
+}
+
+impl History {
+    /// Open the database at `path`, creating it if needed.
+    ///
+    pub(crate) fn open(path: &Path) -> Result<Self> {
+        let conn = Connection::open(path)
+            .map_err(|e| anyhow!("{path:?}: Unable to open the history: {e}"))?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        // The history can be queried while the events are being recorded
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self { conn })
+    }
+
+    /// Record the `events`, all at once.
+    ///
+    pub(crate) fn record(&mut self, events: &[HistoryEvent]) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        {
+            let mut insert = tx.prepare_cached(
+                "INSERT INTO events (time, author, src, via, dst, operation, details)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            )?;
+            for event in events {
+                let (src, via, dst) = match parse_author(&event.author) {
+                    Some((src, via, dst)) => (Some(src), Some(via), Some(dst)),
+                    None => (None, None, None),
+                };
+                insert.execute(params![
+                    event.time,
+                    event.author,
+                    src,
+                    via,
+                    dst,
+                    event.operation,
+                    event.details
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+/// Split the author of the events of a syncjob (ex. `foo -bar-> baz`) into its source, via and
+/// destination volumes. The via volume of a direct syncjob is its destination.
+///
+fn parse_author(author: &str) -> Option<(&str, &str, &str)> {
+    let (src, rest) = author.split_once(" -")?;
+    let (via, dst) = rest.split_once("-> ")?;
+    Some((src, if via == "_" { dst } else { via }, dst))
+}
+
+/// Look up the events recorded in the database at `path` matching the `query`, oldest first.
+///
+pub(crate) fn query(path: &Path, query: &HistoryQuery) -> Result<Vec<HistoryEvent>> {
+    if !path.exists() {
+        bail!("{path:?}: History not found");
+    }
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| anyhow!("{path:?}: Unable to open the history: {e}"))?;
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+
+    let mut conditions = Vec::new();
+    let mut values: Vec<Box<dyn ToSql>> = Vec::new();
+    if let Some(since) = query.since {
+        conditions.push("time >= ?");
+        values.push(Box::new(since.format(TIME_FORMAT).to_string()));
+    }
+    if let Some(until) = query.until {
+        conditions.push("time <= ?");
+        values.push(Box::new(until.format(TIME_FORMAT).to_string()));
+    }
+    if let Some(volume) = &query.volume {
+        conditions.push("? IN (src, via, dst)");
+        values.push(Box::new(volume.clone()));
+    }
+    if let Some(file) = &query.file {
+        // The paths are traced quoted, with their special characters escaped
+        let quoted = format!("{file:?}");
+        let escaped = quoted[1..quoted.len() - 1]
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+        conditions.push("details LIKE ? ESCAPE '\\'");
+        values.push(Box::new(format!("%{escaped}%")));
+    }
+    if let Some(operation) = &query.operation {
+        conditions.push("operation = ? COLLATE NOCASE");
+        values.push(Box::new(operation.clone()));
+    }
+
+    let mut sql = "SELECT time, author, operation, details FROM events".to_owned();
+    if !conditions.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&conditions.join(" AND "));
+    }
+    sql.push_str(" ORDER BY time, id");
+
+    let mut statement = conn.prepare(&sql)?;
+    let events = statement
+        .query_map(
+            rusqlite::params_from_iter(values.iter().map(|v| v.as_ref())),
+            |row| {
+                Ok(HistoryEvent {
+                    time: row.get(0)?,
+                    author: row.get(1)?,
+                    operation: row.get(2)?,
+                    details: row.get(3)?,
+                })
+            },
+        )?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(events)
+}
+
+/// Write the files delivered according to the database at `path` and matching the `query`, oldest
+/// first, to `out` in the given `format`. Returns how many were written.
+///
+/// Only the deliveries traced as structured records are written. The ones traced as text, by
+/// earlier versions or for the paths not valid UTF-8, lack their size and are left out.
+///
+pub(crate) fn export(
+    path: &Path,
+    query: &HistoryQuery,
+    format: ExportFormat,
+    out: impl Write,
+) -> Result<usize> {
+    let query = HistoryQuery {
+        operation: Some("DELIVERED".to_owned()),
+        ..query.clone()
+    };
+    let events = self::query(path, &query)?;
+    let records = events
+        .iter()
+        .filter_map(TransferRecord::from_event)
+        .collect::<Vec<_>>();
+    if records.len() < events.len() {
+        log::warn!(
+            "{} deliveries traced as text, without their size, are left out",
+            events.len() - records.len()
+        );
+    }
+
+    match format {
+        ExportFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(out);
+            for record in &records {
+                writer.serialize(record)?;
+            }
+            writer.flush()?;
+        }
+    }
+    Ok(records.len())
+}
+
+/// Parse a point in time: a local date (`2024-12-31`), a local date and time
+/// (`2024-12-31 23:59:59`, or with a `T`), or a time ago (ex. `90s`, `30m`, `12h`, `7d`, `2w`).
+///
+pub fn parse_time(s: &str) -> Result<NaiveDateTime> {
+    let s = s.trim();
+    for format in [TIME_FORMAT, "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"] {
+        if let Ok(time) = NaiveDateTime::parse_from_str(s, format) {
+            return Ok(time);
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return Ok(date.and_time(Default::default()));
+    }
+
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number = number
+        .parse::<i64>()
+        .map_err(|_| anyhow!("\"{s}\" is not a valid time, expected a date or a time ago"))?;
+    let ago = match unit.trim() {
+        "s" => TimeDelta::try_seconds(number),
+        "m" => TimeDelta::try_minutes(number),
+        "h" => TimeDelta::try_hours(number),
+        "d" => TimeDelta::try_days(number),
+        "w" => TimeDelta::try_weeks(number),
+        _ => bail!("\"{s}\" has an unknown time unit, expected s, m, h, d or w"),
+    }
+    .ok_or_else(|| anyhow!("\"{s}\" is too long ago"))?;
+    Ok(Local::now().naive_local() - ago)
+}
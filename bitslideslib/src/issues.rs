@@ -0,0 +1,141 @@
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use tokio::{fs::OpenOptions, io::AsyncWriteExt};
+
+use crate::history::TIME_FORMAT;
+
+/// Kind of problem the syncjobs went through without stopping.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IssueKind {
+    /// A file was left in its slide for being in use by another program
+    Locked,
+    /// A file was left in its slide for being over the size cap
+    Oversize,
+    /// A folder or a file of a slide could not be read, and was left in place
+    Unreadable,
+    /// The copy of a file did not match its source, even after retrying
+    Checksum,
+    /// A file could not be moved, failing its sync
+    Move,
+    /// A delivered file no longer matched its checksum when checked again
+    Corrupted,
+    /// The checksum file of a delivery could not be written
+    Sidecar,
+    /// The signed manifest of a sync could not be written
+    Manifest,
+    /// The checksums of the delivered files could not be kept
+    Cache,
+    /// A sync ended by an error, tried again later on
+    Sync,
+}
+
+/// IssueKind Display implementation, the same names as in the JSON report.
+///
+impl Display for IssueKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self {
+            Self::Locked => "locked",
+            Self::Oversize => "oversize",
+            Self::Unreadable => "unreadable",
+            Self::Checksum => "checksum",
+            Self::Move => "move",
+            Self::Corrupted => "corrupted",
+            Self::Sidecar => "sidecar",
+            Self::Manifest => "manifest",
+            Self::Cache => "cache",
+            Self::Sync => "sync",
+        };
+        write!(f, "{kind}")
+    }
+}
+
+/// A problem a syncjob went through without stopping.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Issue {
+    /// Local time of the problem
+    pub time: String,
+    /// Syncjob, as in the logs and the trace
+    pub job: String,
+    pub kind: IssueKind,
+    /// File or folder involved, if any
+    pub path: Option<PathBuf>,
+    pub detail: String,
+}
+
+impl Issue {
+    pub(crate) fn new(job: &str, kind: IssueKind, path: Option<&Path>, detail: String) -> Self {
+        Self {
+            time: Local::now().format(TIME_FORMAT).to_string(),
+            job: job.to_owned(),
+            kind,
+            path: path.map(Path::to_owned),
+            detail,
+        }
+    }
+}
+
+/// Issue Display implementation, a line like the ones of the trace.
+///
+impl Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] [{}] {}", self.time, self.job, self.kind)?;
+        if let Some(path) = &self.path {
+            write!(f, " {path:?}:")?;
+        }
+        write!(f, " {}", self.detail)
+    }
+}
+
+/// Error of a move already named in the issues of the run, so the sync it fails is not reported
+/// again.
+///
+#[derive(Debug)]
+pub(crate) struct Reported(pub(crate) String);
+
+/// Reported Display implementation, the error of the move.
+///
+impl Display for Reported {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for Reported {}
+
+/// Report of the issues of a run written next to the `trace`, like `bitslides.log.issues` for
+/// `bitslides.log`.
+///
+pub fn report_path(trace: &Path) -> PathBuf {
+    let mut name = trace.file_name().unwrap_or_default().to_owned();
+    name.push(".issues");
+    trace.with_file_name(name)
+}
+
+/// Append the `issues` to the report at `path`, one JSON object per line, so the report keeps the
+/// issues of every run tracing to the same file.
+///
+pub(crate) async fn write_report(path: &Path, issues: &[Issue]) -> Result<()> {
+    let mut lines = String::new();
+    for issue in issues {
+        lines.push_str(&serde_json::to_string(issue)?);
+        lines.push('\n');
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await
+        .map_err(|e| anyhow!("{path:?}: Unable to open the issues report: {e}"))?;
+    file.write_all(lines.as_bytes()).await?;
+    file.flush().await?;
+    Ok(())
+}
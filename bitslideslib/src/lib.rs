@@ -1,74 +1,518 @@
 use anyhow::{bail, Result};
-use fs::MoveStrategy;
-use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
-use slide::Slide;
+use cache::{Caches, ChecksumCache};
+use filter::{Filter, Share};
+use fs::{throttle::Throttle, MoveStrategy, Transferred};
+use issues::Reported;
+use manifest::Signer;
+use mqtt::Publisher;
+use notify::EventKind;
 use std::{
-    collections::HashMap,
+    cell::OnceCell,
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet, VecDeque},
+    future::Future,
     path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+use syncjob::{Stage, SyncJob, SyncJobs};
+use tokio::{
+    sync::{mpsc, watch, Notify, Semaphore},
+    time::{interval_at, sleep_until, Duration, Instant, MissedTickBehavior},
 };
-use syncjob::{SyncJob, SyncJobs};
-use volume::Volume;
 
 #[cfg(target_os = "windows")]
 use std::ffi::CStr;
 
+use control::{Gate, Registry};
+use progress::Progress;
 use tracer::Tracer;
+use watchers::{watch_mounts, Watchers};
 
+mod cache;
 pub mod config;
+mod control;
+mod filter;
 mod fs;
+mod hasher;
+mod history;
+mod issues;
+mod manifest;
+mod mqtt;
+mod progress;
+mod route;
 mod slide;
 mod syncjob;
+mod tidy;
 mod tracer;
+mod undo;
+mod verify;
 mod volume;
+mod watchers;
 
-pub use config::{Algorithm, CollisionPolicy, GlobalConfig, RootsetConfig};
+pub use config::{
+    Algorithm, Balance, CheckMode, CollisionPolicy, ConflictPolicy, ExportFormat, GlobalConfig,
+    Identical, MqttConfig, OutputFormat, Reverify, RootsetConfig, Sanitize, Sidecar, Trash,
+};
+pub use control::{ControlCommand, JobStatus};
+pub use fs::{Collision, Transfer};
+pub use hasher::{hash_reader, Hasher};
+pub use history::{parse_time, HistoryEvent, HistoryQuery, TransferRecord};
+pub use issues::{report_path, Issue, IssueKind};
+pub use manifest::{generate_key, DeliveryManifest};
+pub use progress::{JobProgress, Phase, ProgressReport};
+pub use route::{Decision, Route};
+pub use slide::Slide;
+pub use syncjob::PlannedJob;
+pub use undo::{UndoOutcome, Undone};
+pub use verify::{Verdict, Verification};
+pub use volume::Volume;
 
 const DEFAULT_SLIDE_CONFIG_FILE: &str = ".slide.yml";
 
+/// Outcome of the syncjobs.
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Summary {
+    /// Number of syncjobs run
+    pub syncjobs: usize,
+    /// Number of syncjobs that ended with an error
+    pub failed: usize,
+    /// What each syncjob did, in the order they were run
+    pub jobs: Vec<JobStatus>,
+    /// Time since the syncjobs were started
+    pub duration: Duration,
+    /// Problems the syncjobs went through without stopping, in the order they happened
+    pub issues: Vec<Issue>,
+}
+
+/// Summary Display implementation, adding up what every syncjob did.
+///
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let total = |stat: fn(&JobStatus) -> u64| self.jobs.iter().map(stat).sum::<u64>();
+        write!(
+            f,
+            "{} file(s) and {} byte(s) moved in {} sync(s) of {} syncjob(s), {} skipped, {} retry(ies), {} failure(s), {} issue(s), took {:?}",
+            total(|job| job.files),
+            total(|job| job.bytes),
+            total(|job| job.syncs),
+            self.jobs.len(),
+            total(|job| job.skipped),
+            total(|job| job.retries),
+            total(|job| job.failures),
+            self.issues.len(),
+            Duration::from_millis(self.duration.as_millis() as u64),
+        )
+    }
+}
+
+impl Summary {
+    /// Account for the result of a syncjob, logging its error if any.
+    ///
+    fn record(&mut self, result: Result<()>) {
+        self.syncjobs += 1;
+        if let Err(e) = result {
+            log::error!("{e}");
+            self.failed += 1;
+        }
+    }
+}
+
 #[allow(dead_code)]
 pub struct Token {
-    /// Watcher OS task handle. Dropped first to force the syncjob tasks to end.
-    watcher: RecommendedWatcher,
-    handles: Vec<tokio::task::JoinHandle<Result<()>>>,
+    /// Syncjobs running, by the batch they were started in
+    batches: Vec<Batch>,
     tracer: Option<tokio::task::JoinHandle<()>>,
+    /// Tracer of the run summary, dropped before awaiting the tracer task
+    trace: Tracer,
+    progress: Option<tokio::task::JoinHandle<()>>,
+    /// Control server, if any
+    control: Option<control::Server>,
+    /// State of the syncjobs, closed to cut their ongoing syncs short
+    registry: Registry,
+    /// Outcome of the syncjobs already finished
+    summary: Summary,
+    /// Notified when the volumes change so that other syncjobs would be planned
+    replanned: Arc<Notify>,
+    /// Task looking for the volumes plugged in or out. None on single passes.
+    planner: Option<tokio::task::JoinHandle<()>>,
+    /// When the syncjobs were started, kept along their rebuilds
+    started: Instant,
+    /// Where the issues of the run are reported, next to the trace if any
+    issues_report: Option<PathBuf>,
+    /// Where the state of the syncjobs is published, if anywhere
+    publisher: Option<Arc<Publisher>>,
+    /// Where the moves report how far they got, for the syncjobs rebuilt. Dropped before awaiting
+    /// the progress display
+    reporter: Progress,
+    /// Bound of the slides synced in parallel, shared with the syncjobs rebuilt
+    permits: Arc<Semaphore>,
+    /// Rate limit of every syncjob together, shared with the syncjobs rebuilt
+    throttle: Option<Arc<Throttle>>,
+}
+
+/// Syncjobs started together, sharing a watcher.
+///
+struct Batch {
+    /// Watcher OS task handle. Dropped first to force the syncjob tasks to end. None on single passes.
+    watcher: Option<Watchers>,
+    /// Syncjobs of the batch not stopped yet
+    running: Vec<Running>,
+}
+
+/// A syncjob started, along with what it was started from.
+///
+struct Running {
+    /// Name of the syncjob, as registered
+    job: String,
+    /// The syncjob as planned, see [`plan_names`]
+    planned: String,
+    /// Slide sending the files back the other way, if synced too
+    reciprocal: Option<PathBuf>,
+    /// Route taken among the ones the slide is spread over, along with their number
+    share: Option<(usize, usize)>,
+    /// How far the syncjob got, for the syncjobs it feeds
+    stage: watch::Receiver<Stage>,
+    handle: tokio::task::JoinHandle<Result<()>>,
+}
+
+/// How a syncjob to start is tied to the others of its plan.
+///
+struct Wiring {
+    /// Where the syncjob tells how far it got
+    stage: watch::Sender<Stage>,
+    /// How far the syncjobs feeding it got
+    upstreams: Vec<watch::Receiver<Stage>>,
+    /// Slide sending the files back the other way, if synced too
+    reciprocal: Option<PathBuf>,
+}
+
+/// What the syncjobs are started with. Loaded before starting any, so that nothing is left half
+/// started if it fails.
+///
+struct Setup {
+    volumes: HashMap<String, Volume>,
+    syncjobs: SyncJobs,
+    move_req: MoveStrategy,
+    filter: Filter,
+    signer: Option<Arc<Signer>>,
 }
 
 impl Token {
+    /// Wait for a shutdown requested through the control interface. Never happens without it.
+    ///
+    pub async fn stopped(&self) {
+        match &self.control {
+            Some(control) => control.stopped().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Sync right away the syncjobs matching the `target` (a syncjob, or a volume meaning every
+    /// syncjob from, via or to it), or every syncjob. Returns their names.
+    ///
+    /// Same as the `sync-now` command of the control interface, whether it is served or not.
+    ///
+    pub fn sync_now(&self, target: Option<&str>) -> Result<Vec<String>> {
+        self.registry.trigger(target)
+    }
+
+    /// What each syncjob did so far: files and bytes moved, retries, failures, and when its last
+    /// sync ended and how long it took.
+    ///
+    /// Same as the `status` command of the control interface, whether it is served or not.
+    ///
+    pub fn stats(&self) -> Vec<JobStatus> {
+        self.registry.snapshot()
+    }
+
+    /// How far the ongoing sync of each syncjob got, or its last one once idle: files and bytes
+    /// pending and moved, and what it is doing. Along with their totals.
+    ///
+    pub fn progress(&self) -> ProgressReport {
+        self.registry.progress().into()
+    }
+
+    /// Wait for the volumes to change so that the syncjobs running are no longer the ones that
+    /// would be planned, like when a drive is plugged in or a slide config file is edited. Never
+    /// happens on single passes.
+    ///
+    /// The syncjobs are not rebuilt on their own, see [`rebuild`].
+    ///
+    pub async fn replanned(&self) {
+        match &self.planner {
+            Some(_) => self.replanned.notified().await,
+            None => std::future::pending().await,
+        }
+    }
+
     fn new(
-        watcher: RecommendedWatcher,
-        handles: Vec<tokio::task::JoinHandle<Result<()>>>,
+        batches: Vec<Batch>,
         tracer: Option<tokio::task::JoinHandle<()>>,
+        trace: Tracer,
+        progress: Option<tokio::task::JoinHandle<()>>,
+        control: Option<control::Server>,
+        registry: Registry,
     ) -> Self {
         Self {
-            watcher,
-            handles,
+            batches,
             tracer,
+            trace,
+            progress,
+            control,
+            registry,
+            summary: Summary::default(),
+            replanned: Arc::new(Notify::new()),
+            planner: None,
+            started: Instant::now(),
+            issues_report: None,
+            publisher: None,
+            reporter: Progress::default(),
+            permits: Arc::new(Semaphore::new(1)),
+            throttle: None,
         }
     }
+
+    /// The syncjobs running, as planned, sorted like [`plan_names`].
+    ///
+    fn plan(&self) -> Vec<String> {
+        let mut names = self
+            .batches
+            .iter()
+            .flat_map(|batch| &batch.running)
+            .map(|running| running.planned.clone())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
 }
 
-pub async fn enough(token: Token) -> Result<()> {
+/// Stop the syncjobs and wait for them to finish.
+///
+/// Returns how many syncjobs were run, how many of them failed and what each of them did. Their
+/// totals are logged and traced as the summary of the run.
+///
+pub async fn enough(token: Token) -> Result<Summary> {
     // TODO: Ideally this should be happening in the Drop impl for Token. But that wont let us control the results of the awaited tasks.
 
-    let watcher = token.watcher;
-    let handles = token.handles;
+    let batches = token.batches;
     let tracer = token.tracer;
+    let progress = token.progress;
+    let mut summary = token.summary;
+
+    if let Some(planner) = token.planner {
+        planner.abort();
+    }
 
-    // Drop the watcher first, so that the mpsc channels can be closed
+    // Nobody can ask about the syncjobs once they are stopping
+    if let Some(control) = token.control {
+        control.shutdown();
+    }
+    // The files being copied are left in place, rather than waiting for them
+    token.registry.close();
+
+    // Drop the watchers first, so that the mpsc channels can be closed
     // and the syncjob tasks can finish
-    drop(watcher);
+    let mut handles = Vec::new();
+    for batch in batches {
+        drop(batch.watcher);
+        handles.extend(batch.running.into_iter().map(|running| running.handle));
+    }
 
     // Await all the handles. When every syncjob task finishes, its
     // tracer mpsc channel will be closed
     for handle in handles {
-        let _ = handle.await?;
+        summary.record(handle.await?);
     }
 
-    // Await the tracer if any
+    summary.jobs = token.registry.snapshot();
+    summary.duration = token.started.elapsed();
+    summary.issues.extend(token.registry.issues());
+    log::info!("Run summary: {summary}");
+    token
+        .trace
+        .async_log("SUMMARY", &summary.to_string())
+        .await?;
+    // Losing the report leaves the issues in the log and the trace
+    if let Some(path) = token.issues_report.filter(|_| !summary.issues.is_empty()) {
+        match issues::write_report(&path, &summary.issues).await {
+            Ok(()) => log::warn!(
+                "{} issue(s) during the run, reported to {path:?}",
+                summary.issues.len()
+            ),
+            Err(e) => log::error!("{e}"),
+        }
+    }
+    if let Some(publisher) = token.publisher {
+        publisher.run_summary(&summary.to_string());
+        publisher.close().await;
+    }
+    drop(token.trace);
+    drop(token.reporter);
+
+    // Await the tracer and the progress display if any
     if let Some(tracer) = tracer {
         tracer.await?;
     }
+    if let Some(progress) = progress {
+        progress.await?;
+    }
 
+    Ok(summary)
+}
+
+/// Start the syncjobs planned from the volumes found now, like after [`Token::replanned`].
+///
+/// The new syncjobs are planned and checked before stopping any, so the ones running are left as
+/// they are if that fails. Only the syncjobs planned differently are restarted, along with the
+/// ones waiting for their stage or sending files back their way, and the ones no longer planned
+/// are stopped. The syncjobs restarted keep their statistics, and stay paused if they were. The
+/// outcome of the syncjobs stopped is accounted for in the [`Summary`] the token ends with.
+///
+pub async fn rebuild(token: &mut Token, config: GlobalConfig) -> Result<()> {
+    if let Some(planner) = token.planner.take() {
+        planner.abort();
+    }
+    let result = restart(token, &config).await;
+    // The volumes are watched again, for the next change to the syncjobs running now
+    token.planner = Some(tokio::spawn(watch_volumes(
+        config.rootsets.clone(),
+        config.strict,
+        config.poll,
+        Duration::from_secs(config.poll_interval),
+        token.plan(),
+        token.replanned.clone(),
+    )));
+    result
+}
+
+/// Restart the syncjobs of the `token` planned differently from the volumes found now, see
+/// [`rebuild`].
+///
+async fn restart(token: &mut Token, config: &GlobalConfig) -> Result<()> {
+    let Setup {
+        volumes,
+        syncjobs,
+        move_req,
+        filter,
+        signer,
+    } = set_up(config)?;
+
+    let running = token
+        .batches
+        .iter()
+        .flat_map(|batch| &batch.running)
+        .map(|running| (running.planned.as_str(), running))
+        .collect::<HashMap<_, _>>();
+    let planned = syncjobs
+        .iter()
+        .map(|syncjob| plan_name(&volumes, syncjob))
+        .collect::<Vec<_>>();
+    let mut restarted = syncjobs
+        .iter()
+        .zip(&planned)
+        .map(|(syncjob, planned)| {
+            running.get(planned.as_str()).is_none_or(|running| {
+                running.reciprocal != reciprocal(&volumes, &syncjobs, syncjob)
+                    || running.share != share_of(syncjob)
+            })
+        })
+        .collect::<Vec<_>>();
+    // The syncjobs fed by one restarted have to wait for its new stage
+    while let Some(index) = (0..syncjobs.len()).find(|&index| {
+        !restarted[index]
+            && syncjobs
+                .iter()
+                .zip(&restarted)
+                .any(|(other, restarted)| *restarted && other.feeds(&syncjobs[index]))
+    }) {
+        restarted[index] = true;
+    }
+    // The slides of the syncjobs to start have to be there
+    for (syncjob, _) in syncjobs.iter().zip(&restarted).filter(|(_, r)| **r) {
+        let path = &volumes[&syncjob.src].slides[&syncjob.dst].path;
+        if let Err(e) = path.canonicalize() {
+            bail!("{path:?}: Unable to sync {syncjob:?}: {e}");
+        }
+    }
+    // The syncjobs left running go on staging for the ones restarted
+    let stages = planned
+        .iter()
+        .zip(&restarted)
+        .map(|(planned, restarted)| match restarted {
+            true => None,
+            false => Some(running[planned.as_str()].stage.clone()),
+        })
+        .collect::<Vec<_>>();
+    let kept = planned
+        .iter()
+        .zip(&restarted)
+        .filter(|(_, r)| !**r)
+        .map(|(planned, _)| planned.as_str())
+        .collect::<HashSet<_>>();
+    let stopping = token
+        .batches
+        .iter()
+        .flat_map(|batch| &batch.running)
+        .filter(|running| !kept.contains(running.planned.as_str()))
+        .map(|running| running.job.clone())
+        .collect::<Vec<_>>();
+    log::info!(
+        "Stopping {} syncjob(s) and starting {}, {} left running",
+        stopping.len(),
+        syncjobs.len() - kept.len(),
+        kept.len()
+    );
+
+    // Nothing failing anymore, the syncjobs planned differently are stopped
+    token.registry.close_jobs(&stopping);
+    for batch in &mut token.batches {
+        let (stopped, running) = std::mem::take(&mut batch.running)
+            .into_iter()
+            .partition::<Vec<_>, _>(|running| stopping.contains(&running.job));
+        batch.running = running;
+        for stopped in stopped {
+            token.summary.record(stopped.handle.await?);
+        }
+    }
+    // Along with the watcher of the batches left without syncjobs
+    token.batches.retain(|batch| !batch.running.is_empty());
+    token.registry.unregister(&stopping);
+
+    // The copies interrupted by a crash are rolled back, but where the syncjobs left running write
+    if !config.dry_run {
+        let busy = syncjobs
+            .iter()
+            .zip(&restarted)
+            .filter(|(_, r)| !**r)
+            .map(|(syncjob, _)| resolved(&slide_folders(&volumes, syncjob).1))
+            .collect::<HashSet<_>>();
+        recover_journals(&volumes, &busy).await;
+    }
+
+    let syncjobs = wire(&volumes, syncjobs, stages);
+    if syncjobs.is_empty() {
+        return Ok(());
+    }
+    let batch = execute_syncjobs(
+        &volumes,
+        syncjobs,
+        config.dry_run,
+        config.once,
+        token.trace.clone(),
+        token.reporter.clone(),
+        token.registry.clone(),
+        token.permits.clone(),
+        token.throttle.clone(),
+        Debounce::new(config.debounce, config.debounce_max),
+        Duration::from_secs(config.poll_interval),
+        config.heartbeat.map(Duration::from_secs),
+        &move_req,
+        &filter,
+        signer,
+    )
+    .await?;
+    token.batches.push(batch);
     Ok(())
 }
 
@@ -81,20 +525,125 @@ pub async fn slide(config: GlobalConfig) -> Result<Token> {
     log::debug!("Config: {config:#?}");
 
     // Maybe a tracer task handle
-    let (trace, tracer) = Tracer::new(&config.trace.as_ref()).await?;
+    let (trace, tracer) = Tracer::new(
+        &config.trace.as_ref(),
+        &config.history.as_ref(),
+        config.output,
+    )
+    .await?;
+    let issues_report = config.trace.as_deref().map(report_path);
+    // Maybe a progress display task handle
+    let (progress, progress_display) = Progress::new(config.progress && !config.dry_run);
 
-    let mut volumes = HashMap::new();
+    let Setup {
+        volumes,
+        syncjobs,
+        move_req,
+        filter,
+        signer,
+    } = set_up(&config)?;
 
-    // Analyze each rootset to extract volumes and slides
-    for rootset_config in config.rootsets {
-        let some_volumes = identify_env(&rootset_config.keyword, &rootset_config.roots);
-        match some_volumes {
-            Ok(v) => volumes.extend(v),
-            Err(_) => log::warn!("Error processing some volumes"),
+    // The copies interrupted by a crash are rolled back before anything is synced again
+    if !config.dry_run {
+        recover_journals(&volumes, &HashSet::new()).await;
+    }
+
+    // Maybe an MQTT publisher, told about every change of state of the syncjobs
+    let publisher = config
+        .mqtt
+        .as_ref()
+        .map(|mqtt| Arc::new(Publisher::connect(mqtt)));
+    let registry = Registry::default().publishing(publisher.clone());
+
+    // Maybe a control server, so the state of the syncjobs can be queried while watching
+    let control = match (&config.control, config.once) {
+        (Some(path), false) => Some(control::Server::bind(path, registry.clone()).await?),
+        _ => None,
+    };
+
+    // The summary of the run is traced once every syncjob is over
+    let summary_trace = trace.annotate_author("Run".to_string());
+
+    // Bound the number of slides synced in parallel, so the syncjobs sharing a drive don't
+    // thrash it
+    let permits = Arc::new(Semaphore::new(config.parallel.max(1)));
+    // The rate limit holds for every syncjob together, but for the slides setting their own
+    let throttle = move_req.rate_limit.and_then(Throttle::new).map(Arc::new);
+
+    let running = vec![None; syncjobs.len()];
+    let batch = execute_syncjobs(
+        &volumes,
+        wire(&volumes, syncjobs, running),
+        config.dry_run,
+        config.once,
+        trace,
+        progress.clone(),
+        registry.clone(),
+        permits.clone(),
+        throttle.clone(),
+        Debounce::new(config.debounce, config.debounce_max),
+        Duration::from_secs(config.poll_interval),
+        config.heartbeat.map(Duration::from_secs),
+        &move_req,
+        &filter,
+        signer,
+    )
+    .await?;
+
+    if config.once {
+        // A pass interrupted, its future dropped, lets its syncjobs end rather than leaving them
+        // moving files unattended
+        let _closing = registry.closing();
+        // Without a watcher every syncjob ends after its first pass. The trace is flushed along
+        // with the summary of the run
+        let mut token = Token::new(vec![], tracer, summary_trace, None, None, registry);
+        token.issues_report = issues_report;
+        token.publisher = publisher;
+        for running in batch.running {
+            token.summary.record(running.handle.await?);
+        }
+        // The display ends once every move reported
+        drop(progress);
+        if let Some(progress_display) = progress_display {
+            progress_display.await?;
         }
+        return Ok(token);
     }
 
-    log::debug!("Volumes for all configs: {volumes:#?}");
+    let mut token = Token::new(
+        vec![batch],
+        tracer,
+        summary_trace,
+        progress_display,
+        control,
+        registry,
+    );
+    token.issues_report = issues_report;
+    token.publisher = publisher;
+    token.reporter = progress;
+    token.permits = permits;
+    token.throttle = throttle;
+    token.planner = Some(tokio::spawn(watch_volumes(
+        config.rootsets.clone(),
+        config.strict,
+        config.poll,
+        Duration::from_secs(config.poll_interval),
+        token.plan(),
+        token.replanned.clone(),
+    )));
+    Ok(token)
+}
+
+/// Identify the volumes of the `config`, and plan the syncjobs between them along with what they
+/// are run with.
+///
+fn set_up(config: &GlobalConfig) -> Result<Setup> {
+    let mut volumes = identify_rootsets(&config.rootsets, config.strict)?;
+    if config.poll {
+        for volume in volumes.values_mut() {
+            volume.poll = true;
+        }
+    }
 
     // Now analyze the volumes to generate the sync jobs
     let syncjobs = build_syncjobs(&mut volumes)?;
@@ -102,40 +651,501 @@ pub async fn slide(config: GlobalConfig) -> Result<Token> {
     log::debug!("Sync jobs: {syncjobs:#?}");
 
     let move_req = MoveStrategy {
-        collision: config.collision,
-        safe: false,
+        collision: config.collision.clone(),
+        conflict: config.conflict,
+        skip_identical: config.skip_identical,
+        safe: config.safe,
+        wip_suffix: config.wip_suffix.clone(),
+        staging: config.staging,
         check: config.check,
-        retries: 5,
+        recheck: config.recheck,
+        check_mode: config.check_mode,
+        retries: config.retries,
+        max_failures: config.max_failures,
+        min_interval: config.min_interval,
+        jobs: config.jobs,
+        delta: config.delta,
+        preserve_times: config.preserve_times,
+        preserve_permissions: config.preserve_permissions,
+        preserve_xattrs: config.preserve_xattrs,
+        rate_limit: config.rate_limit,
+        sanitize: config.sanitize,
+        use_trash: config.use_trash,
+        min_age: config.min_age,
+        max_file_size: config.max_file_size,
+        sidecar: config.sidecar,
+        reverify: config.reverify,
+        delivery_manifest: config.delivery_manifest,
+    };
+
+    let filter = Filter::new(&config.exclude)?.with_size_range(config.min_size, config.max_size);
+
+    // Loaded upfront, so a missing key is found before syncing anything
+    let signer = match &config.signing_key {
+        Some(path) => Some(Arc::new(Signer::load(path)?)),
+        None => None,
     };
 
-    let (watcher, handles) =
-        execute_syncjobs(&volumes, syncjobs, config.dry_run, trace, &move_req).await?;
+    Ok(Setup {
+        volumes,
+        syncjobs,
+        move_req,
+        filter,
+        signer,
+    })
+}
+
+/// Roll back the copies to the slides of the `volumes` interrupted by a crash, but in the `busy`
+/// slides still being synced.
+///
+async fn recover_journals(volumes: &HashMap<String, Volume>, busy: &HashSet<PathBuf>) {
+    for slide in volumes
+        .values()
+        .flat_map(|volume| volume.slides.values())
+        .filter(|slide| !busy.contains(&resolved(&slide.path)))
+    {
+        match fs::journal::recover(&slide.path).await {
+            Ok(0) => {}
+            Ok(rolled_back) => {
+                log::info!(
+                    "Rolled back {rolled_back} interrupted copies to {:?}",
+                    slide.path
+                )
+            }
+            Err(e) => log::warn!("{:?}: Unable to recover the journal: {e}", slide.path),
+        }
+    }
+}
+
+/// Plan the sync jobs without running them.
+///
+/// This function will take the input `config`, identify the volumes and slides, and return the
+/// sync jobs that `slide` would run, in order. Nothing is written to the filesystem.
+///
+pub fn plan(config: &GlobalConfig) -> Result<Vec<PlannedJob>> {
+    let volumes = identify_rootsets(&config.rootsets, config.strict)?;
+
+    Ok(plan_syncjobs(&volumes)
+        .iter()
+        .map(|syncjob| {
+            let (from, to) = slide_folders(&volumes, syncjob);
+            PlannedJob {
+                src: syncjob.src.clone(),
+                via: syncjob.via.clone(),
+                dst: syncjob.dst.clone(),
+                from,
+                to,
+            }
+        })
+        .collect())
+}
+
+/// List the volumes and their slides.
+///
+/// This function will take the input `config` and identify the volumes and slides, without
+/// syncing anything. The volumes are sorted by name.
+///
+pub fn list_volumes(config: &GlobalConfig) -> Result<Vec<Volume>> {
+    let mut volumes = identify_rootsets(&config.rootsets, config.strict)?
+        .into_values()
+        .collect::<Vec<_>>();
+    volumes.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(volumes)
+}
+
+/// Explain the routing decisions.
+///
+/// This function will take the input `config`, identify the volumes and slides, and return the
+/// route decided for every slide: direct, via its default route, or dropped along with the reason.
+///
+pub fn explain_routes(config: &GlobalConfig) -> Result<Vec<Route>> {
+    let volumes = identify_rootsets(&config.rootsets, config.strict)?;
+    Ok(resolve_routes(&volumes))
+}
+
+/// Verify the integrity of the files in the slides and of the ones already delivered.
+///
+/// Every file waiting in the slides is read through, and hashed with the `check` algorithm if
+/// any, so the unreadable ones are found before their move. Every delivery recorded in the
+/// `traces` is checked to be still in place, with the checksum recorded at the move if any.
+/// Nothing is moved.
+///
+/// The checksum files found among the delivered files are checked too, but for the files already
+/// checked from the traces. So are the files listed in the manifests found in the slides, as long
+/// as they were signed by one of the `trusted_keys`.
+///
+/// The delivered files unchanged since their checksum was last computed are not hashed again,
+/// unless `rehash`, which is the only way to catch the corruption leaving the metadata intact.
+///
+pub fn verify(
+    config: &GlobalConfig,
+    traces: &[PathBuf],
+    rehash: bool,
+) -> Result<Vec<Verification>> {
+    let volumes = list_volumes(config)?;
+    let caches = Caches::load(&volumes);
+    let mut verifications = verify::verify_slides(&volumes, config.check);
+    let deliveries = verify::verify_deliveries(traces, &caches, rehash)?;
+    let delivered = deliveries
+        .iter()
+        .map(|v| v.path.clone())
+        .collect::<HashSet<_>>();
+    verifications.extend(deliveries);
+    verifications.extend(
+        verify::verify_sidecars(&volumes, &caches, rehash)
+            .into_iter()
+            .filter(|v| !delivered.contains(&v.path)),
+    );
+    verifications.extend(verify::verify_manifests(
+        &volumes,
+        &config.trusted_keys,
+        &caches,
+    ));
+    if let Err(e) = caches.save() {
+        log::warn!("Unable to save the checksum cache: {e}");
+    }
+    Ok(verifications)
+}
+
+/// Look up the events recorded in the history database at `path` matching the `query`, oldest
+/// first.
+///
+pub fn history(path: &Path, query: &HistoryQuery) -> Result<Vec<HistoryEvent>> {
+    history::query(path, query)
+}
+
+/// Export the files delivered according to the history database at `path` and matching the
+/// `query`, one per row, oldest first, to `out` in the given `format`. Returns how many were
+/// exported.
+///
+pub fn export_history(
+    path: &Path,
+    query: &HistoryQuery,
+    format: ExportFormat,
+    out: impl std::io::Write,
+) -> Result<usize> {
+    history::export(path, query, format, out)
+}
+
+/// Query the state of the syncjobs of the instance running with the control interface at `socket`.
+///
+pub async fn status(socket: &Path) -> Result<Vec<JobStatus>> {
+    control::request(socket, &ControlCommand::Status)
+        .await?
+        .iter()
+        .map(|line| Ok(serde_json::from_str(line)?))
+        .collect()
+}
 
-    Ok(Token::new(watcher, handles, tracer))
+/// Send a command to the instance running with the control interface at `socket`.
+///
+/// Returns the syncjobs affected by the command.
+///
+pub async fn control(socket: &Path, command: &ControlCommand) -> Result<Vec<String>> {
+    control::request(socket, command).await
 }
 
 /// Tidy up the volumes.
 ///
-/// This function traverses the slides of each volume and applies the rules defined in the .slide.yml file.
-///
-pub async fn tidy_up() {
-    unimplemented!();
-    /*
-     * TODO: Execute the tidy-up function
-     * 1. Read a .slide.yml file in foo/slides/foo folder
-     * 2. That file should have this structure
-     *  - rules:
-     *    - rule:
-     *      - regex: "^Media"
-     *      - operation: Move
-     *      - destination: "Media/Inbox" # Relative to volume root (mkdir -p if not existing)
-     *    - rule:
-     *      - regex: "^Photos/Mobile"
-     *      - operation: Move_to_new_dir
-     *      - params:
-     *        - 0: "%Y%M%D"
-     *      - destination: "Media/Photos"
-     */
+/// This function identifies the volumes, and applies the rules defined in the `.slide.yml` file of
+/// the slide named after each volume, where the files delivered to it land. Every move is traced.
+/// Returns the number of files moved, or that would be moved on a dry run.
+///
+pub async fn tidy_up(config: &GlobalConfig) -> Result<usize> {
+    let (trace, handle) = Tracer::new(
+        &config.trace.as_ref(),
+        &config.history.as_ref(),
+        config.output,
+    )
+    .await?;
+    // Only the annotated tracer is kept, so the trace is flushed once it is dropped
+    let tracer = trace.annotate_author("Tidy".to_string());
+    drop(trace);
+
+    let mut volumes = identify_rootsets(&config.rootsets, config.strict)?
+        .into_values()
+        .filter(|volume| !volume.disabled)
+        .collect::<Vec<_>>();
+    volumes.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut tidied = 0;
+    let mut failed = 0;
+    for volume in &volumes {
+        match tidy::tidy_volume(volume, config.dry_run, &config.wip_suffix, &tracer).await {
+            Ok(n) => tidied += n,
+            Err(e) => {
+                log::error!("Error tidying up \"{}\": {e}", volume.name);
+                failed += 1;
+            }
+        }
+    }
+
+    // Flush the trace
+    drop(tracer);
+    if let Some(handle) = handle {
+        handle.await?;
+    }
+
+    if failed > 0 {
+        bail!("Failed to tidy up {failed} volume(s)");
+    }
+    Ok(tidied)
+}
+
+/// Undo the deliveries recorded in the `traces`, oldest first, moving the delivered files back to
+/// where they came from, like after syncing the wrong drive.
+///
+/// The traces are replayed in reverse, from the latest delivery, and only from `since` on if
+/// given. The files no longer where they were delivered, or whose source is taken by another file,
+/// are left alone. Every move is traced. Nothing is moved on a dry run.
+///
+pub async fn undo(
+    config: &GlobalConfig,
+    traces: &[PathBuf],
+    since: Option<chrono::NaiveDateTime>,
+) -> Result<Vec<Undone>> {
+    let (trace, handle) = Tracer::new(
+        &config.trace.as_ref(),
+        &config.history.as_ref(),
+        config.output,
+    )
+    .await?;
+    // Only the annotated tracer is kept, so the trace is flushed once it is dropped
+    let tracer = trace.annotate_author("Undo".to_string());
+    drop(trace);
+
+    let undone = undo::undo_deliveries(traces, since, config.dry_run, &tracer).await;
+
+    // Flush the trace
+    drop(tracer);
+    if let Some(handle) = handle {
+        handle.await?;
+    }
+
+    undone
+}
+
+/// How often the roots are looked at for volumes plugged in or out while watching.
+///
+const HOTPLUG_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Time given to a volume appearing in a root to be fully created or mounted before looking at it.
+///
+const HOTPLUG_SETTLE: Duration = Duration::from_millis(500);
+
+/// Look at the roots of the `rootsets` every [`HOTPLUG_INTERVAL`], or as soon as a folder is
+/// created or removed in them or a volume or slide config file is edited, notifying `replanned`
+/// once the syncjobs planned from the volumes found are not the `plan` running anymore.
+///
+/// The roots, the slides folders of their volumes and the slides are watched, or polled every
+/// `poll_interval` for the rootsets polled or if `poll`, as well as the mounts where the platform
+/// reports them. The rest, like a drive mounted on a folder already there where the mounts are
+/// not watched, is only noticed by looking at the roots. The volumes are only identified
+/// again when the volume or slide folders found in the roots, or their config files, change, so
+/// nothing is logged meanwhile.
+///
+async fn watch_volumes(
+    rootsets: Vec<RootsetConfig>,
+    strict: bool,
+    poll: bool,
+    poll_interval: Duration,
+    plan: Vec<String>,
+    replanned: Arc<Notify>,
+) {
+    let folders = {
+        let rootsets = rootsets.clone();
+        move || {
+            let mut found = Vec::new();
+            let mut watchable = Vec::new();
+            let mut holders = HashSet::new();
+            for rootset in &rootsets {
+                let folders = volume_folders(&rootset.keyword, &rootset.roots);
+                let polled = poll || rootset.poll;
+                let roots = expand_roots(&rootset.roots)
+                    .into_iter()
+                    .filter(|root| root.is_dir())
+                    .collect::<Vec<_>>();
+                holders.extend(roots.iter().cloned());
+                watchable.extend(
+                    roots
+                        .into_iter()
+                        .chain(folders.iter().cloned())
+                        .map(|folder| (folder, polled)),
+                );
+                // The config files are told apart by when they were last modified
+                for folder in folders {
+                    let config = match folder.ends_with(&rootset.keyword) {
+                        true => {
+                            holders.insert(folder.clone());
+                            folder.join(volume::DEFAULT_VOLUME_CONFIG_FILE)
+                        }
+                        false => folder.join(DEFAULT_SLIDE_CONFIG_FILE),
+                    };
+                    if let Ok(modified) = config.metadata().and_then(|m| m.modified()) {
+                        found.push((config, Some(modified)));
+                    }
+                    found.push((folder, None));
+                }
+            }
+            (found, watchable, holders)
+        }
+    };
+
+    // Roots and slides folders, whose entries are volumes and slides, unlike those of the slides
+    let holders: Arc<Mutex<HashSet<PathBuf>>> = Arc::default();
+    let woken = Arc::new(Notify::new());
+    let mut watchers = {
+        let woken = woken.clone();
+        let holders = holders.clone();
+        Watchers::new(
+            move |event: notify::Event| {
+                let is_config = |path: &PathBuf| {
+                    path.file_name().is_some_and(|name| {
+                        name == DEFAULT_SLIDE_CONFIG_FILE
+                            || name == volume::DEFAULT_VOLUME_CONFIG_FILE
+                    })
+                };
+                let is_held = |path: &PathBuf| {
+                    path.parent()
+                        .is_some_and(|parent| holders.lock().unwrap().contains(parent))
+                };
+                // The files landing in a slide change nothing to the volumes
+                if event.paths.iter().any(is_config)
+                    || (matches!(event.kind, EventKind::Create(_) | EventKind::Remove(_))
+                        && event.paths.iter().any(is_held))
+                {
+                    woken.notify_one();
+                }
+            },
+            poll_interval,
+        )
+    };
+    if !watch_mounts(woken.clone()) {
+        log::debug!("The mounts are not watched, the roots are looked at for the drives mounted");
+    }
+    let mut watched = HashSet::new();
+    let mut known = None;
+    loop {
+        let (found, watchable, held) = tokio::task::spawn_blocking(folders.clone())
+            .await
+            .unwrap_or_default();
+        *holders.lock().unwrap() = held;
+        // The folders gone are no longer watched, and watched again if they come back
+        let watchable = watchable.into_iter().collect::<HashSet<_>>();
+        for (folder, polled) in &watchable {
+            if !watched.contains(&(folder.clone(), *polled)) {
+                if let Err(e) = watchers.watch_entries(folder, *polled) {
+                    log::debug!("{folder:?}: Unable to watch for volumes plugged in: {e}");
+                }
+            }
+        }
+        watched = watchable;
+
+        if known.as_ref().is_some_and(|known| *known != found) {
+            let rootsets = rootsets.clone();
+            let plan = plan.clone();
+            let replan = tokio::task::spawn_blocking(move || {
+                identify_rootsets(&rootsets, strict)
+                    .map(|volumes| plan_names(&volumes, &plan_syncjobs(&volumes)) != plan)
+            })
+            .await;
+            match replan {
+                Ok(Ok(true)) => {
+                    log::info!("The volumes changed, the syncjobs have to be rebuilt");
+                    replanned.notify_one();
+                    return;
+                }
+                Ok(Err(e)) => log::warn!("Unable to identify the volumes again: {e}"),
+                _ => {}
+            }
+        }
+        known = Some(found);
+
+        tokio::select! {
+            _ = woken.notified() => tokio::time::sleep(HOTPLUG_SETTLE).await,
+            _ = tokio::time::sleep(HOTPLUG_INTERVAL) => {}
+        }
+    }
+}
+
+/// The volume folders in the `roots` with their slide folders, sorted.
+///
+fn volume_folders(keyword: &str, roots: &[PathBuf]) -> Vec<PathBuf> {
+    let mut folders = Vec::new();
+    for root in expand_roots(roots) {
+        let Ok(entries) = root.read_dir() else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let slides = entry.path().join(keyword);
+            let Ok(slides_entries) = slides.read_dir() else {
+                continue;
+            };
+            folders.push(slides.clone());
+            folders.extend(
+                slides_entries
+                    .flatten()
+                    .map(|slide| slide.path())
+                    .filter(|slide| slide.is_dir()),
+            );
+        }
+    }
+    folders.sort();
+    folders
+}
+
+/// The syncjobs of a plan, sorted, to tell if two plans are the same, see [`plan_name`].
+///
+fn plan_names(volumes: &HashMap<String, Volume>, syncjobs: &SyncJobs) -> Vec<String> {
+    let mut names = syncjobs
+        .iter()
+        .map(|syncjob| plan_name(volumes, syncjob))
+        .collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+/// A syncjob of a plan, to tell if it is the same in two plans. Along with its name, the settings
+/// of the slides and volumes it was started with are compared, so that editing a config file
+/// restarts it.
+///
+fn plan_name(volumes: &HashMap<String, Volume>, syncjob: &SyncJob) -> String {
+    format!(
+        "{syncjob:?} {:?} pull {:?}",
+        volumes[&syncjob.src].slides[&syncjob.dst].overrides, volumes[&syncjob.via].pull
+    )
+}
+
+/// Identify the volumes and slides of every rootset.
+///
+/// If `strict`, fail if any volume or slide config file has unknown fields.
+///
+fn identify_rootsets(rootsets: &[RootsetConfig], strict: bool) -> Result<HashMap<String, Volume>> {
+    let mut volumes = HashMap::new();
+
+    // Analyze each rootset to extract volumes and slides
+    for rootset_config in rootsets {
+        let some_volumes = identify_env(&rootset_config.keyword, &rootset_config.roots);
+        match some_volumes {
+            Ok(v) => volumes.extend(v.into_iter().map(|(name, mut volume)| {
+                volume.poll = rootset_config.poll;
+                (name, volume)
+            })),
+            Err(_) => log::warn!("Error processing some volumes"),
+        }
+    }
+
+    log::debug!("Volumes for all configs: {volumes:#?}");
+
+    // Refuse to run with misspelled settings in the volume or slide config files
+    if strict {
+        let issues = config_issues(&volumes, true);
+        if !issues.is_empty() {
+            bail!("{}", issues.join("\n"));
+        }
+    }
+
+    Ok(volumes)
 }
 
 /// Identify volumes inside a each root folder.
@@ -162,6 +1172,10 @@ fn identify_volumes(root: &Path, keyword: &str) -> Result<HashMap<String, Volume
         if let Ok(file_type) = file_type {
             if file_type.is_dir() {
                 if let Some(volume) = Volume::from_path(entry.path(), keyword) {
+                    log::info!("Volume \"{}\" found at {:?}", volume.name, volume.path);
+                    if let Some(description) = &volume.description {
+                        log::info!(" > {description}");
+                    }
                     volumes.insert(volume.name.clone(), volume);
                 }
             }
@@ -193,16 +1207,22 @@ fn identify_slides(volume: &mut Volume) -> Result<()> {
                     .to_string();
 
                 // Try to fetch the slide configuration if any
-                let slide_conf = {
-                    let slide_conf =
-                        config::SlideConfig::new(slide_fullpath.join(DEFAULT_SLIDE_CONFIG_FILE));
+                let (mut routes, balance, overrides) = {
+                    let slide_conf = config::SlideConfig::new(
+                        slide_fullpath.join(DEFAULT_SLIDE_CONFIG_FILE),
+                        false,
+                    );
                     match slide_conf {
-                        Ok(s) => s.route,
-                        Err(_) => None,
+                        Ok(s) => (s.route, s.balance, s.overrides),
+                        Err(_) => (Vec::new(), None, Default::default()),
                     }
                 };
 
-                volume.add_slide(Slide::new(slide_name, slide_fullpath, slide_conf));
+                let route = (!routes.is_empty()).then(|| routes.remove(0));
+                let mut slide = Slide::new(slide_name, slide_fullpath, route, overrides);
+                slide.alternatives = routes;
+                slide.balance = balance;
+                volume.add_slide(slide);
             }
         }
     }
@@ -220,8 +1240,8 @@ pub fn identify_env(keyword: &str, roots: &[PathBuf]) -> Result<HashMap<String,
     // Identify volumes
     {
         // Identify the volumes in each root
-        for root in roots {
-            match identify_volumes(root, keyword) {
+        for root in expand_roots(roots) {
+            match identify_volumes(&root, keyword) {
                 Ok(v) => volumes.extend(v),
                 Err(e) => log::warn!("{e}"),
             }
@@ -273,51 +1293,179 @@ pub fn identify_env(keyword: &str, roots: &[PathBuf]) -> Result<HashMap<String,
     Ok(volumes)
 }
 
-/// Compose the sync jobs from the volume information.
+/// Check if a root is a glob pattern rather than a plain path.
 ///
-/// This function will create the sync jobs based on the identified slides.
+fn is_glob(root: &Path) -> bool {
+    root.to_string_lossy().contains(['*', '?', '['])
+}
+
+/// Expand the glob patterns found in the roots.
 ///
-fn build_syncjobs(volumes: &mut HashMap<String, Volume>) -> Result<SyncJobs> {
-    let mut syncjobs = Vec::new();
+/// Plain roots are returned untouched. Patterns are expanded to the folders matching them at the
+/// time of the call, so newly mounted drives are picked up on each scan.
+///
+fn expand_roots(roots: &[PathBuf]) -> Vec<PathBuf> {
+    let mut expanded = Vec::new();
 
-    for src_name in volumes.keys() {
-        // Skip disabled volumes
-        if volumes[src_name].disabled {
+    for root in roots {
+        if !is_glob(root) {
+            expanded.push(root.to_owned());
             continue;
         }
+        match glob::glob(&root.to_string_lossy()) {
+            Ok(paths) => {
+                let before = expanded.len();
+                expanded.extend(paths.flatten().filter(|p| p.is_dir()));
+                log::debug!("{root:?} expanded to {:?}", &expanded[before..]);
+            }
+            Err(e) => log::warn!("{root:?}: Invalid root pattern: {e}"),
+        }
+    }
 
-        for (dst_name, slide) in &volumes[src_name].slides {
-            if src_name == dst_name {
-                continue;
+    expanded
+}
+
+/// Check the volume and slide configuration files of the given volumes.
+///
+/// Returns the list of files that cannot be read. If `strict`, files with unknown fields are
+/// reported too.
+///
+fn config_issues(volumes: &HashMap<String, Volume>, strict: bool) -> Vec<String> {
+    let mut issues = Vec::new();
+
+    for volume in volumes.values() {
+        let volume_conf = volume
+            .path
+            .join(&volume.keyword)
+            .join(volume::DEFAULT_VOLUME_CONFIG_FILE);
+        if volume_conf.exists() {
+            if let Err(e) = config::VolumeConfig::new(&volume_conf, strict) {
+                issues.push(format!("{volume_conf:?}: Invalid volume config: {e}"));
             }
-            log::debug!("Evaluating routes from {src_name} to {dst_name}");
+        }
 
-            // If the destination volume is available, its a direct slide
-            if volumes.contains_key(dst_name) && !volumes[dst_name].disabled {
-                syncjobs.push(SyncJob::new(src_name, dst_name, dst_name));
-                log::debug!(" + Added direct route from {src_name} to {dst_name}");
-                continue;
+        for slide in volume.slides.values() {
+            let slide_conf = slide.path.join(DEFAULT_SLIDE_CONFIG_FILE);
+            if slide_conf.exists() {
+                if let Err(e) = config::SlideConfig::new(&slide_conf, strict) {
+                    issues.push(format!("{slide_conf:?}: Invalid slide config: {e}"));
+                }
             }
+        }
+    }
+
+    issues
+}
 
-            match &slide.or_else {
-                // If the slide has a default route, and the default route is available, its a indirect slide
-                Some(def_route_name) => {
-                    if volumes.contains_key(def_route_name) && !volumes[def_route_name].disabled {
-                        syncjobs.push(SyncJob::new(src_name, def_route_name, dst_name));
-                        log::debug!(" + Added indirect route from {src_name} to {dst_name} via {def_route_name}");
+/// Validate the environment described by the rootsets.
+///
+/// This function will check the roots, every `.volume.yml` and `.slide.yml` found, and the routes
+/// declared in them, without performing any sync. Returns the list of problems found, empty if none.
+/// If `strict`, unknown fields in the config files are reported too.
+///
+pub fn validate(rootsets: &[RootsetConfig], strict: bool) -> Vec<String> {
+    let mut issues = Vec::new();
+    let mut volumes: HashMap<String, Volume> = HashMap::new();
+
+    for rootset in rootsets {
+        for root in &rootset.roots {
+            if is_glob(root) {
+                if let Err(e) = glob::Pattern::new(&root.to_string_lossy()) {
+                    issues.push(format!("{root:?}: Invalid root pattern: {e}"));
+                }
+            } else if !root.is_dir() {
+                issues.push(format!("{root:?}: Root is not a folder"));
+            }
+        }
+
+        match identify_env(&rootset.keyword, &rootset.roots) {
+            Ok(v) => {
+                for (name, volume) in v {
+                    if let Some(other) = volumes.get(&name) {
+                        issues.push(format!(
+                            "{:?}: Volume name \"{name}\" already used by {:?}",
+                            volume.path, other.path
+                        ));
                         continue;
                     }
-                    log::info!(
-                        "\"{dst_name}\" and default route \"{def_route_name}\" not available"
-                    );
-                }
-                _ => {
-                    log::info!("\"{dst_name}\" not available and no default route");
+                    volumes.insert(name, volume);
                 }
             }
+            Err(e) => issues.push(format!("{e}")),
+        }
+    }
+
+    // Check the configuration files
+    issues.extend(config_issues(&volumes, strict));
+    for volume in volumes.values() {
+        if volume.send_only && volume.receive_only {
+            issues.push(format!(
+                "{:?}: Volume \"{}\" is both send only and receive only",
+                volume.path, volume.name
+            ));
         }
     }
 
+    // Check the routes
+    for volume in volumes.values() {
+        if volume.disabled {
+            continue;
+        }
+        for (dst_name, slide) in &volume.slides {
+            let unknown = slide
+                .routes()
+                .filter(|route| !volumes.contains_key(*route))
+                .collect::<Vec<_>>();
+            for route in &unknown {
+                issues.push(format!(
+                    "{:?}: Route \"{route}\" is not a known volume",
+                    slide.path
+                ));
+            }
+            if !unknown.is_empty() {
+                continue;
+            }
+            if slide.balance.is_some() && slide.alternatives.is_empty() {
+                issues.push(format!(
+                    "{:?}: Balance set without several routes to spread the files over",
+                    slide.path
+                ));
+            }
+            let reachable = |name: &str| volumes.get(name).is_some_and(|v| !v.disabled);
+            if !reachable(dst_name) && !slide.routes().any(reachable) {
+                issues.push(format!(
+                    "{:?}: Volume \"{dst_name}\" is unreachable from \"{}\"",
+                    slide.path, volume.name
+                ));
+            }
+        }
+    }
+    for route in resolve_routes(&volumes) {
+        if let Decision::Cycle(cycle) = route.decision {
+            issues.push(format!(
+                "Route from \"{}\" to \"{}\" goes around the routing cycle {}",
+                route.src,
+                route.dst,
+                cycle.join(" -> ")
+            ));
+        }
+    }
+
+    issues.sort();
+    issues
+}
+
+/// Compose the sync jobs from the volume information.
+///
+/// This function will create the sync jobs based on the identified slides, never taking files from
+/// a receive only volume or moving any into a send only one, nor between volumes ignoring each
+/// other. The slides whose routes go around in circles are refused, and the cycles reported. So
+/// are the sync jobs moving the same folders as another one, like those of a volume found twice
+/// through overlapping rootsets.
+///
+fn build_syncjobs(volumes: &mut HashMap<String, Volume>) -> Result<SyncJobs> {
+    let syncjobs = plan_syncjobs(volumes);
+
     // Create the slides that are missing in the destination volumes
     for syncjob in &syncjobs {
         if !volumes[&syncjob.via].slides.contains_key(&syncjob.dst) {
@@ -331,19 +1479,501 @@ fn build_syncjobs(volumes: &mut HashMap<String, Volume>) -> Result<SyncJobs> {
     Ok(syncjobs)
 }
 
-/// Execute the sync jobs.
+/// Decide the sync jobs between the volumes, in the order they have to be run.
+///
+fn plan_syncjobs(volumes: &HashMap<String, Volume>) -> SyncJobs {
+    let mut syncjobs = resolve_routes(volumes)
+        .into_iter()
+        .flat_map(|route| match route.decision {
+            Decision::Direct => vec![SyncJob::new(&route.src, &route.dst, &route.dst)],
+            Decision::Via(via) => vec![SyncJob::new(&route.src, &via, &route.dst)],
+            Decision::Spread(vias) => {
+                // Every syncjob takes its share of the files, weighted as the slide says
+                let weights = Arc::new(
+                    vias.iter()
+                        .map(|via| match volumes[&route.src].slides[&route.dst].balance {
+                            Some(Balance::FreeSpace) => {
+                                fs::free_space(&volumes[via].path).map_or(1, |free| free >> 20)
+                            }
+                            _ => 1,
+                        })
+                        .collect::<Vec<_>>(),
+                );
+                vias.iter()
+                    .enumerate()
+                    .map(|(index, via)| {
+                        let mut syncjob = SyncJob::new(&route.src, via, &route.dst);
+                        syncjob.share = Some(Share {
+                            index,
+                            weights: weights.clone(),
+                        });
+                        syncjob
+                    })
+                    .collect()
+            }
+            Decision::Dropped(_) => vec![],
+            Decision::Cycle(cycle) => {
+                log::error!(
+                    "Not syncing {} to {}, its files would go around the routing cycle {}",
+                    route.src,
+                    route.dst,
+                    cycle.join(" -> ")
+                );
+                vec![]
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // Run the syncjobs from higher priority volumes first
+    syncjobs.sort_by_key(|syncjob| {
+        (
+            Reverse(volumes[&syncjob.src].priority),
+            Reverse(volumes[&syncjob.via].priority),
+        )
+    });
+
+    // A volume found twice, through overlapping roots or rootsets, would have its syncjobs race on
+    // the same folders. The first syncjob of each pair of folders is kept
+    let mut kept = HashMap::new();
+    syncjobs.retain(|syncjob| {
+        let (from, to) = slide_folders(volumes, syncjob);
+        let folders = (resolved(&from), resolved(&to));
+        if folders.0 == folders.1 {
+            log::warn!("Not syncing {syncjob:?}, {from:?} and {to:?} are the same folder");
+            return false;
+        }
+        match kept.get(&folders) {
+            Some(other) => {
+                log::warn!("Not syncing {syncjob:?}, {other} already syncs {from:?} to {to:?}");
+                false
+            }
+            None => {
+                kept.insert(folders, format!("{syncjob:?}"));
+                true
+            }
+        }
+    });
+
+    syncjobs
+}
+
+/// Slides a sync job takes the contents from and moves them to. The latter may not exist yet.
+///
+fn slide_folders(volumes: &HashMap<String, Volume>, syncjob: &SyncJob) -> (PathBuf, PathBuf) {
+    let via = &volumes[&syncjob.via];
+    (
+        volumes[&syncjob.src].slides[&syncjob.dst].path.clone(),
+        via.path.join(&via.keyword).join(&syncjob.dst),
+    )
+}
+
+/// Folder a path leads to, following the links, or its parent's for a folder not created yet.
 ///
-/// This function will execute the sync jobs, ideally, in parallel.
+fn resolved(path: &Path) -> PathBuf {
+    path.canonicalize()
+        .ok()
+        .or_else(|| {
+            let parent = path.parent()?.canonicalize().ok()?;
+            Some(parent.join(path.file_name()?))
+        })
+        .unwrap_or_else(|| path.to_owned())
+}
+
+/// Decide the route of every slide of the volumes.
 ///
+/// A destination not available is approached through the default routes declared by the slides
+/// for it, from one volume to the next, as long as the volumes along the way are available. The
+/// volumes and their routes make a graph, and the path taken ends at a volume declaring no route
+/// of its own for the destination:
+///
+/// - The cheapest path wins, adding up the `cost` of the volumes along the way. As every volume
+///   costs 1 by default, that is the path with the fewest hops.
+/// - The volume at the end of the path must have the free space for the files waiting.
+/// - Among the paths as cheap, the routes listed by the slide are in strict priority.
+/// - Without a path with room for the files, the first route available is followed from each
+///   volume.
+/// - With a `balance`, the files are spread over every route available, whatever their order.
+/// - Once files started to be staged on a volume, the next ones follow them there while it is
+///   available, rather than being split between staging volumes.
+///
+/// The files go straight to the last volume of the path. A path leading back to a volume already
+/// on the way is not taken, as the files would bounce between volumes forever. The files of a
+/// volume refusing the destination, or any volume along the way, stay where they are.
+///
+/// The routes are sorted by source and destination volume names.
+///
+fn resolve_routes(volumes: &HashMap<String, Volume>) -> Vec<Route> {
+    let mut routes = Vec::new();
+    let available = |name: &str| volumes.get(name).is_some_and(|v| !v.disabled);
+
+    for (src_name, src) in volumes {
+        for (dst_name, slide) in &src.slides {
+            // The mailbox of the volume itself
+            if src_name == dst_name {
+                continue;
+            }
+            log::debug!("Evaluating routes from {src_name} to {dst_name}");
+
+            let mut hops = Vec::new();
+            let decision = if src.disabled {
+                Decision::Dropped(format!("source volume \"{src_name}\" is disabled"))
+            }
+            // If the destination volume is available, its a direct slide, unless either volume
+            // refuses it
+            else if available(dst_name) {
+                match src.refusal(&volumes[dst_name]) {
+                    Some(reason) => Decision::Dropped(reason),
+                    None => {
+                        log::debug!(" + Added direct route from {src_name} to {dst_name}");
+                        Decision::Direct
+                    }
+                }
+            }
+            // Neither is a destination refused reached through routes
+            else if let Some(reason) = match volumes.get(dst_name) {
+                Some(dst) => src.refusal(dst),
+                None => src
+                    .ignore
+                    .contains(dst_name)
+                    .then(|| format!("\"{src_name}\" ignores \"{dst_name}\"")),
+            } {
+                Decision::Dropped(reason)
+            }
+            // The files may be spread over the default routes available, when there are several
+            else if let Some(vias) = slide
+                .balance
+                .map(|_| {
+                    slide
+                        .routes()
+                        .filter(|route| available(route))
+                        .filter(|route| src.refusal(&volumes[*route]).is_none())
+                        .map(str::to_owned)
+                        .collect::<Vec<_>>()
+                })
+                .filter(|vias| vias.len() > 1)
+            {
+                log::debug!(
+                    " + Added routes from {src_name} to {dst_name} spread over {}",
+                    vias.join(", ")
+                );
+                hops = vias.clone();
+                Decision::Spread(vias)
+            }
+            // The files follow the ones already staged for the destination, as long as the
+            // volume keeping them is available and still keeps the files for it
+            else if let Some(via) = fs::sticky_route(&slide.path).filter(|via| {
+                via != src_name
+                    && available(via)
+                    && src.refusal(&volumes[via]).is_none()
+                    && volumes[via]
+                        .slides
+                        .get(dst_name)
+                        .is_none_or(|slide| !slide.routes().any(available))
+            }) {
+                log::debug!(" + Kept route from {src_name} to {dst_name} via {via}");
+                hops = vec![via.clone()];
+                Decision::Via(via)
+            }
+            // If any default route is available, its a indirect slide
+            else if slide.routes().any(available) {
+                match cheapest_route(volumes, src_name, dst_name, fs::free_space)
+                    .map(Ok)
+                    .unwrap_or_else(|| follow_routes(volumes, src_name, dst_name))
+                {
+                    Ok(path) => {
+                        hops = path;
+                        let via = hops.last().expect("An available route was followed");
+                        // The files are not to pass through any volume refusing them either
+                        match hops.iter().find_map(|hop| src.refusal(&volumes[hop])) {
+                            Some(reason) => Decision::Dropped(reason),
+                            None => {
+                                log::debug!(
+                                    " + Added indirect route from {src_name} to {dst_name} via {via}"
+                                );
+                                Decision::Via(via.to_owned())
+                            }
+                        }
+                    }
+                    Err(cycle) => {
+                        log::debug!(
+                            " - Route from {src_name} to {dst_name} goes around in circles"
+                        );
+                        Decision::Cycle(cycle)
+                    }
+                }
+            } else {
+                let state = |name: &str| match volumes.get(name) {
+                    Some(_) => "is disabled",
+                    None => "is not available",
+                };
+                match (&slide.or_else, slide.alternatives.is_empty()) {
+                    (Some(def_route_name), true) => {
+                        log::info!(
+                            "\"{dst_name}\" and default route \"{def_route_name}\" not available"
+                        );
+                        Decision::Dropped(format!(
+                            "\"{dst_name}\" {} and default route \"{def_route_name}\" {}",
+                            state(dst_name),
+                            state(def_route_name)
+                        ))
+                    }
+                    (Some(_), false) => {
+                        log::info!("\"{dst_name}\" and its default routes not available");
+                        Decision::Dropped(format!(
+                            "\"{dst_name}\" {} and none of the default routes \"{}\" is available",
+                            state(dst_name),
+                            slide.routes().collect::<Vec<_>>().join("\", \"")
+                        ))
+                    }
+                    (None, _) => {
+                        log::info!("\"{dst_name}\" not available and no default route");
+                        Decision::Dropped(format!(
+                            "\"{dst_name}\" {} and no default route",
+                            state(dst_name)
+                        ))
+                    }
+                }
+            };
+
+            routes.push(Route {
+                src: src_name.to_owned(),
+                dst: dst_name.to_owned(),
+                decision,
+                hops,
+            });
+        }
+    }
+
+    routes.sort_by(|a, b| (&a.src, &a.dst).cmp(&(&b.src, &b.dst)));
+    routes
+}
+
+/// Find the cheapest path from a volume towards a destination not available, following the
+/// routes declared by the slides for it.
+///
+/// Returns the volumes along the path, in order, up to the first one declaring no route for the
+/// destination, which keeps the files until it meets the destination. None if there is no such
+/// volume within reach. Paths equally cheap are told apart by the order of the routes of the
+/// volume, in strict priority, then by their number of hops.
+///
+/// The volumes without the free space for the files waiting, as told by `free_space`, are passed
+/// over, rather than failing halfway through the copies. If none has it, the path to the one with
+/// the most free space is returned.
+///
+fn cheapest_route(
+    volumes: &HashMap<String, Volume>,
+    src_name: &str,
+    dst_name: &str,
+    free_space: impl Fn(&Path) -> Option<u64>,
+) -> Option<Vec<String>> {
+    let available = |name: &str| volumes.get(name).is_some_and(|v| !v.disabled);
+    let routes_from = |name: &str| {
+        volumes[name]
+            .slides
+            .get(dst_name)
+            .map(|slide| slide.routes().collect::<Vec<_>>())
+            .unwrap_or_default()
+    };
+
+    // Walking the slide is left for when a volume to carry the files tells its free space
+    let payload = OnceCell::new();
+    let payload = || {
+        *payload.get_or_init(|| {
+            volumes[src_name]
+                .slides
+                .get(dst_name)
+                .map_or(0, |slide| fs::pending_size(&slide.path))
+        })
+    };
+    // The path to the volume with the most free space, among those without enough of it
+    let mut roomiest: Option<(u64, Vec<String>)> = None;
+
+    // Paths by cost, rank of their first hop among the routes of the volume, hops and the order
+    // they were found in
+    let mut paths = BinaryHeap::from([Reverse((
+        0u64,
+        0usize,
+        0usize,
+        0usize,
+        Vec::<String>::new(),
+    ))]);
+    let mut found = 0;
+    let mut reached = HashSet::from([src_name.to_owned()]);
+
+    while let Some(Reverse((cost, rank, _, _, path))) = paths.pop() {
+        let at = path.last().map_or(src_name, String::as_str);
+        if !path.is_empty() {
+            if !reached.insert(at.to_owned()) {
+                continue;
+            }
+            if routes_from(at).is_empty() {
+                match free_space(&volumes[at].path) {
+                    Some(free) if free < payload() => {
+                        log::debug!(
+                            " - {at} has {free} bytes free, {} needed to carry the files",
+                            payload()
+                        );
+                        if roomiest.as_ref().is_none_or(|(most, _)| free > *most) {
+                            roomiest = Some((free, path));
+                        }
+                        continue;
+                    }
+                    _ => return Some(path),
+                }
+            }
+        }
+        for (index, next) in routes_from(at).into_iter().enumerate() {
+            if !available(next) || reached.contains(next) {
+                continue;
+            }
+            found += 1;
+            let mut path = path.clone();
+            path.push(next.to_owned());
+            paths.push(Reverse((
+                cost + u64::from(volumes[next].cost),
+                // The routes of the volume rank the paths, the ones of the volumes after follow
+                match path.len() {
+                    1 => index,
+                    _ => rank,
+                },
+                path.len(),
+                found,
+                path,
+            )));
+        }
+    }
+
+    roomiest.map(|(_, path)| path)
+}
+
+/// Follow the first route available from each volume towards a destination not available, when
+/// no path leads to a volume keeping the files for it.
+///
+/// Returns the volumes reached, in order, as long as they are available. A volume reached twice
+/// is an error, holding the cycle found, as the files would bounce between its volumes forever.
+///
+fn follow_routes(
+    volumes: &HashMap<String, Volume>,
+    src_name: &str,
+    dst_name: &str,
+) -> std::result::Result<Vec<String>, Vec<String>> {
+    let available = |name: &str| volumes.get(name).is_some_and(|v| !v.disabled);
+    let mut reached = vec![src_name.to_owned()];
+
+    while let Some(hop) = volumes[reached.last().unwrap()]
+        .slides
+        .get(dst_name)
+        .and_then(|slide| slide.routes().find(|route| available(route)))
+    {
+        if let Some(start) = reached.iter().position(|name| name == hop) {
+            let mut cycle = reached.split_off(start);
+            cycle.push(hop.to_owned());
+            return Err(cycle);
+        }
+        reached.push(hop.to_owned());
+    }
+
+    Ok(reached.split_off(1))
+}
+
+/// Route taken by a syncjob among the ones its slide is spread over, along with their number.
+///
+fn share_of(syncjob: &SyncJob) -> Option<(usize, usize)> {
+    syncjob
+        .share
+        .as_ref()
+        .map(|share| (share.index, share.weights.len()))
+}
+
+/// The slide sending the files of the `syncjob` back the other way, if synced too.
+///
+fn reciprocal(
+    volumes: &HashMap<String, Volume>,
+    syncjobs: &SyncJobs,
+    syncjob: &SyncJob,
+) -> Option<PathBuf> {
+    syncjobs
+        .iter()
+        .any(|other| {
+            other.src == syncjob.dst
+                && other.via == syncjob.src
+                && other.dst == syncjob.src
+                && syncjob.via == syncjob.dst
+        })
+        .then(|| volumes[&syncjob.dst].slides[&syncjob.src].path.clone())
+}
+
+/// Tie every syncjob of a plan to the others, but the ones with a `running` stage, given in the
+/// order of the plan, which are left out. Returns the rest, along with how to start them.
+///
+/// A syncjob forwarding the files staged by others waits for them before each sync, so nothing
+/// half staged is forwarded.
+///
+fn wire(
+    volumes: &HashMap<String, Volume>,
+    syncjobs: SyncJobs,
+    running: Vec<Option<watch::Receiver<Stage>>>,
+) -> Vec<(SyncJob, Wiring)> {
+    let (senders, stages): (Vec<_>, Vec<_>) = running
+        .into_iter()
+        .map(|stage| match stage {
+            Some(stage) => (None, stage),
+            None => {
+                let (sender, stage) = watch::channel(Stage::default());
+                (Some(sender), stage)
+            }
+        })
+        .unzip();
+    let wiring = senders
+        .into_iter()
+        .zip(&syncjobs)
+        .map(|(stage, syncjob)| {
+            Some(Wiring {
+                stage: stage?,
+                upstreams: syncjobs
+                    .iter()
+                    .zip(&stages)
+                    .filter(|(other, _)| other.feeds(syncjob))
+                    .map(|(_, stage)| stage.clone())
+                    .collect(),
+                reciprocal: reciprocal(volumes, &syncjobs, syncjob),
+            })
+        })
+        .collect::<Vec<_>>();
+    syncjobs
+        .into_iter()
+        .zip(wiring)
+        .filter_map(|(syncjob, wiring)| Some((syncjob, wiring?)))
+        .collect()
+}
+
+/// Execute the sync jobs, each one tied to the others of its plan as its wiring says.
+///
+/// This function will execute the sync jobs, ideally, in parallel, as many at once as `permits`
+/// allow. If `once`, no watcher is set up, so each sync job ends after its first pass.
+///
+#[allow(clippy::too_many_arguments)]
 async fn execute_syncjobs(
     volumes: &HashMap<String, Volume>,
-    mut syncjobs: SyncJobs,
+    mut syncjobs: Vec<(SyncJob, Wiring)>,
     dry_run: bool,
+    once: bool,
     tracer: Tracer,
+    progress: Progress,
+    registry: Registry,
+    permits: Arc<Semaphore>,
+    throttle: Option<Arc<Throttle>>,
+    debounce: Option<Debounce>,
+    poll_interval: Duration,
+    heartbeat: Option<Duration>,
     move_req: &MoveStrategy,
-) -> Result<(RecommendedWatcher, Vec<tokio::task::JoinHandle<Result<()>>>)> {
+    filter: &Filter,
+    signer: Option<Arc<Signer>>,
+) -> Result<Batch> {
     let mut watcher_db = Vec::new();
-    for syncjob in syncjobs.iter_mut() {
+    // Triggers kept to report the pending ones, they do not keep the syncjobs alive
+    let mut triggers = Vec::new();
+    for (syncjob, _) in syncjobs.iter_mut() {
         let path = volumes[&syncjob.src].slides[&syncjob.dst]
             .path
             .canonicalize()?;
@@ -352,21 +1982,57 @@ async fn execute_syncjobs(
         } else {
             bail!("No trigger found for sync job {:?}", syncjob);
         };
-        watcher_db.push((path, trigger));
+        triggers.push(trigger.downgrade());
+        let trigger = match volumes[&syncjob.via].pull_every().filter(|_| !once) {
+            // The sources of a volume pulling its files may not report any event
+            Some(every) => polled(trigger, every),
+            // A burst of events, like a folder being copied, only triggers a sync once it calms
+            // down
+            None => match debounce.filter(|_| !once) {
+                Some(debounce) => debounced(trigger, debounce),
+                None => trigger,
+            },
+        };
+        let filter = filter.clone().with_share(syncjob.share.clone());
+        watcher_db.push((path, trigger, filter));
     }
+    // The .wip files written into a slide may come from the syncjobs of any other slide
+    let mut wip_suffixes = volumes
+        .values()
+        .flat_map(|volume| volume.slides.values())
+        .filter_map(|slide| slide.overrides.wip_suffix.clone())
+        .chain([move_req.wip_suffix.clone()])
+        .collect::<Vec<_>>();
+    wip_suffixes.sort();
+    wip_suffixes.dedup();
 
     let mut watcher = {
         let tracer = tracer.annotate_author("Watcher".to_string());
         tracer.async_log("Init", "Starting slides sync...").await?;
 
-        notify::recommended_watcher(
-            move |res: std::result::Result<notify::Event, notify::Error>| {
-                if let Ok(event) = res {
+        for volume in volumes.values() {
+            if let Some(description) = &volume.description {
+                tracer
+                    .async_log(
+                        "Volume",
+                        &format!("{} at {:?}: {description}", volume.name, volume.path),
+                    )
+                    .await?;
+            }
+        }
+
+        // Dropping the triggers makes each syncjob end after its first pass
+        if once {
+            drop(watcher_db);
+            None
+        } else {
+            Some(Watchers::new(
+                move |event: notify::Event| {
                     match event.kind {
                         EventKind::Modify(_) | EventKind::Create(_) | EventKind::Remove(_) => {
                             let _ = tracer
                                 .sync_log("Event", &format!("Filesystem event: {:?} ", event));
-                            for (path, trigger) in &watcher_db {
+                            for (path, trigger, filter) in &watcher_db {
                                 // Check if any event path is within the watched directory
                                 for event_path in &event.paths {
                                     let event_path = event_path.canonicalize();
@@ -377,6 +2043,21 @@ async fn execute_syncjobs(
                                         );
                                         // FIXME: Maybe this doesnt work
                                         if event_path.starts_with(path) {
+                                            // What the syncs write along the way, like the
+                                            // trash of their own slide or the .wip files of the
+                                            // others, does not need another sync
+                                            if fs::is_own(path, &event_path, &wip_suffixes) {
+                                                continue;
+                                            }
+                                            // Neither does what the syncs would leave in place
+                                            if fs::is_held_back(
+                                                path,
+                                                &event_path,
+                                                event_path.is_dir(),
+                                                filter,
+                                            ) {
+                                                continue;
+                                            }
                                             if trigger.capacity() > 0 {
                                                 let _deleteme =
                                                     tracer.sync_log("Event", "launched");
@@ -391,57 +2072,505 @@ async fn execute_syncjobs(
                         }
                         _ => {}
                     }
-                }
-            },
-        )?
+                },
+                poll_interval,
+            ))
+        }
     };
 
     // TODO: Measure the next block
     {
-        let mut handles = Vec::new();
-
-        for mut syncjob in syncjobs.into_iter() {
+        let mut running = Vec::new();
+        for ((mut syncjob, wiring), trigger) in syncjobs.into_iter().zip(triggers) {
             log::debug!("Syncing {:?}", syncjob);
+            let Wiring {
+                stage,
+                mut upstreams,
+                reciprocal,
+            } = wiring;
             let src = volumes[&syncjob.src].slides[&syncjob.dst].path.clone();
             let dst = volumes[&syncjob.via].slides[&syncjob.dst].path.clone();
             let mut trace = tracer.annotate_author(format!("{:?}", syncjob));
-            let move_req = move_req.clone();
+            let beat = trace.clone();
+            let progress = progress.annotate_job(format!("{:?}", syncjob));
+            let overrides = &volumes[&syncjob.src].slides[&syncjob.dst].overrides;
+            let mut move_req = move_req.with_overrides(overrides);
+            let throttle = match overrides.rate_limit {
+                Some(rate) => Throttle::new(rate).map(Arc::new),
+                None => throttle.clone(),
+            };
+            // The files only passing through a volume get their checksum files at the last one
+            if syncjob.via != syncjob.dst {
+                move_req.sidecar = None;
+            }
+            let filter = filter.clone().with_share(syncjob.share.clone());
+            // Only the files checked have a checksum to keep, in the keyword folder of the
+            // destination volume
+            let cache = match (move_req.check, dst.parent()) {
+                (Some(_), Some(keyword_dir)) if !dry_run => {
+                    Some(Arc::new(ChecksumCache::load(keyword_dir)))
+                }
+                _ => None,
+            };
+            let signer = signer.clone();
+            let permits = permits.clone();
+            let job = format!("{:?}", syncjob);
+            let registry = registry.clone();
+            let mut gate = registry.register(
+                job.clone(),
+                [
+                    syncjob.src.clone(),
+                    syncjob.via.clone(),
+                    syncjob.dst.clone(),
+                ],
+                Some(trigger),
+            );
+            // The moves report how far they got to the registry
+            let progress = progress.annotate_registry(registry.clone());
+
+            if let Some(watcher) = watcher
+                .as_mut()
+                .filter(|_| volumes[&syncjob.via].pull_every().is_none())
+            {
+                watcher.watch(&src, volumes[&syncjob.src].poll)?;
+            }
 
-            watcher.watch(&src, RecursiveMode::Recursive)?;
+            // What the syncjob is started from, to tell if it has to be restarted
+            let planned = plan_name(volumes, &syncjob);
+            let share = share_of(&syncjob);
+            let (name, tied, staged) = (job.clone(), reciprocal.clone(), stage.subscribe());
 
             // Spawn a new tokio async task for this syncjob
             let handle = tokio::spawn(async move {
+                // Deliveries to check again, along with when, the earliest first
+                let mut pending = VecDeque::new();
+                // Syncs failed in a row
+                let mut failures = 0;
                 loop {
-                    if let Err(e) =
-                        sync_slide(&syncjob, &src, &dst, dry_run, &mut trace, &move_req).await
+                    // A paused syncjob waits to be resumed, or for the instance to stop
+                    if *gate.wait_for(|gate| *gate != Gate::Paused).await? == Gate::Closed {
+                        return Ok(());
+                    }
+                    for upstream in upstreams.iter_mut() {
+                        tokio::select! {
+                            // A syncjob that ended has nothing else to stage
+                            _ = upstream.wait_for(Stage::settled) => {}
+                            _ = gate.wait_for(|gate| *gate == Gate::Closed) => return Ok(()),
+                        }
+                    }
+                    let permit = permits.acquire().await?;
+                    if !registry.started(&job) {
+                        continue;
+                    }
+                    let started = Instant::now();
+                    stage.send_modify(|stage| stage.syncing = true);
+                    let result = with_heartbeat(
+                        sync_slide(
+                            &syncjob,
+                            &src,
+                            &dst,
+                            reciprocal.as_deref(),
+                            dry_run,
+                            &mut trace,
+                            &progress,
+                            &gate,
+                            &move_req,
+                            &filter,
+                            cache.as_ref(),
+                            throttle.as_ref(),
+                        ),
+                        heartbeat,
+                        &job,
+                        &registry,
+                        &beat,
+                    )
+                    .await?;
+                    stage.send_modify(|stage| {
+                        stage.passes += 1;
+                        stage.syncing = false;
+                    });
+                    registry.finished(&job, &result);
+                    // Losing the cache only costs hashing the files again
+                    if let Some(Err(e)) = cache.as_ref().map(|cache| cache.save()) {
+                        log::warn!("{job}: Unable to save the checksum cache: {e}");
+                        registry.report(&job, IssueKind::Cache, None, e.to_string());
+                    }
+                    drop(permit);
+                    let report = pass_report(&result, started.elapsed());
+                    log::info!("{job}: {report}");
+                    trace.async_log("SUMMARY", &report).await?;
+                    registry.summed_up(&job, &report);
+                    let transferred = match result {
+                        Ok(transferred) => {
+                            failures = 0;
+                            transferred
+                        }
+                        Err(e) => {
+                            failures += 1;
+                            // The move failing it already named its file
+                            if !e.chain().any(|cause| cause.is::<Reported>()) {
+                                registry.report(&job, IssueKind::Sync, None, e.to_string());
+                            }
+                            // A single pass has no later sync to wait for
+                            if once {
+                                bail!("Error syncing {:?} -> {:?}: {:?}", src, dst, e);
+                            }
+                            if move_req.max_failures > 0 && failures >= move_req.max_failures {
+                                bail!(
+                                    "Error syncing {:?} -> {:?}, giving up after {failures} failed syncs in a row: {:?}",
+                                    src,
+                                    dst,
+                                    e
+                                );
+                            }
+                            // The sync is attempted again, waiting longer after each failure
+                            let backoff = Duration::from_secs(1 << (failures - 1).min(8));
+                            log::warn!(
+                                "Error syncing {:?} -> {:?}, trying again in {}s: {:?}",
+                                src,
+                                dst,
+                                backoff.as_secs(),
+                                e
+                            );
+                            trace.async_log("FAILED", &format!("{e}")).await?;
+                            tokio::select! {
+                                _ = tokio::time::sleep(backoff) => continue,
+                                _ = gate.wait_for(|gate| *gate == Gate::Closed) => {
+                                    bail!("Error syncing {:?} -> {:?}: {:?}", src, dst, e)
+                                }
+                            }
+                        }
+                    };
+                    // The deliveries are already done, so a missing manifest is only reported
+                    if let Some(signer) = signer
+                        .as_ref()
+                        .filter(|_| !transferred.deliveries.is_empty())
                     {
-                        bail!("Error syncing {:?} -> {:?}: {:?}", src, dst, e);
+                        match signer
+                            .write(&dst, &syncjob.src, &syncjob.dst, &transferred.deliveries)
+                            .await
+                        {
+                            Ok(manifest) => {
+                                trace
+                                    .async_log("MANIFEST", &format!("{manifest:?}"))
+                                    .await?
+                            }
+                            Err(e) => {
+                                log::error!("{dst:?}: Unable to write the manifest: {e}");
+                                registry.report(
+                                    &job,
+                                    IssueKind::Manifest,
+                                    Some(&dst),
+                                    e.to_string(),
+                                );
+                            }
+                        }
+                    }
+                    if move_req.delivery_manifest && !transferred.transfers.is_empty() {
+                        let manifest = DeliveryManifest::new(
+                            &syncjob.src,
+                            &syncjob.via,
+                            &syncjob.dst,
+                            transferred.transfers.clone(),
+                        );
+                        match manifest.write(&dst).await {
+                            Ok(manifest) => {
+                                trace
+                                    .async_log("MANIFEST", &format!("{manifest:?}"))
+                                    .await?
+                            }
+                            Err(e) => {
+                                log::error!("{dst:?}: Unable to write the delivery manifest: {e}");
+                                registry.report(
+                                    &job,
+                                    IssueKind::Manifest,
+                                    Some(&dst),
+                                    e.to_string(),
+                                );
+                            }
+                        }
+                    }
+                    let (settling, reverify) = (transferred.settling, transferred.reverify);
+                    if let Some(check) = move_req.reverify {
+                        let due = Instant::now() + Duration::from_secs(u64::from(check.after) * 60);
+                        pending.extend(
+                            reverify
+                                .into_iter()
+                                .map(|reverification| (due, reverification)),
+                        );
+                    }
+                    // A sync cut short by a pause goes on once resumed, without a trigger
+                    if *gate.borrow() != Gate::Open {
+                        continue;
                     }
+                    // The files left to settle are synced then, as no other event may come. The
+                    // deliveries due meanwhile are checked again
+                    let settled = settling.map(|settling| Instant::now() + settling);
+                    let triggered = loop {
+                        let due = pending.front().map(|(due, _)| *due);
+                        tokio::select! {
+                            triggered = syncjob.borrow_receiver().recv() => break triggered,
+                            _ = sleep_until(settled.unwrap_or_else(Instant::now)), if settled.is_some() => {
+                                break Some(());
+                            }
+                            _ = sleep_until(due.unwrap_or_else(Instant::now)), if due.is_some() => {
+                                reverify_due(&mut pending, &move_req, &trace, &progress).await?;
+                            }
+                        }
+                    };
                     // None is received when the mpsc::Sender is dropped
-                    if syncjob.borrow_receiver().recv().await.is_none() {
+                    if triggered.is_none() {
+                        // A single pass does not linger for the checks not due yet
+                        if once {
+                            reverify_due(&mut pending, &move_req, &trace, &progress).await?;
+                            if !pending.is_empty() {
+                                log::info!(
+                                    "{job}: {} delivered file(s) not checked again, the single pass is over",
+                                    pending.len()
+                                );
+                            }
+                            return Ok(());
+                        }
+                        // Nothing else is synced, but the deliveries are still checked when due,
+                        // unless stopping
+                        while let Some((due, _)) = pending.front() {
+                            tokio::select! {
+                                _ = sleep_until(*due) => {
+                                    reverify_due(&mut pending, &move_req, &trace, &progress).await?;
+                                }
+                                _ = async {
+                                    let _ = gate.wait_for(|gate| *gate == Gate::Closed).await;
+                                } => break,
+                            }
+                        }
                         return Ok(());
                     }
+                    // A slide written all the time is not synced over and over. The triggers
+                    // meanwhile are batched into the next sync, so the last writes are synced too
+                    if move_req.min_interval > 0 {
+                        let next = started + Duration::from_secs(move_req.min_interval);
+                        if next > Instant::now() {
+                            log::debug!(
+                                "{job}: Syncing again in {}s",
+                                (next - Instant::now()).as_secs()
+                            );
+                        }
+                        tokio::select! {
+                            _ = sleep_until(next) => {}
+                            // Stopping right away, the syncjob ends on the next loop
+                            _ = gate.wait_for(|gate| *gate == Gate::Closed) => {}
+                        }
+                    }
                 }
             });
-            handles.push(handle);
+            running.push(Running {
+                job: name,
+                planned,
+                reciprocal: tied,
+                share,
+                stage: staged,
+                handle,
+            });
         }
 
-        Ok((watcher, handles))
+        Ok(Batch { watcher, running })
+    }
+
+    // The anonymous tracer and progress will be dropped here
+}
+
+/// How the bursts of events of a syncjob are batched into a single sync.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Debounce {
+    /// Time without events awaited before syncing
+    pub(crate) quiet: Duration,
+    /// Longest time a burst may postpone the sync, if bounded
+    pub(crate) max: Option<Duration>,
+}
+
+impl Debounce {
+    /// Batching configured in seconds, if any. A `quiet` period of 0 syncs after every event, and
+    /// a `max` delay of 0 lets the bursts postpone the sync for as long as they last.
+    ///
+    fn new(quiet: Option<u64>, max: Option<u64>) -> Option<Self> {
+        quiet.filter(|secs| *secs > 0).map(|quiet| Self {
+            quiet: Duration::from_secs(quiet),
+            max: max.filter(|secs| *secs > 0).map(Duration::from_secs),
+        })
     }
+}
 
-    // The anonymous tracer will be dropped here
+/// Forward the events of a syncjob to its trigger once no other event came for the quiet period of
+/// the `debounce`, or once the burst lasted for its maximum delay, returning the sender of the
+/// events.
+///
+/// The forwarding ends once the events sender is dropped, letting the syncjob end.
+///
+fn debounced(trigger: mpsc::Sender<()>, debounce: Debounce) -> mpsc::Sender<()> {
+    let (events, mut rx) = mpsc::channel(1);
+    tokio::spawn(async move {
+        while rx.recv().await.is_some() {
+            // A slide changing all the time is still synced every so often
+            let deadline = debounce.max.map(|max| Instant::now() + max);
+            loop {
+                let quiet = match deadline {
+                    Some(deadline) => debounce
+                        .quiet
+                        .min(deadline.saturating_duration_since(Instant::now())),
+                    None => debounce.quiet,
+                };
+                match tokio::time::timeout(quiet, rx.recv()).await {
+                    Ok(Some(())) if deadline.is_none_or(|deadline| Instant::now() < deadline) => {}
+                    _ => break,
+                }
+            }
+            // A full channel means a sync is already pending
+            let _ = trigger.try_send(());
+        }
+    });
+    events
+}
+
+/// Trigger a syncjob every time `every` elapses, as well as on its events, returning the sender
+/// of the events.
+///
+/// The polling ends once the events sender is dropped, letting the syncjob end.
+///
+fn polled(trigger: mpsc::Sender<()>, every: Duration) -> mpsc::Sender<()> {
+    let (events, mut rx) = mpsc::channel(1);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval_at(Instant::now() + every, every);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                event = rx.recv() => if event.is_none() {
+                    break;
+                },
+                _ = interval.tick() => {}
+            }
+            // A full channel means a sync is already pending
+            let _ = trigger.try_send(());
+        }
+    });
+    events
+}
+
+/// Check again the pending deliveries that are due, the earliest first.
+///
+async fn reverify_due(
+    pending: &mut VecDeque<(Instant, fs::reverify::Reverification)>,
+    move_req: &MoveStrategy,
+    tracer: &Tracer,
+    progress: &Progress,
+) -> Result<()> {
+    let restore = move_req.reverify.is_some_and(|check| check.restore);
+    while let Some((due, _)) = pending.front() {
+        if *due > Instant::now() {
+            break;
+        }
+        if let Some((_, reverification)) = pending.pop_front() {
+            reverification.run(restore, tracer, progress).await?;
+        }
+    }
+    Ok(())
 }
 
-/// Sync the contents of a slide.
+/// Await the `sync` of the `job`, tracing how far it got every `every` while it lasts, so a sync
+/// slowly moving a huge file can be told apart from one stuck for good.
 ///
+async fn with_heartbeat<T>(
+    sync: impl Future<Output = T>,
+    every: Option<Duration>,
+    job: &str,
+    registry: &Registry,
+    tracer: &Tracer,
+) -> Result<T> {
+    let Some(every) = every else {
+        return Ok(sync.await);
+    };
+    tokio::pin!(sync);
+    let started = Instant::now();
+    let mut beats = interval_at(started + every, every);
+    beats.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            result = &mut sync => return Ok(result),
+            _ = beats.tick() => {
+                let report = heartbeat_report(registry, job, started.elapsed());
+                log::info!("{job}: {report}");
+                tracer.async_log("HEARTBEAT", &report).await?;
+            }
+        }
+    }
+}
+
+/// One-line report of an ongoing sync of a slide: the files and bytes moved and pending, the files
+/// being copied along with the bytes copied so far, and how long it has lasted.
+///
+fn heartbeat_report(registry: &Registry, job: &str, took: Duration) -> String {
+    let progress = registry
+        .progress()
+        .into_iter()
+        .find(|progress| progress.job == job)
+        .unwrap_or_default();
+    let copying = registry
+        .copying(job)
+        .iter()
+        .map(|(file, size, copied)| format!("{file:?} ({copied} of {size} byte(s))"))
+        .collect::<Vec<_>>();
+    format!(
+        "{} file(s) and {} byte(s) moved, {} file(s) and {} byte(s) pending, copying {}, for {:?}",
+        progress.files_moved,
+        progress.bytes_moved,
+        progress.files_pending,
+        progress.bytes_pending,
+        match copying.is_empty() {
+            true => "nothing".to_owned(),
+            false => copying.join(", "),
+        },
+        Duration::from_secs(took.as_secs())
+    )
+}
+
+/// One-line summary of a sync of a slide: the files and bytes moved, the files skipped, whether it
+/// failed and how long it took.
+///
+fn pass_report(result: &Result<Transferred>, took: Duration) -> String {
+    let (files, bytes, skipped) = match result {
+        Ok(transferred) => (transferred.files, transferred.bytes, transferred.skipped),
+        Err(_) => (0, 0, 0),
+    };
+    format!(
+        "{files} file(s) and {bytes} byte(s) moved, {skipped} skipped, {} failure(s), took {:?}",
+        u8::from(result.is_err()),
+        Duration::from_millis(took.as_millis() as u64)
+    )
+}
+
+/// Sync the contents of a slide, returning the files and bytes delivered.
+///
+/// The files found under the same path in the `reciprocal` slide, the one sending files back the
+/// other way, are dealt with first, as the conflict policy says. The checksums of the files
+/// delivered are kept in the `cache` when given, saved by the caller. The moves stop once the
+/// `gate` of the syncjob is no longer open, that is, paused or closed. The copies wait for the
+/// `throttle` of the syncjob, if any.
+///
+#[allow(clippy::too_many_arguments)]
 async fn sync_slide(
     syncjob: &SyncJob,
     src: &PathBuf,
     dst: &Path,
+    reciprocal: Option<&Path>,
     dry_run: bool,
     tracer: &mut Tracer,
+    progress: &Progress,
+    gate: &watch::Receiver<Gate>,
     move_req: &MoveStrategy,
-) -> Result<()> {
+    filter: &Filter,
+    cache: Option<&Arc<ChecksumCache>>,
+    throttle: Option<&Arc<Throttle>>,
+) -> Result<Transferred> {
     log::info!("Syncing {:?}", syncjob);
 
     let entries = src.read_dir();
@@ -449,22 +2578,73 @@ async fn sync_slide(
         bail!("{src:?} cannot be read");
     }
 
+    // Both slides may hold files displaced by previous syncs
+    if let Some(trash) = move_req.use_trash.filter(|_| !dry_run) {
+        for slide in [src.as_path(), dst] {
+            fs::trash::purge(slide, trash.retention).await?;
+        }
+    }
+
+    if let Some(reciprocal) = reciprocal {
+        fs::conflict::resolve(
+            src,
+            reciprocal,
+            (&syncjob.src, &syncjob.dst),
+            move_req,
+            filter,
+            dry_run,
+            tracer,
+        )
+        .await?;
+    }
+
     // Sync every folder inside the slide
+    let mut transferred = Transferred::default();
     for entry in entries?.flatten() {
         let entry_path = entry.path();
         let file_type = entry.file_type();
         if let Ok(file_type) = file_type {
+            // The staging, trash and journal folders hold the files of the moves into this slide
+            if fs::is_internal(&entry_path) {
+                continue;
+            }
             // The slide should only contain directories or config files
             if !file_type.is_dir() {
                 log::warn!("{} is not a directory", entry_path.display());
                 continue;
             }
             let dst = dst.join(entry.file_name());
-            fs::sync(&entry_path, &dst, dry_run, tracer, move_req).await?;
+            transferred += fs::sync(
+                &entry_path,
+                &dst,
+                dry_run,
+                tracer,
+                progress,
+                Some(gate),
+                move_req,
+                filter,
+                cache,
+                throttle,
+            )
+            .await?;
         }
     }
 
-    Ok(())
+    // Only a full pass tells which files are over the cap
+    if !dry_run && !fs::is_cancelled(Some(gate)) {
+        fs::report_oversize(src, &transferred.oversize).await?;
+    }
+
+    // The next files for an absent destination follow the ones staged, until it is met
+    if !dry_run {
+        if syncjob.via == syncjob.dst {
+            fs::stick_route(src, None).await?;
+        } else if syncjob.share.is_none() && transferred.files > 0 {
+            fs::stick_route(src, Some(&syncjob.via)).await?;
+        }
+    }
+
+    Ok(transferred)
 }
 
 #[cfg(test)]
@@ -0,0 +1,267 @@
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, bail, Result};
+use chrono::Local;
+use ed25519_dalek::{Signature, Signer as _, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::Algorithm,
+    fs::{Delivery, Transfer, DELIVERY_MANIFEST_PREFIX, MANIFEST_DIR},
+    hasher::hash_reader,
+};
+
+/// Algorithm of the checksums listed for the files delivered without one.
+///
+const FALLBACK_ALGORITHM: Algorithm = Algorithm::BLAKE3;
+
+/// Create a new signing key at `path`, readable only by its owner where supported. An existing
+/// key is only replaced if `force`.
+///
+/// Returns the public key, to be trusted by the machines receiving the manifests.
+///
+pub fn generate_key(path: &Path, force: bool) -> Result<String> {
+    if path.exists() && !force {
+        bail!("{path:?} already exists, use --force to replace it");
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut seed = [0u8; ed25519_dalek::SECRET_KEY_LENGTH];
+    getrandom::getrandom(&mut seed).map_err(|e| anyhow!("Unable to generate a key: {e}"))?;
+    let key = SigningKey::from_bytes(&seed);
+
+    // The mode only applies to a new file, so the key replacing another one is written apart and
+    // renamed over it
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("{path:?}: Not a file"))?;
+    let tmp = path.with_file_name(format!(".{}.tmp", file_name.to_string_lossy()));
+    let _ = std::fs::remove_file(&tmp);
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let written = options.open(&tmp).and_then(|mut file| {
+        std::io::Write::write_all(
+            &mut file,
+            format!("{}\n", to_hex(key.as_bytes())).as_bytes(),
+        )?;
+        file.sync_all()
+    });
+    if let Err(e) = written.and_then(|_| std::fs::rename(&tmp, path)) {
+        let _ = std::fs::remove_file(&tmp);
+        bail!("{path:?}: Unable to write the signing key: {e}");
+    }
+
+    Ok(to_hex(key.verifying_key().as_bytes()))
+}
+
+/// Key signing the manifests of the deliveries.
+///
+#[derive(Debug)]
+pub(crate) struct Signer(SigningKey);
+
+impl Signer {
+    /// Read the key written by `generate_key`.
+    ///
+    pub(crate) fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("{path:?}: Unable to read the signing key: {e}"))?;
+        let seed = from_hex(content.trim())
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| anyhow!("{path:?}: Invalid signing key"))?;
+        Ok(Self(SigningKey::from_bytes(&seed)))
+    }
+
+    /// Write the manifest of the files delivered to a slide by a sync, returning where.
+    ///
+    /// The files delivered without a checksum are hashed now.
+    ///
+    pub(crate) async fn write(
+        &self,
+        slide: &Path,
+        source: &str,
+        destination: &str,
+        deliveries: &[Delivery],
+    ) -> Result<PathBuf> {
+        let mut files = Vec::new();
+        for delivery in deliveries {
+            let (algorithm, checksum) = match &delivery.checksum {
+                Some((algorithm, checksum)) => (*algorithm, checksum.clone()),
+                None => {
+                    let path = delivery.path.clone();
+                    let checksum = tokio::task::spawn_blocking(move || {
+                        hash_reader(&mut std::fs::File::open(path)?, FALLBACK_ALGORITHM)
+                    })
+                    .await??;
+                    (FALLBACK_ALGORITHM, checksum)
+                }
+            };
+            let path = delivery.path.strip_prefix(slide).map_err(|_| {
+                anyhow!("{:?}: Delivered out of the slide {slide:?}", delivery.path)
+            })?;
+            files.push(Entry {
+                path: path.to_string_lossy().replace('\\', "/"),
+                size: delivery.size,
+                algorithm: format!("{algorithm:?}").to_lowercase(),
+                checksum,
+            });
+        }
+
+        let now = Local::now();
+        let manifest = Manifest {
+            created: now.to_rfc3339(),
+            source: source.to_owned(),
+            destination: destination.to_owned(),
+            files,
+        };
+        let signature = self.0.sign(&serde_json::to_vec(&manifest)?);
+        let signed = Signed {
+            manifest,
+            public_key: to_hex(self.0.verifying_key().as_bytes()),
+            signature: to_hex(&signature.to_bytes()),
+        };
+
+        let dir = slide.join(MANIFEST_DIR);
+        tokio::fs::create_dir_all(&dir).await?;
+        let name = format!("{}-{source}.json", now.format("%Y%m%dT%H%M%S%.3f"));
+        // Written aside first, so a crash never leaves a truncated manifest
+        let partial = dir.join(format!(".{name}.tmp"));
+        tokio::fs::write(&partial, serde_json::to_vec_pretty(&signed)?).await?;
+        let path = dir.join(name);
+        tokio::fs::rename(&partial, &path).await?;
+        Ok(path)
+    }
+}
+
+/// A file listed in a manifest.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Entry {
+    /// Path relative to the slide, with `/` as separator
+    pub(crate) path: String,
+    pub(crate) size: u64,
+    pub(crate) algorithm: String,
+    pub(crate) checksum: String,
+}
+
+impl Entry {
+    pub(crate) fn algorithm(&self) -> Option<Algorithm> {
+        Algorithm::from_str(&self.algorithm).ok()
+    }
+}
+
+/// Files delivered to a slide by a sync.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Manifest {
+    /// When the manifest was written, RFC 3339
+    pub(crate) created: String,
+    /// Name of the volume the files were delivered from
+    pub(crate) source: String,
+    /// Name of the volume the files are meant for
+    pub(crate) destination: String,
+    pub(crate) files: Vec<Entry>,
+}
+
+impl Manifest {
+    /// Read a manifest, checking it was signed by one of the `trusted` public keys and not
+    /// altered since.
+    ///
+    pub(crate) fn read(path: &Path, trusted: &[String]) -> Result<Self> {
+        let signed: Signed = serde_json::from_slice(&std::fs::read(path)?)?;
+        if !trusted
+            .iter()
+            .any(|key| key.eq_ignore_ascii_case(&signed.public_key))
+        {
+            bail!("signed by the untrusted key {}", signed.public_key);
+        }
+
+        let key = from_hex(&signed.public_key)
+            .and_then(|bytes| bytes.try_into().ok())
+            .and_then(|bytes| VerifyingKey::from_bytes(&bytes).ok())
+            .ok_or_else(|| anyhow!("invalid public key {}", signed.public_key))?;
+        let signature = from_hex(&signed.signature)
+            .and_then(|bytes| Signature::from_slice(&bytes).ok())
+            .ok_or_else(|| anyhow!("invalid signature"))?;
+        key.verify(&serde_json::to_vec(&signed.manifest)?, &signature)
+            .map_err(|_| anyhow!("altered since signed"))?;
+
+        Ok(signed.manifest)
+    }
+}
+
+/// Files delivered to a slide by a sync, along with where they came from, so the machine receiving
+/// them can tell what arrived without the trace of the sender.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeliveryManifest {
+    /// When the manifest was written, RFC 3339
+    pub created: String,
+    /// Name of the volume the files were delivered from
+    pub source: String,
+    /// Name of the volume the files were delivered to, on their way to the destination one
+    pub via: String,
+    /// Name of the volume the files are meant for
+    pub destination: String,
+    pub files: Vec<Transfer>,
+}
+
+impl DeliveryManifest {
+    pub(crate) fn new(source: &str, via: &str, destination: &str, files: Vec<Transfer>) -> Self {
+        Self {
+            created: Local::now().to_rfc3339(),
+            source: source.to_owned(),
+            via: via.to_owned(),
+            destination: destination.to_owned(),
+            files,
+        }
+    }
+
+    /// Write the manifest at the root of the `slide` the files were delivered to, returning where.
+    ///
+    pub(crate) async fn write(&self, slide: &Path) -> Result<PathBuf> {
+        let created = chrono::DateTime::parse_from_rfc3339(&self.created)?;
+        let name = format!(
+            "{DELIVERY_MANIFEST_PREFIX}{}-{}.json",
+            created.format("%Y%m%dT%H%M%S%.3f"),
+            self.source
+        );
+        // Written aside first, so a crash never leaves a truncated manifest
+        let partial = slide.join(format!("{name}.tmp"));
+        tokio::fs::write(&partial, serde_json::to_vec_pretty(self)?).await?;
+        let path = slide.join(name);
+        tokio::fs::rename(&partial, &path).await?;
+        Ok(path)
+    }
+}
+
+/// A manifest along with its signature, as written to disk.
+///
+#[derive(Debug, Serialize, Deserialize)]
+struct Signed {
+    manifest: Manifest,
+    /// Public key of the signer, hex
+    public_key: String,
+    /// Signature of the manifest serialized as compact JSON, hex
+    signature: String,
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
@@ -0,0 +1,180 @@
+use std::{sync::Mutex, time::Duration};
+
+use rumqttc::{AsyncClient, ConnectionError, Event, LastWill, MqttOptions, Outgoing, Packet, QoS};
+use tokio::task::JoinHandle;
+
+use crate::{config::MqttConfig, control::JobStatus};
+
+/// Port of the broker, unless configured.
+///
+const DEFAULT_PORT: u16 = 1883;
+
+/// Prefix of the topics, unless configured.
+///
+const DEFAULT_TOPIC: &str = "bitslides";
+
+/// Time between two attempts to reach the broker.
+///
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// How long the messages still queued have to reach the broker once stopping.
+///
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Number of messages queued while the broker cannot be reached, the later ones being dropped.
+///
+const QUEUE_SIZE: usize = 64;
+
+/// Publisher of the state of the syncjobs to an MQTT broker.
+///
+/// Every topic is under the configured prefix:
+/// - `<prefix>/status`: `online` while running, `offline` once stopped or gone. Retained
+/// - `<prefix>/job/<src>/<via>/<dst>`: status of the syncjob as JSON, as the `status` command
+///   reports it, every time a sync starts or ends and the syncjob is paused or resumed. Retained
+/// - `<prefix>/job/<src>/<via>/<dst>/summary`: summary of each sync, as traced
+/// - `<prefix>/volume/<name>`: `syncing` while any syncjob from, via or to the volume syncs, and
+///   `idle` once none does. Retained
+/// - `<prefix>/summary`: summary of the run, once stopped
+///
+/// Publishing is best effort: the messages are queued while the broker cannot be reached, and
+/// dropped once the queue is full, so the syncs are never held back.
+///
+pub(crate) struct Publisher {
+    client: AsyncClient,
+    /// Prefix of the topics
+    topic: String,
+    /// Task talking to the broker, until disconnected
+    connection: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Publisher {
+    /// Start connecting to the broker, announcing the instance online once connected.
+    ///
+    pub(crate) fn connect(config: &MqttConfig) -> Self {
+        let topic = config.topic.clone().unwrap_or(DEFAULT_TOPIC.to_owned());
+        let port = config.port.unwrap_or(DEFAULT_PORT);
+        let client_id = config
+            .client_id
+            .clone()
+            .unwrap_or_else(|| format!("bitslides-{}", std::process::id()));
+
+        let mut options = MqttOptions::new(client_id, &config.host, port);
+        options.set_keep_alive(Duration::from_secs(30));
+        // The broker announces the instance offline itself if it goes away without stopping
+        options.set_last_will(LastWill::new(
+            format!("{topic}/status"),
+            "offline",
+            QoS::AtMostOnce,
+            true,
+        ));
+        if let Some(username) = &config.username {
+            options.set_credentials(username, config.password.clone().unwrap_or_default());
+        }
+
+        let (client, mut eventloop) = AsyncClient::new(options, QUEUE_SIZE);
+        let broker = format!("{}:{port}", config.host);
+        let connection = tokio::spawn(async move {
+            // Only the first failure in a row is worth a warning
+            let mut reachable = true;
+            loop {
+                match eventloop.poll().await {
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                        log::debug!("Connected to the MQTT broker at {broker}");
+                        reachable = true;
+                    }
+                    Ok(Event::Outgoing(Outgoing::Disconnect))
+                    | Err(ConnectionError::RequestsDone) => break,
+                    Ok(_) => {}
+                    Err(e) => {
+                        if reachable {
+                            log::warn!(
+                                "Unable to reach the MQTT broker at {broker}, retrying: {e}"
+                            );
+                        }
+                        reachable = false;
+                        tokio::time::sleep(RECONNECT_DELAY).await;
+                    }
+                }
+            }
+        });
+
+        let publisher = Self {
+            client,
+            topic,
+            connection: Mutex::new(Some(connection)),
+        };
+        publisher.publish("status", "online", true);
+        publisher
+    }
+
+    fn publish(&self, subtopic: &str, payload: impl Into<Vec<u8>>, retain: bool) {
+        let topic = format!("{}/{subtopic}", self.topic);
+        if let Err(e) = self
+            .client
+            .try_publish(&topic, QoS::AtMostOnce, retain, payload)
+        {
+            log::debug!("Unable to publish to {topic}: {e}");
+        }
+    }
+
+    /// Publish the `status` of the syncjob between the `volumes`.
+    ///
+    pub(crate) fn job(&self, volumes: &[String; 3], status: &JobStatus) {
+        match serde_json::to_vec(status) {
+            Ok(payload) => self.publish(&job_topic(volumes), payload, true),
+            Err(e) => log::debug!("Unable to publish the status of {}: {e}", status.job),
+        }
+    }
+
+    /// Publish the `report` of a sync of the syncjob between the `volumes`.
+    ///
+    pub(crate) fn sync_summary(&self, volumes: &[String; 3], report: &str) {
+        self.publish(&format!("{}/summary", job_topic(volumes)), report, false);
+    }
+
+    /// Publish whether any syncjob from, via or to the `volume` is `syncing`.
+    ///
+    pub(crate) fn volume(&self, volume: &str, syncing: bool) {
+        let state = match syncing {
+            true => "syncing",
+            false => "idle",
+        };
+        self.publish(&format!("volume/{}", level(volume)), state, true);
+    }
+
+    /// Publish the `summary` of the run.
+    ///
+    pub(crate) fn run_summary(&self, summary: &str) {
+        self.publish("summary", summary, false);
+    }
+
+    /// Disconnect from the broker, announcing the instance offline, once the messages queued are
+    /// sent or [`CLOSE_TIMEOUT`] is over.
+    ///
+    pub(crate) async fn close(&self) {
+        self.publish("status", "offline", true);
+        let _ = self.client.try_disconnect();
+        let connection = self.connection.lock().unwrap().take();
+        if let Some(mut connection) = connection {
+            if tokio::time::timeout(CLOSE_TIMEOUT, &mut connection)
+                .await
+                .is_err()
+            {
+                connection.abort();
+            }
+        }
+    }
+}
+
+/// Topic of the syncjob between the `volumes`, relative to the prefix.
+///
+fn job_topic(volumes: &[String; 3]) -> String {
+    let [src, via, dst] = volumes.each_ref().map(|volume| level(volume));
+    format!("job/{src}/{via}/{dst}")
+}
+
+/// Topic level naming a volume, the characters MQTT reserves replaced.
+///
+fn level(name: &str) -> String {
+    name.replace(['/', '+', '#'], "_")
+}
@@ -0,0 +1,269 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::mpsc::{self, Sender},
+    task::JoinHandle,
+};
+
+use crate::{control::Registry, issues::IssueKind};
+
+/// What a syncjob is doing.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Phase {
+    /// Waiting for a change in the slide
+    #[default]
+    Idle,
+    /// Walking the slide, no file being moved right now
+    Scanning,
+    /// Moving files
+    Moving,
+    /// Waiting to be resumed
+    Paused,
+    /// The last sync ended by an error
+    Failed,
+}
+
+/// Progress of the ongoing sync of a syncjob, or of its last one once idle.
+///
+/// The slides are walked while their files are moved, so the files pending are the ones found so
+/// far and not moved yet, not every file in the slide.
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JobProgress {
+    /// Syncjob, as in the logs and the trace
+    pub job: String,
+    pub phase: Phase,
+    /// Number of files found and not moved yet
+    pub files_pending: u64,
+    /// Number of bytes of those files left to move
+    pub bytes_pending: u64,
+    /// Number of files moved
+    pub files_moved: u64,
+    /// Number of bytes moved, those of the files being copied included
+    pub bytes_moved: u64,
+    /// Files pending, being moved or about to be
+    pub current: Vec<PathBuf>,
+}
+
+/// Progress of every syncjob, along with their totals.
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProgressReport {
+    pub files_pending: u64,
+    pub bytes_pending: u64,
+    pub files_moved: u64,
+    pub bytes_moved: u64,
+    pub jobs: Vec<JobProgress>,
+}
+
+impl From<Vec<JobProgress>> for ProgressReport {
+    fn from(jobs: Vec<JobProgress>) -> Self {
+        let total = |stat: fn(&JobProgress) -> u64| jobs.iter().map(stat).sum::<u64>();
+        Self {
+            files_pending: total(|job| job.files_pending),
+            bytes_pending: total(|job| job.bytes_pending),
+            files_moved: total(|job| job.files_moved),
+            bytes_moved: total(|job| job.bytes_moved),
+            jobs,
+        }
+    }
+}
+
+/// Progress display abstraction
+///
+/// Shows a progress bar for each syncjob and for each file being moved, with the bytes copied so
+/// far. Like the tracer, the moves report through a channel, and a single task draws the bars.
+/// The moves are also accounted for in the registry of the syncjobs, displayed or not, see
+/// [`JobProgress`], along with the problems they go through without stopping the sync.
+///
+#[derive(Clone, Default)]
+pub struct Progress {
+    tx: Option<Sender<Update>>,
+    job: Option<String>,
+    registry: Option<Registry>,
+}
+
+/// A single progress report of a file being moved.
+///
+enum Update {
+    /// The copy of a file started, or restarted after a failed attempt
+    Start {
+        job: String,
+        file: PathBuf,
+        size: u64,
+    },
+    /// Some more bytes of the file were copied
+    Advance { file: PathBuf, bytes: u64 },
+    /// The copy of the file ended, `completed` or not
+    Finish { file: PathBuf, completed: bool },
+}
+
+impl Progress {
+    const CHANNEL_SIZE: usize = 64;
+
+    pub fn new(enabled: bool) -> (Self, Option<JoinHandle<()>>) {
+        // The user may want no progress display at all
+        if !enabled {
+            return (Self::default(), None);
+        }
+
+        let (tx, mut rx) = mpsc::channel::<Update>(Self::CHANNEL_SIZE);
+
+        let handle = tokio::spawn(async move {
+            let multi = MultiProgress::new();
+            let job_style = ProgressStyle::with_template(
+                "{msg} [{elapsed_precise}] {bytes}/{total_bytes} ({bytes_per_sec})",
+            )
+            .unwrap_or_else(|_| ProgressStyle::default_bar());
+            let file_style = ProgressStyle::with_template(
+                "  {msg} [{bar:30}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})",
+            )
+            .unwrap_or_else(|_| ProgressStyle::default_bar())
+            .progress_chars("=> ");
+
+            let mut jobs: HashMap<String, ProgressBar> = HashMap::new();
+            let mut files: HashMap<PathBuf, (ProgressBar, ProgressBar)> = HashMap::new();
+
+            while let Some(update) = rx.recv().await {
+                match update {
+                    Update::Start { job, file, size } => {
+                        let job_bar = jobs
+                            .entry(job.clone())
+                            .or_insert_with(|| {
+                                multi.add(
+                                    ProgressBar::new(0)
+                                        .with_style(job_style.clone())
+                                        .with_message(job),
+                                )
+                            })
+                            .clone();
+                        job_bar.inc_length(size);
+                        let file_bar = multi.insert_after(
+                            &job_bar,
+                            ProgressBar::new(size)
+                                .with_style(file_style.clone())
+                                .with_message(
+                                    file.file_name()
+                                        .map(|name| name.to_string_lossy().to_string())
+                                        .unwrap_or_default(),
+                                ),
+                        );
+                        files.insert(file, (file_bar, job_bar));
+                    }
+                    Update::Advance { file, bytes } => {
+                        if let Some((file_bar, job_bar)) = files.get(&file) {
+                            file_bar.inc(bytes);
+                            job_bar.inc(bytes);
+                        }
+                    }
+                    Update::Finish { file, completed } => {
+                        if let Some((file_bar, job_bar)) = files.remove(&file) {
+                            if !completed {
+                                // The file is copied again on retry, or left in place, so the
+                                // failed attempt does not count
+                                job_bar.set_position(
+                                    job_bar.position().saturating_sub(file_bar.position()),
+                                );
+                                job_bar.set_length(
+                                    job_bar
+                                        .length()
+                                        .unwrap_or_default()
+                                        .saturating_sub(file_bar.length().unwrap_or_default()),
+                                );
+                            }
+                            file_bar.finish_and_clear();
+                        }
+                    }
+                }
+            }
+
+            for job_bar in jobs.values() {
+                job_bar.finish();
+            }
+        });
+
+        (
+            Self {
+                tx: Some(tx),
+                ..Default::default()
+            },
+            Some(handle),
+        )
+    }
+
+    pub fn annotate_job(&self, job: String) -> Self {
+        Self {
+            job: Some(job),
+            ..self.clone()
+        }
+    }
+
+    pub(crate) fn annotate_registry(&self, registry: Registry) -> Self {
+        Self {
+            registry: Some(registry),
+            ..self.clone()
+        }
+    }
+
+    /// Account for a file found in the slide, to be moved.
+    ///
+    pub(crate) fn pending(&self, file: &Path, size: u64) {
+        if let (Some(registry), Some(job)) = (&self.registry, &self.job) {
+            registry.pending(job, file, size);
+        }
+    }
+
+    /// Account for a file no longer pending, whether it was `delivered` or left in place.
+    ///
+    pub(crate) fn done(&self, file: &Path, delivered: bool) {
+        if let (Some(registry), Some(job)) = (&self.registry, &self.job) {
+            registry.done(job, file, delivered);
+        }
+    }
+
+    /// Record a problem gone through without stopping, as an issue of the syncjob.
+    ///
+    pub(crate) fn issue(&self, kind: IssueKind, path: Option<&Path>, detail: impl Display) {
+        if let (Some(registry), Some(job)) = (&self.registry, &self.job) {
+            registry.report(job, kind, path, detail.to_string());
+        }
+    }
+
+    // The display is best effort, so failing to report is not an error
+
+    pub async fn start(&self, file: &Path, size: u64) {
+        if let (Some(registry), Some(job)) = (&self.registry, &self.job) {
+            registry.restarted(job, file);
+        }
+        if let Some(tx) = &self.tx {
+            let job = self.job.clone().unwrap_or_default();
+            let file = file.to_owned();
+            let _ = tx.send(Update::Start { job, file, size }).await;
+        }
+    }
+
+    pub async fn advance(&self, file: &Path, bytes: u64) {
+        if let (Some(registry), Some(job)) = (&self.registry, &self.job) {
+            registry.copied(job, file, bytes);
+        }
+        if let Some(tx) = &self.tx {
+            let file = file.to_owned();
+            let _ = tx.send(Update::Advance { file, bytes }).await;
+        }
+    }
+
+    pub async fn finish(&self, file: &Path, completed: bool) {
+        if let Some(tx) = &self.tx {
+            let file = file.to_owned();
+            let _ = tx.send(Update::Finish { file, completed }).await;
+        }
+    }
+}
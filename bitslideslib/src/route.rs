@@ -0,0 +1,77 @@
+use std::fmt::Display;
+
+use serde::Serialize;
+
+/// Routing decision taken for a slide.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "decision", content = "detail", rename_all = "lowercase")]
+pub enum Decision {
+    /// The destination volume is available
+    Direct,
+    /// The destination volume is not available, but the default route is. Holds the volume the
+    /// files go to, the last one reached following the default routes
+    Via(String),
+    /// The destination volume is not available, and the files are spread over several default
+    /// routes. Holds the volumes the files go to
+    Spread(Vec<String>),
+    /// The slide is not synced. Holds the reason
+    Dropped(String),
+    /// The slide is not synced, as its files would go around in circles. Holds the volumes of the
+    /// cycle, the first one repeated at the end
+    Cycle(Vec<String>),
+}
+
+/// Route of a slide, from its source volume towards its destination volume.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Route {
+    /// Source volume
+    pub src: String,
+    /// Destination volume
+    pub dst: String,
+    /// What was decided for this slide
+    #[serde(flatten)]
+    pub decision: Decision,
+    /// Volumes reached following the default routes, in order. Empty unless routed via another
+    /// volume
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub hops: Vec<String>,
+}
+
+/// Route Display implementation.
+///
+impl Display for Route {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.decision {
+            Decision::Direct => write!(f, "{} -> {}: direct", self.src, self.dst),
+            Decision::Via(_) if self.hops.len() > 1 => {
+                write!(
+                    f,
+                    "{} -> {}: via {}",
+                    self.src,
+                    self.dst,
+                    self.hops.join(" -> ")
+                )
+            }
+            Decision::Via(via) => write!(f, "{} -> {}: via {via}", self.src, self.dst),
+            Decision::Spread(vias) => write!(
+                f,
+                "{} -> {}: spread over {}",
+                self.src,
+                self.dst,
+                vias.join(", ")
+            ),
+            Decision::Dropped(reason) => {
+                write!(f, "{} -> {}: dropped, {reason}", self.src, self.dst)
+            }
+            Decision::Cycle(cycle) => write!(
+                f,
+                "{} -> {}: dropped, routing cycle {}",
+                self.src,
+                self.dst,
+                cycle.join(" -> ")
+            ),
+        }
+    }
+}
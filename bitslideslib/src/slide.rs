@@ -1,15 +1,29 @@
 use std::path::PathBuf;
 
+use serde::Serialize;
+
+use crate::config::{Balance, StrategyOverrides};
+
 /// Slide representation.
 ///
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct Slide {
     /// Name of the destination volume
     pub name: String,
     /// Path to the slide. Ex. /path/to/volumes/foo/slides/bar
     pub path: PathBuf,
     /// Name of the default route towards the destination volume
+    #[serde(rename = "route")]
     pub or_else: Option<String>,
+    /// Names of the other routes towards the destination volume, in order of preference
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub alternatives: Vec<String>,
+    /// How the files are spread over the routes available, if they are
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub balance: Option<Balance>,
+    /// Move strategy overrides for the files coming out of this slide
+    #[serde(skip)]
+    pub overrides: StrategyOverrides,
 }
 
 /// Slide implementation.
@@ -17,22 +31,44 @@ pub struct Slide {
 impl Slide {
     /// Create a new slide.
     ///
-    pub fn new(name: String, path: PathBuf, or_else: Option<String>) -> Self {
+    pub fn new(
+        name: String,
+        path: PathBuf,
+        or_else: Option<String>,
+        overrides: StrategyOverrides,
+    ) -> Self {
         Self {
             name,
             path,
             or_else,
+            alternatives: Vec::new(),
+            balance: None,
+            overrides,
         }
     }
+
+    /// Names of every route towards the destination volume, the default one first.
+    ///
+    pub fn routes(&self) -> impl Iterator<Item = &str> {
+        self.or_else
+            .iter()
+            .chain(&self.alternatives)
+            .map(String::as_str)
+    }
 }
 
-#[cfg(false)]
 impl std::fmt::Display for Slide {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if self.or_else.is_none() {
-            write!(f, "{}", self.name,)
-        } else {
-            write!(f, "{} (->{})", self.name, self.or_else.as_ref().unwrap())
+        match &self.or_else {
+            Some(_) => write!(
+                f,
+                "{} (->{})",
+                self.name,
+                self.routes()
+                    .collect::<Vec<_>>()
+                    .join(if self.balance.is_some() { "+" } else { "|" })
+            ),
+            None => write!(f, "{}", self.name),
         }
     }
 }
@@ -1,7 +1,13 @@
-use std::fmt::Debug;
+use std::{
+    fmt::{Debug, Display},
+    path::PathBuf,
+};
 
+use serde::Serialize;
 use tokio::sync::mpsc;
 
+use crate::filter::Share;
+
 /// SyncJob representation.
 ///
 /// A syncjob defines a source and a final destination, optionally passing via another volume.
@@ -17,6 +23,8 @@ pub(crate) struct SyncJob {
     pub(crate) via: String,
     /// Destination volume
     pub(crate) dst: String,
+    /// Files taken, if the slide is spread over several proxy volumes
+    pub(crate) share: Option<Share>,
     /// Implementation details
     inner: SyncJobInner,
 }
@@ -57,6 +65,7 @@ impl SyncJob {
             src: src.to_string(),
             via: via.to_string(),
             dst: dst.to_string(),
+            share: None,
             inner: SyncJobInner { tx: Some(tx), rx },
         }
     }
@@ -84,6 +93,31 @@ impl SyncJob {
     pub(crate) fn borrow_receiver(&mut self) -> &mut tokio::sync::mpsc::Receiver<()> {
         &mut self.inner.rx
     }
+
+    /// Whether this sync job stages files into the slide the `other` one takes its contents from,
+    /// as an indirect job does for the job forwarding them on to the destination.
+    ///
+    pub(crate) fn feeds(&self, other: &SyncJob) -> bool {
+        self.via != self.dst && self.via == other.src && self.dst == other.dst
+    }
+}
+
+/// Progress of a sync job, as followed by the sync jobs it feeds.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Stage {
+    /// Number of syncs over
+    pub(crate) passes: u64,
+    /// Whether a sync is ongoing
+    pub(crate) syncing: bool,
+}
+
+impl Stage {
+    /// Whether the files of the sync job are all staged, for now.
+    ///
+    pub(crate) fn settled(&self) -> bool {
+        self.passes > 0 && !self.syncing
+    }
 }
 
 /// SyncJob Debug implementation.
@@ -111,3 +145,37 @@ impl PartialEq for SyncJob {
 // FIXME: Move to a owned type (struct {inner: Vec<SyncJob>}) and impl iterator on it. Also provide a sort
 // method to sort the syncjobs by sync order
 pub type SyncJobs = Vec<SyncJob>;
+
+/// A sync job as planned, with the folders involved.
+///
+/// This is the public, inert counterpart of [`SyncJob`], used to report what would be run.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PlannedJob {
+    /// Source volume
+    pub src: String,
+    /// Proxy volume, equal to `dst` for direct jobs
+    pub via: String,
+    /// Destination volume
+    pub dst: String,
+    /// Slide the contents are taken from
+    pub from: PathBuf,
+    /// Slide the contents are moved to. It may not exist yet
+    pub to: PathBuf,
+}
+
+/// PlannedJob Display implementation.
+///
+impl Display for PlannedJob {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} -{}-> {}: {:?} -> {:?}",
+            self.src,
+            if self.via == self.dst { "_" } else { &self.via },
+            self.dst,
+            self.from,
+            self.to
+        )
+    }
+}
@@ -93,6 +93,11 @@ impl TestFolder {
 /// };
 /// let context = install_scenario(&scenario, tempdir)?;
 /// ```
+#[allow(
+    clippy::needless_borrow,
+    clippy::needless_borrows_for_generic_args,
+    clippy::redundant_field_names
+)]
 fn install_scenario(scenario: &TestFolder, tempdir: tempfile::TempDir) -> Result<TestContext> {
     fn install_folder(folder: &TestFolder, parent: &Path) -> Result<()> {
         std::fs::create_dir_all(&parent)?;
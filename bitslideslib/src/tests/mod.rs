@@ -4,11 +4,16 @@ use crate::CollisionPolicy;
 
 use super::config::{GlobalConfig, RootsetConfig};
 use super::*;
-use checksums::{hash_file, Algorithm};
+use crate::Algorithm;
 use pretty_assertions::assert_eq;
 
 use common::setup;
 
+/// Hash a file, for the checksums expected by the tests.
+fn hash_file(path: &Path, algorithm: Algorithm) -> String {
+    hash_reader(&mut std::fs::File::open(path).unwrap(), algorithm).unwrap()
+}
+
 /// Test the identification of volumes inside a root folder
 #[test]
 fn test_identify_volumes() {
@@ -48,6 +53,293 @@ fn test_identify_slides() {
     }
 }
 
+/// Test the move strategy overrides declared in a slide configuration file
+#[test]
+fn test_identify_slides_overrides() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+
+    // Prerequisite: Declare some overrides for the "bar" slide of the "foo" volume
+    let slides = ctx.roots[0].join("foo").join("slides");
+    std::fs::write(
+        slides.join("bar").join(DEFAULT_SLIDE_CONFIG_FILE),
+        "collision: \"rename:old\"\ncheck: md5\nsafe: true\nretries: 2\njobs: 4\nrate_limit: 10M",
+    )
+    .unwrap();
+    std::fs::write(
+        slides.join("baz").join(DEFAULT_SLIDE_CONFIG_FILE),
+        "route: bar\ncheck: none",
+    )
+    .unwrap();
+
+    // Action: Identify the slides of the volume
+    let mut volume = Volume::new("foo".to_string(), false, "slides", ctx.roots[0].join("foo"));
+    identify_slides(&mut volume).unwrap();
+
+    // Check: The overrides are read
+    assert_eq!(
+        volume.slides["bar"].overrides,
+        config::StrategyOverrides {
+            collision: Some(CollisionPolicy::Rename {
+                suffix: "old".to_owned()
+            }),
+            conflict: None,
+            check: Some(Some(Algorithm::MD5)),
+            recheck: None,
+            check_mode: None,
+            safe: Some(true),
+            wip_suffix: None,
+            staging: None,
+            retries: Some(2),
+            max_failures: None,
+            min_interval: None,
+            jobs: Some(4),
+            delta: None,
+            preserve_times: None,
+            preserve_permissions: None,
+            preserve_xattrs: None,
+            rate_limit: Some(10 * 1024 * 1024),
+            skip_identical: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: None,
+        }
+    );
+    assert_eq!(volume.slides["baz"].or_else, Some("bar".to_owned()));
+    assert_eq!(volume.slides["baz"].overrides.check, Some(None));
+    assert_eq!(volume.slides["foo"].overrides, Default::default());
+
+    // Check: The overrides take precedence over the global strategy
+    let global = MoveStrategy {
+        collision: CollisionPolicy::Fail,
+        safe: false,
+        wip_suffix: "wip".to_owned(),
+        staging: false,
+        check: Some(Algorithm::BLAKE),
+        recheck: false,
+        check_mode: CheckMode::Full,
+        retries: 5,
+        jobs: 1,
+        delta: false,
+        preserve_times: false,
+        preserve_permissions: false,
+        preserve_xattrs: false,
+        rate_limit: None,
+        sanitize: None,
+        use_trash: None,
+        min_age: None,
+        max_file_size: None,
+        sidecar: None,
+        reverify: None,
+        delivery_manifest: false,
+        conflict: ConflictPolicy::Report,
+        max_failures: 5,
+        min_interval: 0,
+        skip_identical: None,
+    };
+    let strategy = global.with_overrides(&volume.slides["baz"].overrides);
+    assert_eq!(strategy.check, None);
+    assert_eq!(strategy.collision, CollisionPolicy::Fail);
+    assert_eq!(strategy.retries, 5);
+    assert_eq!(strategy.jobs, 1);
+    let strategy = global.with_overrides(&volume.slides["bar"].overrides);
+    assert_eq!(strategy.rate_limit, Some(10 * 1024 * 1024));
+    assert_eq!(strategy.jobs, 4);
+}
+
+/// Test the parsing of the collision policies
+#[test]
+fn test_collision_policy_from_str() {
+    assert_eq!(
+        "overwrite".parse::<CollisionPolicy>().unwrap(),
+        CollisionPolicy::Overwrite
+    );
+    assert_eq!(
+        "Skip".parse::<CollisionPolicy>().unwrap(),
+        CollisionPolicy::Skip
+    );
+    assert_eq!(
+        "fail".parse::<CollisionPolicy>().unwrap(),
+        CollisionPolicy::Fail
+    );
+    assert_eq!(
+        "rename".parse::<CollisionPolicy>().unwrap(),
+        CollisionPolicy::Rename {
+            suffix: CollisionPolicy::DEFAULT_RENAME_SUFFIX.to_owned()
+        }
+    );
+    assert_eq!(
+        "rename:bak".parse::<CollisionPolicy>().unwrap(),
+        CollisionPolicy::Rename {
+            suffix: "bak".to_owned()
+        }
+    );
+    assert_eq!(
+        "keep-newer".parse::<CollisionPolicy>().unwrap(),
+        CollisionPolicy::KeepNewer {
+            remove_older: false
+        }
+    );
+    assert_eq!(
+        "keep-newer:remove".parse::<CollisionPolicy>().unwrap(),
+        CollisionPolicy::KeepNewer { remove_older: true }
+    );
+    assert!("keep-newer:bak".parse::<CollisionPolicy>().is_err());
+    assert_eq!(
+        "rename:{stem} ({n}).{ext}"
+            .parse::<CollisionPolicy>()
+            .unwrap(),
+        CollisionPolicy::Rename {
+            suffix: "{stem} ({n}).{ext}".to_owned()
+        }
+    );
+    assert!("rename:{stem}-{date}".parse::<CollisionPolicy>().is_err());
+    assert!("rename:{stem".parse::<CollisionPolicy>().is_err());
+    assert!("rename:old/{stem}".parse::<CollisionPolicy>().is_err());
+    assert!("rename:".parse::<CollisionPolicy>().is_err());
+    assert!("skip:bak".parse::<CollisionPolicy>().is_err());
+    assert!("merge".parse::<CollisionPolicy>().is_err());
+}
+
+/// Test the parsing of the comparisons of the identical files
+#[test]
+fn test_parse_identical() {
+    assert_eq!(
+        config::parse_identical("quick").unwrap(),
+        Some(Identical::Quick)
+    );
+    assert_eq!(
+        config::parse_identical("Contents").unwrap(),
+        Some(Identical::Contents)
+    );
+    assert_eq!(config::parse_identical("none").unwrap(), None);
+    assert!(config::parse_identical("hash").is_err());
+}
+
+/// Test the parsing of the sanitizations of the file names
+#[test]
+fn test_parse_sanitize() {
+    assert_eq!(
+        config::parse_sanitize("underscore").unwrap(),
+        Some(Sanitize::Underscore)
+    );
+    assert_eq!(
+        config::parse_sanitize("Unicode").unwrap(),
+        Some(Sanitize::Unicode)
+    );
+    assert_eq!(config::parse_sanitize("none").unwrap(), None);
+    assert!(config::parse_sanitize("dash").is_err());
+}
+
+/// Test the parsing of the uses of the trash
+#[test]
+fn test_parse_trash() {
+    assert_eq!(
+        config::parse_trash("overwritten").unwrap(),
+        Some(Trash {
+            sources: false,
+            retention: Trash::DEFAULT_RETENTION
+        })
+    );
+    assert_eq!(
+        config::parse_trash("All:7").unwrap(),
+        Some(Trash {
+            sources: true,
+            retention: 7
+        })
+    );
+    assert_eq!(config::parse_trash("none").unwrap(), None);
+    assert!(config::parse_trash("all:week").is_err());
+    assert!(config::parse_trash("sources").is_err());
+}
+
+/// Test the parsing of the second checks of the deliveries
+#[test]
+fn test_parse_reverify() {
+    assert_eq!(
+        config::parse_reverify("30").unwrap(),
+        Some(Reverify {
+            after: 30,
+            restore: false
+        })
+    );
+    assert_eq!(
+        config::parse_reverify("5:Restore").unwrap(),
+        Some(Reverify {
+            after: 5,
+            restore: true
+        })
+    );
+    assert_eq!(config::parse_reverify("none").unwrap(), None);
+    assert!(config::parse_reverify("soon").is_err());
+    assert!(config::parse_reverify("30:repair").is_err());
+
+    // Check: The minutes may be given as a number in the config files
+    let overrides: config::StrategyOverrides = serde_yaml::from_str("reverify: 10").unwrap();
+    assert_eq!(
+        overrides.reverify,
+        Some(Some(Reverify {
+            after: 10,
+            restore: false
+        }))
+    );
+}
+
+/// Test the parsing of the check modes
+#[test]
+fn test_parse_check_mode() {
+    use crate::config::CheckMode;
+
+    assert_eq!("full".parse::<CheckMode>().unwrap(), CheckMode::Full);
+    assert_eq!(
+        "sampled".parse::<CheckMode>().unwrap(),
+        CheckMode::Sampled {
+            threshold: 1024 * 1024 * 1024,
+            edge: 16 * 1024 * 1024,
+            blocks: 64
+        }
+    );
+    assert_eq!(
+        "Sampled:4G:1M:8".parse::<CheckMode>().unwrap(),
+        CheckMode::Sampled {
+            threshold: 4 * 1024 * 1024 * 1024,
+            edge: 1024 * 1024,
+            blocks: 8
+        }
+    );
+    assert!("partial".parse::<CheckMode>().is_err());
+    assert!("sampled:big".parse::<CheckMode>().is_err());
+    assert!("sampled:1G:1M:8:2".parse::<CheckMode>().is_err());
+
+    // Check: The mode is parsed from the config files too
+    let overrides: config::StrategyOverrides =
+        serde_yaml::from_str("check_mode: \"sampled:2G\"").unwrap();
+    assert!(matches!(
+        overrides.check_mode,
+        Some(CheckMode::Sampled { threshold, .. }) if threshold == 2 * 1024 * 1024 * 1024
+    ));
+}
+
+/// Test the parsing of the file sizes
+#[test]
+fn test_parse_size() {
+    use crate::config::parse_size;
+
+    assert_eq!(parse_size("0").unwrap(), 0);
+    assert_eq!(parse_size("1500").unwrap(), 1500);
+    assert_eq!(parse_size("512K").unwrap(), 512 * 1024);
+    assert_eq!(parse_size("10 MB").unwrap(), 10 * 1024 * 1024);
+    assert_eq!(parse_size("1.5GiB").unwrap(), 3 * 512 * 1024 * 1024);
+    assert_eq!(parse_size("2t").unwrap(), 2 * 1024_u64.pow(4));
+    assert!(parse_size("").is_err());
+    assert!(parse_size("G").is_err());
+    assert!(parse_size("10X").is_err());
+}
+
 /// Test the identification of volumes and slides inside a set of root folders
 #[test]
 fn test_identify_env() {
@@ -106,6 +398,109 @@ fn test_identify_env() {
     assert_eq!(volumes["disabled"].name, "disabled".to_owned());
 }
 
+/// Test the identification of volumes with glob patterns in the roots
+#[test]
+fn test_identify_env_glob_roots() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+
+    // Action: Call identify_env with a pattern matching every root folder
+    let roots = vec![ctx.temp_dir.path().join("root*")];
+    let volumes = identify_env("slides", &roots).unwrap();
+
+    // Check: The result should contain the same volumes as the explicit roots
+    assert_eq!(volumes.len(), 5);
+
+    // Action: Create a new root matching the pattern and rescan
+    let new_root = ctx.temp_dir.path().join("root2");
+    std::fs::create_dir_all(new_root.join("qux").join("slides")).unwrap();
+    let volumes = identify_env("slides", &roots).unwrap();
+
+    // Check: The new volume is picked up
+    assert_eq!(volumes.len(), 6);
+    assert!(volumes.contains_key("qux"));
+}
+
+/// Test the validation of the environment
+#[test]
+fn test_validate() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+
+    // Prerequisite: Add a broken volume config and a missing root
+    std::fs::write(
+        ctx.roots[0]
+            .join("bar")
+            .join("slides")
+            .join(volume::DEFAULT_VOLUME_CONFIG_FILE),
+        "name: [",
+    )
+    .unwrap();
+    let mut roots = ctx.roots.clone();
+    roots.push(ctx.temp_dir.path().join("not-found"));
+
+    // Action: Validate the rootset
+    let issues = validate(
+        &[RootsetConfig {
+            keyword: "slides".to_string(),
+            roots,
+            poll: false,
+        }],
+        false,
+    );
+
+    // Check: Every problem is reported
+    for needle in [
+        "Root is not a folder",
+        "Invalid volume config",
+        "Route \"not-found-lol\" is not a known volume",
+        "Volume \"quux_\" is unreachable from \"baz\"",
+    ] {
+        assert!(
+            issues.iter().any(|i| i.contains(needle)),
+            "Missing issue: {needle}. Issues: {issues:#?}"
+        );
+    }
+
+    // Check: Routed slides are reachable
+    assert!(!issues
+        .iter()
+        .any(|i| i.contains("\"qux_\" is unreachable from \"baz\"")));
+}
+
+/// Test the strict validation of the config files
+#[test]
+fn test_validate_strict() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+
+    // Prerequisite: Misspell a setting in a slide config
+    let slide_conf = ctx.roots[0]
+        .join("bar")
+        .join("slides")
+        .join("foo")
+        .join(DEFAULT_SLIDE_CONFIG_FILE);
+    std::fs::write(&slide_conf, "rout: baz\n").unwrap();
+    let rootsets = [RootsetConfig {
+        keyword: "slides".to_string(),
+        roots: ctx.roots.clone(),
+        poll: false,
+    }];
+
+    // Action: Validate the rootset, leniently and strictly
+    let lenient = validate(&rootsets, false);
+    let strict = validate(&rootsets, true);
+
+    // Check: The typo is only reported in strict mode
+    assert!(!lenient.iter().any(|i| i.contains("Unknown field")));
+    assert!(
+        strict
+            .iter()
+            .any(|i| i.contains("Invalid slide config: Unknown field `rout`")),
+        "Issues: {strict:#?}"
+    );
+}
+
 /// Test the building of sync jobs between volumes
 #[test]
 fn test_build_syncjobs() {
@@ -155,179 +550,4197 @@ fn test_build_syncjobs() {
     assert!(!syncjobs.contains(&SyncJob::new("disabled", "foo", "foo")));
 }
 
-/// Test the execution of sync jobs between volumes
-#[tokio::test]
-async fn test_execute_syncjobs() {
+/// Test the planning of sync jobs, which must not touch the filesystem
+#[test]
+fn test_plan() {
     // Prerequisite: Setup the test context
     let ctx = setup().unwrap();
+    let missing_slide = ctx.roots[0].join("bar").join("slides").join("qux_");
+    assert!(!missing_slide.exists());
 
-    // Prerequisite: Create a tracer that writes to a known location
-    let trace_path = ctx.temp_dir.path().join("test.trace");
-    let (tracer, handle) = {
-        let (tracer, handle) = tracer::Tracer::new(&Some(&trace_path)).await.unwrap();
-        (
-            tracer.annotate_author("test_execute_syncjobs".to_owned()),
-            handle.expect("Should have a handle"),
-        )
-    };
-
-    // Prerequisite: Identify the volumes in the root folders
-    let mut volumes: HashMap<String, Volume> = identify_env("slides", &ctx.roots).unwrap();
+    // Action: Plan the sync jobs
+    let jobs = plan(&GlobalConfig {
+        rootsets: vec![RootsetConfig {
+            keyword: "slides".to_string(),
+            roots: ctx.roots.clone(),
+            poll: false,
+        }],
+        dry_run: false,
+        trace: None,
+        history: None,
+        check: None,
+        recheck: false,
+        check_mode: CheckMode::Full,
+        collision: CollisionPolicy::Fail,
+        safe: true,
+        wip_suffix: "wip".to_owned(),
+        staging: false,
+        retries: 0,
+        jobs: 1,
+        parallel: 1,
+        debounce: None,
+        debounce_max: None,
+        poll: false,
+        poll_interval: 30,
+        heartbeat: None,
+        delta: false,
+        preserve_times: false,
+        preserve_permissions: false,
+        preserve_xattrs: false,
+        rate_limit: None,
+        sanitize: None,
+        use_trash: None,
+        min_age: None,
+        max_file_size: None,
+        sidecar: None,
+        reverify: None,
+        delivery_manifest: false,
+        conflict: ConflictPolicy::Report,
+        max_failures: 5,
+        min_interval: 0,
+        skip_identical: None,
+        progress: false,
+        min_size: None,
+        max_size: None,
+        control: None,
+        signing_key: None,
+        trusted_keys: vec![],
+        mqtt: None,
+        exclude: vec![],
+        strict: false,
+        once: false,
+        output: OutputFormat::Text,
+    })
+    .unwrap();
 
-    // Prerequisite: Build the sync jobs between the volumes
-    let syncjobs = build_syncjobs(&mut volumes).unwrap();
+    // Check: The indirect job is planned, but its destination slide is not created
+    let indirect = jobs
+        .iter()
+        .find(|j| j.dst == "qux_")
+        .expect("Missing the indirect job");
+    assert_eq!(
+        (indirect.src.as_str(), indirect.via.as_str()),
+        ("baz", "bar")
+    );
+    assert_eq!(indirect.to, missing_slide);
+    assert!(!missing_slide.exists());
+    assert_eq!(jobs.len(), 10);
 
-    // Action: Execute the sync jobs
-    {
-        let move_req = MoveStrategy {
-            collision: CollisionPolicy::Fail,
-            safe: false,
-            check: None,
-            retries: 5,
-        };
-        execute_syncjobs(&volumes, syncjobs, false, tracer, &move_req)
-            .await
-            .unwrap();
-    }
+    // Check: The planned jobs can be printed as JSON
+    let json = serde_json::to_value(indirect).unwrap();
+    assert_eq!(json["via"], "bar");
+    assert_eq!(json["to"], missing_slide.to_string_lossy().as_ref());
+}
 
-    // Check: The tracer has traced some info
-    {
-        // Force flush and close the tracer
-        handle.await.unwrap();
+/// Test the listing of the volumes
+#[test]
+fn test_list_volumes() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
 
-        let trace_content = std::fs::read_to_string(&trace_path).unwrap();
-        let traces: String = trace_content
-            .lines()
-            .filter(|line| !line.trim().is_empty())
-            .map(|line| line.to_owned())
-            .collect();
+    // Action: List the volumes
+    let volumes = list_volumes(&GlobalConfig {
+        rootsets: vec![RootsetConfig {
+            keyword: "slides".to_string(),
+            roots: ctx.roots.clone(),
+            poll: false,
+        }],
+        dry_run: false,
+        trace: None,
+        history: None,
+        check: None,
+        recheck: false,
+        check_mode: CheckMode::Full,
+        collision: CollisionPolicy::Fail,
+        safe: true,
+        wip_suffix: "wip".to_owned(),
+        staging: false,
+        retries: 0,
+        jobs: 1,
+        parallel: 1,
+        debounce: None,
+        debounce_max: None,
+        poll: false,
+        poll_interval: 30,
+        heartbeat: None,
+        delta: false,
+        preserve_times: false,
+        preserve_permissions: false,
+        preserve_xattrs: false,
+        rate_limit: None,
+        sanitize: None,
+        use_trash: None,
+        min_age: None,
+        max_file_size: None,
+        sidecar: None,
+        reverify: None,
+        delivery_manifest: false,
+        conflict: ConflictPolicy::Report,
+        max_failures: 5,
+        min_interval: 0,
+        skip_identical: None,
+        progress: false,
+        min_size: None,
+        max_size: None,
+        control: None,
+        signing_key: None,
+        trusted_keys: vec![],
+        mqtt: None,
+        exclude: vec![],
+        strict: false,
+        once: false,
+        output: OutputFormat::Text,
+    })
+    .unwrap();
 
-        #[cfg(false)]
-        {
-            println!("Traces:");
-            for trace in traces.lines() {
-                println!("  {:?}", trace);
-            }
-        }
+    // Check: Every volume is listed, sorted by name, disabled ones included
+    let names = volumes.iter().map(|v| v.name.as_str()).collect::<Vec<_>>();
+    let mut sorted = names.clone();
+    sorted.sort();
+    assert_eq!(names, sorted);
+    assert!(names.contains(&"disabled"));
 
-        for needle in &[
-            "Starting slides sync...",
-            "[foo -_-> bar] MKDIR",
-            "[bar -_-> foo] MKDIR",
-            "[foo -_-> bar] MV",
-            "[bar -_-> foo] MV",
-        ] {
-            assert!(traces.contains(needle), "Missing trace: {}", needle);
-        }
-    }
+    // Check: The routes are shown
+    let baz = volumes.iter().find(|v| v.name == "baz").unwrap();
+    assert!(baz.to_string().contains("qux_ (->bar)"), "{baz}");
+}
 
-    // Check: The slides should be synchronized correctly
-    {
-        // Check: The source slide should not have any contents
-        {
-            let src = &volumes["foo"].slides["bar"].path;
-            let file = src.join("media").join("bigfile");
-            assert!(!file.exists());
-        }
+/// Test the explanation of the routing decisions
+#[test]
+fn test_explain_routes() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+    let volumes = identify_env("slides", &ctx.roots).unwrap();
 
-        // Check: The destination slide should have the contents of the source slide
-        {
-            let dst = &volumes["bar"].slides["bar"].path;
-            let file = dst.join("media").join("bigfile");
-            let expected = "F1C9645DBC14EFDDC7D8A322685F26EB";
-            assert!(
-                file.exists()
-                    && file.is_file()
-                    && file.metadata().unwrap().len() == 1024 * 1024 * 10
-                    && hash_file(&file, Algorithm::MD5) == expected,
-                "bigfile checksum: {} expected: {}",
-                hash_file(&file, Algorithm::MD5),
-                expected
-            );
-        }
+    // Action: Resolve the routes
+    let routes = resolve_routes(&volumes);
 
-        // Check: The source slide should not have any contents
-        {
-            let src = &volumes["bar"].slides["foo"].path;
-            let slide = src;
-            let top = slide.join("photos");
-            let folder = top.join("trip-to-rome");
-            let file = folder.join("photo1.jpg");
-            assert!(!file.exists());
-            assert!(!folder.exists());
-            assert!(top.exists());
-            assert!(slide.exists());
-        }
+    // Check: Every kind of decision is explained
+    let find = |src: &str, dst: &str| {
+        routes
+            .iter()
+            .find(|r| r.src == src && r.dst == dst)
+            .unwrap_or_else(|| panic!("Missing route {src} -> {dst}: {routes:#?}"))
+    };
+    assert_eq!(find("foo", "bar").decision, Decision::Direct);
+    assert_eq!(
+        find("baz", "qux_").decision,
+        Decision::Via("bar".to_owned())
+    );
+    assert!(matches!(
+        &find("baz", "quux_").decision,
+        Decision::Dropped(reason) if reason.contains("no default route")
+    ));
 
-        // Check: The destination slide should have the contents of the source slide
-        {
-            let dst = &volumes["foo"].slides["foo"].path;
-            let file = dst.join("photos").join("trip-to-rome").join("photo1.jpg");
-            let expected = "92AB673D915A94DCF187720E8AC0D608";
-            assert!(
-                file.exists()
-                    && file.is_file()
-                    && file.metadata().unwrap().len() == 1024 * 16
-                    && hash_file(&file, Algorithm::MD5) == expected,
-                "bigfile checksum: {} expected: {}",
-                hash_file(&file, Algorithm::MD5),
-                expected
-            );
-        }
-    }
+    // Check: The routes are sorted, and match the planned sync jobs
+    let mut sorted = routes.clone();
+    sorted.sort_by(|a, b| (&a.src, &a.dst).cmp(&(&b.src, &b.dst)));
+    assert_eq!(routes, sorted);
+    assert_eq!(
+        routes
+            .iter()
+            .filter(|r| !matches!(r.decision, Decision::Dropped(_)))
+            .count(),
+        plan_syncjobs(&volumes).len()
+    );
 }
 
-/// Test the execution of sync jobs between volumes with a missing source (i.e. The user deleted a source slide)
-#[tokio::test]
-#[ignore]
-async fn test_execute_syncjobs_with_missing_source() {
+/// Test following the default routes across several volumes
+#[test]
+fn test_route_chains() {
     // Prerequisite: Setup the test context
     let ctx = setup().unwrap();
 
-    let (tracer, handle) = {
-        let trace_path = ctx.temp_dir.path().join("test.trace");
-        let (tracer, handle) = tracer::Tracer::new(&Some(&trace_path)).await.unwrap();
-        (
-            tracer.annotate_author("test_execute_syncjobs_with_missing_source".to_owned()),
-            handle.expect("Should have a handle"),
-        )
-    };
+    // Prerequisite: Route "qux_" from "bar" on to "foo"
+    let qux = ctx.roots[0].join("bar").join("slides").join("qux_");
+    std::fs::create_dir(&qux).unwrap();
+    std::fs::write(qux.join(DEFAULT_SLIDE_CONFIG_FILE), "route: foo").unwrap();
+    let volumes = identify_env("slides", &ctx.roots).unwrap();
 
-    // Prerequisite: Identify the volumes in the root folders
-    let mut volumes = identify_env("slides", &ctx.roots).unwrap();
+    // Action: Resolve the routes
+    let routes = resolve_routes(&volumes);
+
+    // Check: The files go as far as the routes lead, through every volume on the way
+    let route = routes
+        .iter()
+        .find(|r| r.src == "baz" && r.dst == "qux_")
+        .unwrap();
+    assert_eq!(route.decision, Decision::Via("foo".to_owned()));
+    assert_eq!(route.hops, vec!["bar".to_owned(), "foo".to_owned()]);
+    assert_eq!(route.to_string(), "baz -> qux_: via bar -> foo");
+    assert!(plan_syncjobs(&volumes)
+        .iter()
+        .any(|job| job.src == "baz" && job.via == "foo" && job.dst == "qux_"));
+
+    // Prerequisite: Route "qux_" from "foo" back to "baz"
+    let qux = ctx.roots[0].join("foo").join("slides").join("qux_");
+    std::fs::create_dir(&qux).unwrap();
+    std::fs::write(qux.join(DEFAULT_SLIDE_CONFIG_FILE), "route: baz").unwrap();
+    let volumes = identify_env("slides", &ctx.roots).unwrap();
+
+    // Action: Resolve the routes
+    let routes = resolve_routes(&volumes);
+
+    // Check: The cycle is found, and the slide refused rather than bouncing between volumes
+    let route = routes
+        .iter()
+        .find(|r| r.src == "baz" && r.dst == "qux_")
+        .unwrap();
+    assert_eq!(
+        route.decision,
+        Decision::Cycle(["baz", "bar", "foo", "baz"].map(str::to_owned).to_vec())
+    );
+    assert_eq!(
+        route.to_string(),
+        "baz -> qux_: dropped, routing cycle baz -> bar -> foo -> baz"
+    );
+    assert!(!plan_syncjobs(&volumes)
+        .iter()
+        .any(|job| job.dst == "qux_" && ["baz", "bar", "foo"].contains(&job.src.as_str())));
+
+    // Check: The cycle is reported by the validation
+    let issues = validate(
+        &[RootsetConfig {
+            keyword: "slides".to_string(),
+            roots: ctx.roots.clone(),
+            poll: false,
+        }],
+        false,
+    );
+    assert!(
+        issues.iter().any(|i| i
+            == "Route from \"baz\" to \"qux_\" goes around the routing cycle baz -> bar -> foo -> baz"),
+        "Missing cycle. Issues: {issues:#?}"
+    );
+}
+
+/// Test picking the cheapest of several routes
+#[test]
+fn test_route_costs() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+
+    // Prerequisite: Route "qux_" from "baz" through either "bar" or "els"
+    let baz = ctx.roots[1].join("baz").join("slides");
+    std::fs::write(
+        baz.join("qux_").join(DEFAULT_SLIDE_CONFIG_FILE),
+        "route: [bar, els]",
+    )
+    .unwrap();
+    let volumes = identify_env("slides", &ctx.roots).unwrap();
+    assert_eq!(
+        volumes["baz"].slides["qux_"].or_else,
+        Some("bar".to_owned())
+    );
+    assert_eq!(
+        volumes["baz"].slides["qux_"].alternatives,
+        vec!["els".to_owned()]
+    );
+
+    // Check: Both are as cheap, so the first one is taken
+    let find = |volumes: &HashMap<String, Volume>| {
+        resolve_routes(volumes)
+            .into_iter()
+            .find(|r| r.src == "baz" && r.dst == "qux_")
+            .unwrap()
+    };
+    assert_eq!(find(&volumes).decision, Decision::Via("bar".to_owned()));
+
+    // Prerequisite: Make "bar" expensive
+    std::fs::write(
+        ctx.roots[0]
+            .join("bar")
+            .join("slides")
+            .join(volume::DEFAULT_VOLUME_CONFIG_FILE),
+        "cost: 5",
+    )
+    .unwrap();
+    let volumes = identify_env("slides", &ctx.roots).unwrap();
+    assert_eq!(volumes["bar"].cost, 5);
+
+    // Check: The cheaper one is taken instead
+    assert_eq!(find(&volumes).decision, Decision::Via("els".to_owned()));
+
+    // Prerequisite: Route "qux_" from "els" on to "foo"
+    std::fs::write(
+        ctx.roots[1]
+            .join("els")
+            .join("slides")
+            .join("qux_")
+            .join(DEFAULT_SLIDE_CONFIG_FILE),
+        "route: foo",
+    )
+    .unwrap();
+    let volumes = identify_env("slides", &ctx.roots).unwrap();
+
+    // Check: Two cheap hops still beat an expensive one
+    let route = find(&volumes);
+    assert_eq!(route.decision, Decision::Via("foo".to_owned()));
+    assert_eq!(route.hops, vec!["els".to_owned(), "foo".to_owned()]);
+
+    // Prerequisite: More files waiting than any volume has room for
+    let free = fs::free_space(ctx.temp_dir.path()).unwrap();
+    std::fs::create_dir(baz.join("qux_").join("huge")).unwrap();
+    if std::fs::File::create(baz.join("qux_").join("huge").join("disk.img"))
+        .and_then(|file| file.set_len(free + 1))
+        .is_err()
+    {
+        eprintln!("Unable to create a file that large here, skipping");
+        return;
+    }
+    assert_eq!(fs::pending_size(&baz.join("qux_")), free + 1);
+
+    // Check: The files still go to the volume with the most room, rather than nowhere
+    let route = find(&volumes);
+    assert!(
+        [
+            Decision::Via("foo".to_owned()),
+            Decision::Via("bar".to_owned())
+        ]
+        .contains(&route.decision),
+        "{route}"
+    );
+}
+
+/// Test falling back on the routes listed, in order, while the first ones are away
+#[test]
+fn test_route_fallbacks() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+
+    // Prerequisite: Route "qux_" from "baz" through "nas_", "bar" or "els", "nas_" being away
+    let baz = ctx.roots[1].join("baz").join("slides");
+    std::fs::write(
+        baz.join("qux_").join(DEFAULT_SLIDE_CONFIG_FILE),
+        "route: [nas_, bar, els]",
+    )
+    .unwrap();
+    let find = || {
+        let volumes = identify_env("slides", &ctx.roots).unwrap();
+        resolve_routes(&volumes)
+            .into_iter()
+            .find(|r| r.src == "baz" && r.dst == "qux_")
+            .unwrap()
+    };
+
+    // Check: The first route present is taken
+    assert_eq!(find().decision, Decision::Via("bar".to_owned()));
+
+    // Prerequisite: A file waiting, with room for it everywhere but on "bar"
+    std::fs::create_dir(baz.join("qux_").join("waiting")).unwrap();
+    std::fs::write(baz.join("qux_").join("waiting").join("file.txt"), "waiting").unwrap();
+    let volumes = identify_env("slides", &ctx.roots).unwrap();
+    let bar = volumes["bar"].path.clone();
+    let room = |full: bool| {
+        let bar = bar.clone();
+        move |path: &Path| Some(if full && path == bar { 0 } else { u64::MAX })
+    };
+
+    // Check: The first route is taken while it has room, and passed over once full
+    assert_eq!(
+        cheapest_route(&volumes, "baz", "qux_", room(false)),
+        Some(vec!["bar".to_owned()])
+    );
+    assert_eq!(
+        cheapest_route(&volumes, "baz", "qux_", room(true)),
+        Some(vec!["els".to_owned()])
+    );
+
+    // Action: Disable "bar"
+    std::fs::write(
+        ctx.roots[0]
+            .join("bar")
+            .join("slides")
+            .join(volume::DEFAULT_VOLUME_CONFIG_FILE),
+        "disabled: true",
+    )
+    .unwrap();
+
+    // Check: The next route is taken
+    assert_eq!(find().decision, Decision::Via("els".to_owned()));
+
+    // Action: Disable "els" too
+    std::fs::write(
+        ctx.roots[1]
+            .join("els")
+            .join("slides")
+            .join(volume::DEFAULT_VOLUME_CONFIG_FILE),
+        "disabled: true",
+    )
+    .unwrap();
+
+    // Check: The slide is dropped, naming every route
+    assert_eq!(
+        find().decision,
+        Decision::Dropped(
+            "\"qux_\" is not available and none of the default routes \"nas_\", \"bar\", \"els\" is available"
+                .to_owned()
+        )
+    );
+}
+
+/// Test the files following the ones already staged, even if a better route appears
+#[tokio::test]
+async fn test_route_sticky() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+
+    // Prerequisite: Route "qux_" from "baz" through either "bar" or "els"
+    let baz = ctx.roots[1].join("baz").join("slides");
+    std::fs::write(
+        baz.join("qux_").join(DEFAULT_SLIDE_CONFIG_FILE),
+        "route: [bar, els]",
+    )
+    .unwrap();
+    let find = || {
+        let volumes = identify_env("slides", &ctx.roots).unwrap();
+        resolve_routes(&volumes)
+            .into_iter()
+            .find(|r| r.src == "baz" && r.dst == "qux_")
+            .unwrap()
+    };
+    let config = GlobalConfig {
+        rootsets: vec![RootsetConfig {
+            keyword: "slides".to_string(),
+            roots: ctx.roots.clone(),
+            poll: false,
+        }],
+        dry_run: false,
+        trace: None,
+        history: None,
+        check: Some(Algorithm::MD5),
+        recheck: false,
+        check_mode: CheckMode::Full,
+        collision: CollisionPolicy::Fail,
+        safe: true,
+        wip_suffix: "wip".to_owned(),
+        staging: false,
+        retries: 5,
+        jobs: 1,
+        parallel: 1,
+        debounce: None,
+        debounce_max: None,
+        poll: false,
+        poll_interval: 30,
+        heartbeat: None,
+        delta: false,
+        preserve_times: false,
+        preserve_permissions: false,
+        preserve_xattrs: false,
+        rate_limit: None,
+        sanitize: None,
+        use_trash: None,
+        min_age: None,
+        max_file_size: None,
+        sidecar: None,
+        reverify: None,
+        delivery_manifest: false,
+        conflict: ConflictPolicy::Report,
+        max_failures: 5,
+        min_interval: 0,
+        skip_identical: None,
+        progress: false,
+        min_size: None,
+        max_size: None,
+        control: None,
+        signing_key: None,
+        trusted_keys: vec![],
+        mqtt: None,
+        exclude: vec![],
+        strict: false,
+        once: true,
+        output: OutputFormat::Text,
+    };
+
+    // Action: Stage a file for "qux_"
+    std::fs::create_dir(baz.join("qux_").join("first")).unwrap();
+    std::fs::write(baz.join("qux_").join("first").join("first.txt"), b"First").unwrap();
+    enough(slide(config.clone()).await.unwrap()).await.unwrap();
+
+    // Check: The file was staged in "bar", which is recorded
+    let bar = ctx.roots[0].join("bar").join("slides");
+    assert!(bar.join("qux_").join("first").join("first.txt").exists());
+    assert_eq!(fs::sticky_route(&baz.join("qux_")), Some("bar".to_owned()));
+
+    // Action: Make "bar" expensive, so "els" would be taken otherwise
+    std::fs::write(bar.join(volume::DEFAULT_VOLUME_CONFIG_FILE), "cost: 5").unwrap();
+
+    // Check: The files keep following the ones staged
+    assert_eq!(find().decision, Decision::Via("bar".to_owned()));
+
+    // Action: Disable "bar"
+    std::fs::write(
+        bar.join(volume::DEFAULT_VOLUME_CONFIG_FILE),
+        "disabled: true",
+    )
+    .unwrap();
+
+    // Check: The files are not stranded, the next route is taken
+    assert_eq!(find().decision, Decision::Via("els".to_owned()));
+
+    // Action: Enable "bar" again, and plug "qux_" in
+    std::fs::remove_file(bar.join(volume::DEFAULT_VOLUME_CONFIG_FILE)).unwrap();
+    std::fs::create_dir_all(ctx.roots[0].join("qux_").join("slides")).unwrap();
+    std::fs::create_dir(baz.join("qux_").join("second")).unwrap();
+    std::fs::write(
+        baz.join("qux_").join("second").join("second.txt"),
+        b"Second",
+    )
+    .unwrap();
+    enough(slide(config).await.unwrap()).await.unwrap();
+
+    // Check: The files reached "qux_", and the route is forgotten
+    assert_eq!(find().decision, Decision::Direct);
+    assert!(ctx.roots[0]
+        .join("qux_")
+        .join("slides")
+        .join("qux_")
+        .join("second")
+        .join("second.txt")
+        .exists());
+    assert_eq!(fs::sticky_route(&baz.join("qux_")), None);
+}
+
+/// Test spreading the files of a slide over several routes
+#[tokio::test]
+async fn test_route_spread() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+
+    // Prerequisite: Spread "qux_" from "baz" over "bar" and "foo"
+    let baz = ctx.roots[1].join("baz").join("slides");
+    std::fs::write(
+        baz.join("qux_").join(DEFAULT_SLIDE_CONFIG_FILE),
+        "route: [bar, foo]\nbalance: even",
+    )
+    .unwrap();
+    let volumes = identify_env("slides", &ctx.roots).unwrap();
+    assert_eq!(volumes["baz"].slides["qux_"].balance, Some(Balance::Even));
+
+    // Check: The route is spread, a syncjob for each volume
+    let route = resolve_routes(&volumes)
+        .into_iter()
+        .find(|r| r.src == "baz" && r.dst == "qux_")
+        .unwrap();
+    assert_eq!(
+        route.decision,
+        Decision::Spread(vec!["bar".to_owned(), "foo".to_owned()])
+    );
+    assert_eq!(route.to_string(), "baz -> qux_: spread over bar, foo");
+    let syncjobs = plan_syncjobs(&volumes);
+    for via in ["bar", "foo"] {
+        assert!(syncjobs.contains(&SyncJob::new("baz", via, "qux_")));
+    }
+
+    // Check: Every file falls in a single share, and none in a share weighing nothing
+    let weights = Arc::new(vec![0, 3, 1]);
+    let shares = (0..3)
+        .map(|index| Share {
+            index,
+            weights: weights.clone(),
+        })
+        .collect::<Vec<_>>();
+    for file in 0..100 {
+        let path = PathBuf::from("folder").join(format!("{file}.txt"));
+        let taken = shares.iter().filter(|share| share.takes(&path)).count();
+        assert_eq!(taken, 1);
+        assert!(!shares[0].takes(&path));
+    }
+
+    // Action: Drop some files for "qux_" and sync once
+    let many = baz.join("qux_").join("many");
+    std::fs::create_dir(&many).unwrap();
+    for file in 0..20 {
+        std::fs::write(many.join(format!("{file}.txt")), format!("File {file}")).unwrap();
+    }
+    let config = GlobalConfig {
+        rootsets: vec![RootsetConfig {
+            keyword: "slides".to_string(),
+            roots: ctx.roots.clone(),
+            poll: false,
+        }],
+        dry_run: false,
+        trace: None,
+        history: None,
+        check: Some(Algorithm::MD5),
+        recheck: false,
+        check_mode: CheckMode::Full,
+        collision: CollisionPolicy::Fail,
+        safe: true,
+        wip_suffix: "wip".to_owned(),
+        staging: false,
+        retries: 5,
+        jobs: 1,
+        parallel: 1,
+        debounce: None,
+        debounce_max: None,
+        poll: false,
+        poll_interval: 30,
+        heartbeat: None,
+        delta: false,
+        preserve_times: false,
+        preserve_permissions: false,
+        preserve_xattrs: false,
+        rate_limit: None,
+        sanitize: None,
+        use_trash: None,
+        min_age: None,
+        max_file_size: None,
+        sidecar: None,
+        reverify: None,
+        delivery_manifest: false,
+        conflict: ConflictPolicy::Report,
+        max_failures: 5,
+        min_interval: 0,
+        skip_identical: None,
+        progress: false,
+        min_size: None,
+        max_size: None,
+        control: None,
+        signing_key: None,
+        trusted_keys: vec![],
+        mqtt: None,
+        exclude: vec![],
+        strict: false,
+        once: true,
+        output: OutputFormat::Text,
+    };
+    let summary = enough(slide(config).await.unwrap()).await.unwrap();
+    assert_eq!(summary.failed, 0);
+
+    // Check: The files left "baz", spread over both volumes
+    let count = |folder: PathBuf| folder.read_dir().map_or(0, |entries| entries.count());
+    assert_eq!(count(many), 0);
+    let staged = ["bar", "foo"].map(|via| {
+        count(
+            ctx.roots[0]
+                .join(via)
+                .join("slides")
+                .join("qux_")
+                .join("many"),
+        )
+    });
+    assert_eq!(staged.iter().sum::<usize>(), 20);
+    assert!(staged.iter().all(|files| *files > 0), "{staged:?}");
+}
+
+/// Test the volumes only sending, only receiving, or ignoring others
+#[test]
+fn test_volume_constraints() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+
+    // Prerequisite: "foo" is send only, "els" receive only, and "bar" ignores "baz"
+    let volume_conf = |root: usize, name: &str| {
+        ctx.roots[root]
+            .join(name)
+            .join("slides")
+            .join(volume::DEFAULT_VOLUME_CONFIG_FILE)
+    };
+    std::fs::write(volume_conf(0, "foo"), "send_only: true").unwrap();
+    std::fs::write(volume_conf(1, "els"), "receive_only: true").unwrap();
+    std::fs::write(volume_conf(0, "bar"), "ignore: [baz]").unwrap();
+    let mut volumes = identify_env("slides", &ctx.roots).unwrap();
+    assert!(volumes["foo"].send_only && !volumes["foo"].receive_only);
+    assert!(volumes["els"].receive_only);
+    assert_eq!(volumes["bar"].ignore, vec!["baz".to_owned()]);
+
+    // Action: Build the sync jobs
+    let syncjobs = build_syncjobs(&mut volumes).unwrap();
+
+    // Check: Nothing goes into "foo", nothing comes out of "els", and nothing between "bar" and
+    // "baz", whatever the route
+    let expected_syncjobs = [
+        SyncJob::new("foo", "bar", "bar"),
+        SyncJob::new("foo", "baz", "baz"),
+    ];
+    assert_eq!(syncjobs.len(), expected_syncjobs.len(), "{syncjobs:?}");
+    for expected_syncjob in expected_syncjobs {
+        assert!(
+            syncjobs.contains(&expected_syncjob),
+            "Missing {expected_syncjob:?}"
+        );
+    }
+
+    // Check: The reasons are explained
+    let routes = resolve_routes(&volumes);
+    let decision = |src: &str, dst: &str| {
+        routes
+            .iter()
+            .find(|r| r.src == src && r.dst == dst)
+            .map(|r| r.decision.clone())
+            .unwrap()
+    };
+    assert_eq!(
+        decision("bar", "foo"),
+        Decision::Dropped("\"foo\" is send only".to_owned())
+    );
+    assert_eq!(
+        decision("els", "bar"),
+        Decision::Dropped("\"els\" is receive only".to_owned())
+    );
+    assert_eq!(
+        decision("baz", "qux_"),
+        Decision::Dropped("\"bar\" ignores \"baz\"".to_owned())
+    );
+
+    // Check: A volume neither sending nor receiving is reported
+    std::fs::write(volume_conf(1, "els"), "send_only: true\nreceive_only: true").unwrap();
+    let issues = validate(
+        &[RootsetConfig {
+            keyword: "slides".to_string(),
+            roots: ctx.roots.clone(),
+            poll: false,
+        }],
+        false,
+    );
+    assert!(
+        issues
+            .iter()
+            .any(|i| i.ends_with("Volume \"els\" is both send only and receive only")),
+        "Missing issue. Issues: {issues:#?}"
+    );
+}
+
+/// Test the volumes refusing each other kept apart through routes too
+#[test]
+fn test_volume_constraints_routes() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+    let baz_conf = ctx.roots[1]
+        .join("baz")
+        .join("slides")
+        .join(volume::DEFAULT_VOLUME_CONFIG_FILE);
+    let decision = || {
+        let volumes = identify_env("slides", &ctx.roots).unwrap();
+        resolve_routes(&volumes)
+            .into_iter()
+            .find(|r| r.src == "baz" && r.dst == "qux_")
+            .map(|r| r.decision)
+            .unwrap()
+    };
+
+    // Prerequisite: Route "qux_" from "bar" on to "foo"
+    let qux = ctx.roots[0].join("bar").join("slides").join("qux_");
+    std::fs::create_dir(&qux).unwrap();
+    std::fs::write(qux.join(DEFAULT_SLIDE_CONFIG_FILE), "route: foo").unwrap();
+    assert_eq!(decision(), Decision::Via("foo".to_owned()));
+
+    // Action: "baz" ignores the destination
+    std::fs::write(&baz_conf, "ignore: [qux_]").unwrap();
+
+    // Check: The files do not go through the routes either
+    assert_eq!(
+        decision(),
+        Decision::Dropped("\"baz\" ignores \"qux_\"".to_owned())
+    );
+
+    // Action: "baz" ignores the volume the files would only pass through
+    std::fs::write(&baz_conf, "ignore: [bar]").unwrap();
+
+    // Check: The files do not go past it to the next one
+    assert_eq!(
+        decision(),
+        Decision::Dropped("\"baz\" ignores \"bar\"".to_owned())
+    );
+}
+
+/// Test the ordering of sync jobs by volume priority
+#[test]
+fn test_build_syncjobs_priority() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+
+    // Prerequisite: Give the "els" volume the highest priority, and describe it
+    std::fs::write(
+        ctx.roots[1]
+            .join("els")
+            .join("slides")
+            .join(volume::DEFAULT_VOLUME_CONFIG_FILE),
+        "priority: 10\ndescription: \"Backup drive\"",
+    )
+    .unwrap();
+    std::fs::write(
+        ctx.roots[0]
+            .join("foo")
+            .join("slides")
+            .join(volume::DEFAULT_VOLUME_CONFIG_FILE),
+        "priority: -1",
+    )
+    .unwrap();
+
+    // Prerequisite: Identify the volumes in the root folders
+    let mut volumes: HashMap<String, Volume> = identify_env("slides", &ctx.roots).unwrap();
+
+    // Check: The volume settings are read
+    assert_eq!(volumes["els"].priority, 10);
+    assert_eq!(volumes["els"].description, Some("Backup drive".to_owned()));
+    assert!(!volumes["els"].disabled);
+    assert_eq!(volumes["foo"].priority, -1);
+    assert_eq!(volumes["bar"].priority, 0);
+
+    // Action: Build the sync jobs
+    let syncjobs = build_syncjobs(&mut volumes).unwrap();
+
+    // Check: The sync jobs from "els" come first, and the ones from "foo" last
+    assert!(syncjobs[..3].iter().all(|j| j.src == "els"));
+    assert!(syncjobs[syncjobs.len() - 2..]
+        .iter()
+        .all(|j| j.src == "foo"));
+}
+
+/// Test the sync jobs of a volume found twice, under different names
+#[cfg(unix)]
+#[test]
+fn test_build_syncjobs_overlapping() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+
+    // Prerequisite: The "foo" volume is also found as "qux" in another rootset, as a bind mount
+    // would have it
+    let other_root = ctx.temp_dir.path().join("other_root");
+    std::fs::create_dir(&other_root).unwrap();
+    std::os::unix::fs::symlink(ctx.roots[0].join("foo"), other_root.join("qux")).unwrap();
+    let mut volumes = identify_env("slides", &ctx.roots).unwrap();
+    let mut qux = Volume::from_path(other_root.join("qux"), "slides").unwrap();
+    identify_slides(&mut qux).unwrap();
+    volumes.insert(qux.name.clone(), qux);
+
+    // Action: Build the sync jobs
+    let syncjobs = build_syncjobs(&mut volumes).unwrap();
+
+    // Check: The first sync job of the same folders is kept, the other one dropped
+    assert!(syncjobs.contains(&SyncJob::new("foo", "bar", "bar")));
+    assert!(!syncjobs.contains(&SyncJob::new("qux", "bar", "bar")));
+
+    // Check: The mailbox of "foo" is not moved onto itself
+    assert!(!syncjobs.contains(&SyncJob::new("qux", "foo", "foo")));
+
+    // Check: No folder is synced twice
+    let folders = syncjobs
+        .iter()
+        .map(|syncjob| {
+            let (from, to) = slide_folders(&volumes, syncjob);
+            (resolved(&from), resolved(&to))
+        })
+        .collect::<HashSet<_>>();
+    assert_eq!(folders.len(), syncjobs.len(), "{syncjobs:?}");
+}
+
+/// Test the execution of sync jobs between volumes
+#[tokio::test]
+async fn test_execute_syncjobs() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+
+    // Prerequisite: Create a tracer that writes to a known location
+    let trace_path = ctx.temp_dir.path().join("test.trace");
+    let (tracer, handle) = {
+        let (tracer, handle) = tracer::Tracer::new(&Some(&trace_path), &None, Default::default())
+            .await
+            .unwrap();
+        (
+            tracer.annotate_author("test_execute_syncjobs".to_owned()),
+            handle.expect("Should have a handle"),
+        )
+    };
+
+    // Prerequisite: Identify the volumes in the root folders
+    let mut volumes: HashMap<String, Volume> = identify_env("slides", &ctx.roots).unwrap();
 
     // Prerequisite: Build the sync jobs between the volumes
     let syncjobs = build_syncjobs(&mut volumes).unwrap();
 
-    // Remove a source slide to simulate a missing source
-    {
-        let missing_slide = volumes
-            .get_mut("foo")
+    // Action: Execute the sync jobs
+    {
+        let move_req = MoveStrategy {
+            collision: CollisionPolicy::Fail,
+            safe: false,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
+            check: None,
+            recheck: false,
+            check_mode: CheckMode::Full,
+            retries: 5,
+            jobs: 1,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
+        };
+        let running = vec![None; syncjobs.len()];
+        execute_syncjobs(
+            &volumes,
+            wire(&volumes, syncjobs, running),
+            false,
+            false,
+            tracer,
+            Progress::default(),
+            Registry::default(),
+            Arc::new(Semaphore::new(1)),
+            None,
+            None,
+            Duration::from_secs(30),
+            None,
+            &move_req,
+            &Filter::default(),
+            None,
+        )
+        .await
+        .unwrap();
+    }
+
+    // Check: The tracer has traced some info
+    {
+        // Force flush and close the tracer
+        handle.await.unwrap();
+
+        let trace_content = std::fs::read_to_string(&trace_path).unwrap();
+        let traces: String = trace_content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.to_owned())
+            .collect();
+
+        #[cfg(false)]
+        {
+            println!("Traces:");
+            for trace in traces.lines() {
+                println!("  {:?}", trace);
+            }
+        }
+
+        for needle in &[
+            "Starting slides sync...",
+            "[foo -_-> bar] MKDIR",
+            "[bar -_-> foo] MKDIR",
+            "[foo -_-> bar] MV",
+            "[bar -_-> foo] MV",
+        ] {
+            assert!(traces.contains(needle), "Missing trace: {}", needle);
+        }
+    }
+
+    // Check: The slides should be synchronized correctly
+    {
+        // Check: The source slide should not have any contents
+        {
+            let src = &volumes["foo"].slides["bar"].path;
+            let file = src.join("media").join("bigfile");
+            assert!(!file.exists());
+        }
+
+        // Check: The destination slide should have the contents of the source slide
+        {
+            let dst = &volumes["bar"].slides["bar"].path;
+            let file = dst.join("media").join("bigfile");
+            let expected = "F1C9645DBC14EFDDC7D8A322685F26EB";
+            assert!(
+                file.exists()
+                    && file.is_file()
+                    && file.metadata().unwrap().len() == 1024 * 1024 * 10
+                    && hash_file(&file, Algorithm::MD5) == expected,
+                "bigfile checksum: {} expected: {}",
+                hash_file(&file, Algorithm::MD5),
+                expected
+            );
+        }
+
+        // Check: The source slide should not have any contents
+        {
+            let src = &volumes["bar"].slides["foo"].path;
+            let slide = src;
+            let top = slide.join("photos");
+            let folder = top.join("trip-to-rome");
+            let file = folder.join("photo1.jpg");
+            assert!(!file.exists());
+            assert!(!folder.exists());
+            assert!(top.exists());
+            assert!(slide.exists());
+        }
+
+        // Check: The destination slide should have the contents of the source slide
+        {
+            let dst = &volumes["foo"].slides["foo"].path;
+            let file = dst.join("photos").join("trip-to-rome").join("photo1.jpg");
+            let expected = "92AB673D915A94DCF187720E8AC0D608";
+            assert!(
+                file.exists()
+                    && file.is_file()
+                    && file.metadata().unwrap().len() == 1024 * 16
+                    && hash_file(&file, Algorithm::MD5) == expected,
+                "bigfile checksum: {} expected: {}",
+                hash_file(&file, Algorithm::MD5),
+                expected
+            );
+        }
+    }
+}
+
+/// Test the forwarding syncjobs waiting for the files staged by others
+#[tokio::test]
+async fn test_execute_syncjobs_staging() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+    let volumes: HashMap<String, Volume> = identify_env("slides", &ctx.roots).unwrap();
+
+    // Prerequisite: Files waiting to be staged in "bar" on their way to "foo"
+    let staged = volumes["baz"].slides["foo"].path.join("staged");
+    std::fs::create_dir(&staged).unwrap();
+    for i in 0..20 {
+        std::fs::write(staged.join(format!("{i}.txt")), [0xaau8; 64 * 1024]).unwrap();
+    }
+
+    // Prerequisite: The forwarding syncjob comes first, and can run along with the staging one
+    let syncjobs = vec![
+        SyncJob::new("bar", "foo", "foo"),
+        SyncJob::new("baz", "bar", "foo"),
+    ];
+    assert!(syncjobs[1].feeds(&syncjobs[0]));
+    assert!(!syncjobs[0].feeds(&syncjobs[1]));
+
+    // Action: Run a single pass
+    let move_req = MoveStrategy {
+        collision: CollisionPolicy::Fail,
+        safe: false,
+        wip_suffix: "wip".to_owned(),
+        staging: false,
+        check: None,
+        recheck: false,
+        check_mode: CheckMode::Full,
+        retries: 0,
+        jobs: 1,
+        delta: false,
+        preserve_times: false,
+        preserve_permissions: false,
+        preserve_xattrs: false,
+        rate_limit: None,
+        sanitize: None,
+        use_trash: None,
+        min_age: None,
+        max_file_size: None,
+        sidecar: None,
+        reverify: None,
+        delivery_manifest: false,
+        conflict: ConflictPolicy::Report,
+        max_failures: 5,
+        min_interval: 0,
+        skip_identical: None,
+    };
+    let running = vec![None; syncjobs.len()];
+    let batch = execute_syncjobs(
+        &volumes,
+        wire(&volumes, syncjobs, running),
+        false,
+        true,
+        Tracer::new(&None, &None, OutputFormat::Text)
+            .await
+            .unwrap()
+            .0,
+        Progress::default(),
+        Registry::default(),
+        Arc::new(Semaphore::new(2)),
+        None,
+        None,
+        Duration::from_secs(30),
+        None,
+        &move_req,
+        &Filter::default(),
+        None,
+    )
+    .await
+    .unwrap();
+    for running in batch.running {
+        running.handle.await.unwrap().unwrap();
+    }
+
+    // Check: The staged files were forwarded in the same pass, none left behind in "bar"
+    let forwarded = volumes["foo"].slides["foo"].path.join("staged");
+    for i in 0..20 {
+        assert!(
+            forwarded.join(format!("{i}.txt")).exists(),
+            "Missing {i}.txt"
+        );
+    }
+    let left = volumes["bar"].slides["foo"].path.join("staged");
+    assert!(!left.exists() || left.read_dir().unwrap().next().is_none());
+}
+
+/// Test the execution of sync jobs between volumes with a missing source (i.e. The user deleted a source slide)
+#[tokio::test]
+#[ignore]
+async fn test_execute_syncjobs_with_missing_source() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+
+    let (tracer, handle) = {
+        let trace_path = ctx.temp_dir.path().join("test.trace");
+        let (tracer, handle) = tracer::Tracer::new(&Some(&trace_path), &None, Default::default())
+            .await
+            .unwrap();
+        (
+            tracer.annotate_author("test_execute_syncjobs_with_missing_source".to_owned()),
+            handle.expect("Should have a handle"),
+        )
+    };
+
+    // Prerequisite: Identify the volumes in the root folders
+    let mut volumes = identify_env("slides", &ctx.roots).unwrap();
+
+    // Prerequisite: Build the sync jobs between the volumes
+    let syncjobs = build_syncjobs(&mut volumes).unwrap();
+
+    // Remove a source slide to simulate a missing source
+    {
+        let missing_slide = volumes
+            .get_mut("foo")
+            .unwrap()
+            .slides
+            .remove("bar")
+            .unwrap();
+        std::fs::remove_dir_all(missing_slide.path).unwrap();
+    }
+
+    // Execute the sync jobs
+    let result = {
+        let move_req = MoveStrategy {
+            collision: CollisionPolicy::Fail,
+            safe: false,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
+            check: None,
+            recheck: false,
+            check_mode: CheckMode::Full,
+            retries: 5,
+            jobs: 1,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
+        };
+        let running = vec![None; syncjobs.len()];
+        execute_syncjobs(
+            &volumes,
+            wire(&volumes, syncjobs, running),
+            false,
+            false,
+            tracer,
+            Progress::default(),
+            Registry::default(),
+            Arc::new(Semaphore::new(1)),
+            None,
+            None,
+            Duration::from_secs(30),
+            None,
+            &move_req,
+            &Filter::default(),
+            None,
+        )
+        .await
+    };
+
+    // Verify that the sync jobs failed due to the missing source
+    assert!(
+        result.is_err(),
+        "Expected error due to missing source slide"
+    );
+
+    handle.await.unwrap();
+}
+
+/// Test the files sent both ways between two volumes
+#[tokio::test]
+async fn test_reciprocal_conflicts() {
+    for conflict in [
+        ConflictPolicy::Report,
+        ConflictPolicy::Newer,
+        ConflictPolicy::Rename,
+    ] {
+        // Prerequisite: Setup the test context
+        let ctx = setup().unwrap();
+        let slides = |volume: &str| ctx.roots[0].join(volume).join("slides");
+        let trace_dir = tempfile::tempdir().unwrap();
+        let trace_path = trace_dir.path().join("trace.log");
+
+        // Prerequisite: "foo" sends back a newer photo under the same path "bar" sends it
+        let path = Path::new("photos").join("trip-to-rome").join("photo1.jpg");
+        let ours = slides("foo").join("bar").join(&path);
+        std::fs::create_dir_all(ours.parent().unwrap()).unwrap();
+        std::fs::write(&ours, b"Edited").unwrap();
+        filetime::set_file_mtime(
+            slides("bar").join("foo").join(&path),
+            filetime::FileTime::from_unix_time(0, 0),
+        )
+        .unwrap();
+
+        // Prerequisite: "bar" already has a file under the name its photo would be renamed to
+        let aside = slides("bar")
+            .join("foo")
+            .join("photos")
+            .join("trip-to-rome")
+            .join("photo1.bar.jpg");
+        std::fs::write(&aside, b"Aside").unwrap();
+
+        // Action: Run a single pass
+        let token = slide(GlobalConfig {
+            rootsets: vec![RootsetConfig {
+                keyword: "slides".to_string(),
+                roots: ctx.roots.clone(),
+                poll: false,
+            }],
+            dry_run: false,
+            trace: Some(trace_path.clone()),
+            history: None,
+            check: None,
+            recheck: false,
+            check_mode: CheckMode::Full,
+            collision: CollisionPolicy::Fail,
+            safe: true,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
+            retries: 5,
+            jobs: 1,
+            parallel: 16,
+            debounce: None,
+            debounce_max: None,
+            poll: false,
+            poll_interval: 30,
+            heartbeat: None,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
+            progress: false,
+            min_size: None,
+            max_size: None,
+            control: None,
+            signing_key: None,
+            trusted_keys: vec![],
+            mqtt: None,
+            exclude: vec![],
+            strict: false,
+            once: true,
+            output: OutputFormat::Text,
+        })
+        .await
+        .unwrap();
+        enough(token).await.unwrap();
+
+        // Check: The photos cross each other, only the newer one goes, or both go renamed
+        let delivered = |volume: &str, name: &str| {
+            std::fs::read(
+                slides(volume)
+                    .join(volume)
+                    .join("photos")
+                    .join("trip-to-rome")
+                    .join(name),
+            )
+            .ok()
+        };
+        let original = Some(vec![0xffu8; 16 * 1024]);
+        match conflict {
+            ConflictPolicy::Report => {
+                assert_eq!(delivered("bar", "photo1.jpg"), Some(b"Edited".to_vec()));
+                assert_eq!(delivered("foo", "photo1.jpg"), original);
+            }
+            ConflictPolicy::Newer => {
+                assert_eq!(delivered("bar", "photo1.jpg"), Some(b"Edited".to_vec()));
+                assert_eq!(delivered("foo", "photo1.jpg"), None);
+            }
+            ConflictPolicy::Rename => {
+                assert_eq!(delivered("bar", "photo1.foo.jpg"), Some(b"Edited".to_vec()));
+                assert_eq!(delivered("foo", "photo1.bar (2).jpg"), original);
+            }
+        }
+
+        // Check: The file already under the name is not replaced
+        assert_eq!(delivered("foo", "photo1.bar.jpg"), Some(b"Aside".to_vec()));
+
+        // Check: The conflict is resolved once, by one of the syncjobs both ways
+        let trace = std::fs::read_to_string(&trace_path).unwrap();
+        assert_eq!(
+            trace
+                .lines()
+                .filter(|line| line.contains("] CONFLICT "))
+                .count(),
+            1
+        );
+    }
+}
+
+/// Test the single pass mode
+#[tokio::test]
+async fn test_slide_once() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+
+    // Prerequisite: A file waiting in the bar -> foo slide
+    let src_dir = ctx.roots[0]
+        .join("bar")
+        .join("slides")
+        .join("foo")
+        .join("test_once");
+    std::fs::create_dir(&src_dir).unwrap();
+    let src_file = src_dir.join("once.txt");
+    std::fs::write(&src_file, b"Once upon a time").unwrap();
+
+    // Action: Run a single pass
+    let token = slide(GlobalConfig {
+        rootsets: vec![RootsetConfig {
+            keyword: "slides".to_string(),
+            roots: ctx.roots.clone(),
+            poll: false,
+        }],
+        dry_run: false,
+        trace: None,
+        history: None,
+        check: Some(Algorithm::MD5),
+        recheck: false,
+        check_mode: CheckMode::Full,
+        collision: CollisionPolicy::Fail,
+        safe: true,
+        wip_suffix: "wip".to_owned(),
+        staging: false,
+        retries: 5,
+        jobs: 1,
+        parallel: 1,
+        debounce: None,
+        debounce_max: None,
+        poll: false,
+        poll_interval: 30,
+        heartbeat: None,
+        delta: false,
+        preserve_times: false,
+        preserve_permissions: false,
+        preserve_xattrs: false,
+        rate_limit: None,
+        sanitize: None,
+        use_trash: None,
+        min_age: None,
+        max_file_size: None,
+        sidecar: None,
+        reverify: None,
+        delivery_manifest: false,
+        conflict: ConflictPolicy::Report,
+        max_failures: 5,
+        min_interval: 0,
+        skip_identical: None,
+        progress: false,
+        min_size: None,
+        max_size: None,
+        control: None,
+        signing_key: None,
+        trusted_keys: vec![],
+        mqtt: None,
+        exclude: vec![],
+        strict: false,
+        once: true,
+        output: OutputFormat::Text,
+    })
+    .await
+    .unwrap();
+
+    // Check: Every syncjob is over by the time slide() returns
+    assert!(!src_file.exists());
+    assert!(ctx.roots[0]
+        .join("foo")
+        .join("slides")
+        .join("foo")
+        .join("test_once")
+        .join("once.txt")
+        .exists());
+    enough(token).await.unwrap();
+}
+
+/// Test the single pass mode not waiting for the deliveries to be checked again
+#[tokio::test]
+async fn test_slide_once_reverify() {
+    for after in [30, 0] {
+        // Prerequisite: Setup the test context
+        let ctx = setup().unwrap();
+        let trace_dir = tempfile::tempdir().unwrap();
+        let trace_path = trace_dir.path().join("trace.log");
+
+        // Prerequisite: A file waiting in the bar -> foo slide
+        let src_dir = ctx.roots[0]
+            .join("bar")
+            .join("slides")
+            .join("foo")
+            .join("test_once_reverify");
+        std::fs::create_dir(&src_dir).unwrap();
+        std::fs::write(src_dir.join("once.txt"), b"Checked later").unwrap();
+
+        // Action: Run a single pass, checking the deliveries again some minutes later
+        let token = tokio::time::timeout(
+            Duration::from_secs(60),
+            slide(GlobalConfig {
+                rootsets: vec![RootsetConfig {
+                    keyword: "slides".to_string(),
+                    roots: ctx.roots.clone(),
+                    poll: false,
+                }],
+                dry_run: false,
+                trace: Some(trace_path.clone()),
+                history: None,
+                check: Some(Algorithm::MD5),
+                recheck: false,
+                check_mode: CheckMode::Full,
+                collision: CollisionPolicy::Fail,
+                safe: true,
+                wip_suffix: "wip".to_owned(),
+                staging: false,
+                retries: 5,
+                jobs: 1,
+                parallel: 1,
+                debounce: None,
+                debounce_max: None,
+                poll: false,
+                poll_interval: 30,
+                heartbeat: None,
+                delta: false,
+                preserve_times: false,
+                preserve_permissions: false,
+                preserve_xattrs: false,
+                rate_limit: None,
+                sanitize: None,
+                use_trash: None,
+                min_age: None,
+                max_file_size: None,
+                sidecar: None,
+                reverify: Some(Reverify {
+                    after,
+                    restore: false,
+                }),
+                delivery_manifest: false,
+                conflict: ConflictPolicy::Report,
+                max_failures: 5,
+                min_interval: 0,
+                skip_identical: None,
+                progress: false,
+                min_size: None,
+                max_size: None,
+                control: None,
+                signing_key: None,
+                trusted_keys: vec![],
+                mqtt: None,
+                exclude: vec![],
+                strict: false,
+                once: true,
+                output: OutputFormat::Text,
+            }),
+        )
+        .await
+        .expect("The single pass should not wait for the checks")
+        .unwrap();
+        enough(token).await.unwrap();
+
+        // Check: The file is only checked again if already due
+        let trace = std::fs::read_to_string(&trace_path).unwrap();
+        assert_eq!(
+            trace
+                .lines()
+                .any(|line| line.contains("REVERIFIED") && line.contains("once.txt")),
+            after == 0,
+            "{trace}"
+        );
+    }
+}
+
+/// Test the syncs waiting for the filesystem events to calm down
+#[tokio::test]
+async fn test_debounce() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+
+    // Action: Start the monitoring, waiting for a second without events before syncing
+    let token = {
+        let config = GlobalConfig {
+            rootsets: vec![RootsetConfig {
+                keyword: "slides".to_string(),
+                roots: ctx.roots.clone(),
+                poll: false,
+            }],
+            dry_run: false,
+            trace: None,
+            history: None,
+            check: Some(Algorithm::MD5),
+            recheck: false,
+            check_mode: CheckMode::Full,
+            collision: CollisionPolicy::Fail,
+            safe: true,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
+            retries: 5,
+            jobs: 1,
+            parallel: 1,
+            debounce: Some(1),
+            debounce_max: None,
+            poll: false,
+            poll_interval: 30,
+            heartbeat: None,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
+            progress: false,
+            min_size: None,
+            max_size: None,
+            control: None,
+            signing_key: None,
+            trusted_keys: vec![],
+            mqtt: None,
+            exclude: vec![],
+            strict: false,
+            once: false,
+            output: OutputFormat::Text,
+        };
+        slide(config).await.unwrap()
+    };
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+
+    // Action: Keep writing files for longer than the quiet period
+    let src_dir = ctx.roots[0]
+        .join("bar")
+        .join("slides")
+        .join("foo")
+        .join("test_debounce");
+    std::fs::create_dir(&src_dir).unwrap();
+    for i in 0..6 {
+        std::fs::write(src_dir.join(format!("{i}.txt")), b"Burst").unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+    }
+
+    // Check: Nothing is synced while the events keep coming
+    let dst_dir = ctx.roots[0]
+        .join("foo")
+        .join("slides")
+        .join("foo")
+        .join("test_debounce");
+    assert!(!dst_dir.join("0.txt").exists());
+
+    // Check: Everything is synced once they calm down
+    tokio::time::sleep(tokio::time::Duration::from_millis(2000)).await;
+    enough(token).await.unwrap();
+    for i in 0..6 {
+        assert!(dst_dir.join(format!("{i}.txt")).exists(), "{i}.txt");
+    }
+}
+
+/// Test the slides polled for changes rather than watched
+#[tokio::test]
+async fn test_poll() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+
+    // Action: Start the monitoring, polling the slides every second
+    let token = {
+        let config = GlobalConfig {
+            rootsets: vec![RootsetConfig {
+                keyword: "slides".to_string(),
+                roots: ctx.roots.clone(),
+                poll: true,
+            }],
+            dry_run: false,
+            trace: None,
+            history: None,
+            check: Some(Algorithm::MD5),
+            recheck: false,
+            check_mode: CheckMode::Full,
+            collision: CollisionPolicy::Fail,
+            safe: true,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
+            retries: 5,
+            jobs: 1,
+            parallel: 1,
+            debounce: None,
+            debounce_max: None,
+            poll: false,
+            poll_interval: 1,
+            heartbeat: None,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
+            progress: false,
+            min_size: None,
+            max_size: None,
+            control: None,
+            signing_key: None,
+            trusted_keys: vec![],
+            mqtt: None,
+            exclude: vec![],
+            strict: false,
+            once: false,
+            output: OutputFormat::Text,
+        };
+        slide(config).await.unwrap()
+    };
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+
+    // Action: Drop a file for "bar"
+    let slide = ctx.roots[0].join("foo").join("slides").join("bar");
+    std::fs::create_dir(slide.join("polled")).unwrap();
+    std::fs::write(slide.join("polled").join("polled.txt"), b"Polled").unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(2500)).await;
+    enough(token).await.unwrap();
+
+    // Check: The change was found by the next scan, and the file synced
+    assert!(!slide.join("polled").join("polled.txt").exists());
+    assert!(ctx.roots[0]
+        .join("bar")
+        .join("slides")
+        .join("bar")
+        .join("polled")
+        .join("polled.txt")
+        .exists());
+}
+
+/// Test the volumes pulling their files rather than watching their sources
+#[tokio::test]
+async fn test_pull() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+
+    // Prerequisite: "foo" pulls its files every second
+    std::fs::write(
+        ctx.roots[0]
+            .join("foo")
+            .join("slides")
+            .join(volume::DEFAULT_VOLUME_CONFIG_FILE),
+        "pull: 1",
+    )
+    .unwrap();
+
+    // Action: Start the monitoring
+    let token = {
+        let config = GlobalConfig {
+            rootsets: vec![RootsetConfig {
+                keyword: "slides".to_string(),
+                roots: ctx.roots.clone(),
+                poll: false,
+            }],
+            dry_run: false,
+            trace: None,
+            history: None,
+            check: Some(Algorithm::MD5),
+            recheck: false,
+            check_mode: CheckMode::Full,
+            collision: CollisionPolicy::Fail,
+            safe: true,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
+            retries: 5,
+            jobs: 1,
+            parallel: 1,
+            // The events wait longer than the test, so only the pulls sync
+            debounce: Some(60),
+            debounce_max: None,
+            poll: false,
+            poll_interval: 30,
+            heartbeat: None,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
+            progress: false,
+            min_size: None,
+            max_size: None,
+            control: None,
+            signing_key: None,
+            trusted_keys: vec![],
+            mqtt: None,
+            exclude: vec![],
+            strict: false,
+            once: false,
+            output: OutputFormat::Text,
+        };
+        slide(config).await.unwrap()
+    };
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+
+    // Action: Drop files for "foo" and for "bar"
+    let slides = ctx.roots[0].join("foo").join("slides");
+    std::fs::create_dir(
+        ctx.roots[0]
+            .join("bar")
+            .join("slides")
+            .join("foo")
+            .join("pulled"),
+    )
+    .unwrap();
+    std::fs::write(
+        ctx.roots[0]
+            .join("bar")
+            .join("slides")
+            .join("foo")
+            .join("pulled")
+            .join("pulled.txt"),
+        b"Pulled",
+    )
+    .unwrap();
+    std::fs::create_dir(slides.join("bar").join("pushed")).unwrap();
+    std::fs::write(
+        slides.join("bar").join("pushed").join("pushed.txt"),
+        b"Pushed",
+    )
+    .unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(2500)).await;
+    enough(token).await.unwrap();
+
+    // Check: The files for "foo" were pulled, while the ones for "bar" wait for their events
+    assert!(slides
+        .join("foo")
+        .join("pulled")
+        .join("pulled.txt")
+        .exists());
+    assert!(slides
+        .join("bar")
+        .join("pushed")
+        .join("pushed.txt")
+        .exists());
+}
+
+/// Test rebuilding the syncjobs once a volume is plugged in while watching
+#[tokio::test]
+async fn test_hotplug() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+    let config = GlobalConfig {
+        rootsets: vec![RootsetConfig {
+            keyword: "slides".to_string(),
+            roots: ctx.roots.clone(),
+            poll: false,
+        }],
+        dry_run: false,
+        trace: None,
+        history: None,
+        check: Some(Algorithm::MD5),
+        recheck: false,
+        check_mode: CheckMode::Full,
+        collision: CollisionPolicy::Fail,
+        safe: true,
+        wip_suffix: "wip".to_owned(),
+        staging: false,
+        retries: 5,
+        jobs: 1,
+        parallel: 1,
+        debounce: None,
+        debounce_max: None,
+        poll: false,
+        poll_interval: 30,
+        heartbeat: None,
+        delta: false,
+        preserve_times: false,
+        preserve_permissions: false,
+        preserve_xattrs: false,
+        rate_limit: None,
+        sanitize: None,
+        use_trash: None,
+        min_age: None,
+        max_file_size: None,
+        sidecar: None,
+        reverify: None,
+        delivery_manifest: false,
+        conflict: ConflictPolicy::Report,
+        max_failures: 5,
+        min_interval: 0,
+        skip_identical: None,
+        progress: false,
+        min_size: None,
+        max_size: None,
+        control: None,
+        signing_key: None,
+        trusted_keys: vec![],
+        mqtt: None,
+        exclude: vec![],
+        strict: false,
+        once: false,
+        output: OutputFormat::Text,
+    };
+
+    // Action: Start the monitoring, with "qux_" missing
+    let mut token = slide(config.clone()).await.unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+
+    // Action: Drop a file for "qux_", staged in "bar", and another one for "bar"
+    let baz = ctx.roots[1].join("baz").join("slides");
+    std::fs::create_dir(baz.join("qux_").join("staged")).unwrap();
+    std::fs::write(
+        baz.join("qux_").join("staged").join("staged.txt"),
+        b"Staged",
+    )
+    .unwrap();
+    std::fs::create_dir(baz.join("bar").join("direct")).unwrap();
+    std::fs::write(baz.join("bar").join("direct").join("direct.txt"), b"Direct").unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(1500)).await;
+    let staged = ctx.roots[0]
+        .join("bar")
+        .join("slides")
+        .join("qux_")
+        .join("staged")
+        .join("staged.txt");
+    assert!(staged.exists());
+    let direct = token
+        .stats()
+        .into_iter()
+        .find(|job| job.files == 1 && !job.job.contains("qux_"))
+        .unwrap();
+
+    // Action: Plug "qux_" in
+    let qux = ctx.roots[0].join("qux_").join("slides");
+    std::fs::create_dir_all(&qux).unwrap();
+
+    // Check: The syncjobs have to be rebuilt
+    tokio::time::timeout(HOTPLUG_INTERVAL * 3, token.replanned())
+        .await
+        .unwrap();
+
+    // Action: Rebuild the syncjobs
+    rebuild(&mut token, config).await.unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+    token.sync_now(None).unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(1500)).await;
+    let summary = enough(token).await.unwrap();
+
+    // Check: The staged file reached "qux_"
+    assert!(!staged.exists());
+    assert!(qux.join("qux_").join("staged").join("staged.txt").exists());
+
+    // Check: The syncjobs kept their statistics, and the ones stopped are accounted for
+    let carried = summary
+        .jobs
+        .iter()
+        .find(|job| job.job == direct.job)
+        .unwrap();
+    assert_eq!(carried.files, 1);
+    assert!(carried.syncs >= direct.syncs);
+    assert!(summary.syncjobs > summary.jobs.len());
+    assert_eq!(summary.failed, 0);
+}
+
+/// Test the volumes plugged in being noticed as soon as they appear in a root
+#[tokio::test]
+async fn test_hotplug_events() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+    let config = GlobalConfig {
+        rootsets: vec![RootsetConfig {
+            keyword: "slides".to_string(),
+            roots: ctx.roots.clone(),
+            poll: false,
+        }],
+        dry_run: false,
+        trace: None,
+        history: None,
+        check: Some(Algorithm::MD5),
+        recheck: false,
+        check_mode: CheckMode::Full,
+        collision: CollisionPolicy::Fail,
+        safe: true,
+        wip_suffix: "wip".to_owned(),
+        staging: false,
+        retries: 5,
+        jobs: 1,
+        parallel: 1,
+        debounce: None,
+        debounce_max: None,
+        poll: false,
+        poll_interval: 30,
+        heartbeat: None,
+        delta: false,
+        preserve_times: false,
+        preserve_permissions: false,
+        preserve_xattrs: false,
+        rate_limit: None,
+        sanitize: None,
+        use_trash: None,
+        min_age: None,
+        max_file_size: None,
+        sidecar: None,
+        reverify: None,
+        delivery_manifest: false,
+        conflict: ConflictPolicy::Report,
+        max_failures: 5,
+        min_interval: 0,
+        skip_identical: None,
+        progress: false,
+        min_size: None,
+        max_size: None,
+        control: None,
+        signing_key: None,
+        trusted_keys: vec![],
+        mqtt: None,
+        exclude: vec![],
+        strict: false,
+        once: false,
+        output: OutputFormat::Text,
+    };
+
+    // Action: Start the monitoring, with "qux_" missing
+    let token = slide(config).await.unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+
+    // Action: Plug "qux_" in
+    std::fs::create_dir_all(ctx.roots[0].join("qux_").join("slides")).unwrap();
+
+    // Check: The syncjobs have to be rebuilt before the roots are looked at again
+    tokio::time::timeout(HOTPLUG_INTERVAL / 2, token.replanned())
+        .await
+        .unwrap();
+    let summary = enough(token).await.unwrap();
+    assert_eq!(summary.failed, 0);
+}
+
+/// Test the drives mounted in a root being noticed as soon as they are mounted
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_hotplug_mounts() {
+    /// Unmount a folder once dropped
+    struct Mounted(PathBuf);
+    impl Drop for Mounted {
+        fn drop(&mut self) {
+            let _ = std::process::Command::new("umount").arg(&self.0).status();
+        }
+    }
+
+    // Prerequisite: Setup the test context, with an empty folder for "qux_" to be mounted on
+    let ctx = setup().unwrap();
+    let mountpoint = ctx.roots[0].join("qux_");
+    std::fs::create_dir(&mountpoint).unwrap();
+    let drive = tempfile::tempdir().unwrap();
+    std::fs::create_dir(drive.path().join("slides")).unwrap();
+    let config = GlobalConfig {
+        rootsets: vec![RootsetConfig {
+            keyword: "slides".to_string(),
+            roots: ctx.roots.clone(),
+            poll: false,
+        }],
+        dry_run: false,
+        trace: None,
+        history: None,
+        check: Some(Algorithm::MD5),
+        recheck: false,
+        check_mode: CheckMode::Full,
+        collision: CollisionPolicy::Fail,
+        safe: true,
+        wip_suffix: "wip".to_owned(),
+        staging: false,
+        retries: 5,
+        jobs: 1,
+        parallel: 1,
+        debounce: None,
+        debounce_max: None,
+        poll: false,
+        poll_interval: 30,
+        heartbeat: None,
+        delta: false,
+        preserve_times: false,
+        preserve_permissions: false,
+        preserve_xattrs: false,
+        rate_limit: None,
+        sanitize: None,
+        use_trash: None,
+        min_age: None,
+        max_file_size: None,
+        sidecar: None,
+        reverify: None,
+        delivery_manifest: false,
+        conflict: ConflictPolicy::Report,
+        max_failures: 5,
+        min_interval: 0,
+        skip_identical: None,
+        progress: false,
+        min_size: None,
+        max_size: None,
+        control: None,
+        signing_key: None,
+        trusted_keys: vec![],
+        mqtt: None,
+        exclude: vec![],
+        strict: false,
+        once: false,
+        output: OutputFormat::Text,
+    };
+
+    // Action: Start the monitoring, with "qux_" missing
+    let token = slide(config).await.unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+
+    // Action: Mount "qux_" on its folder, which is not watched
+    let mounted = std::process::Command::new("mount")
+        .arg("--bind")
+        .arg(drive.path())
+        .arg(&mountpoint)
+        .output()
+        .is_ok_and(|output| output.status.success());
+    if !mounted {
+        // Mounting takes privileges the tests may not have
+        enough(token).await.unwrap();
+        return;
+    }
+    let _mounted = Mounted(mountpoint);
+
+    // Check: The syncjobs have to be rebuilt before the roots are looked at again
+    tokio::time::timeout(HOTPLUG_INTERVAL / 2, token.replanned())
+        .await
+        .unwrap();
+    enough(token).await.unwrap();
+}
+
+/// Test the syncs not triggering other syncs by themselves
+#[tokio::test]
+async fn test_own_events() {
+    // Prerequisite: Setup the test context, reporting the files over 2 bytes in their slide
+    let ctx = setup().unwrap();
+    let config = GlobalConfig {
+        rootsets: vec![RootsetConfig {
+            keyword: "slides".to_string(),
+            roots: ctx.roots.clone(),
+            poll: false,
+        }],
+        dry_run: false,
+        trace: None,
+        history: None,
+        check: Some(Algorithm::MD5),
+        recheck: false,
+        check_mode: CheckMode::Full,
+        collision: CollisionPolicy::Fail,
+        safe: true,
+        wip_suffix: "wip".to_owned(),
+        staging: false,
+        retries: 5,
+        jobs: 1,
+        parallel: 1,
+        debounce: None,
+        debounce_max: None,
+        poll: false,
+        poll_interval: 30,
+        heartbeat: None,
+        delta: false,
+        preserve_times: false,
+        preserve_permissions: false,
+        preserve_xattrs: false,
+        rate_limit: None,
+        sanitize: None,
+        use_trash: None,
+        min_age: None,
+        max_file_size: Some(2),
+        sidecar: None,
+        reverify: None,
+        delivery_manifest: false,
+        conflict: ConflictPolicy::Report,
+        max_failures: 5,
+        min_interval: 0,
+        skip_identical: None,
+        progress: false,
+        min_size: None,
+        max_size: None,
+        control: None,
+        signing_key: None,
+        trusted_keys: vec![],
+        mqtt: None,
+        exclude: vec![],
+        strict: false,
+        once: false,
+        output: OutputFormat::Text,
+    };
+
+    // Action: Start the monitoring, and drop an oversize file for "bar" once it settled
+    let token = slide(config).await.unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+    let syncs = |token: &Token| {
+        token
+            .stats()
+            .into_iter()
+            .find(|job| job.job == "baz -_-> bar")
+            .unwrap()
+            .syncs
+    };
+    let before = syncs(&token);
+    let baz = ctx.roots[1].join("baz").join("slides");
+    let dropped = ctx.roots[1].join("dropped");
+    std::fs::create_dir(&dropped).unwrap();
+    std::fs::write(dropped.join("file.txt"), b"File").unwrap();
+    std::fs::rename(&dropped, baz.join("bar").join("dropped")).unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(1500)).await;
+
+    // Check: The file was reported in a single sync, writing the report not triggering another
+    assert!(baz.join("bar").join(fs::OVERSIZE_REPORT).exists());
+    assert_eq!(syncs(&token), before + 1);
+    enough(token).await.unwrap();
+}
+
+/// Test the changes the syncs would leave in place not triggering any
+#[tokio::test]
+async fn test_held_back_events() {
+    // Prerequisite: Setup the test context, excluding the .tmp files
+    let ctx = setup().unwrap();
+    let config = GlobalConfig {
+        rootsets: vec![RootsetConfig {
+            keyword: "slides".to_string(),
+            roots: ctx.roots.clone(),
+            poll: false,
+        }],
+        dry_run: false,
+        trace: None,
+        history: None,
+        check: Some(Algorithm::MD5),
+        recheck: false,
+        check_mode: CheckMode::Full,
+        collision: CollisionPolicy::Fail,
+        safe: true,
+        wip_suffix: "wip".to_owned(),
+        staging: false,
+        retries: 5,
+        jobs: 1,
+        parallel: 1,
+        debounce: None,
+        debounce_max: None,
+        poll: false,
+        poll_interval: 30,
+        heartbeat: None,
+        delta: false,
+        preserve_times: false,
+        preserve_permissions: false,
+        preserve_xattrs: false,
+        rate_limit: None,
+        sanitize: None,
+        use_trash: None,
+        min_age: None,
+        max_file_size: None,
+        sidecar: None,
+        reverify: None,
+        delivery_manifest: false,
+        conflict: ConflictPolicy::Report,
+        max_failures: 5,
+        min_interval: 0,
+        skip_identical: None,
+        progress: false,
+        min_size: None,
+        max_size: None,
+        control: None,
+        signing_key: None,
+        trusted_keys: vec![],
+        mqtt: None,
+        exclude: vec!["*.tmp".to_owned()],
+        strict: false,
+        once: false,
+        output: OutputFormat::Text,
+    };
+
+    // Action: Start the monitoring, and drop a folder for "bar" holding back its drafts
+    let token = slide(config).await.unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+    let syncs = |token: &Token| {
+        token
+            .stats()
+            .into_iter()
+            .find(|job| job.job == "baz -_-> bar")
+            .unwrap()
+            .syncs
+    };
+    let baz = ctx.roots[1].join("baz").join("slides").join("bar");
+    let dropped = ctx.roots[1].join("docs");
+    std::fs::create_dir(&dropped).unwrap();
+    std::fs::write(dropped.join(filter::IGNORE_FILE), "drafts/\n").unwrap();
+    std::fs::rename(&dropped, baz.join("docs")).unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(1000)).await;
+    let before = syncs(&token);
+
+    // Action: Write a draft, an excluded file and the swap file of an editor
+    std::fs::create_dir(baz.join("docs").join("drafts")).unwrap();
+    std::fs::write(baz.join("docs").join("drafts").join("draft.txt"), b"Draft").unwrap();
+    std::fs::write(baz.join("docs").join("scratch.tmp"), b"Scratch").unwrap();
+    std::fs::write(baz.join("docs").join(".notes.txt.swp"), b"Swap").unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(1000)).await;
+
+    // Check: None of them triggered a sync
+    assert_eq!(syncs(&token), before);
+
+    // Action: Write a file to deliver
+    std::fs::write(baz.join("docs").join("notes.txt"), b"Notes").unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(1000)).await;
+
+    // Check: It triggered a sync, delivering it
+    assert!(syncs(&token) > before);
+    assert!(ctx.roots[0]
+        .join("bar")
+        .join("slides")
+        .join("bar")
+        .join("docs")
+        .join("notes.txt")
+        .exists());
+    enough(token).await.unwrap();
+}
+
+/// Test the least time between two syncs of a slide written all the time
+#[tokio::test]
+async fn test_min_interval() {
+    // Prerequisite: Setup the test context, syncing a slide at most every 2 seconds
+    let ctx = setup().unwrap();
+    let config = GlobalConfig {
+        rootsets: vec![RootsetConfig {
+            keyword: "slides".to_string(),
+            roots: ctx.roots.clone(),
+            poll: false,
+        }],
+        dry_run: false,
+        trace: None,
+        history: None,
+        check: Some(Algorithm::MD5),
+        recheck: false,
+        check_mode: CheckMode::Full,
+        collision: CollisionPolicy::Fail,
+        safe: true,
+        wip_suffix: "wip".to_owned(),
+        staging: false,
+        retries: 5,
+        jobs: 1,
+        parallel: 1,
+        debounce: None,
+        debounce_max: None,
+        poll: false,
+        poll_interval: 30,
+        heartbeat: None,
+        delta: false,
+        preserve_times: false,
+        preserve_permissions: false,
+        preserve_xattrs: false,
+        rate_limit: None,
+        sanitize: None,
+        use_trash: None,
+        min_age: None,
+        max_file_size: None,
+        sidecar: None,
+        reverify: None,
+        delivery_manifest: false,
+        conflict: ConflictPolicy::Report,
+        max_failures: 5,
+        min_interval: 2,
+        skip_identical: None,
+        progress: false,
+        min_size: None,
+        max_size: None,
+        control: None,
+        signing_key: None,
+        trusted_keys: vec![],
+        mqtt: None,
+        exclude: vec![],
+        strict: false,
+        once: false,
+        output: OutputFormat::Text,
+    };
+
+    // Action: Start the monitoring, and write into a slide every 100ms for 1.5s
+    let token = slide(config).await.unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+    let syncs = |token: &Token| {
+        token
+            .stats()
+            .into_iter()
+            .find(|job| job.job == "baz -_-> bar")
+            .unwrap()
+            .syncs
+    };
+    let before = syncs(&token);
+    let baz = ctx.roots[1].join("baz").join("slides").join("bar");
+    std::fs::create_dir(baz.join("busy")).unwrap();
+    for i in 0..15 {
+        std::fs::write(baz.join("busy").join(format!("{i}.txt")), b"Busy").unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    }
+
+    // Check: The slide was synced once at most meanwhile
+    assert!(syncs(&token) <= before + 1);
+
+    // Check: Every file is delivered once the writes stop
+    tokio::time::sleep(tokio::time::Duration::from_millis(2500)).await;
+    let delivered = ctx.roots[0]
+        .join("bar")
+        .join("slides")
+        .join("bar")
+        .join("busy");
+    for i in 0..15 {
+        assert!(delivered.join(format!("{i}.txt")).exists(), "{i}");
+    }
+    enough(token).await.unwrap();
+}
+
+/// Test the syncjobs being restarted once a slide or volume config file is edited
+#[tokio::test]
+async fn test_config_reload() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+    let config = GlobalConfig {
+        rootsets: vec![RootsetConfig {
+            keyword: "slides".to_string(),
+            roots: ctx.roots.clone(),
+            poll: false,
+        }],
+        dry_run: false,
+        trace: None,
+        history: None,
+        check: Some(Algorithm::MD5),
+        recheck: false,
+        check_mode: CheckMode::Full,
+        collision: CollisionPolicy::Fail,
+        safe: true,
+        wip_suffix: "wip".to_owned(),
+        staging: false,
+        retries: 5,
+        jobs: 1,
+        parallel: 1,
+        debounce: None,
+        debounce_max: None,
+        poll: false,
+        poll_interval: 30,
+        heartbeat: None,
+        delta: false,
+        preserve_times: false,
+        preserve_permissions: false,
+        preserve_xattrs: false,
+        rate_limit: None,
+        sanitize: None,
+        use_trash: None,
+        min_age: None,
+        max_file_size: None,
+        sidecar: None,
+        reverify: None,
+        delivery_manifest: false,
+        conflict: ConflictPolicy::Report,
+        max_failures: 5,
+        min_interval: 0,
+        skip_identical: None,
+        progress: false,
+        min_size: None,
+        max_size: None,
+        control: None,
+        signing_key: None,
+        trusted_keys: vec![],
+        mqtt: None,
+        exclude: vec![],
+        strict: false,
+        once: false,
+        output: OutputFormat::Text,
+    };
+
+    // Action: Start the monitoring
+    let mut token = slide(config.clone()).await.unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+
+    // Action: Set some move strategy overrides for a slide
+    let baz = ctx.roots[1].join("baz").join("slides");
+    std::fs::write(
+        baz.join("bar").join(DEFAULT_SLIDE_CONFIG_FILE),
+        "retries: 1\n",
+    )
+    .unwrap();
+
+    // Check: The syncjobs have to be rebuilt right away
+    tokio::time::timeout(HOTPLUG_INTERVAL / 2, token.replanned())
+        .await
+        .unwrap();
+
+    // Action: Rebuild the syncjobs, and disable "baz"
+    rebuild(&mut token, config).await.unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+    assert!(token.stats().iter().any(|job| job.job.starts_with("baz ")));
+    std::fs::write(
+        baz.join(volume::DEFAULT_VOLUME_CONFIG_FILE),
+        "name: baz\ndisabled: true\n",
+    )
+    .unwrap();
+
+    // Check: The syncjobs have to be rebuilt right away
+    tokio::time::timeout(HOTPLUG_INTERVAL / 2, token.replanned())
+        .await
+        .unwrap();
+    let summary = enough(token).await.unwrap();
+
+    // Check: Only the syncjob of the slide edited was restarted
+    assert_eq!(summary.syncjobs, summary.jobs.len() + 1);
+    assert_eq!(summary.failed, 0);
+}
+
+/// Test the syncjobs left running when rebuilding them fails
+#[tokio::test]
+async fn test_hotplug_failed() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+    let config = GlobalConfig {
+        rootsets: vec![RootsetConfig {
+            keyword: "slides".to_string(),
+            roots: ctx.roots.clone(),
+            poll: false,
+        }],
+        dry_run: false,
+        trace: None,
+        history: None,
+        check: Some(Algorithm::MD5),
+        recheck: false,
+        check_mode: CheckMode::Full,
+        collision: CollisionPolicy::Fail,
+        safe: true,
+        wip_suffix: "wip".to_owned(),
+        staging: false,
+        retries: 5,
+        jobs: 1,
+        parallel: 1,
+        debounce: None,
+        debounce_max: None,
+        poll: false,
+        poll_interval: 30,
+        heartbeat: None,
+        delta: false,
+        preserve_times: false,
+        preserve_permissions: false,
+        preserve_xattrs: false,
+        rate_limit: None,
+        sanitize: None,
+        use_trash: None,
+        min_age: None,
+        max_file_size: None,
+        sidecar: None,
+        reverify: None,
+        delivery_manifest: false,
+        conflict: ConflictPolicy::Report,
+        max_failures: 5,
+        min_interval: 0,
+        skip_identical: None,
+        progress: false,
+        min_size: None,
+        max_size: None,
+        control: None,
+        signing_key: None,
+        trusted_keys: vec![],
+        mqtt: None,
+        exclude: vec![],
+        strict: false,
+        once: false,
+        output: OutputFormat::Text,
+    };
+
+    // Action: Start the monitoring
+    let mut token = slide(config.clone()).await.unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+    let jobs = token.stats().len();
+
+    // Action: Rebuild the syncjobs with a signing key missing
+    let missing = GlobalConfig {
+        signing_key: Some(ctx.roots[0].join("missing.key")),
+        ..config
+    };
+    let result = rebuild(&mut token, missing).await;
+
+    // Check: The rebuild failed, leaving the syncjobs running
+    assert!(result.is_err());
+    assert_eq!(token.stats().len(), jobs);
+
+    // Action: Drop a file for "bar"
+    let baz = ctx.roots[1].join("baz").join("slides");
+    std::fs::create_dir(baz.join("bar").join("direct")).unwrap();
+    std::fs::write(baz.join("bar").join("direct").join("direct.txt"), b"Direct").unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(1500)).await;
+    let summary = enough(token).await.unwrap();
+
+    // Check: The file was delivered, and no syncjob was stopped
+    assert!(ctx.roots[0]
+        .join("bar")
+        .join("slides")
+        .join("bar")
+        .join("direct")
+        .join("direct.txt")
+        .exists());
+    assert_eq!(summary.syncjobs, jobs);
+    assert_eq!(summary.failed, 0);
+}
+
+/// Test the traced events being recorded in the history, and looked up
+#[tokio::test]
+async fn test_history() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+    let history_dir = tempfile::tempdir().unwrap();
+    let history_path = history_dir.path().join("history.db");
+
+    // Prerequisite: A file waiting in the bar -> foo slide
+    let src_dir = ctx.roots[0]
+        .join("bar")
+        .join("slides")
+        .join("foo")
+        .join("test_history");
+    std::fs::create_dir(&src_dir).unwrap();
+    std::fs::write(src_dir.join("recorded.txt"), b"For the record").unwrap();
+
+    // Action: Run a single pass recording the history
+    let token = slide(GlobalConfig {
+        rootsets: vec![RootsetConfig {
+            keyword: "slides".to_string(),
+            roots: ctx.roots.clone(),
+            poll: false,
+        }],
+        dry_run: false,
+        trace: None,
+        history: Some(history_path.clone()),
+        check: Some(Algorithm::MD5),
+        recheck: false,
+        check_mode: CheckMode::Full,
+        collision: CollisionPolicy::Fail,
+        safe: true,
+        wip_suffix: "wip".to_owned(),
+        staging: false,
+        retries: 5,
+        jobs: 1,
+        parallel: 1,
+        debounce: None,
+        debounce_max: None,
+        poll: false,
+        poll_interval: 30,
+        heartbeat: None,
+        delta: false,
+        preserve_times: false,
+        preserve_permissions: false,
+        preserve_xattrs: false,
+        rate_limit: None,
+        sanitize: None,
+        use_trash: None,
+        min_age: None,
+        max_file_size: None,
+        sidecar: None,
+        reverify: None,
+        delivery_manifest: false,
+        conflict: ConflictPolicy::Report,
+        max_failures: 5,
+        min_interval: 0,
+        skip_identical: None,
+        progress: false,
+        min_size: None,
+        max_size: None,
+        control: None,
+        signing_key: None,
+        trusted_keys: vec![],
+        mqtt: None,
+        exclude: vec![],
+        strict: false,
+        once: true,
+        output: OutputFormat::Text,
+    })
+    .await
+    .unwrap();
+    enough(token).await.unwrap();
+
+    // Check: The move is recorded along with the syncjob
+    let events = history(&history_path, &HistoryQuery::default()).unwrap();
+    let moved = events
+        .iter()
+        .find(|e| e.operation == "MV" && e.details.contains("recorded.txt"))
+        .unwrap();
+    assert_eq!(moved.author, "bar -_-> foo");
+    assert!(events.iter().any(|e| e.operation == "MKDIR"));
+
+    // Check: The events are filtered by volume, file, operation and time
+    let query = |query: HistoryQuery| history(&history_path, &query).unwrap();
+    assert!(query(HistoryQuery {
+        volume: Some("foo".to_owned()),
+        ..Default::default()
+    })
+    .contains(moved));
+    assert!(query(HistoryQuery {
+        volume: Some("els".to_owned()),
+        operation: Some("MV".to_owned()),
+        ..Default::default()
+    })
+    .is_empty());
+    assert_eq!(
+        query(HistoryQuery {
+            file: Some("recorded.txt".to_owned()),
+            operation: Some("mv".to_owned()),
+            ..Default::default()
+        }),
+        vec![moved.clone()]
+    );
+    assert!(query(HistoryQuery {
+        file: Some("recorded_txt".to_owned()),
+        ..Default::default()
+    })
+    .is_empty());
+    assert!(query(HistoryQuery {
+        since: Some(parse_time("1h").unwrap()),
+        until: Some(parse_time("0s").unwrap()),
+        ..Default::default()
+    })
+    .contains(moved));
+    assert!(query(HistoryQuery {
+        until: Some(parse_time("1d").unwrap()),
+        ..Default::default()
+    })
+    .is_empty());
+
+    // Check: A missing history is an error
+    assert!(history(
+        &history_dir.path().join("missing.db"),
+        &HistoryQuery::default()
+    )
+    .is_err());
+}
+
+/// Test the files delivered being exported from the history
+#[tokio::test]
+async fn test_history_export() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+    let history_dir = tempfile::tempdir().unwrap();
+    let history_path = history_dir.path().join("history.db");
+
+    // Prerequisite: Two files waiting in the bar -> foo slide
+    let src_dir = ctx.roots[0]
+        .join("bar")
+        .join("slides")
+        .join("foo")
+        .join("test_history_export");
+    std::fs::create_dir(&src_dir).unwrap();
+    std::fs::write(src_dir.join("a.txt"), b"Exported").unwrap();
+    std::fs::write(src_dir.join("b.txt"), b"Exported, too").unwrap();
+
+    // Action: Run a single pass recording the history
+    let token = slide(GlobalConfig {
+        rootsets: vec![RootsetConfig {
+            keyword: "slides".to_string(),
+            roots: ctx.roots.clone(),
+            poll: false,
+        }],
+        dry_run: false,
+        trace: None,
+        history: Some(history_path.clone()),
+        check: Some(Algorithm::MD5),
+        recheck: false,
+        check_mode: CheckMode::Full,
+        collision: CollisionPolicy::Fail,
+        safe: true,
+        wip_suffix: "wip".to_owned(),
+        staging: false,
+        retries: 5,
+        jobs: 1,
+        parallel: 1,
+        debounce: None,
+        debounce_max: None,
+        poll: false,
+        poll_interval: 30,
+        heartbeat: None,
+        delta: false,
+        preserve_times: false,
+        preserve_permissions: false,
+        preserve_xattrs: false,
+        rate_limit: None,
+        sanitize: None,
+        use_trash: None,
+        min_age: None,
+        max_file_size: None,
+        sidecar: None,
+        reverify: None,
+        delivery_manifest: false,
+        conflict: ConflictPolicy::Report,
+        max_failures: 5,
+        min_interval: 0,
+        skip_identical: None,
+        progress: false,
+        min_size: None,
+        max_size: None,
+        control: None,
+        signing_key: None,
+        trusted_keys: vec![],
+        mqtt: None,
+        exclude: vec![],
+        strict: false,
+        once: true,
+        output: OutputFormat::Text,
+    })
+    .await
+    .unwrap();
+    enough(token).await.unwrap();
+
+    // Action: Export the deliveries of the files as CSV
+    let export = |query: HistoryQuery| {
+        let mut out = Vec::new();
+        let exported = export_history(&history_path, &query, ExportFormat::Csv, &mut out).unwrap();
+        (exported, String::from_utf8(out).unwrap())
+    };
+    let (exported, csv) = export(HistoryQuery {
+        file: Some("test_history_export".to_owned()),
+        ..Default::default()
+    });
+
+    // Check: A header row, then a row per file with its syncjob, volumes, paths and size
+    assert_eq!(exported, 2);
+    let mut reader = csv::Reader::from_reader(csv.as_bytes());
+    assert_eq!(
+        reader.headers().unwrap().iter().collect::<Vec<_>>(),
+        vec![
+            "time",
+            "job",
+            "source",
+            "via",
+            "destination",
+            "src",
+            "dst",
+            "size",
+            "duration_ms",
+            "throughput",
+            "checksum",
+            "retries",
+            "collision"
+        ]
+    );
+    let mut rows = reader
+        .records()
+        .map(|row| row.unwrap().iter().map(str::to_owned).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    rows.sort_by(|a, b| a[5].cmp(&b[5]));
+    assert_eq!(rows.len(), 2);
+    let dst_dir = ctx.roots[0]
+        .join("foo")
+        .join("slides")
+        .join("foo")
+        .join("test_history_export");
+    for (row, (name, size)) in rows.iter().zip([("a.txt", "8"), ("b.txt", "13")]) {
+        assert_eq!(
+            row[1..8],
+            [
+                "bar -_-> foo".to_owned(),
+                "bar".to_owned(),
+                "foo".to_owned(),
+                "foo".to_owned(),
+                src_dir.join(name).to_string_lossy().to_string(),
+                dst_dir.join(name).to_string_lossy().to_string(),
+                size.to_owned()
+            ]
+        );
+        assert_eq!(row[12], "none");
+    }
+
+    // Check: The deliveries are filtered like the events
+    assert_eq!(
+        export(HistoryQuery {
+            file: Some("test_history_export/b.txt".to_owned()),
+            ..Default::default()
+        })
+        .0,
+        1
+    );
+    let (exported, csv) = export(HistoryQuery {
+        volume: Some("els".to_owned()),
+        ..Default::default()
+    });
+    assert_eq!((exported, csv.as_str()), (0, ""));
+}
+
+/// Test the parsing of the points in time of the history queries
+#[test]
+fn test_parse_time() {
+    assert_eq!(
+        parse_time("2024-12-31").unwrap().to_string(),
+        "2024-12-31 00:00:00"
+    );
+    assert_eq!(
+        parse_time("2024-12-31 23:59:59").unwrap(),
+        parse_time("2024-12-31T23:59:59").unwrap()
+    );
+    let time = parse_time("2h").unwrap();
+    let ago = chrono::Local::now().naive_local() - time;
+    assert!(ago >= chrono::TimeDelta::hours(2));
+    assert!(ago < chrono::TimeDelta::hours(2) + chrono::TimeDelta::minutes(1));
+    assert!(parse_time("2 fortnights").is_err());
+    assert!(parse_time("yesterday").is_err());
+}
+
+/// Test the summaries of each sync and of the whole run
+#[tokio::test]
+async fn test_summaries() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+    let trace_dir = tempfile::tempdir().unwrap();
+    let trace_path = trace_dir.path().join("trace.log");
+
+    // Prerequisite: A file to move and a file too small to be moved in the bar -> foo slide
+    let src_dir = ctx.roots[0]
+        .join("bar")
+        .join("slides")
+        .join("foo")
+        .join("test_summaries");
+    std::fs::create_dir(&src_dir).unwrap();
+    std::fs::write(src_dir.join("moved.txt"), b"Moved").unwrap();
+    std::fs::write(src_dir.join("small.txt"), b"S").unwrap();
+
+    // Action: Run a single pass
+    let token = slide(GlobalConfig {
+        rootsets: vec![RootsetConfig {
+            keyword: "slides".to_string(),
+            roots: ctx.roots.clone(),
+            poll: false,
+        }],
+        dry_run: false,
+        trace: Some(trace_path.clone()),
+        history: None,
+        check: Some(Algorithm::MD5),
+        recheck: false,
+        check_mode: CheckMode::Full,
+        collision: CollisionPolicy::Fail,
+        safe: true,
+        wip_suffix: "wip".to_owned(),
+        staging: false,
+        retries: 5,
+        jobs: 1,
+        parallel: 1,
+        debounce: None,
+        debounce_max: None,
+        poll: false,
+        poll_interval: 30,
+        heartbeat: None,
+        delta: false,
+        preserve_times: false,
+        preserve_permissions: false,
+        preserve_xattrs: false,
+        rate_limit: None,
+        sanitize: None,
+        use_trash: None,
+        min_age: None,
+        max_file_size: None,
+        sidecar: None,
+        reverify: None,
+        delivery_manifest: false,
+        conflict: ConflictPolicy::Report,
+        max_failures: 5,
+        min_interval: 0,
+        skip_identical: None,
+        progress: false,
+        min_size: Some(4),
+        max_size: None,
+        control: None,
+        signing_key: None,
+        trusted_keys: vec![],
+        mqtt: None,
+        exclude: vec![],
+        strict: false,
+        once: true,
+        output: OutputFormat::Text,
+    })
+    .await
+    .unwrap();
+    let summary = enough(token).await.unwrap();
+
+    // Check: The file too small is skipped
+    assert!(src_dir.join("small.txt").exists());
+    let job = summary
+        .jobs
+        .iter()
+        .find(|job| job.job == "bar -_-> foo")
+        .unwrap();
+    assert_eq!((job.syncs, job.files, job.skipped), (1, 2, 1));
+
+    // Check: The totals of the run add up what every syncjob did
+    let total = |stat: fn(&JobStatus) -> u64| summary.jobs.iter().map(stat).sum::<u64>();
+    assert!(summary.to_string().starts_with(&format!(
+        "{} file(s) and {} byte(s) moved in {} sync(s) of {} syncjob(s), {} skipped, 0 retry(ies), 0 failure(s), 0 issue(s), took ",
+        total(|job| job.files),
+        total(|job| job.bytes),
+        total(|job| job.syncs),
+        summary.jobs.len(),
+        total(|job| job.skipped),
+    )));
+
+    // Check: Each sync is summed up in the trace, and so is the run once every syncjob is over
+    let trace = std::fs::read_to_string(&trace_path).unwrap();
+    assert!(trace.lines().any(|line| line.contains(&format!(
+        "[bar -_-> foo] SUMMARY {} file(s) and {} byte(s) moved, 1 skipped, 0 failure(s), took ",
+        job.files, job.bytes
+    ))));
+    assert_eq!(
+        trace
+            .lines()
+            .filter(|line| line.contains("] SUMMARY "))
+            .count(),
+        summary.jobs.len() + 1
+    );
+    let last = trace.lines().last().unwrap();
+    assert!(last.ends_with(&format!("[Run] SUMMARY {summary}")));
+}
+
+/// Test the records traced for each delivered file
+#[tokio::test]
+async fn test_transfer_records() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+    let trace_dir = tempfile::tempdir().unwrap();
+    let trace_path = trace_dir.path().join("trace.log");
+
+    // Prerequisite: Two files waiting in the bar -> foo slide, one of them already at the
+    // destination
+    let src_dir = ctx.roots[0]
+        .join("bar")
+        .join("slides")
+        .join("foo")
+        .join("test_records");
+    let dst_dir = ctx.roots[0]
+        .join("foo")
+        .join("slides")
+        .join("foo")
+        .join("test_records");
+    std::fs::create_dir(&src_dir).unwrap();
+    std::fs::create_dir(&dst_dir).unwrap();
+    std::fs::write(src_dir.join("new.txt"), b"New").unwrap();
+    std::fs::write(src_dir.join("taken.txt"), b"Taken").unwrap();
+    std::fs::write(dst_dir.join("taken.txt"), b"Taken before").unwrap();
+
+    // Action: Run a single pass, renaming the files colliding
+    let token = slide(GlobalConfig {
+        rootsets: vec![RootsetConfig {
+            keyword: "slides".to_string(),
+            roots: ctx.roots.clone(),
+            poll: false,
+        }],
+        dry_run: false,
+        trace: Some(trace_path.clone()),
+        history: None,
+        check: Some(Algorithm::MD5),
+        recheck: false,
+        check_mode: CheckMode::Full,
+        collision: "rename".parse().unwrap(),
+        safe: true,
+        wip_suffix: "wip".to_owned(),
+        staging: false,
+        retries: 5,
+        jobs: 1,
+        parallel: 1,
+        debounce: None,
+        debounce_max: None,
+        poll: false,
+        poll_interval: 30,
+        heartbeat: None,
+        delta: false,
+        preserve_times: false,
+        preserve_permissions: false,
+        preserve_xattrs: false,
+        rate_limit: None,
+        sanitize: None,
+        use_trash: None,
+        min_age: None,
+        max_file_size: None,
+        sidecar: None,
+        reverify: None,
+        delivery_manifest: false,
+        conflict: ConflictPolicy::Report,
+        max_failures: 5,
+        min_interval: 0,
+        skip_identical: None,
+        progress: false,
+        min_size: None,
+        max_size: None,
+        control: None,
+        signing_key: None,
+        trusted_keys: vec![],
+        mqtt: None,
+        exclude: vec![],
+        strict: false,
+        once: true,
+        output: OutputFormat::Text,
+    })
+    .await
+    .unwrap();
+    enough(token).await.unwrap();
+
+    // Check: Each delivery is traced as a record of the transfer
+    let trace = std::fs::read_to_string(&trace_path).unwrap();
+    let transfers = trace
+        .lines()
+        .filter_map(|line| line.split_once("] DELIVERED "))
+        .map(|(_, details)| serde_json::from_str::<Transfer>(details).unwrap())
+        .filter(|transfer| transfer.src.starts_with(&src_dir))
+        .collect::<Vec<_>>();
+    assert_eq!(transfers.len(), 2, "{trace}");
+    let new = transfers
+        .iter()
+        .find(|transfer| transfer.src == src_dir.join("new.txt"))
+        .unwrap();
+    assert_eq!(new.dst, dst_dir.join("new.txt"));
+    assert_eq!(new.size, 3);
+    assert_eq!(new.retries, 0);
+    assert_eq!(new.collision, Collision::None);
+    let taken = transfers
+        .iter()
+        .find(|transfer| transfer.src == src_dir.join("taken.txt"))
+        .unwrap();
+    assert_ne!(taken.dst, dst_dir.join("taken.txt"));
+    assert!(taken.dst.exists());
+    assert_eq!(taken.size, 5);
+    assert_eq!(taken.collision, Collision::Renamed);
+}
+
+/// Test the deliveries being undone from the trace
+#[tokio::test]
+async fn test_undo() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+    let trace_dir = tempfile::tempdir().unwrap();
+    let trace_path = trace_dir.path().join("trace.log");
+    let mut config = GlobalConfig {
+        rootsets: vec![RootsetConfig {
+            keyword: "slides".to_string(),
+            roots: ctx.roots.clone(),
+            poll: false,
+        }],
+        dry_run: false,
+        trace: Some(trace_path.clone()),
+        history: None,
+        check: Some(Algorithm::MD5),
+        recheck: false,
+        check_mode: CheckMode::Full,
+        collision: CollisionPolicy::Fail,
+        safe: true,
+        wip_suffix: "wip".to_owned(),
+        staging: false,
+        retries: 5,
+        jobs: 1,
+        parallel: 1,
+        debounce: None,
+        debounce_max: None,
+        poll: false,
+        poll_interval: 30,
+        heartbeat: None,
+        delta: false,
+        preserve_times: false,
+        preserve_permissions: false,
+        preserve_xattrs: false,
+        rate_limit: None,
+        sanitize: None,
+        use_trash: None,
+        min_age: None,
+        max_file_size: None,
+        sidecar: None,
+        reverify: None,
+        delivery_manifest: false,
+        conflict: ConflictPolicy::Report,
+        max_failures: 5,
+        min_interval: 0,
+        skip_identical: None,
+        progress: false,
+        min_size: None,
+        max_size: None,
+        control: None,
+        signing_key: None,
+        trusted_keys: vec![],
+        mqtt: None,
+        exclude: vec![],
+        strict: false,
+        once: true,
+        output: OutputFormat::Text,
+    };
+
+    // Prerequisite: Three files synced from the bar -> foo slide
+    let src_dir = ctx.roots[0]
+        .join("bar")
+        .join("slides")
+        .join("foo")
+        .join("test_undo");
+    let dst_dir = ctx.roots[0]
+        .join("foo")
+        .join("slides")
+        .join("foo")
+        .join("test_undo");
+    std::fs::create_dir(&src_dir).unwrap();
+    for name in ["restored.txt", "occupied.txt", "missing.txt"] {
+        std::fs::write(src_dir.join(name), name).unwrap();
+    }
+    enough(slide(config.clone()).await.unwrap()).await.unwrap();
+    assert!(dst_dir.join("restored.txt").exists());
+
+    // Prerequisite: A file took the place of a source, and a delivered file was removed
+    std::fs::create_dir_all(&src_dir).unwrap();
+    std::fs::write(src_dir.join("occupied.txt"), b"Newcomer").unwrap();
+    std::fs::remove_file(dst_dir.join("missing.txt")).unwrap();
+    let outcome = |undone: &[Undone], name: &str| {
+        undone
+            .iter()
+            .find(|u| u.path == dst_dir.join(name))
+            .map(|u| (u.to.clone(), u.outcome.clone()))
+            .unwrap()
+    };
+
+    // Action: Undo the deliveries on a dry run
+    config.dry_run = true;
+    let traces = [trace_path.clone()];
+    let undone = undo(&config, &traces, None).await.unwrap();
+
+    // Check: Nothing is moved
+    assert_eq!(
+        outcome(&undone, "restored.txt"),
+        (src_dir.join("restored.txt"), UndoOutcome::Restored)
+    );
+    assert!(dst_dir.join("restored.txt").exists());
+    assert!(!src_dir.join("restored.txt").exists());
+
+    // Check: Nothing was delivered from then on
+    let since = parse_time("1d").unwrap() + chrono::TimeDelta::days(2);
+    assert!(undo(&config, &traces, Some(since))
+        .await
+        .unwrap()
+        .is_empty());
+
+    // Action: Undo the deliveries
+    config.dry_run = false;
+    let undone = undo(&config, &traces, parse_time("1h").ok()).await.unwrap();
+
+    // Check: The files still delivered are moved back, unless their source is taken
+    assert_eq!(outcome(&undone, "restored.txt").1, UndoOutcome::Restored);
+    assert!(!dst_dir.join("restored.txt").exists());
+    assert_eq!(
+        std::fs::read_to_string(src_dir.join("restored.txt")).unwrap(),
+        "restored.txt"
+    );
+    assert_eq!(outcome(&undone, "occupied.txt").1, UndoOutcome::Occupied);
+    assert!(dst_dir.join("occupied.txt").exists());
+    assert_eq!(
+        std::fs::read_to_string(src_dir.join("occupied.txt")).unwrap(),
+        "Newcomer"
+    );
+    assert_eq!(outcome(&undone, "missing.txt").1, UndoOutcome::Missing);
+
+    // Check: The moves back are traced
+    let trace = std::fs::read_to_string(&trace_path).unwrap();
+    assert!(trace.contains(&format!(
+        "[Undo] UNDO {:?} -> {:?}",
+        dst_dir.join("restored.txt"),
+        src_dir.join("restored.txt")
+    )));
+}
+
+/// Test a file never moved back over one taking its place
+#[tokio::test]
+async fn test_undo_occupied() {
+    // Prerequisite: A delivered file, and another one where it came from
+    let temp_dir = tempfile::tempdir().unwrap();
+    let (path, to) = (
+        temp_dir.path().join("delivered.txt"),
+        temp_dir.path().join("source.txt"),
+    );
+    std::fs::write(&path, "delivered").unwrap();
+    std::fs::write(&to, "created since").unwrap();
+
+    // Action: Move the file back
+    let result = undo::move_back(&path, &to).await;
+
+    // Check: Both files are left as they were
+    let e = result.unwrap_err();
+    assert_eq!(
+        e.downcast_ref::<std::io::Error>().map(|e| e.kind()),
+        Some(std::io::ErrorKind::AlreadyExists)
+    );
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "delivered");
+    assert_eq!(std::fs::read_to_string(&to).unwrap(), "created since");
+}
+
+/// Test the progress of the syncjobs
+#[tokio::test]
+async fn test_progress() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+
+    // Prerequisite: A running instance, done with its first syncs
+    let token = slide(GlobalConfig {
+        rootsets: vec![RootsetConfig {
+            keyword: "slides".to_string(),
+            roots: ctx.roots.clone(),
+            poll: false,
+        }],
+        dry_run: false,
+        trace: None,
+        history: None,
+        check: Some(Algorithm::MD5),
+        recheck: false,
+        check_mode: CheckMode::Full,
+        collision: CollisionPolicy::Fail,
+        safe: true,
+        wip_suffix: "wip".to_owned(),
+        staging: false,
+        retries: 5,
+        jobs: 1,
+        parallel: 1,
+        // The events wait longer than the test, so only the requested syncs move anything
+        debounce: Some(60),
+        debounce_max: None,
+        poll: false,
+        poll_interval: 30,
+        heartbeat: None,
+        delta: false,
+        preserve_times: false,
+        preserve_permissions: false,
+        preserve_xattrs: false,
+        rate_limit: None,
+        sanitize: None,
+        use_trash: None,
+        min_age: None,
+        max_file_size: None,
+        sidecar: None,
+        reverify: None,
+        delivery_manifest: false,
+        conflict: ConflictPolicy::Report,
+        max_failures: 5,
+        min_interval: 0,
+        skip_identical: None,
+        progress: false,
+        min_size: None,
+        max_size: None,
+        control: None,
+        signing_key: None,
+        trusted_keys: vec![],
+        mqtt: None,
+        exclude: vec![],
+        strict: false,
+        once: false,
+        output: OutputFormat::Text,
+    })
+    .await
+    .unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+    let job = "bar -_-> foo";
+    let job_progress = |report: &ProgressReport| {
+        report
+            .jobs
+            .iter()
+            .find(|progress| progress.job == job)
+            .cloned()
+            .unwrap()
+    };
+
+    // Check: Every syncjob is idle, with nothing pending
+    let report = token.progress();
+    assert_eq!(report.jobs.len(), token.stats().len());
+    for progress in &report.jobs {
+        assert_eq!(progress.phase, Phase::Idle, "{progress:?}");
+        assert_eq!((progress.files_pending, progress.bytes_pending), (0, 0));
+        assert!(progress.current.is_empty());
+    }
+
+    // Action: Drop two files for foo, and sync them
+    let src_dir = ctx.roots[0]
+        .join("bar")
+        .join("slides")
+        .join("foo")
+        .join("test_progress");
+    std::fs::create_dir(&src_dir).unwrap();
+    std::fs::write(src_dir.join("a.txt"), b"Progress").unwrap();
+    std::fs::write(src_dir.join("b.txt"), b"More progress").unwrap();
+    token.sync_now(Some(job)).unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+
+    // Check: The last sync of the syncjob moved just them
+    let report = token.progress();
+    let progress = job_progress(&report);
+    assert_eq!(progress.phase, Phase::Idle);
+    assert_eq!((progress.files_moved, progress.bytes_moved), (2, 21));
+    assert_eq!((progress.files_pending, progress.bytes_pending), (0, 0));
+
+    // Check: The totals add up the syncjobs
+    let total = |stat: fn(&JobProgress) -> u64| report.jobs.iter().map(stat).sum::<u64>();
+    assert_eq!(report.files_moved, total(|job| job.files_moved));
+    assert_eq!(report.bytes_moved, total(|job| job.bytes_moved));
+    assert_eq!(report.files_pending, 0);
+    assert_eq!(report.bytes_pending, 0);
+
+    // Action: Sync again, with nothing left to move
+    token.sync_now(Some(job)).unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+
+    // Check: The progress is the one of the last sync
+    let progress = job_progress(&token.progress());
+    assert_eq!((progress.files_moved, progress.bytes_moved), (0, 0));
+
+    enough(token).await.unwrap();
+}
+
+/// Test the issues of a run being reported
+#[tokio::test]
+async fn test_issues() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+    let trace_dir = tempfile::tempdir().unwrap();
+    let trace_path = trace_dir.path().join("trace.log");
+
+    // Prerequisite: A file over the size cap and a file locked by another program in the
+    // bar -> foo slide
+    let src_dir = ctx.roots[0]
+        .join("bar")
+        .join("slides")
+        .join("foo")
+        .join("test_issues");
+    std::fs::create_dir(&src_dir).unwrap();
+    std::fs::write(src_dir.join("big.txt"), b"Over the size cap").unwrap();
+    std::fs::write(src_dir.join("locked.txt"), b"Locked").unwrap();
+    let locked = std::fs::File::open(src_dir.join("locked.txt")).unwrap();
+    locked.lock().unwrap();
+
+    // Action: Run a single pass
+    let token = slide(GlobalConfig {
+        rootsets: vec![RootsetConfig {
+            keyword: "slides".to_string(),
+            roots: ctx.roots.clone(),
+            poll: false,
+        }],
+        dry_run: false,
+        trace: Some(trace_path.clone()),
+        history: None,
+        check: Some(Algorithm::MD5),
+        recheck: false,
+        check_mode: CheckMode::Full,
+        collision: CollisionPolicy::Fail,
+        safe: true,
+        wip_suffix: "wip".to_owned(),
+        staging: false,
+        retries: 5,
+        jobs: 1,
+        parallel: 1,
+        debounce: None,
+        debounce_max: None,
+        poll: false,
+        poll_interval: 30,
+        heartbeat: None,
+        delta: false,
+        preserve_times: false,
+        preserve_permissions: false,
+        preserve_xattrs: false,
+        rate_limit: None,
+        sanitize: None,
+        use_trash: None,
+        min_age: None,
+        max_file_size: Some(10),
+        sidecar: None,
+        reverify: None,
+        delivery_manifest: false,
+        conflict: ConflictPolicy::Report,
+        max_failures: 5,
+        min_interval: 0,
+        skip_identical: None,
+        progress: false,
+        min_size: None,
+        max_size: None,
+        control: None,
+        signing_key: None,
+        trusted_keys: vec![],
+        mqtt: None,
+        exclude: vec![],
+        strict: false,
+        once: true,
+        output: OutputFormat::Text,
+    })
+    .await
+    .unwrap();
+    let summary = enough(token).await.unwrap();
+    drop(locked);
+
+    // Check: Both files are left in place, and reported as issues of the syncjob
+    let issues = summary
+        .issues
+        .iter()
+        .filter(|issue| {
+            issue
+                .path
+                .as_ref()
+                .is_some_and(|path| path.starts_with(&src_dir))
+        })
+        .map(|issue| (issue.job.as_str(), issue.kind, issue.path.clone().unwrap()))
+        .collect::<Vec<_>>();
+    assert_eq!(issues.len(), 2, "{issues:?}");
+    assert!(issues.contains(&("bar -_-> foo", IssueKind::Oversize, src_dir.join("big.txt"))));
+    assert!(issues.contains(&(
+        "bar -_-> foo",
+        IssueKind::Locked,
+        src_dir.join("locked.txt")
+    )));
+    assert!(summary
+        .to_string()
+        .contains(&format!(", {} issue(s), ", summary.issues.len())));
+
+    // Check: The issues are reported next to the trace, one JSON object per line
+    let report = std::fs::read_to_string(report_path(&trace_path)).unwrap();
+    let reported = report
+        .lines()
+        .map(|line| serde_json::from_str::<Issue>(line).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(reported, summary.issues);
+}
+
+/// Test the same issue happening again being kept once
+#[test]
+fn test_issues_repeated() {
+    // Prerequisite: A file locked sync after sync, and another one locked once
+    let registry = Registry::default();
+    let (locked, other) = (
+        PathBuf::from("/slide/locked.txt"),
+        PathBuf::from("/slide/other.txt"),
+    );
+    for sync in 1..=3 {
+        let detail = format!("Locked in sync {sync}");
+        registry.report("bar -_-> foo", IssueKind::Locked, Some(&locked), detail);
+    }
+    registry.report(
+        "bar -_-> foo",
+        IssueKind::Locked,
+        Some(&other),
+        "Locked".to_owned(),
+    );
+    registry.report(
+        "baz -_-> foo",
+        IssueKind::Locked,
+        Some(&locked),
+        "Locked".to_owned(),
+    );
+
+    // Check: Only the latest of the repeated ones is kept
+    let issues = registry
+        .issues()
+        .into_iter()
+        .map(|issue| (issue.job, issue.path.unwrap(), issue.detail))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        issues,
+        vec![
+            (
+                "bar -_-> foo".to_owned(),
+                locked.clone(),
+                "Locked in sync 3".to_owned()
+            ),
+            ("bar -_-> foo".to_owned(), other, "Locked".to_owned()),
+            ("baz -_-> foo".to_owned(), locked, "Locked".to_owned()),
+        ]
+    );
+}
+
+/// Test the syncjobs of a pass let go once the pass is interrupted
+#[tokio::test]
+async fn test_registry_closing() {
+    // Prerequisite: A syncjob registered
+    let registry = Registry::default();
+    let gate = registry.register(
+        "bar -_-> foo".to_owned(),
+        ["bar", "foo", "foo"].map(str::to_owned),
+        None,
+    );
+
+    // Action: Interrupt a pass holding the guard
+    let pass = async {
+        let _closing = registry.closing();
+        std::future::pending::<()>().await
+    };
+    assert!(tokio::time::timeout(Duration::from_millis(10), pass)
+        .await
+        .is_err());
+
+    // Check: The syncjob was told to end
+    assert_eq!(*gate.borrow(), control::Gate::Closed);
+}
+
+/// Test the manifests of the deliveries written at the destination
+#[tokio::test]
+async fn test_delivery_manifests() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+    let trace_dir = tempfile::tempdir().unwrap();
+    let trace_path = trace_dir.path().join("trace.log");
+    let config = GlobalConfig {
+        rootsets: vec![RootsetConfig {
+            keyword: "slides".to_string(),
+            roots: ctx.roots.clone(),
+            poll: false,
+        }],
+        dry_run: false,
+        trace: Some(trace_path.clone()),
+        history: None,
+        check: Some(Algorithm::MD5),
+        recheck: false,
+        check_mode: CheckMode::Full,
+        collision: CollisionPolicy::Fail,
+        safe: true,
+        wip_suffix: "wip".to_owned(),
+        staging: false,
+        retries: 5,
+        jobs: 1,
+        parallel: 1,
+        debounce: None,
+        debounce_max: None,
+        poll: false,
+        poll_interval: 30,
+        heartbeat: None,
+        delta: false,
+        preserve_times: false,
+        preserve_permissions: false,
+        preserve_xattrs: false,
+        rate_limit: None,
+        sanitize: None,
+        use_trash: None,
+        min_age: None,
+        max_file_size: None,
+        sidecar: None,
+        reverify: None,
+        delivery_manifest: true,
+        conflict: ConflictPolicy::Report,
+        max_failures: 5,
+        min_interval: 0,
+        skip_identical: None,
+        progress: false,
+        min_size: None,
+        max_size: None,
+        control: None,
+        signing_key: None,
+        trusted_keys: vec![],
+        mqtt: None,
+        exclude: vec![],
+        strict: false,
+        once: true,
+        output: OutputFormat::Text,
+    };
+
+    // Prerequisite: Two files in the bar -> foo slide
+    let src_dir = ctx.roots[0]
+        .join("bar")
+        .join("slides")
+        .join("foo")
+        .join("test_delivery_manifests");
+    let dst_slide = ctx.roots[0].join("foo").join("slides").join("foo");
+    std::fs::create_dir(&src_dir).unwrap();
+    std::fs::write(src_dir.join("a.txt"), b"First").unwrap();
+    std::fs::write(src_dir.join("b.txt"), b"Second").unwrap();
+    let manifests = || {
+        std::fs::read_dir(&dst_slide)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| {
+                let name = path.file_name().unwrap().to_string_lossy();
+                name.starts_with(".bitslides-manifest-") && name.ends_with("-bar.json")
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // Action: Run a single pass
+    enough(slide(config.clone()).await.unwrap()).await.unwrap();
+
+    // Check: A manifest at the root of the destination slide lists both files and their source
+    let written = manifests();
+    assert_eq!(written.len(), 1, "{written:?}");
+    let manifest: DeliveryManifest =
+        serde_json::from_slice(&std::fs::read(&written[0]).unwrap()).unwrap();
+    assert_eq!(
+        (
+            manifest.source.as_str(),
+            manifest.via.as_str(),
+            manifest.destination.as_str()
+        ),
+        ("bar", "foo", "foo")
+    );
+    for (name, size) in [("a.txt", 5), ("b.txt", 6)] {
+        let file = manifest
+            .files
+            .iter()
+            .find(|file| file.src == src_dir.join(name))
+            .unwrap();
+        assert_eq!(
+            file.dst,
+            dst_slide.join("test_delivery_manifests").join(name)
+        );
+        assert_eq!(file.size, size);
+    }
+
+    // Check: The manifest is traced
+    let trace = std::fs::read_to_string(&trace_path).unwrap();
+    assert!(trace.contains(&format!("[bar -_-> foo] MANIFEST {:?}", written[0])));
+
+    // Action: Run another pass, with nothing left to deliver
+    enough(slide(config).await.unwrap()).await.unwrap();
+
+    // Check: No other manifest is written, and the one written stays in place
+    assert_eq!(manifests(), written);
+}
+
+/// Test the heartbeats traced along a sync lasting longer than their interval
+#[tokio::test]
+async fn test_heartbeat() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+    let trace_path = ctx.temp_dir.path().join("test_heartbeat.trace");
+    let job = "bar -_-> foo";
+    let (tracer, handle) = {
+        let (tracer, handle) = tracer::Tracer::new(&Some(&trace_path), &None, Default::default())
+            .await
+            .unwrap();
+        (
+            tracer.annotate_author(job.to_owned()),
+            handle.expect("Should have a handle"),
+        )
+    };
+
+    // Prerequisite: A sync copying a file, 400 of its 1000 bytes done, and another one pending
+    let registry = Registry::default();
+    let _gate = registry.register(
+        job.to_owned(),
+        ["bar".to_owned(), "foo".to_owned(), "foo".to_owned()],
+        None,
+    );
+    assert!(registry.started(job));
+    let (huge, next) = (
+        PathBuf::from("/slide/huge.bin"),
+        PathBuf::from("/slide/next.bin"),
+    );
+    registry.pending(job, &huge, 1000);
+    registry.pending(job, &next, 10);
+    registry.restarted(job, &huge);
+    registry.copied(job, &huge, 400);
+
+    // Action: Await a sync lasting 750ms with heartbeats every 300ms, then one without heartbeats
+    let sync = tokio::time::sleep(Duration::from_millis(750));
+    let every = Some(Duration::from_millis(300));
+    with_heartbeat(sync, every, job, &registry, &tracer)
+        .await
+        .unwrap();
+    let sync = tokio::time::sleep(Duration::from_millis(750));
+    with_heartbeat(sync, None, job, &registry, &tracer)
+        .await
+        .unwrap();
+    drop(tracer);
+    handle.await.unwrap();
+
+    // Check: Two heartbeats were traced, with the file being copied and the bytes done
+    let trace = std::fs::read_to_string(&trace_path).unwrap();
+    let heartbeats = trace
+        .lines()
+        .filter(|line| line.contains("HEARTBEAT"))
+        .collect::<Vec<_>>();
+    assert_eq!(heartbeats.len(), 2, "{trace}");
+    for heartbeat in heartbeats {
+        assert!(
+            heartbeat.contains(&format!(
+                "[{job}] HEARTBEAT 0 file(s) and 400 byte(s) moved, 2 file(s) and 610 byte(s) \
+                 pending, copying \"/slide/huge.bin\" (400 of 1000 byte(s)), for "
+            )),
+            "{heartbeat}"
+        );
+    }
+}
+
+/// Test the MQTT password kept out of the logged configuration
+#[test]
+fn test_mqtt_password_redacted() {
+    // Prerequisite: A broker reached with a password
+    let mqtt = MqttConfig {
+        host: "127.0.0.1".to_owned(),
+        port: None,
+        topic: None,
+        client_id: None,
+        username: Some("bitslides".to_owned()),
+        password: Some("s3cr3t".to_owned()),
+        unknown: Default::default(),
+    };
+
+    // Action: Format the configuration as logged
+    let logged = format!(
+        "{:#?}",
+        GlobalConfig {
+            rootsets: vec![],
+            dry_run: false,
+            trace: None,
+            history: None,
+            check: None,
+            recheck: false,
+            check_mode: CheckMode::Full,
+            collision: CollisionPolicy::Fail,
+            safe: true,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
+            retries: 5,
+            jobs: 1,
+            parallel: 1,
+            debounce: None,
+            debounce_max: None,
+            poll: false,
+            poll_interval: 30,
+            heartbeat: None,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
+            progress: false,
+            min_size: None,
+            max_size: None,
+            control: None,
+            signing_key: None,
+            trusted_keys: vec![],
+            mqtt: Some(mqtt),
+            exclude: vec![],
+            strict: false,
+            once: false,
+            output: OutputFormat::Text,
+        }
+    );
+
+    // Check: The username is there, but not the password
+    assert!(logged.contains("\"bitslides\""));
+    assert!(!logged.contains("s3cr3t"));
+}
+
+/// Test the state of the syncjobs published to an MQTT broker
+#[tokio::test]
+async fn test_mqtt() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+
+    // Prerequisite: A broker accepting a single client, collecting the topic, payload and retain
+    // flag of every message published until it disconnects
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let broker = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut published = Vec::new();
+        loop {
+            // Packet type and flags, then the remaining length as a variable length integer
+            let header = stream.read_u8().await.unwrap();
+            let (mut length, mut shift) = (0, 0);
+            loop {
+                let byte = stream.read_u8().await.unwrap();
+                length |= usize::from(byte & 0x7f) << shift;
+                shift += 7;
+                if byte & 0x80 == 0 {
+                    break;
+                }
+            }
+            let mut body = vec![0; length];
+            stream.read_exact(&mut body).await.unwrap();
+            match header >> 4 {
+                // CONNECT, accepted
+                1 => stream.write_all(&[0x20, 0x02, 0x00, 0x00]).await.unwrap(),
+                // PUBLISH at most once, without packet identifier
+                3 => {
+                    let split = 2 + usize::from(u16::from_be_bytes([body[0], body[1]]));
+                    let topic = String::from_utf8(body[2..split].to_vec()).unwrap();
+                    let payload = String::from_utf8(body[split..].to_vec()).unwrap();
+                    published.push((topic, payload, header & 1 == 1));
+                }
+                // PINGREQ
+                12 => stream.write_all(&[0xd0, 0x00]).await.unwrap(),
+                // DISCONNECT
+                14 => break published,
+                _ => {}
+            }
+        }
+    });
+    let on = |published: &Vec<(String, String, bool)>, topic: &str| {
+        published
+            .iter()
+            .filter(|(t, _, _)| t == topic)
+            .map(|(_, payload, retain)| (payload.clone(), *retain))
+            .collect::<Vec<_>>()
+    };
+
+    // Prerequisite: A file for foo in the bar slide
+    let src_dir = ctx.roots[0]
+        .join("bar")
+        .join("slides")
+        .join("foo")
+        .join("test_mqtt");
+    std::fs::create_dir(&src_dir).unwrap();
+    std::fs::write(src_dir.join("a.txt"), b"Published").unwrap();
+
+    // Action: Run a single pass, publishing to the broker
+    let token = slide(GlobalConfig {
+        rootsets: vec![RootsetConfig {
+            keyword: "slides".to_string(),
+            roots: ctx.roots.clone(),
+            poll: false,
+        }],
+        dry_run: false,
+        trace: None,
+        history: None,
+        check: Some(Algorithm::MD5),
+        recheck: false,
+        check_mode: CheckMode::Full,
+        collision: CollisionPolicy::Fail,
+        safe: true,
+        wip_suffix: "wip".to_owned(),
+        staging: false,
+        retries: 5,
+        jobs: 1,
+        parallel: 1,
+        debounce: None,
+        debounce_max: None,
+        poll: false,
+        poll_interval: 30,
+        heartbeat: None,
+        delta: false,
+        preserve_times: false,
+        preserve_permissions: false,
+        preserve_xattrs: false,
+        rate_limit: None,
+        sanitize: None,
+        use_trash: None,
+        min_age: None,
+        max_file_size: None,
+        sidecar: None,
+        reverify: None,
+        delivery_manifest: false,
+        conflict: ConflictPolicy::Report,
+        max_failures: 5,
+        min_interval: 0,
+        skip_identical: None,
+        progress: false,
+        min_size: None,
+        max_size: None,
+        control: None,
+        signing_key: None,
+        trusted_keys: vec![],
+        mqtt: Some(MqttConfig {
+            host: "127.0.0.1".to_owned(),
+            port: Some(port),
+            topic: Some("home/bitslides".to_owned()),
+            client_id: None,
+            username: None,
+            password: None,
+            unknown: Default::default(),
+        }),
+        exclude: vec![],
+        strict: false,
+        once: true,
+        output: OutputFormat::Text,
+    })
+    .await
+    .unwrap();
+    let summary = enough(token).await.unwrap();
+    let published = broker.await.unwrap();
+
+    // Check: The instance was announced online, then offline, both retained
+    assert_eq!(
+        on(&published, "home/bitslides/status"),
+        vec![("online".to_owned(), true), ("offline".to_owned(), true)]
+    );
+
+    // Check: The status of the syncjob was published when registered, synced and done, retained
+    let statuses = on(&published, "home/bitslides/job/bar/foo/foo")
+        .into_iter()
+        .map(|(payload, retain)| {
+            assert!(retain);
+            serde_json::from_str::<JobStatus>(&payload).unwrap()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        statuses
+            .iter()
+            .map(|status| (status.job.as_str(), status.syncing, status.syncs))
+            .collect::<Vec<_>>(),
+        vec![
+            ("bar -_-> foo", false, 0),
+            ("bar -_-> foo", true, 0),
+            ("bar -_-> foo", false, 1)
+        ]
+    );
+    assert!(statuses[2].files >= 1);
+
+    // Check: The summary of its sync was published, as traced
+    let sync_summaries = on(&published, "home/bitslides/job/bar/foo/foo/summary");
+    assert_eq!(sync_summaries.len(), 1);
+    assert!(
+        sync_summaries[0].0.contains(" file(s) and ") && !sync_summaries[0].1,
+        "{sync_summaries:?}"
+    );
+
+    // Check: Its volumes were syncing along the way, and are idle in the end
+    for volume in ["bar", "foo"] {
+        let states = on(&published, &format!("home/bitslides/volume/{volume}"));
+        assert!(states.contains(&("syncing".to_owned(), true)), "{states:?}");
+        assert_eq!(states.last(), Some(&("idle".to_owned(), true)));
+    }
+
+    // Check: The summary of the run was published
+    assert_eq!(
+        on(&published, "home/bitslides/summary"),
+        vec![(summary.to_string(), false)]
+    );
+}
+
+/// Test the syncjobs going on after a failed sync, until too many fail in a row
+#[tokio::test]
+async fn test_failed_syncs() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+
+    // Action: Start the monitoring, giving up on a slide after 2 failed syncs
+    let token = {
+        let config = GlobalConfig {
+            rootsets: vec![RootsetConfig {
+                keyword: "slides".to_string(),
+                roots: ctx.roots.clone(),
+                poll: false,
+            }],
+            dry_run: false,
+            trace: None,
+            history: None,
+            check: Some(Algorithm::MD5),
+            recheck: false,
+            check_mode: CheckMode::Full,
+            collision: CollisionPolicy::Fail,
+            safe: true,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
+            retries: 5,
+            jobs: 1,
+            parallel: 1,
+            debounce: None,
+            debounce_max: None,
+            poll: false,
+            poll_interval: 30,
+            heartbeat: None,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 2,
+            min_interval: 0,
+            skip_identical: None,
+            progress: false,
+            min_size: None,
+            max_size: None,
+            control: None,
+            signing_key: None,
+            trusted_keys: vec![],
+            mqtt: None,
+            exclude: vec![],
+            strict: false,
+            once: false,
+            output: OutputFormat::Text,
+        };
+        slide(config).await.unwrap()
+    };
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+
+    // Action: Take away the slides of "baz" and "foo" for "bar", so their syncs fail
+    let baz = ctx.roots[1].join("baz").join("slides").join("bar");
+    let foo = ctx.roots[0].join("foo").join("slides").join("bar");
+    std::fs::remove_dir_all(&baz).unwrap();
+    std::fs::remove_dir_all(&foo).unwrap();
+    token.sync_now(Some("baz")).unwrap();
+    token.sync_now(Some("foo")).unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+
+    // Action: Bring the slide of "baz" back, with a file
+    std::fs::create_dir_all(baz.join("back")).unwrap();
+    std::fs::write(baz.join("back").join("back.txt"), b"Back").unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(2500)).await;
+    let summary = enough(token).await.unwrap();
+
+    // Check: The syncjob of "baz" synced again once its slide was back
+    assert!(ctx.roots[0]
+        .join("bar")
+        .join("slides")
+        .join("bar")
+        .join("back")
+        .join("back.txt")
+        .exists());
+    let find = |job: &str| {
+        summary
+            .jobs
+            .iter()
+            .find(|status| status.job == job)
             .unwrap()
-            .slides
-            .remove("bar")
-            .unwrap();
-        std::fs::remove_dir_all(missing_slide.path).unwrap();
-    }
+    };
+    let baz = find("baz -_-> bar");
+    assert_eq!((baz.failures, baz.files), (1, 1));
+    assert_eq!(baz.error, None);
 
-    // Execute the sync jobs
-    let result = {
-        let move_req = MoveStrategy {
+    // Check: The syncjob of "foo" gave up after failing twice in a row
+    let foo = find("foo -_-> bar");
+    assert_eq!(foo.failures, 2);
+    assert!(foo.error.is_some());
+    assert_eq!(summary.failed, 1);
+}
+
+/// Test syncing right away on request
+#[tokio::test]
+async fn test_sync_now() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+
+    // Action: Start the monitoring, the events waiting longer than the test for a sync
+    let token = {
+        let config = GlobalConfig {
+            rootsets: vec![RootsetConfig {
+                keyword: "slides".to_string(),
+                roots: ctx.roots.clone(),
+                poll: false,
+            }],
+            dry_run: false,
+            trace: None,
+            history: None,
+            check: Some(Algorithm::MD5),
+            recheck: false,
+            check_mode: CheckMode::Full,
             collision: CollisionPolicy::Fail,
-            safe: false,
-            check: None,
+            safe: true,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
             retries: 5,
+            jobs: 1,
+            parallel: 1,
+            debounce: Some(60),
+            debounce_max: None,
+            poll: false,
+            poll_interval: 30,
+            heartbeat: None,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
+            progress: false,
+            min_size: None,
+            max_size: None,
+            control: None,
+            signing_key: None,
+            trusted_keys: vec![],
+            mqtt: None,
+            exclude: vec![],
+            strict: false,
+            once: false,
+            output: OutputFormat::Text,
         };
-        execute_syncjobs(&volumes, syncjobs, false, tracer, &move_req).await
+        slide(config).await.unwrap()
     };
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
 
-    // Verify that the sync jobs failed due to the missing source
-    assert!(
-        result.is_err(),
-        "Expected error due to missing source slide"
-    );
+    // Action: Drop a file, then ask for the slides involving foo to be synced
+    let src_dir = ctx.roots[0]
+        .join("bar")
+        .join("slides")
+        .join("foo")
+        .join("test_sync_now");
+    std::fs::create_dir(&src_dir).unwrap();
+    std::fs::write(src_dir.join("now.txt"), b"Right now").unwrap();
+    let jobs = token.sync_now(Some("foo")).unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
 
-    handle.await.unwrap();
+    // Check: The file is moved without waiting for the events to calm down
+    assert!(jobs.contains(&"bar -_-> foo".to_string()), "{jobs:?}");
+    assert!(ctx.roots[0]
+        .join("foo")
+        .join("slides")
+        .join("foo")
+        .join("test_sync_now")
+        .join("now.txt")
+        .exists());
+
+    // Check: Unknown targets are reported
+    assert!(token.sync_now(Some("nowhere")).is_err());
+    enough(token).await.unwrap();
 }
 
 /// Test the real-time file monitoring behavior
@@ -348,13 +4761,53 @@ async fn test_file_monitoring_behavior() {
             rootsets: vec![RootsetConfig {
                 keyword: "slides".to_string(),
                 roots: ctx.roots.clone(),
+                poll: false,
             }],
             dry_run: false,
             trace: Some(trace_path.clone()),
+            history: None,
             check: Some(Algorithm::MD5),
+            recheck: false,
+            check_mode: CheckMode::Full,
             collision: CollisionPolicy::Fail,
             safe: true,
+            wip_suffix: "wip".to_owned(),
+            staging: false,
             retries: 5,
+            jobs: 1,
+            parallel: 1,
+            debounce: None,
+            debounce_max: None,
+            poll: false,
+            poll_interval: 30,
+            heartbeat: None,
+            delta: false,
+            preserve_times: false,
+            preserve_permissions: false,
+            preserve_xattrs: false,
+            rate_limit: None,
+            sanitize: None,
+            use_trash: None,
+            min_age: None,
+            max_file_size: None,
+            sidecar: None,
+            reverify: None,
+            delivery_manifest: false,
+            conflict: ConflictPolicy::Report,
+            max_failures: 5,
+            min_interval: 0,
+            skip_identical: None,
+            progress: false,
+            min_size: None,
+            max_size: None,
+            control: None,
+            signing_key: None,
+            trusted_keys: vec![],
+            mqtt: None,
+            exclude: vec![],
+            strict: false,
+            once: false,
+            output: OutputFormat::Text,
         };
         slide(config).await.unwrap()
     };
@@ -431,3 +4884,994 @@ async fn test_file_monitoring_behavior() {
         "Synchronized file content should match"
     );
 }
+
+/// Test the verification of the slides and the deliveries
+#[tokio::test]
+async fn test_verify() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+    let trace = ctx.temp_dir.path().join("verify.trace");
+
+    // Prerequisite: Some files waiting in the bar -> foo slide, one with an awkward name
+    let src_dir = ctx.roots[0]
+        .join("bar")
+        .join("slides")
+        .join("foo")
+        .join("test_verify");
+    std::fs::create_dir(&src_dir).unwrap();
+    for file in ["plain.txt", "say \"hi\"\t.txt"] {
+        std::fs::write(src_dir.join(file), file).unwrap();
+    }
+
+    let config = GlobalConfig {
+        rootsets: vec![RootsetConfig {
+            keyword: "slides".to_string(),
+            roots: ctx.roots.clone(),
+            poll: false,
+        }],
+        dry_run: false,
+        trace: Some(trace.clone()),
+        history: None,
+        check: Some(Algorithm::MD5),
+        recheck: false,
+        check_mode: CheckMode::Full,
+        collision: CollisionPolicy::Fail,
+        safe: true,
+        wip_suffix: "wip".to_owned(),
+        staging: false,
+        retries: 0,
+        jobs: 1,
+        parallel: 1,
+        debounce: None,
+        debounce_max: None,
+        poll: false,
+        poll_interval: 30,
+        heartbeat: None,
+        delta: false,
+        preserve_times: false,
+        preserve_permissions: false,
+        preserve_xattrs: false,
+        rate_limit: None,
+        sanitize: None,
+        use_trash: None,
+        min_age: None,
+        max_file_size: None,
+        // The files merely renamed are only hashed for a consumer of their checksum
+        sidecar: Some(Sidecar::Folder),
+        reverify: None,
+        delivery_manifest: false,
+        conflict: ConflictPolicy::Report,
+        max_failures: 5,
+        min_interval: 0,
+        skip_identical: None,
+        progress: false,
+        min_size: None,
+        max_size: None,
+        control: None,
+        signing_key: None,
+        trusted_keys: vec![],
+        mqtt: None,
+        exclude: vec![],
+        strict: false,
+        once: true,
+        output: OutputFormat::Text,
+    };
+
+    // Check: The files waiting in the slides are readable
+    let verifications = verify(&config, &[], false).unwrap();
+    assert!(verifications.iter().all(|v| v.is_ok() && v.from.is_none()));
+    assert_eq!(
+        verifications
+            .iter()
+            .filter(|v| v.path.starts_with(&src_dir))
+            .count(),
+        2
+    );
+
+    // Action: Deliver them
+    let token = slide(config.clone()).await.unwrap();
+    enough(token).await.unwrap();
+
+    // Check: The deliveries are recorded in the trace, and still in place
+    let dst_dir = ctx.roots[0]
+        .join("foo")
+        .join("slides")
+        .join("foo")
+        .join("test_verify");
+    let verifications = verify(&config, std::slice::from_ref(&trace), false).unwrap();
+    assert!(verifications.iter().all(|v| v.is_ok()));
+    assert_eq!(
+        verifications
+            .iter()
+            .filter(|v| v.path.starts_with(&dst_dir) && v.from.is_some())
+            .count(),
+        2,
+        "{verifications:?}"
+    );
+
+    // Check: The files renamed within the same filesystem were traced with the checksum of their
+    // checksum file
+    let plain = dst_dir.join("plain.txt");
+    let content = std::fs::read_to_string(&trace).unwrap();
+    let delivered = content
+        .lines()
+        .filter_map(verify::parse_delivery)
+        .find(|delivered| delivered.path == plain)
+        .unwrap();
+    assert_eq!(
+        delivered.checksum,
+        Some((Algorithm::MD5, hash_file(&plain, Algorithm::MD5)))
+    );
+
+    // Prerequisite: The file corrupted, keeping its size and modification time
+    let modified = filetime::FileTime::from_last_modification_time(&plain.metadata().unwrap());
+    let mut corrupted = std::fs::read(&plain).unwrap();
+    corrupted[0] ^= 0xFF;
+    std::fs::write(&plain, corrupted).unwrap();
+    filetime::set_file_mtime(&plain, modified).unwrap();
+
+    // Check: The cached checksum hides the corruption, unless the files are hashed again
+    let verifications = verify(&config, std::slice::from_ref(&trace), false).unwrap();
+    assert!(verifications.iter().all(|v| v.is_ok()));
+    let verifications = verify(&config, std::slice::from_ref(&trace), true).unwrap();
+    assert!(verifications.iter().any(|v| v.path == plain && !v.is_ok()));
+
+    // Check: Tampered and deleted files are reported
+    std::fs::write(dst_dir.join("plain.txt"), "tampered").unwrap();
+    std::fs::remove_file(dst_dir.join("say \"hi\"\t.txt")).unwrap();
+    let verifications = verify(&config, std::slice::from_ref(&trace), false).unwrap();
+    let verdict = |file: &str| {
+        verifications
+            .iter()
+            .find(|v| v.path == dst_dir.join(file))
+            .map(|v| v.verdict.clone())
+    };
+    assert!(matches!(verdict("plain.txt"), Some(Verdict::Mismatch(_))));
+    assert_eq!(verdict("say \"hi\"\t.txt"), Some(Verdict::Missing));
+}
+
+/// Test the verification of the files listed in the checksum files of the deliveries
+#[tokio::test]
+async fn test_verify_sidecars() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+
+    // Prerequisite: Some files waiting in the bar -> foo slide
+    let src_dir = ctx.roots[0]
+        .join("bar")
+        .join("slides")
+        .join("foo")
+        .join("test_verify_sidecars");
+    std::fs::create_dir(&src_dir).unwrap();
+    for file in ["a.txt", "b.txt"] {
+        std::fs::write(src_dir.join(file), file).unwrap();
+    }
+
+    let config = GlobalConfig {
+        rootsets: vec![RootsetConfig {
+            keyword: "slides".to_string(),
+            roots: ctx.roots.clone(),
+            poll: false,
+        }],
+        dry_run: false,
+        trace: None,
+        history: None,
+        check: Some(Algorithm::SHA2256),
+        recheck: false,
+        check_mode: CheckMode::Full,
+        collision: CollisionPolicy::Fail,
+        safe: true,
+        wip_suffix: "wip".to_owned(),
+        staging: false,
+        retries: 0,
+        jobs: 1,
+        parallel: 1,
+        debounce: None,
+        debounce_max: None,
+        poll: false,
+        poll_interval: 30,
+        heartbeat: None,
+        delta: false,
+        preserve_times: false,
+        preserve_permissions: false,
+        preserve_xattrs: false,
+        rate_limit: None,
+        sanitize: None,
+        use_trash: None,
+        min_age: None,
+        max_file_size: None,
+        sidecar: Some(Sidecar::Folder),
+        reverify: None,
+        delivery_manifest: false,
+        conflict: ConflictPolicy::Report,
+        max_failures: 5,
+        min_interval: 0,
+        skip_identical: None,
+        progress: false,
+        min_size: None,
+        max_size: None,
+        control: None,
+        signing_key: None,
+        trusted_keys: vec![],
+        mqtt: None,
+        exclude: vec![],
+        strict: false,
+        once: true,
+        output: OutputFormat::Text,
+    };
+
+    // Action: Deliver them, listing their checksums
+    let token = slide(config.clone()).await.unwrap();
+    enough(token).await.unwrap();
+
+    // Check: The files listed are verified without any trace
+    let dst_dir = ctx.roots[0]
+        .join("foo")
+        .join("slides")
+        .join("foo")
+        .join("test_verify_sidecars");
+    assert!(dst_dir.join("SHA256SUMS").exists());
+    let verifications = verify(&config, &[], false).unwrap();
+    assert!(verifications.iter().all(|v| v.is_ok()), "{verifications:?}");
+    assert_eq!(
+        verifications
+            .iter()
+            .filter(|v| v.path.starts_with(&dst_dir))
+            .count(),
+        2
+    );
+
+    // Check: Tampered and deleted files are reported
+    std::fs::write(dst_dir.join("a.txt"), "tampered").unwrap();
+    std::fs::remove_file(dst_dir.join("b.txt")).unwrap();
+    let verifications = verify(&config, &[], false).unwrap();
+    let verdict = |file: &str| {
+        verifications
+            .iter()
+            .find(|v| v.path == dst_dir.join(file))
+            .map(|v| v.verdict.clone())
+    };
+    assert!(matches!(verdict("a.txt"), Some(Verdict::Mismatch(_))));
+    assert_eq!(verdict("b.txt"), Some(Verdict::Missing));
+}
+
+/// Test the signed manifests of the deliveries
+#[tokio::test]
+async fn test_signed_manifests() {
+    // Prerequisite: Setup the test context, with a signing key
+    let ctx = setup().unwrap();
+    let key = ctx.temp_dir.path().join("keys").join("bitslides.key");
+    let public_key = generate_key(&key, false).unwrap();
+    assert!(generate_key(&key, false).is_err());
+
+    // Check: A key replacing a readable file is readable by its owner only
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let replaced = ctx.temp_dir.path().join("keys").join("replaced.key");
+        std::fs::write(&replaced, "").unwrap();
+        std::fs::set_permissions(&replaced, std::fs::Permissions::from_mode(0o644)).unwrap();
+        generate_key(&replaced, true).unwrap();
+        let mode = std::fs::metadata(&replaced).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    // Prerequisite: Some files waiting in the bar -> foo slide
+    let src_dir = ctx.roots[0]
+        .join("bar")
+        .join("slides")
+        .join("foo")
+        .join("test_signed_manifests");
+    std::fs::create_dir(&src_dir).unwrap();
+    for file in ["a.txt", "b.txt"] {
+        std::fs::write(src_dir.join(file), file).unwrap();
+    }
+
+    let config = GlobalConfig {
+        rootsets: vec![RootsetConfig {
+            keyword: "slides".to_string(),
+            roots: ctx.roots.clone(),
+            poll: false,
+        }],
+        dry_run: false,
+        trace: None,
+        history: None,
+        check: Some(Algorithm::SHA2256),
+        recheck: false,
+        check_mode: CheckMode::Full,
+        collision: CollisionPolicy::Fail,
+        safe: true,
+        wip_suffix: "wip".to_owned(),
+        staging: false,
+        retries: 0,
+        jobs: 1,
+        parallel: 1,
+        debounce: None,
+        debounce_max: None,
+        poll: false,
+        poll_interval: 30,
+        heartbeat: None,
+        delta: false,
+        preserve_times: false,
+        preserve_permissions: false,
+        preserve_xattrs: false,
+        rate_limit: None,
+        sanitize: None,
+        use_trash: None,
+        min_age: None,
+        max_file_size: None,
+        sidecar: None,
+        reverify: None,
+        delivery_manifest: false,
+        conflict: ConflictPolicy::Report,
+        max_failures: 5,
+        min_interval: 0,
+        skip_identical: None,
+        progress: false,
+        min_size: None,
+        max_size: None,
+        control: None,
+        signing_key: Some(key),
+        trusted_keys: vec![public_key],
+        mqtt: None,
+        exclude: vec![],
+        strict: false,
+        once: true,
+        output: OutputFormat::Text,
+    };
+
+    // Action: Deliver them, signing their manifest
+    let token = slide(config.clone()).await.unwrap();
+    enough(token).await.unwrap();
+
+    // Check: A single manifest was written, never synced, listing both files
+    let slide_dir = ctx.roots[0].join("foo").join("slides").join("foo");
+    let dst_dir = slide_dir.join("test_signed_manifests");
+    let manifests = std::fs::read_dir(slide_dir.join(".bitslides-manifests"))
+        .unwrap()
+        .flatten()
+        .map(|e| e.path())
+        .collect::<Vec<_>>();
+    assert_eq!(manifests.len(), 1, "{manifests:?}");
+    let manifest = &manifests[0];
+    let verifications = verify(&config, &[], false).unwrap();
+    assert!(verifications.iter().all(|v| v.is_ok()), "{verifications:?}");
+    assert_eq!(
+        verifications
+            .iter()
+            .filter(|v| v.path.starts_with(&dst_dir))
+            .count(),
+        2
+    );
+
+    // Check: A manifest signed by an unknown key is reported instead of its files
+    let untrusted = GlobalConfig {
+        trusted_keys: vec![],
+        mqtt: None,
+        ..config.clone()
+    };
+    let verifications = verify(&untrusted, &[], false).unwrap();
+    assert!(!verifications.iter().any(|v| v.path.starts_with(&dst_dir)));
+    let verification = verifications.iter().find(|v| &v.path == manifest);
+    assert!(matches!(
+        verification.map(|v| &v.verdict),
+        Some(Verdict::Tampered(_))
+    ));
+
+    // Check: Tampered and deleted files are reported, even if tampered keeping the size and the
+    // modification time their checksum is cached for
+    let tampered = dst_dir.join("a.txt");
+    let modified =
+        filetime::FileTime::from_last_modification_time(&std::fs::metadata(&tampered).unwrap());
+    std::fs::write(&tampered, "A.TXT").unwrap();
+    filetime::set_file_mtime(&tampered, modified).unwrap();
+    std::fs::remove_file(dst_dir.join("b.txt")).unwrap();
+    let verifications = verify(&config, &[], false).unwrap();
+    let verdict = |file: &str| {
+        verifications
+            .iter()
+            .find(|v| v.path == dst_dir.join(file))
+            .map(|v| v.verdict.clone())
+    };
+    assert!(matches!(verdict("a.txt"), Some(Verdict::Mismatch(_))));
+    assert_eq!(verdict("b.txt"), Some(Verdict::Missing));
+
+    // Check: An altered manifest is reported
+    let content = std::fs::read_to_string(manifest).unwrap();
+    std::fs::write(manifest, content.replace("\"bar\"", "\"baz\"")).unwrap();
+    let verifications = verify(&config, &[], false).unwrap();
+    assert!(!verifications.iter().any(|v| v.path.starts_with(&dst_dir)));
+    let verification = verifications.iter().find(|v| &v.path == manifest);
+    assert_eq!(
+        verification.map(|v| &v.verdict),
+        Some(&Verdict::Tampered("altered since signed".to_owned()))
+    );
+}
+
+/// Test the control interface of a running instance
+#[cfg(unix)]
+#[tokio::test]
+async fn test_control_status() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+    let socket = ctx.temp_dir.path().join("run").join("bitslides.sock");
+
+    // Action: Start the monitoring with the control interface
+    let config = GlobalConfig {
+        rootsets: vec![RootsetConfig {
+            keyword: "slides".to_string(),
+            roots: ctx.roots.clone(),
+            poll: false,
+        }],
+        dry_run: false,
+        trace: None,
+        history: None,
+        check: None,
+        recheck: false,
+        check_mode: CheckMode::Full,
+        collision: CollisionPolicy::Fail,
+        safe: true,
+        wip_suffix: "wip".to_owned(),
+        staging: false,
+        retries: 0,
+        jobs: 1,
+        parallel: 1,
+        debounce: None,
+        debounce_max: None,
+        poll: false,
+        poll_interval: 30,
+        heartbeat: None,
+        delta: false,
+        preserve_times: false,
+        preserve_permissions: false,
+        preserve_xattrs: false,
+        rate_limit: None,
+        sanitize: None,
+        use_trash: None,
+        min_age: None,
+        max_file_size: None,
+        sidecar: None,
+        reverify: None,
+        delivery_manifest: false,
+        conflict: ConflictPolicy::Report,
+        max_failures: 5,
+        min_interval: 0,
+        skip_identical: None,
+        progress: false,
+        min_size: None,
+        max_size: None,
+        control: Some(socket.clone()),
+        signing_key: None,
+        trusted_keys: vec![],
+        mqtt: None,
+        exclude: vec![],
+        strict: false,
+        once: false,
+        output: OutputFormat::Text,
+    };
+    let jobs = plan(&config).unwrap().len();
+    let token = slide(config.clone()).await.unwrap();
+
+    // Wait for initial sync to complete
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+
+    // Check: Every syncjob is reported, along with the files of the initial sync
+    let statuses = status(&socket).await.unwrap();
+    assert_eq!(statuses.len(), jobs, "{statuses:?}");
+    assert!(statuses.iter().all(|s| s.last_sync.is_some() && !s.syncing));
+    let bar_foo = statuses.iter().find(|s| s.job == "bar -_-> foo").unwrap();
+    assert_eq!(bar_foo.files, 1, "{bar_foo:?}");
+    assert!(bar_foo.bytes > 0);
+    assert_eq!(bar_foo.syncs, 1, "{bar_foo:?}");
+    assert_eq!((bar_foo.retries, bar_foo.failures), (0, 0));
+    assert!(bar_foo.last_duration_ms.is_some());
+
+    // Check: The token tells the same as the control interface
+    assert_eq!(token.stats(), statuses);
+
+    // Check: A second instance cannot take over the socket
+    assert!(slide(config.clone()).await.is_err());
+
+    // Check: The socket is gone after the shutdown, and the summary keeps what each syncjob did
+    let summary = enough(token).await.unwrap();
+    assert!(!socket.exists());
+    assert!(status(&socket).await.is_err());
+    assert_eq!(summary.jobs.len(), jobs);
+    let bar_foo = summary
+        .jobs
+        .iter()
+        .find(|s| s.job == "bar -_-> foo")
+        .unwrap();
+    assert_eq!((bar_foo.files, bar_foo.syncs), (1, 1), "{bar_foo:?}");
+
+    // Check: A file other than a socket is never replaced
+    std::fs::write(&socket, "notes").unwrap();
+    assert!(slide(config).await.is_err());
+    assert_eq!(std::fs::read_to_string(&socket).unwrap(), "notes");
+}
+
+/// Test pausing, resuming and stopping a running instance through the control interface
+#[cfg(unix)]
+#[tokio::test]
+async fn test_control_commands() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+    let socket = ctx.temp_dir.path().join("bitslides.sock");
+
+    // Prerequisite: A running instance with the control interface
+    let token = slide(GlobalConfig {
+        rootsets: vec![RootsetConfig {
+            keyword: "slides".to_string(),
+            roots: ctx.roots.clone(),
+            poll: false,
+        }],
+        dry_run: false,
+        trace: None,
+        history: None,
+        check: None,
+        recheck: false,
+        check_mode: CheckMode::Full,
+        collision: CollisionPolicy::Fail,
+        safe: true,
+        wip_suffix: "wip".to_owned(),
+        staging: false,
+        retries: 0,
+        jobs: 1,
+        parallel: 1,
+        // The events wait longer than the test, so only the commands sync
+        debounce: Some(60),
+        debounce_max: None,
+        poll: false,
+        poll_interval: 30,
+        heartbeat: None,
+        delta: false,
+        preserve_times: false,
+        preserve_permissions: false,
+        preserve_xattrs: false,
+        rate_limit: None,
+        sanitize: None,
+        use_trash: None,
+        min_age: None,
+        max_file_size: None,
+        sidecar: None,
+        reverify: None,
+        delivery_manifest: false,
+        conflict: ConflictPolicy::Report,
+        max_failures: 5,
+        min_interval: 0,
+        skip_identical: None,
+        progress: false,
+        min_size: None,
+        max_size: None,
+        control: Some(socket.clone()),
+        signing_key: None,
+        trusted_keys: vec![],
+        mqtt: None,
+        exclude: vec![],
+        strict: false,
+        once: false,
+        output: OutputFormat::Text,
+    })
+    .await
+    .unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+
+    // Action: Pause the syncjobs involving foo
+    let paused = control(&socket, &ControlCommand::Pause(Some("foo".to_string())))
+        .await
+        .unwrap();
+
+    // Check: Only those are paused
+    assert!(paused.contains(&"bar -_-> foo".to_string()), "{paused:?}");
+    let statuses = status(&socket).await.unwrap();
+    for s in &statuses {
+        assert_eq!(s.paused, paused.contains(&s.job), "{s:?}");
+    }
+
+    // Action: Drop a file for foo, and ask for a sync
+    let src_dir = ctx.roots[0]
+        .join("bar")
+        .join("slides")
+        .join("foo")
+        .join("test_pause");
+    std::fs::create_dir(&src_dir).unwrap();
+    std::fs::write(src_dir.join("paused.txt"), b"Wait for it").unwrap();
+    control(
+        &socket,
+        &ControlCommand::SyncNow(Some("bar -_-> foo".to_string())),
+    )
+    .await
+    .unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+
+    // Check: Nothing is moved while paused
+    let dst_file = ctx.roots[0]
+        .join("foo")
+        .join("slides")
+        .join("foo")
+        .join("test_pause")
+        .join("paused.txt");
+    assert!(!dst_file.exists());
+
+    // Action: Resume them
+    control(&socket, &ControlCommand::Resume(Some("foo".to_string())))
+        .await
+        .unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+
+    // Check: The pending sync is run
+    assert!(dst_file.exists());
+
+    // Action: Pause them again, drop another file, and resume them without asking for a sync
+    control(&socket, &ControlCommand::Pause(Some("foo".to_string())))
+        .await
+        .unwrap();
+    std::fs::write(src_dir.join("missed.txt"), b"Catch up").unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+    control(&socket, &ControlCommand::Resume(Some("foo".to_string())))
+        .await
+        .unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+
+    // Check: Resuming syncs right away
+    assert!(dst_file.with_file_name("missed.txt").exists());
+
+    // Check: Unknown targets are reported
+    assert!(
+        control(&socket, &ControlCommand::Pause(Some("nowhere".to_string())))
+            .await
+            .is_err()
+    );
+
+    // Action: Request a shutdown, after pausing everything
+    control(&socket, &ControlCommand::Pause(None))
+        .await
+        .unwrap();
+    control(&socket, &ControlCommand::Shutdown).await.unwrap();
+
+    // Check: The instance learns about it, and stops even with paused syncjobs
+    tokio::time::timeout(tokio::time::Duration::from_secs(1), token.stopped())
+        .await
+        .unwrap();
+    enough(token).await.unwrap();
+}
+
+/// Test the parsing of the control commands
+#[test]
+fn test_control_command_from_str() {
+    for command in [
+        ControlCommand::Status,
+        ControlCommand::Pause(None),
+        ControlCommand::Pause(Some("bar -_-> foo".to_string())),
+        ControlCommand::Resume(Some("foo".to_string())),
+        ControlCommand::SyncNow(None),
+        ControlCommand::Shutdown,
+    ] {
+        assert_eq!(
+            command.to_string().parse::<ControlCommand>().unwrap(),
+            command
+        );
+    }
+    assert!("status foo".parse::<ControlCommand>().is_err());
+    assert!("unplug".parse::<ControlCommand>().is_err());
+}
+
+/// Test the tidy-up of the files delivered to a volume
+#[tokio::test]
+async fn test_tidy_up() {
+    // Prerequisite: Setup the test context
+    let ctx = setup().unwrap();
+    let foo = ctx.roots[0].join("foo");
+    let inbox = foo.join("slides").join("foo");
+
+    // Prerequisite: Some tidy-up rules for foo, and files delivered to it
+    std::fs::write(
+        inbox.join(DEFAULT_SLIDE_CONFIG_FILE),
+        r#"
+rules:
+  - regex: "^Media"
+    operation: move
+    destination: "Media/Inbox"
+  - regex: "^Photos/Mobile"
+    operation: Move_to_new_dir
+    params: ["fixed"]
+    destination: "Media/Photos"
+"#,
+    )
+    .unwrap();
+    for file in [
+        "Media/movie.mkv",
+        "Photos/Mobile/2024/a.jpg",
+        "Documents/letter.txt",
+    ] {
+        let file = inbox.join(file);
+        std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+        std::fs::write(file, b"tidy me").unwrap();
+    }
+
+    let config = GlobalConfig {
+        rootsets: vec![RootsetConfig {
+            keyword: "slides".to_string(),
+            roots: ctx.roots.clone(),
+            poll: false,
+        }],
+        dry_run: true,
+        trace: None,
+        history: None,
+        check: None,
+        recheck: false,
+        check_mode: CheckMode::Full,
+        collision: CollisionPolicy::Fail,
+        safe: true,
+        wip_suffix: "wip".to_owned(),
+        staging: false,
+        retries: 0,
+        jobs: 1,
+        parallel: 1,
+        debounce: None,
+        debounce_max: None,
+        poll: false,
+        poll_interval: 30,
+        heartbeat: None,
+        delta: false,
+        preserve_times: false,
+        preserve_permissions: false,
+        preserve_xattrs: false,
+        rate_limit: None,
+        sanitize: None,
+        use_trash: None,
+        min_age: None,
+        max_file_size: None,
+        sidecar: None,
+        reverify: None,
+        delivery_manifest: false,
+        conflict: ConflictPolicy::Report,
+        max_failures: 5,
+        min_interval: 0,
+        skip_identical: None,
+        progress: false,
+        min_size: None,
+        max_size: None,
+        control: None,
+        signing_key: None,
+        trusted_keys: vec![],
+        mqtt: None,
+        exclude: vec![],
+        strict: false,
+        once: true,
+        output: OutputFormat::Text,
+    };
+
+    // Check: A dry run moves nothing
+    assert_eq!(tidy_up(&config).await.unwrap(), 2);
+    assert!(inbox.join("Media").join("movie.mkv").exists());
+
+    // Action: Tidy up
+    let config = GlobalConfig {
+        dry_run: false,
+        ..config
+    };
+    assert_eq!(tidy_up(&config).await.unwrap(), 2);
+
+    // Check: The matching files are moved, the rest are left in place
+    assert!(foo.join("Media").join("Inbox").join("movie.mkv").exists());
+    assert!(foo
+        .join("Media")
+        .join("Photos")
+        .join("fixed")
+        .join("2024")
+        .join("a.jpg")
+        .exists());
+    assert!(inbox.join("Documents").join("letter.txt").exists());
+    assert!(!inbox.join("Media").exists());
+    assert!(inbox.join(DEFAULT_SLIDE_CONFIG_FILE).exists());
+
+    // Check: Files are never overwritten
+    std::fs::create_dir_all(inbox.join("Media")).unwrap();
+    std::fs::write(inbox.join("Media").join("movie.mkv"), b"again").unwrap();
+    assert_eq!(tidy_up(&config).await.unwrap(), 0);
+    assert!(inbox.join("Media").join("movie.mkv").exists());
+
+    // Action: A rule moving the files out of the volume
+    std::fs::write(
+        inbox.join(DEFAULT_SLIDE_CONFIG_FILE),
+        r#"
+rules:
+  - regex: "^Media"
+    operation: move
+    destination: "../elsewhere"
+"#,
+    )
+    .unwrap();
+
+    // Check: The rule is refused and nothing is moved
+    assert!(tidy_up(&config).await.is_err());
+    assert!(inbox.join("Media").join("movie.mkv").exists());
+}
+
+/// Test the checksums of the hashers, fed at once or in pieces.
+#[test]
+fn test_hashers() {
+    use std::str::FromStr;
+
+    let data = (0..=255u8).cycle().take(100_000).collect::<Vec<_>>();
+
+    // Check: The algorithms of the checksums crate give the same checksums through the trait
+    for name in [
+        "sha1",
+        "sha2-224",
+        "sha2-256",
+        "sha2-384",
+        "sha2-512",
+        "sha3-256",
+        "sha3-512",
+        "blake",
+        "blake2b",
+        "blake2s",
+        "blake3",
+        "crc64",
+        "crc32",
+        "crc32c",
+        "crc16",
+        "crc8",
+        "md5",
+        "md6-128",
+        "md6-256",
+        "md6-512",
+        "whirlpool",
+        "xor8",
+    ] {
+        let algorithm = Algorithm::from_str(name).unwrap();
+        let expected = checksums::hash_reader(
+            &mut &data[..],
+            checksums::Algorithm::from_str(name).unwrap(),
+        );
+        assert_eq!(
+            hash_reader(&mut &data[..], algorithm).unwrap(),
+            expected,
+            "{name}"
+        );
+    }
+
+    // Check: The xxHash checksums match the reference ones
+    for (name, expected) in [
+        ("xxh64", "EF46DB3751D8E999"),
+        ("xxh128", "99AA06D3014798D86001C324468D497F"),
+    ] {
+        let algorithm = Algorithm::from_str(name).unwrap();
+        assert_eq!(hash_reader(&mut &[][..], algorithm).unwrap(), expected);
+    }
+
+    // Check: Feeding the data in pieces gives the same checksum
+    for algorithm in [
+        Algorithm::BLAKE3,
+        Algorithm::XXH64,
+        Algorithm::XXH128,
+        Algorithm::MD5,
+    ] {
+        let mut hasher = algorithm.hasher();
+        for piece in data.chunks(1000) {
+            hasher.update(piece);
+        }
+        assert_eq!(
+            hasher.finish().unwrap(),
+            hash_reader(&mut &data[..], algorithm).unwrap(),
+            "{algorithm:?}"
+        );
+    }
+
+    // Check: The names of the trace are read back
+    assert_eq!(Algorithm::from_str("XXH128"), Ok(Algorithm::XXH128));
+    assert!(Algorithm::from_str("xxh32").is_err());
+}
+
+/// Test the checksum cache, kept while the files stay unchanged.
+#[test]
+fn test_checksum_cache() {
+    use crate::cache::{ChecksumCache, CACHE_FILE};
+
+    // Prerequisite: A keyword folder with a couple of files
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let root = tmp_dir.path();
+    let (kept, changed, removed) = (
+        root.join("kept"),
+        root.join("changed"),
+        root.join("removed"),
+    );
+    for file in [&kept, &changed, &removed] {
+        std::fs::write(file, "contents").unwrap();
+    }
+
+    // Action: Cache the checksums of every file, and save them
+    let cache = ChecksumCache::load(root);
+    for file in [&kept, &changed, &removed] {
+        cache.insert(file, Algorithm::BLAKE3, "CHECKSUM");
+    }
+    cache.save().unwrap();
+
+    // Check: The checksums are found once loaded again, only for the same algorithm
+    let cache = ChecksumCache::load(root);
+    assert_eq!(
+        cache.get(&kept, Algorithm::BLAKE3),
+        Some("CHECKSUM".to_owned())
+    );
+    assert_eq!(cache.get(&kept, Algorithm::XXH64), None);
+
+    // Check: The files outside of the keyword folder are not cached
+    let outside = tempfile::tempdir().unwrap();
+    let other = outside.path().join("other");
+    std::fs::write(&other, "contents").unwrap();
+    cache.insert(&other, Algorithm::BLAKE3, "CHECKSUM");
+    assert_eq!(cache.get(&other, Algorithm::BLAKE3), None);
+
+    // Prerequisite: A file modified, and another one removed
+    std::fs::write(&changed, "modified").unwrap();
+    filetime::set_file_mtime(
+        &changed,
+        filetime::FileTime::from_unix_time(1_000_000_000, 0),
+    )
+    .unwrap();
+    std::fs::remove_file(&removed).unwrap();
+
+    // Check: The checksums of the modified and the removed files are no longer valid
+    assert_eq!(cache.get(&changed, Algorithm::BLAKE3), None);
+    assert_eq!(cache.get(&removed, Algorithm::BLAKE3), None);
+
+    // Action: Save the cache
+    cache.save().unwrap();
+
+    // Check: Only the unchanged file is left in the cache
+    let content = std::fs::read_to_string(root.join(CACHE_FILE)).unwrap();
+    assert!(content.contains("\"kept\""));
+    assert!(!content.contains("\"changed\"") && !content.contains("\"removed\""));
+}
+
+/// Test the bound on the delay of the syncs postponed by bursts of filesystem events
+#[tokio::test]
+async fn test_debounce_max() {
+    /// Send an event every 50ms for a second, returning whether a sync was triggered meanwhile
+    async fn burst(debounce: Debounce) -> (bool, mpsc::Receiver<()>) {
+        let (trigger, mut syncs) = mpsc::channel(1);
+        let events = debounced(trigger, debounce);
+        for _ in 0..20 {
+            events.send(()).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        (syncs.try_recv().is_ok(), syncs)
+    }
+
+    // Prerequisite: A quiet period longer than the time between two events
+    let quiet = Duration::from_millis(200);
+
+    // Action: A burst postponing the sync for as long as it lasts
+    let (synced, mut syncs) = burst(Debounce { quiet, max: None }).await;
+
+    // Check: The slide is synced once the burst ends, not before
+    assert!(!synced);
+    tokio::time::timeout(Duration::from_secs(1), syncs.recv())
+        .await
+        .expect("The slide was not synced after the burst");
+
+    // Action: A burst longer than the maximum delay
+    let max = Some(Duration::from_millis(300));
+    let (synced, _) = burst(Debounce { quiet, max }).await;
+
+    // Check: The slide is synced during the burst
+    assert!(synced);
+
+    // Check: The batching is configured in seconds, 0 disabling it
+    assert_eq!(Debounce::new(None, Some(60)), None);
+    assert_eq!(Debounce::new(Some(0), Some(60)), None);
+    assert_eq!(
+        Debounce::new(Some(2), Some(0)),
+        Some(Debounce {
+            quiet: Duration::from_secs(2),
+            max: None
+        })
+    );
+    assert_eq!(
+        Debounce::new(Some(2), Some(60)).and_then(|d| d.max),
+        Some(Duration::from_secs(60))
+    );
+}
@@ -0,0 +1,199 @@
+use std::{
+    fmt::Write,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Result};
+use chrono::Local;
+use regex::Regex;
+
+use crate::{
+    config::{SlideConfig, TidyOperation, TidyRule},
+    fs::{delete_empty_folders, is_internal},
+    tracer::Tracer,
+    volume::Volume,
+    DEFAULT_SLIDE_CONFIG_FILE,
+};
+
+/// Date format of the folders created by `move_to_new_dir` when not given.
+///
+const DEFAULT_DIR_FORMAT: &str = "%Y%m%d";
+
+/// A tidy-up rule, ready to be applied.
+///
+struct Rule {
+    regex: Regex,
+    /// Folder the matching files are moved to
+    destination: PathBuf,
+}
+
+impl Rule {
+    /// Compile a rule of the volume at `root`. The new folders are named after the current date.
+    ///
+    fn new(rule: &TidyRule, root: &Path) -> Result<Self> {
+        let regex = Regex::new(&rule.regex)
+            .map_err(|e| anyhow!("Invalid tidy-up rule \"{}\": {e}", rule.regex))?;
+        let mut destination = rule.destination.clone();
+        if rule.operation == TidyOperation::MoveToNewDir {
+            let format = rule
+                .params
+                .first()
+                .map(|p| p.as_str())
+                .unwrap_or(DEFAULT_DIR_FORMAT);
+            let mut dir = String::new();
+            write!(dir, "{}", Local::now().format(format))
+                .map_err(|_| anyhow!("Invalid date format \"{format}\""))?;
+            destination.push(dir);
+        }
+        check_destination(&destination)
+            .map_err(|e| anyhow!("Invalid tidy-up rule \"{}\": {e}", rule.regex))?;
+        let destination = root.join(destination);
+        Ok(Self { regex, destination })
+    }
+
+    /// Where a file at the `relative` path is moved to, if it matches.
+    ///
+    /// The part of the path matched at its start is dropped, so `^Photos/Mobile` moves
+    /// `Photos/Mobile/2024/a.jpg` to `<destination>/2024/a.jpg`.
+    ///
+    fn apply(&self, relative: &str) -> Option<PathBuf> {
+        let found = self.regex.find(relative)?;
+        let rest = match found.start() {
+            0 => relative[found.end()..].trim_start_matches('/'),
+            _ => relative,
+        };
+        let rest = match rest {
+            // The whole path matched, keep the file name at least
+            "" => relative.rsplit('/').next().unwrap_or(relative),
+            rest => rest,
+        };
+        Some(self.destination.join(rest))
+    }
+}
+
+/// Check that the destination of a rule is a folder inside the volume, so no file is moved out
+/// of it.
+///
+fn check_destination(destination: &Path) -> Result<()> {
+    if destination
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        bail!("{destination:?} must be a relative path without \"..\"");
+    }
+    Ok(())
+}
+
+/// Tidy up the slide of a volume named after itself, where the files delivered to it land.
+///
+/// The rules are read from the config file of that slide. Each file is moved by the first rule
+/// matching it, files matching none are left in place. A file is never overwritten. Returns the
+/// number of files moved.
+///
+pub(crate) async fn tidy_volume(
+    volume: &Volume,
+    dry_run: bool,
+    wip_suffix: &str,
+    tracer: &Tracer,
+) -> Result<usize> {
+    let Some(slide) = volume.slides.get(&volume.name) else {
+        return Ok(0);
+    };
+    let config_file = slide.path.join(DEFAULT_SLIDE_CONFIG_FILE);
+    if !config_file.exists() {
+        return Ok(0);
+    }
+    let rules = SlideConfig::new(&config_file, false)?
+        .rules
+        .iter()
+        .map(|rule| Rule::new(rule, &volume.path))
+        .collect::<Result<Vec<_>>>()
+        .map_err(|e| anyhow!("{config_file:?}: {e}"))?;
+    if rules.is_empty() {
+        return Ok(0);
+    }
+
+    let mut tidied = 0;
+    let mut jobs = vec![slide.path.clone()];
+    while let Some(job) = jobs.pop() {
+        let mut entries = std::fs::read_dir(&job)?
+            .flatten()
+            .map(|e| e.path())
+            .collect::<Vec<_>>();
+        entries.sort();
+        for src in entries {
+            if src.is_dir() {
+                if !is_internal(&src) {
+                    jobs.push(src);
+                }
+                continue;
+            }
+            if src == config_file || is_wip(&src, wip_suffix) {
+                continue;
+            }
+            let relative = src
+                .strip_prefix(&slide.path)?
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            let Some(dst) = rules.iter().find_map(|rule| rule.apply(&relative)) else {
+                continue;
+            };
+
+            if dst.exists() {
+                log::warn!("Tidy: {dst:?} already exists, leaving {src:?} in place");
+                tracer
+                    .async_log("SKIP", &format!("{src:?}: {dst:?} already exists"))
+                    .await?;
+                continue;
+            }
+            log::info!("Tidy: {src:?} -> {dst:?}");
+            tracer
+                .async_log("TIDY", &format!("{src:?} -> {dst:?}"))
+                .await?;
+            if !dry_run {
+                move_within_volume(&src, &dst).await?;
+            }
+            tidied += 1;
+        }
+    }
+
+    if !dry_run && tidied > 0 {
+        delete_empty_folders(&slide.path).await?;
+    }
+
+    Ok(tidied)
+}
+
+/// Check if a file is the sidecar of a move in progress.
+///
+fn is_wip(path: &Path, suffix: &str) -> bool {
+    path.file_name()
+        .map(|name| name.to_string_lossy())
+        .is_some_and(|name| {
+            name.starts_with('.')
+                && name
+                    .strip_suffix(suffix)
+                    .is_some_and(|name| name.ends_with('.'))
+        })
+}
+
+/// Move a file, renaming it if possible.
+///
+/// The destination may be on a different filesystem if the volume has mount points inside.
+///
+async fn move_within_volume(src: &Path, dst: &Path) -> Result<()> {
+    if let Some(parent) = dst.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    if tokio::fs::rename(src, dst).await.is_ok() {
+        return Ok(());
+    }
+    if let Err(e) = tokio::fs::copy(src, dst).await {
+        let _ = tokio::fs::remove_file(dst).await;
+        bail!("Unable to move {src:?} -> {dst:?}: {e}");
+    }
+    tokio::fs::remove_file(src).await?;
+    Ok(())
+}
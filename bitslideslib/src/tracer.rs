@@ -9,54 +9,103 @@ use tokio::{
     task::JoinHandle,
 };
 
+use crate::{
+    config::OutputFormat,
+    history::{History, HistoryEvent, TIME_FORMAT},
+};
+
 /// Tracer abstraction
 ///
-/// The tracer is a logging utility that asynchronously writes trace messages to a file.
+/// The tracer is a logging utility that asynchronously writes trace messages to a file, and
+/// optionally to stdout as JSON lines and to a history database.
 /// It uses a channel-based approach to avoid blocking the main execution flow when writing logs.
 ///
+#[derive(Clone)]
 pub struct Tracer {
-    tx: Option<Sender<String>>,
+    tx: Option<Sender<HistoryEvent>>,
     author: Option<String>,
 }
 
 impl Tracer {
     const CHANNEL_SIZE: usize = 32;
 
-    pub async fn new(path: &Option<&PathBuf>) -> Result<(Self, Option<JoinHandle<()>>)> {
-        match path {
-            Some(trace_path) => {
-                let mut file = OpenOptions::new()
+    pub async fn new(
+        path: &Option<&PathBuf>,
+        history: &Option<&PathBuf>,
+        output: OutputFormat,
+    ) -> Result<(Self, Option<JoinHandle<()>>)> {
+        let mut file = match path {
+            Some(trace_path) => Some(
+                OpenOptions::new()
                     .create(true)
                     .append(true)
                     .open(trace_path)
-                    .await?;
-
-                let (tx, mut rx) = mpsc::channel::<String>(Self::CHANNEL_SIZE);
-
-                let handle = tokio::spawn(async move {
-                    while let Some(msg) = rx.recv().await {
-                        let _ = file.write_all(msg.as_bytes()).await;
-                        let _ = file.write_all(b"\n").await;
-                    }
-                });
-
-                Ok((
-                    Self {
-                        tx: Some(tx),
-                        author: None,
-                    },
-                    Some(handle),
-                ))
+                    .await?,
+            ),
+            None => None,
+        };
+        let mut history = match history {
+            Some(history_path) => {
+                let history_path = history_path.to_path_buf();
+                Some(tokio::task::spawn_blocking(move || History::open(&history_path)).await??)
             }
-            // The user may want to disable tracing by not providing a path
-            None => Ok((
+            None => None,
+        };
+        let json = output == OutputFormat::Json;
+
+        // The user may want to disable tracing by not providing a path
+        if file.is_none() && history.is_none() && !json {
+            return Ok((
                 Self {
                     tx: None,
                     author: None,
                 },
                 None,
-            )),
+            ));
         }
+
+        let (tx, mut rx) = mpsc::channel::<HistoryEvent>(Self::CHANNEL_SIZE);
+
+        let handle = tokio::spawn(async move {
+            let mut events = Vec::with_capacity(Self::CHANNEL_SIZE);
+            while rx.recv_many(&mut events, Self::CHANNEL_SIZE).await > 0 {
+                for event in &events {
+                    if let Some(file) = file.as_mut() {
+                        let _ = file.write_all(format!("{event}\n").as_bytes()).await;
+                    }
+                    if json {
+                        if let Ok(line) = serde_json::to_string(event) {
+                            println!("{line}");
+                        }
+                    }
+                }
+                // Tokio writes in the background, so the trace is only complete once flushed
+                if let Some(file) = file.as_mut() {
+                    let _ = file.flush().await;
+                }
+                // The events pending are recorded at once, away from the runtime
+                if let Some(mut db) = history.take() {
+                    let batch = std::mem::take(&mut events);
+                    history = tokio::task::spawn_blocking(move || {
+                        if let Err(e) = db.record(&batch) {
+                            log::warn!("Unable to record the history: {e}");
+                        }
+                        db
+                    })
+                    .await
+                    .ok();
+                }
+                events.clear();
+            }
+        });
+
+        Ok((
+            Self {
+                tx: Some(tx),
+                author: None,
+            },
+            Some(handle),
+        ))
     }
 
     pub fn annotate_author(&self, author: String) -> Self {
@@ -66,32 +115,30 @@ impl Tracer {
         }
     }
 
-    fn compose_log_message(&self, operation: &str, details: &str) -> Result<String> {
+    fn compose_event(&self, operation: &str, details: &str) -> Result<HistoryEvent> {
         let author = if let Some(author) = &self.author {
             author
         } else {
             bail!("Tracer author not set")
         };
-        Ok(format!(
-            "[{}] [{}] {} {}",
-            Local::now().format("%Y-%m-%d %H:%M:%S"),
-            author,
-            operation,
-            details
-        ))
+        Ok(HistoryEvent {
+            time: Local::now().format(TIME_FORMAT).to_string(),
+            author: author.to_owned(),
+            operation: operation.to_owned(),
+            details: details.to_owned(),
+        })
     }
 
     pub async fn async_log(&self, operation: &str, details: &str) -> Result<()> {
         if let Some(tx) = &self.tx {
-            tx.send(self.compose_log_message(operation, details)?)
-                .await?;
+            tx.send(self.compose_event(operation, details)?).await?;
         }
         Ok(())
     }
 
     pub fn sync_log(&self, operation: &str, details: &str) -> Result<()> {
         if let Some(tx) = &self.tx {
-            tx.blocking_send(self.compose_log_message(operation, details)?)?;
+            tx.blocking_send(self.compose_event(operation, details)?)?;
         }
         Ok(())
     }
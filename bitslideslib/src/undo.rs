@@ -0,0 +1,179 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use chrono::NaiveDateTime;
+use filetime::FileTime;
+use serde::Serialize;
+use tokio::io::AsyncWriteExt;
+
+use crate::{
+    fs::conflict::rename_new, history::TIME_FORMAT, tracer::Tracer, verify::parse_delivery,
+};
+
+/// Outcome of undoing a delivery
+///
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(tag = "status", content = "detail", rename_all = "lowercase")]
+pub enum UndoOutcome {
+    /// The file was moved back to where it came from, or would be on a dry run
+    Restored,
+    /// The file is no longer where it was delivered
+    Missing,
+    /// Another file took the place the file came from, so it was left in place
+    Occupied,
+    /// The file could not be moved back
+    Failed(String),
+}
+
+/// A delivery undone, or attempted to be
+///
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct Undone {
+    /// Where the file was delivered
+    pub path: PathBuf,
+    /// Where the file came from, and is moved back to
+    pub to: PathBuf,
+    #[serde(flatten)]
+    pub outcome: UndoOutcome,
+}
+
+impl Undone {
+    pub fn is_restored(&self) -> bool {
+        self.outcome == UndoOutcome::Restored
+    }
+}
+
+impl Display for Undone {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} -> {:?}: ", self.path, self.to)?;
+        match &self.outcome {
+            UndoOutcome::Restored => write!(f, "restored"),
+            UndoOutcome::Missing => write!(f, "missing, moved or removed since its delivery"),
+            UndoOutcome::Occupied => write!(f, "left in place, its source is taken"),
+            UndoOutcome::Failed(detail) => write!(f, "failed, {detail}"),
+        }
+    }
+}
+
+/// Move the files delivered according to the `traces` back to where they came from, the latest
+/// delivery first, so the files which went through several volumes retrace their way.
+///
+/// Only the deliveries traced from `since` on are undone, if given. A file no longer where it was
+/// delivered, or whose source is taken by another file, is left alone. Every move is traced.
+///
+pub(crate) async fn undo_deliveries(
+    traces: &[PathBuf],
+    since: Option<NaiveDateTime>,
+    dry_run: bool,
+    tracer: &Tracer,
+) -> Result<Vec<Undone>> {
+    let mut deliveries = Vec::new();
+    for trace in traces {
+        let content = std::fs::read_to_string(trace)
+            .map_err(|e| anyhow!("{trace:?}: Unable to read the trace: {e}"))?;
+        deliveries.extend(
+            content
+                .lines()
+                .filter(|line| since.is_none_or(|since| traced_at(line) >= Some(since)))
+                .filter_map(parse_delivery),
+        );
+    }
+
+    // The files moved so far, so a dry run follows them along their way back too
+    let mut moved = HashMap::new();
+    let exists = |path: &Path, moved: &HashMap<PathBuf, bool>| {
+        moved.get(path).copied().unwrap_or_else(|| path.exists())
+    };
+
+    let mut undone = Vec::new();
+    for delivered in deliveries.into_iter().rev() {
+        let (path, to) = (delivered.path, delivered.from);
+        let outcome = if !exists(&path, &moved) {
+            UndoOutcome::Missing
+        } else if exists(&to, &moved) {
+            log::warn!("Undo: {to:?} already exists, leaving {path:?} in place");
+            UndoOutcome::Occupied
+        } else {
+            log::info!("Undo: {path:?} -> {to:?}");
+            tracer
+                .async_log("UNDO", &format!("{path:?} -> {to:?}"))
+                .await?;
+            let restored = match dry_run {
+                true => Ok(()),
+                false => move_back(&path, &to).await,
+            };
+            match restored {
+                Ok(()) => {
+                    moved.insert(path.clone(), false);
+                    moved.insert(to.clone(), true);
+                    UndoOutcome::Restored
+                }
+                // Taken since checked
+                Err(e)
+                    if e.downcast_ref::<std::io::Error>()
+                        .is_some_and(|e| e.kind() == ErrorKind::AlreadyExists) =>
+                {
+                    log::warn!("Undo: {to:?} already exists, leaving {path:?} in place");
+                    UndoOutcome::Occupied
+                }
+                Err(e) => {
+                    log::error!("Undo: Unable to move {path:?} back to {to:?}: {e}");
+                    UndoOutcome::Failed(e.to_string())
+                }
+            }
+        };
+        undone.push(Undone { path, to, outcome });
+    }
+
+    Ok(undone)
+}
+
+/// Local time a trace line was written at, if it holds one.
+///
+fn traced_at(line: &str) -> Option<NaiveDateTime> {
+    let (time, _) = line.strip_prefix('[')?.split_once(']')?;
+    NaiveDateTime::parse_from_str(time, TIME_FORMAT).ok()
+}
+
+/// Move a file back to where it came from, creating its folder if needed. The files on another
+/// filesystem are copied, keeping their modification time, and removed.
+///
+/// A file taking the place meanwhile is never replaced, failing with
+/// [`ErrorKind::AlreadyExists`] instead.
+///
+pub(crate) async fn move_back(path: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    match rename_new(path, to) {
+        Ok(()) => return Ok(()),
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => return Err(e.into()),
+        Err(_) => {}
+    }
+    let metadata = tokio::fs::metadata(path).await?;
+    let mut writer = tokio::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(to)
+        .await?;
+    let copied = async {
+        let mut reader = tokio::fs::File::open(path).await?;
+        tokio::io::copy(&mut reader, &mut writer).await?;
+        writer.flush().await?;
+        writer.set_permissions(metadata.permissions()).await
+    }
+    .await;
+    drop(writer);
+    if let Err(e) = copied {
+        let _ = tokio::fs::remove_file(to).await;
+        return Err(e.into());
+    }
+    filetime::set_file_mtime(to, FileTime::from_last_modification_time(&metadata))?;
+    tokio::fs::remove_file(path).await?;
+    Ok(())
+}
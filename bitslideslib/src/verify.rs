@@ -0,0 +1,449 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    fs::File,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::{
+    cache::{Caches, ChecksumCache},
+    config::Algorithm,
+    fs::{is_internal, sidecar, Transfer, MANIFEST_DIR},
+    hasher::hash_reader,
+    manifest::Manifest,
+    volume::Volume,
+};
+
+/// Outcome of the verification of a file
+///
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(tag = "status", content = "detail", rename_all = "lowercase")]
+pub enum Verdict {
+    /// The file is readable, and matches the recorded checksum if any
+    Ok,
+    /// The checksum of the file differs from the recorded one
+    Mismatch(String),
+    /// The file is no longer where it was delivered
+    Missing,
+    /// The file cannot be read
+    Unreadable(String),
+    /// The manifest listing the files is not signed by a trusted key, or was altered since
+    Tampered(String),
+}
+
+/// Verification of a single file
+///
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct Verification {
+    /// File verified
+    pub path: PathBuf,
+    /// Where the file was delivered from. None for the files not verified from a trace
+    pub from: Option<PathBuf>,
+    #[serde(flatten)]
+    pub verdict: Verdict,
+}
+
+impl Verification {
+    pub fn is_ok(&self) -> bool {
+        self.verdict == Verdict::Ok
+    }
+}
+
+impl Display for Verification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}: ", self.path)?;
+        match &self.verdict {
+            Verdict::Ok => write!(f, "ok"),
+            Verdict::Mismatch(detail) => write!(f, "checksum mismatch, {detail}"),
+            Verdict::Missing => write!(f, "missing"),
+            Verdict::Unreadable(detail) => write!(f, "unreadable, {detail}"),
+            Verdict::Tampered(detail) => write!(f, "tampered manifest, {detail}"),
+        }?;
+        if let Some(from) = &self.from {
+            write!(f, " (delivered from {from:?})")?;
+        }
+        Ok(())
+    }
+}
+
+/// A delivery read back from a trace.
+///
+#[derive(Debug)]
+pub(crate) struct Delivered {
+    pub(crate) from: PathBuf,
+    pub(crate) path: PathBuf,
+    pub(crate) checksum: Option<(Algorithm, String)>,
+}
+
+/// Hash a file, or just read it through if there is no algorithm.
+///
+fn hash(path: &Path, algorithm: Option<Algorithm>) -> Result<Option<String>> {
+    let mut file = File::open(path)?;
+    match algorithm {
+        Some(algorithm) => Ok(Some(hash_reader(&mut file, algorithm)?)),
+        None => {
+            std::io::copy(&mut file, &mut std::io::sink())?;
+            Ok(None)
+        }
+    }
+}
+
+/// Read every file waiting in the slides, so the unreadable ones are found before their move.
+///
+/// Like the syncs, only the contents of the folders inside each outgoing slide are considered.
+///
+pub fn verify_slides(volumes: &[Volume], algorithm: Option<Algorithm>) -> Vec<Verification> {
+    let mut verifications = Vec::new();
+
+    for volume in volumes {
+        // The slide named after the volume holds the files delivered to it, not waiting ones
+        let mut slides = volume
+            .slides
+            .values()
+            .filter(|slide| slide.name != volume.name)
+            .collect::<Vec<_>>();
+        slides.sort_by(|a, b| a.name.cmp(&b.name));
+        for slide in slides {
+            let mut jobs = match slide.path.read_dir() {
+                Ok(entries) => entries
+                    .flatten()
+                    .map(|e| e.path())
+                    .filter(|p| p.is_dir() && !is_internal(p))
+                    .collect::<Vec<_>>(),
+                Err(e) => {
+                    verifications.push(Verification {
+                        path: slide.path.clone(),
+                        from: None,
+                        verdict: Verdict::Unreadable(e.to_string()),
+                    });
+                    continue;
+                }
+            };
+            jobs.sort();
+
+            while let Some(job) = jobs.pop() {
+                let mut entries = match job.read_dir() {
+                    Ok(entries) => entries.flatten().map(|e| e.path()).collect::<Vec<_>>(),
+                    Err(e) => {
+                        verifications.push(Verification {
+                            path: job,
+                            from: None,
+                            verdict: Verdict::Unreadable(e.to_string()),
+                        });
+                        continue;
+                    }
+                };
+                entries.sort();
+                for path in entries {
+                    if path.is_dir() {
+                        if !is_internal(&path) {
+                            jobs.push(path);
+                        }
+                        continue;
+                    }
+                    let verdict = match hash(&path, algorithm) {
+                        Ok(_) => Verdict::Ok,
+                        Err(e) => Verdict::Unreadable(e.to_string()),
+                    };
+                    verifications.push(Verification {
+                        path,
+                        from: None,
+                        verdict,
+                    });
+                }
+            }
+        }
+    }
+
+    verifications
+}
+
+/// Checksum of a delivered file, taken from the cache if the file is unchanged since it was
+/// computed, unless `rehash`. The checksums computed are added to the cache.
+///
+fn hash_delivered(
+    path: &Path,
+    algorithm: Algorithm,
+    cache: Option<&ChecksumCache>,
+    rehash: bool,
+) -> Result<String> {
+    if let Some(checksum) = cache
+        .filter(|_| !rehash)
+        .and_then(|cache| cache.get(path, algorithm))
+    {
+        return Ok(checksum);
+    }
+    let checksum = hash_reader(&mut File::open(path)?, algorithm)?;
+    if let Some(cache) = cache {
+        cache.insert(path, algorithm, &checksum);
+    }
+    Ok(checksum)
+}
+
+/// Check that the deliveries recorded in the traces are still in place, with their checksum.
+///
+/// The traces are read in the given order. Files delivered to a staging slide and forwarded
+/// afterwards are only checked at their last destination.
+///
+pub fn verify_deliveries(
+    traces: &[PathBuf],
+    caches: &Caches,
+    rehash: bool,
+) -> Result<Vec<Verification>> {
+    let mut deliveries = BTreeMap::new();
+
+    for trace in traces {
+        let content = std::fs::read_to_string(trace)
+            .map_err(|e| anyhow!("{trace:?}: Unable to read the trace: {e}"))?;
+        for delivered in content.lines().filter_map(parse_delivery) {
+            deliveries.remove(&delivered.from);
+            deliveries.insert(delivered.path.clone(), delivered);
+        }
+    }
+
+    Ok(deliveries
+        .into_values()
+        .map(|delivered| {
+            let verdict = if !delivered.path.exists() {
+                Verdict::Missing
+            } else {
+                match &delivered.checksum {
+                    Some((algorithm, expected)) => {
+                        let cache = caches.of(&delivered.path);
+                        match hash_delivered(&delivered.path, *algorithm, cache, rehash) {
+                            Ok(actual) if actual.eq_ignore_ascii_case(expected) => Verdict::Ok,
+                            Ok(actual) => {
+                                Verdict::Mismatch(format!("expected {expected}, found {actual}"))
+                            }
+                            Err(e) => Verdict::Unreadable(e.to_string()),
+                        }
+                    }
+                    None => match hash(&delivered.path, None) {
+                        Ok(_) => Verdict::Ok,
+                        Err(e) => Verdict::Unreadable(e.to_string()),
+                    },
+                }
+            };
+            Verification {
+                path: delivered.path,
+                from: Some(delivered.from),
+                verdict,
+            }
+        })
+        .collect())
+}
+
+/// Check the files listed in the checksum files found among the ones delivered to each volume.
+///
+/// These are the `sha256sum`-like files written along with the deliveries when asked to, or
+/// brought by any other tool.
+///
+pub fn verify_sidecars(volumes: &[Volume], caches: &Caches, rehash: bool) -> Vec<Verification> {
+    let mut verifications = Vec::new();
+
+    let mut jobs = volumes
+        .iter()
+        .filter_map(|volume| volume.slides.get(&volume.name))
+        .map(|slide| slide.path.clone())
+        .collect::<Vec<_>>();
+    while let Some(job) = jobs.pop() {
+        let Ok(entries) = job.read_dir() else {
+            continue;
+        };
+        let mut entries = entries.flatten().map(|e| e.path()).collect::<Vec<_>>();
+        entries.sort();
+        for path in entries {
+            if path.is_dir() {
+                if !is_internal(&path) {
+                    jobs.push(path);
+                }
+                continue;
+            }
+            let Some(algorithm) = sidecar::algorithm_of(&path) else {
+                continue;
+            };
+            let listed = match sidecar::read(&path) {
+                Ok(listed) => listed,
+                Err(e) => {
+                    verifications.push(Verification {
+                        path,
+                        from: None,
+                        verdict: Verdict::Unreadable(e.to_string()),
+                    });
+                    continue;
+                }
+            };
+            for (file, expected) in listed {
+                let verdict = if !file.exists() {
+                    Verdict::Missing
+                } else {
+                    match hash_delivered(&file, algorithm, caches.of(&file), rehash) {
+                        Ok(actual) if actual.eq_ignore_ascii_case(&expected) => Verdict::Ok,
+                        Ok(actual) => {
+                            Verdict::Mismatch(format!("expected {expected}, found {actual}"))
+                        }
+                        Err(e) => Verdict::Unreadable(e.to_string()),
+                    }
+                };
+                verifications.push(Verification {
+                    path: file,
+                    from: None,
+                    verdict,
+                });
+            }
+        }
+    }
+
+    verifications
+}
+
+/// Check the files listed in the signed manifests found in the slides of each volume.
+///
+/// A manifest not signed by one of the `trusted` public keys, or altered since, is reported
+/// instead of its files, as nothing it lists can be relied on. The files are always hashed again,
+/// as the cached checksums are not signed: anyone able to write the drive could make a tampered
+/// file pass. The checksums computed are still added to the `caches`.
+///
+pub fn verify_manifests(
+    volumes: &[Volume],
+    trusted: &[String],
+    caches: &Caches,
+) -> Vec<Verification> {
+    let mut verifications = Vec::new();
+
+    for volume in volumes {
+        let mut slides = volume.slides.values().collect::<Vec<_>>();
+        slides.sort_by(|a, b| a.name.cmp(&b.name));
+        for slide in slides {
+            let Ok(entries) = slide.path.join(MANIFEST_DIR).read_dir() else {
+                continue;
+            };
+            let mut manifests = entries
+                .flatten()
+                .map(|e| e.path())
+                .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+                .collect::<Vec<_>>();
+            manifests.sort();
+            for path in manifests {
+                let manifest = match Manifest::read(&path, trusted) {
+                    Ok(manifest) => manifest,
+                    Err(e) => {
+                        verifications.push(Verification {
+                            path,
+                            from: None,
+                            verdict: Verdict::Tampered(e.to_string()),
+                        });
+                        continue;
+                    }
+                };
+                for entry in manifest.files {
+                    let file = slide.path.join(&entry.path);
+                    let verdict = match (entry.algorithm(), std::fs::metadata(&file)) {
+                        (_, Err(_)) => Verdict::Missing,
+                        (_, Ok(metadata)) if metadata.len() != entry.size => Verdict::Mismatch(
+                            format!("expected {} bytes, found {}", entry.size, metadata.len()),
+                        ),
+                        (None, _) => {
+                            Verdict::Tampered(format!("unknown algorithm \"{}\"", entry.algorithm))
+                        }
+                        (Some(algorithm), _) => {
+                            match hash_delivered(&file, algorithm, caches.of(&file), true) {
+                                Ok(actual) if actual.eq_ignore_ascii_case(&entry.checksum) => {
+                                    Verdict::Ok
+                                }
+                                Ok(actual) => Verdict::Mismatch(format!(
+                                    "expected {}, found {actual}",
+                                    entry.checksum
+                                )),
+                                Err(e) => Verdict::Unreadable(e.to_string()),
+                            }
+                        }
+                    };
+                    verifications.push(Verification {
+                        path: file,
+                        from: None,
+                        verdict,
+                    });
+                }
+            }
+        }
+    }
+
+    verifications
+}
+
+/// Parse a delivery from a trace line, as written by `fs::Transfer::trace_details`.
+///
+/// The deliveries traced as text, by earlier versions or for the paths not valid UTF-8, are
+/// parsed too.
+///
+pub(crate) fn parse_delivery(line: &str) -> Option<Delivered> {
+    let (_, details) = line.split_once("] DELIVERED ")?;
+    let parse_checksum = |checksum: &str| -> Option<Option<(Algorithm, String)>> {
+        match checksum.trim() {
+            "" => Some(None),
+            checksum => {
+                let (algorithm, checksum) = checksum.split_once(':')?;
+                Some(Some((
+                    Algorithm::from_str(algorithm).ok()?,
+                    checksum.to_owned(),
+                )))
+            }
+        }
+    };
+    if details.starts_with('{') {
+        let transfer = serde_json::from_str::<Transfer>(details).ok()?;
+        return Some(Delivered {
+            from: transfer.src,
+            path: transfer.dst,
+            checksum: parse_checksum(transfer.checksum.as_deref().unwrap_or_default())?,
+        });
+    }
+    let (from, rest) = parse_quoted(details)?;
+    let (path, rest) = parse_quoted(rest.strip_prefix(" -> ")?)?;
+    Some(Delivered {
+        from: PathBuf::from(from),
+        path: PathBuf::from(path),
+        checksum: parse_checksum(rest)?,
+    })
+}
+
+/// Parse a string quoted as the `Debug` format does, returning it along with the rest of the input.
+///
+fn parse_quoted(s: &str) -> Option<(String, &str)> {
+    let quoted = s.strip_prefix('"')?;
+    let mut chars = quoted.char_indices();
+    let mut parsed = String::new();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((parsed, &quoted[i + 1..])),
+            '\\' => match chars.next()?.1 {
+                'n' => parsed.push('\n'),
+                'r' => parsed.push('\r'),
+                't' => parsed.push('\t'),
+                '0' => parsed.push('\0'),
+                'u' => {
+                    // Unicode escapes look like \u{1f600}
+                    if chars.next()?.1 != '{' {
+                        return None;
+                    }
+                    let hex = chars
+                        .by_ref()
+                        .map(|(_, c)| c)
+                        .take_while(|c| *c != '}')
+                        .collect::<String>();
+                    parsed.push(char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?);
+                }
+                escaped => parsed.push(escaped),
+            },
+            c => parsed.push(c),
+        }
+    }
+
+    None
+}
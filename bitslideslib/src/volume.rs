@@ -2,7 +2,12 @@ use crate::config;
 
 use super::slide::Slide;
 use anyhow::Result;
-use std::{collections::HashMap, path::PathBuf};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 pub const DEFAULT_VOLUME_CONFIG_FILE: &str = ".volume.yml";
 
@@ -10,12 +15,31 @@ pub const DEFAULT_VOLUME_CONFIG_FILE: &str = ".volume.yml";
 ///
 /// A volume is a storage unit that contains a slides folder (or the chosen keyword).
 ///
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct Volume {
     /// Name of the volume
     pub name: String,
     /// Whether the volume is disabled or not
     pub disabled: bool,
+    /// Priority of the volume. Syncjobs from higher priority volumes are run first
+    pub priority: i32,
+    /// Cost of routing files through the volume. The cheapest routes are preferred
+    pub cost: u32,
+    /// Whether files are only taken from the volume, never moved into it
+    pub send_only: bool,
+    /// Whether files are only moved into the volume, never taken from it
+    pub receive_only: bool,
+    /// Volumes never exchanging files with this one
+    pub ignore: Vec<String>,
+    /// Seconds between the pulls of the files moved into the volume, if their sources are not
+    /// watched
+    pub pull: Option<u64>,
+    /// Whether the slides of the volume are polled for changes rather than watched, as set for
+    /// its rootset
+    #[serde(skip)]
+    pub poll: bool,
+    /// Free-form description of the volume
+    pub description: Option<String>,
     /// Keyword used for the slides subfolder
     pub keyword: String,
     /// Path to the volume root. Ex. /path/to/volumes/foo
@@ -31,6 +55,14 @@ impl Volume {
         Self {
             name,
             disabled,
+            priority: 0,
+            cost: 1,
+            send_only: false,
+            receive_only: false,
+            ignore: Vec::new(),
+            pull: None,
+            poll: false,
+            description: None,
             keyword: keyword.to_owned(),
             path,
             slides: HashMap::new(),
@@ -42,84 +74,154 @@ impl Volume {
     pub fn from_path(maybe_volume: PathBuf, keyword: &str) -> Option<Self> {
         let slides_path = maybe_volume.join(keyword);
         if slides_path.exists() {
-            let mut disabled = false;
-
-            // Try to retrieve the configured name first
             let volume_conf =
-                config::VolumeConfig::new(slides_path.join(DEFAULT_VOLUME_CONFIG_FILE));
-            if let Ok(v) = volume_conf {
-                if let Some(n) = v.disabled {
-                    disabled = n;
-                }
-                if let Some(n) = v.name {
-                    return Some(Self::new(n, disabled, keyword, maybe_volume.to_owned()));
-                }
-            }
+                config::VolumeConfig::new(slides_path.join(DEFAULT_VOLUME_CONFIG_FILE), false).ok();
 
-            // Otherwise try to retrieve it from the OS context
-            #[allow(clippy::single_match)]
-            match maybe_volume.file_name() {
+            let disabled = volume_conf
+                .as_ref()
+                .and_then(|v| v.disabled)
+                .unwrap_or(false);
+
+            // Try to retrieve the configured name first, otherwise from the OS context
+            let name = volume_conf
+                .as_ref()
+                .and_then(|v| v.name.clone())
+                .or_else(|| Self::os_name(&maybe_volume));
+
+            match name {
                 Some(name) => {
-                    let name = name.to_string_lossy().to_string();
-                    return Some(Self::new(name, disabled, keyword, maybe_volume.to_owned()));
+                    let mut volume = Self::new(name, disabled, keyword, maybe_volume);
+                    if let Some(v) = volume_conf {
+                        volume.priority = v.priority.unwrap_or_default();
+                        volume.cost = v.cost.unwrap_or(1);
+                        volume.send_only = v.send_only.unwrap_or_default();
+                        volume.receive_only = v.receive_only.unwrap_or_default();
+                        volume.ignore = v.ignore.unwrap_or_default();
+                        volume.pull = v.pull;
+                        volume.description = v.description;
+                    }
+                    return Some(volume);
                 }
                 None => {
-                    #[cfg(target_os = "windows")]
-                    {
-                        const VOLUME_NAME_MAX_LEN: usize = 256;
-                        let mut volume_name = [0u16; VOLUME_NAME_MAX_LEN];
-                        let name = unsafe {
-                            windows::Win32::Storage::FileSystem::GetVolumeInformationW(
-                                &windows::core::HSTRING::from(maybe_volume.as_os_str()),
-                                Some(&mut volume_name),
-                                None,
-                                None,
-                                None,
-                                None,
-                            )
-                        };
-                        if name.is_ok() {
-                            let name = String::from_utf16_lossy(&volume_name)
-                                .trim_end_matches('\0')
-                                .to_owned();
-                            return Some(Self::new(
-                                name,
-                                disabled,
-                                keyword,
-                                maybe_volume.to_owned(),
-                            ));
-                        }
-                    }
+                    log::warn!(
+                        "A volume has been identified at {maybe_volume:?} but it is nameless"
+                    );
                 }
-            };
-
-            log::warn!("A volume has been identified at {maybe_volume:?} but it is nameless");
+            }
         }
         None
     }
 
+    /// Retrieve the name of a volume from the OS context.
+    ///
+    fn os_name(maybe_volume: &Path) -> Option<String> {
+        // The None arm is only meaningful under Windows
+        #[allow(clippy::manual_map)]
+        match maybe_volume.file_name() {
+            Some(name) => Some(name.to_string_lossy().to_string()),
+            None => {
+                #[cfg(target_os = "windows")]
+                {
+                    const VOLUME_NAME_MAX_LEN: usize = 256;
+                    let mut volume_name = [0u16; VOLUME_NAME_MAX_LEN];
+                    let name = unsafe {
+                        windows::Win32::Storage::FileSystem::GetVolumeInformationW(
+                            &windows::core::HSTRING::from(maybe_volume.as_os_str()),
+                            Some(&mut volume_name),
+                            None,
+                            None,
+                            None,
+                            None,
+                        )
+                    };
+                    if name.is_ok() {
+                        let name = String::from_utf16_lossy(&volume_name)
+                            .trim_end_matches('\0')
+                            .to_owned();
+                        return Some(name);
+                    }
+                }
+                None
+            }
+        }
+    }
+
     /// Add a slide to the volume.
     ///
     pub fn add_slide(&mut self, slide: Slide) {
         self.slides.insert(slide.name.clone(), slide);
     }
 
+    /// Why the files of this volume cannot be moved into the `other` one, None if they can.
+    ///
+    pub fn refusal(&self, other: &Volume) -> Option<String> {
+        if self.receive_only {
+            Some(format!("\"{}\" is receive only", self.name))
+        } else if other.send_only {
+            Some(format!("\"{}\" is send only", other.name))
+        } else if self.ignore.contains(&other.name) {
+            Some(format!("\"{}\" ignores \"{}\"", self.name, other.name))
+        } else if other.ignore.contains(&self.name) {
+            Some(format!("\"{}\" ignores \"{}\"", other.name, self.name))
+        } else {
+            None
+        }
+    }
+
+    /// Time between the pulls of the files moved into the volume, None if their sources are
+    /// watched instead.
+    ///
+    pub fn pull_every(&self) -> Option<Duration> {
+        self.pull.filter(|secs| *secs > 0).map(Duration::from_secs)
+    }
+
     /// Create a new slide and add it to the volume.
     ///
     pub fn create_slide(&mut self, name: &str) -> Result<()> {
         let path = self.path.join(&self.keyword).join(name);
         std::fs::create_dir_all(&path)?;
-        self.slides
-            .insert(name.to_owned(), Slide::new(name.to_owned(), path, None));
+        self.slides.insert(
+            name.to_owned(),
+            Slide::new(
+                name.to_owned(),
+                path,
+                None,
+                config::StrategyOverrides::default(),
+            ),
+        );
         Ok(())
     }
 }
 
-#[cfg(false)]
 impl std::fmt::Display for Volume {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.name)?;
-        for slide in self.slides.values() {
+        write!(
+            f,
+            "{} at {:?} (keyword \"{}\", priority {}{}{}{})",
+            self.name,
+            self.path,
+            self.keyword,
+            self.priority,
+            if self.disabled { ", disabled" } else { "" },
+            if self.send_only { ", send only" } else { "" },
+            if self.receive_only {
+                ", receive only"
+            } else {
+                ""
+            }
+        )?;
+        if let Some(every) = self.pull_every() {
+            write!(f, "\n  ! pulls every {every:?}")?;
+        }
+        if !self.ignore.is_empty() {
+            write!(f, "\n  ! ignores {}", self.ignore.join(", "))?;
+        }
+        if let Some(description) = &self.description {
+            write!(f, "\n  > {description}")?;
+        }
+        let mut slides = self.slides.values().collect::<Vec<_>>();
+        slides.sort_by_key(|slide| &slide.name);
+        for slide in slides {
             write!(f, "\n  - {}", slide)?;
         }
         Ok(())
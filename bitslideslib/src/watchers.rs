@@ -0,0 +1,218 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, Weak},
+    time::Duration,
+};
+
+use anyhow::Result;
+use notify::{Config, ErrorKind, Event, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::Notify;
+
+/// Handler of the filesystem events, shared by every backend.
+///
+type Handler = Arc<dyn Fn(Event) + Send + Sync>;
+
+/// What to do once the recommended backend runs out of watches.
+///
+const EXHAUSTED_HINT: &str = "Out of inotify watches, polling it instead. Raise the limit to \
+    watch it, e.g. `sysctl fs.inotify.max_user_watches=524288`";
+
+/// Filesystem watchers of the slides.
+///
+/// The slides are watched by the recommended backend of the platform, unless they have to be
+/// polled or the backend fails to watch them, as happens on some network filesystems and FUSE
+/// mounts, or once it runs out of watches on large trees. Those are scanned for changes every so
+/// often instead.
+///
+pub(crate) struct Watchers {
+    /// Recommended backend, None if it could not be started
+    recommended: Arc<Mutex<Option<RecommendedWatcher>>>,
+    /// Folders watched by the recommended backend, until they are polled
+    watched: Arc<Mutex<Vec<(PathBuf, RecursiveMode)>>>,
+    /// Polling backend, shared with the recommended one to poll the folders it gives up on
+    polling: Arc<Mutex<Polling>>,
+}
+
+impl Watchers {
+    /// Create the watchers, calling the `handler` on every event. The slides polled are scanned
+    /// every `interval`.
+    ///
+    pub(crate) fn new(handler: impl Fn(Event) + Send + Sync + 'static, interval: Duration) -> Self {
+        let handler: Handler = Arc::new(handler);
+        let watched: Arc<Mutex<Vec<(PathBuf, RecursiveMode)>>> = Arc::default();
+        let polling = Arc::new(Mutex::new(Polling {
+            handler: handler.clone(),
+            watcher: None,
+            interval,
+        }));
+        let recommended: Arc<Mutex<Option<RecommendedWatcher>>> = Arc::default();
+        let started = {
+            let watched = watched.clone();
+            let polling = polling.clone();
+            // Not kept alive by its own handler
+            let backend = Arc::downgrade(&recommended);
+            notify::recommended_watcher(move |res: notify::Result<Event>| match res {
+                Ok(event) => handler(event),
+                // The folders created from now on in the slide would never trigger a sync
+                Err(e) if matches!(e.kind, ErrorKind::MaxFilesWatch) => {
+                    let mut watched = watched.lock().unwrap();
+                    let mut released = vec![];
+                    for path in &e.paths {
+                        let Some(index) = watched
+                            .iter()
+                            .position(|(folder, _)| path.starts_with(folder))
+                        else {
+                            continue;
+                        };
+                        let (folder, mode) = watched.remove(index);
+                        log::warn!("{folder:?}: {EXHAUSTED_HINT}");
+                        if let Err(e) = polling.lock().unwrap().watch(&folder, mode) {
+                            log::warn!("{folder:?}: Unable to poll the folder: {e}");
+                        }
+                        released.push(folder);
+                    }
+                    unwatch(backend.clone(), released);
+                }
+                Err(e) => log::debug!("Filesystem watcher error: {e}"),
+            })
+        };
+        match started {
+            Ok(started) => *recommended.lock().unwrap() = Some(started),
+            Err(e) => log::warn!("Unable to start the filesystem watcher, polling the slides: {e}"),
+        }
+        Self {
+            recommended,
+            watched,
+            polling,
+        }
+    }
+
+    /// Watch a folder and its contents. It is polled if `poll`, or if the recommended backend
+    /// fails to watch it.
+    ///
+    pub(crate) fn watch(&mut self, path: &Path, poll: bool) -> Result<()> {
+        self.watch_with(path, RecursiveMode::Recursive, poll)
+    }
+
+    /// Watch the entries of a folder, but not what they hold. It is polled like in [`Self::watch`].
+    ///
+    pub(crate) fn watch_entries(&mut self, path: &Path, poll: bool) -> Result<()> {
+        self.watch_with(path, RecursiveMode::NonRecursive, poll)
+    }
+
+    fn watch_with(&mut self, path: &Path, mode: RecursiveMode, poll: bool) -> Result<()> {
+        if let Some(recommended) = self.recommended.lock().unwrap().as_mut().filter(|_| !poll) {
+            match recommended.watch(path, mode) {
+                Ok(()) => {
+                    self.watched.lock().unwrap().push((path.to_owned(), mode));
+                    return Ok(());
+                }
+                Err(e) if matches!(e.kind, ErrorKind::MaxFilesWatch) => {
+                    // The subfolders watched before running out are released
+                    let _ = recommended.unwatch(path);
+                    log::warn!("{path:?}: {EXHAUSTED_HINT}");
+                }
+                Err(e) => log::warn!("{path:?}: Unable to watch the folder, polling it: {e}"),
+            }
+        }
+        self.polling.lock().unwrap().watch(path, mode)
+    }
+}
+
+/// Release the watches of the recommended `backend` on the folders now polled, so it stops
+/// reporting them twice and gets the watches back for the other folders.
+///
+/// The backend waits for its event loop to unwatch, and its handler runs on that loop, so this is
+/// done on a thread of its own.
+///
+fn unwatch(backend: Weak<Mutex<Option<RecommendedWatcher>>>, folders: Vec<PathBuf>) {
+    if folders.is_empty() {
+        return;
+    }
+    std::thread::spawn(move || {
+        let Some(backend) = backend.upgrade() else {
+            return;
+        };
+        let mut recommended = backend.lock().unwrap();
+        for folder in folders {
+            if let Some(recommended) = recommended.as_mut() {
+                let _ = recommended.unwatch(&folder);
+            }
+        }
+    });
+}
+
+/// Polling backend, started along with the first folder polled.
+///
+struct Polling {
+    handler: Handler,
+    watcher: Option<PollWatcher>,
+    /// Time between two scans of the folders polled
+    interval: Duration,
+}
+
+impl Polling {
+    fn watch(&mut self, path: &Path, mode: RecursiveMode) -> Result<()> {
+        let watcher = match &mut self.watcher {
+            Some(watcher) => watcher,
+            None => {
+                let handler = self.handler.clone();
+                self.watcher.insert(PollWatcher::new(
+                    move |res: notify::Result<Event>| {
+                        if let Ok(event) = res {
+                            handler(event);
+                        }
+                    },
+                    Config::default().with_poll_interval(self.interval),
+                )?)
+            }
+        };
+        watcher.watch(path, mode)?;
+        log::debug!("{path:?}: Polled every {}s", self.interval.as_secs_f32());
+        Ok(())
+    }
+}
+
+/// Notify `changed` every time a filesystem is mounted or unmounted, until nothing else holds it.
+///
+/// Returns false where the mounts cannot be watched, that is everywhere but on Linux, where the
+/// kernel reports the changes of the mount table through `/proc/self/mounts`.
+///
+pub(crate) fn watch_mounts(changed: Arc<Notify>) -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        use nix::{
+            errno::Errno,
+            poll::{poll, PollFd, PollFlags},
+        };
+        use std::os::fd::AsFd;
+
+        let mounts = match std::fs::File::open("/proc/self/mounts") {
+            Ok(mounts) => mounts,
+            Err(e) => {
+                log::warn!("Unable to watch the mounts: {e}");
+                return false;
+            }
+        };
+        std::thread::spawn(move || {
+            // The mount table changed since the last poll when it reports an exceptional condition
+            while Arc::strong_count(&changed) > 1 {
+                let mut fds = [PollFd::new(mounts.as_fd(), PollFlags::POLLPRI)];
+                match poll(&mut fds, 1000u16) {
+                    Ok(0) | Err(Errno::EINTR) => {}
+                    Ok(_) => changed.notify_one(),
+                    Err(e) => {
+                        log::warn!("Unable to watch the mounts: {e}");
+                        break;
+                    }
+                }
+            }
+        });
+        true
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        drop(changed);
+        false
+    }
+}